@@ -0,0 +1,163 @@
+//! Account indices of the router instructions.
+//!
+//! Handlers index their accounts with these instead of bare numbers, and the
+//! instruction builders lay the accounts out in the same order. Each list is
+//! contiguous from zero, optional trailing accounts included.
+
+// Swap, also SwapExactOut, SwapBestFee candidates and the Raydium leg of a Route
+pub const SWAP_PROGRAM_ACCOUNT: usize = 0;
+pub const SWAP_PROGRAM_TOKEN_A: usize = 1;
+pub const SWAP_PROGRAM_TOKEN_B: usize = 2;
+pub const SWAP_POOL_PROGRAM: usize = 3;
+pub const SWAP_POOL_COIN_TOKEN_ACCOUNT: usize = 4;
+pub const SWAP_POOL_PC_TOKEN_ACCOUNT: usize = 5;
+pub const SWAP_SPL_TOKEN: usize = 6;
+pub const SWAP_AMM_ID: usize = 7;
+pub const SWAP_AMM_AUTHORITY: usize = 8;
+pub const SWAP_AMM_OPEN_ORDERS: usize = 9;
+pub const SWAP_AMM_TARGET: usize = 10;
+pub const SWAP_SERUM_MARKET: usize = 11;
+pub const SWAP_SERUM_PROGRAM: usize = 12;
+pub const SWAP_SERUM_BIDS: usize = 13;
+pub const SWAP_SERUM_ASKS: usize = 14;
+pub const SWAP_SERUM_EVENT_QUEUE: usize = 15;
+pub const SWAP_SERUM_COIN_VAULT: usize = 16;
+pub const SWAP_SERUM_PC_VAULT: usize = 17;
+pub const SWAP_SERUM_VAULT_SIGNER: usize = 18;
+/// Optional, Swap only, receives the input a partial fill leaves unconsumed
+pub const SWAP_REFUND: usize = 19;
+
+// BeforeTransfer
+pub const BEFORE_TRANSFER_USER: usize = 0;
+pub const BEFORE_TRANSFER_TOKEN_PROGRAM: usize = 1;
+pub const BEFORE_TRANSFER_USER_SOL: usize = 2;
+pub const BEFORE_TRANSFER_PROGRAM_SOL: usize = 3;
+pub const BEFORE_TRANSFER_USER_TRANSFER_AUTHORITY: usize = 4;
+/// Optional, starts the sequence in the stored config
+pub const BEFORE_TRANSFER_PROGRAM_ACCOUNT: usize = 5;
+/// Optional, makes the deposit a transfer_checked
+pub const BEFORE_TRANSFER_MINT: usize = 6;
+
+// AfterTransfer
+pub const AFTER_TRANSFER_TOKEN_PROGRAM: usize = 0;
+pub const AFTER_TRANSFER_PROGRAM_ACCOUNT: usize = 1;
+pub const AFTER_TRANSFER_PROGRAM_KIN: usize = 2;
+pub const AFTER_TRANSFER_PROGRAM_SOL: usize = 3;
+pub const AFTER_TRANSFER_DESTINATION: usize = 4;
+pub const AFTER_TRANSFER_FEE_RECIPIENT: usize = 5;
+pub const AFTER_TRANSFER_PROGRAM_KIN_MINT: usize = 6;
+pub const AFTER_TRANSFER_PROGRAM_SOL_MINT: usize = 7;
+/// Optional, defaults to the payout token program
+pub const AFTER_TRANSFER_FEE_TOKEN_PROGRAM: usize = 8;
+/// Optional, required when the config routes an lp fee
+pub const AFTER_TRANSFER_LP_FEE_RECIPIENT: usize = 9;
+/// Optional, the zero id opts out of the referral
+pub const AFTER_TRANSFER_REFERRER: usize = 10;
+
+/// Pool program of a Route, every protocol takes it after the program account
+/// and the program token a and b accounts
+pub const ROUTE_POOL_PROGRAM: usize = SWAP_POOL_PROGRAM;
+
+// SwapNativeIn, the swap accounts follow
+pub const SWAP_NATIVE_IN_USER: usize = 0;
+pub const SWAP_NATIVE_IN_SYSTEM_PROGRAM: usize = 1;
+pub const SWAP_NATIVE_IN_SWAP: usize = 2;
+
+// SwapTwoHop, the accounts of the two pools follow
+pub const SWAP_TWO_HOP_PROGRAM_ACCOUNT: usize = 0;
+pub const SWAP_TWO_HOP_PROGRAM_TOKEN_IN: usize = 1;
+pub const SWAP_TWO_HOP_PROGRAM_TOKEN_MID: usize = 2;
+pub const SWAP_TWO_HOP_PROGRAM_TOKEN_OUT: usize = 3;
+pub const SWAP_TWO_HOP_POOLS: usize = 4;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::utils::swap::{AFTER_TRANSFER_ACCOUNTS, BEFORE_TRANSFER_ACCOUNTS, SWAP_ACCOUNTS},
+    };
+
+    #[test]
+    fn test_layouts_contiguous() {
+        let layouts: [(&str, &[usize], usize); 5] = [
+            (
+                "swap",
+                &[
+                    SWAP_PROGRAM_ACCOUNT,
+                    SWAP_PROGRAM_TOKEN_A,
+                    SWAP_PROGRAM_TOKEN_B,
+                    SWAP_POOL_PROGRAM,
+                    SWAP_POOL_COIN_TOKEN_ACCOUNT,
+                    SWAP_POOL_PC_TOKEN_ACCOUNT,
+                    SWAP_SPL_TOKEN,
+                    SWAP_AMM_ID,
+                    SWAP_AMM_AUTHORITY,
+                    SWAP_AMM_OPEN_ORDERS,
+                    SWAP_AMM_TARGET,
+                    SWAP_SERUM_MARKET,
+                    SWAP_SERUM_PROGRAM,
+                    SWAP_SERUM_BIDS,
+                    SWAP_SERUM_ASKS,
+                    SWAP_SERUM_EVENT_QUEUE,
+                    SWAP_SERUM_COIN_VAULT,
+                    SWAP_SERUM_PC_VAULT,
+                    SWAP_SERUM_VAULT_SIGNER,
+                    SWAP_REFUND,
+                ],
+                SWAP_ACCOUNTS + 1,
+            ),
+            (
+                "before transfer",
+                &[
+                    BEFORE_TRANSFER_USER,
+                    BEFORE_TRANSFER_TOKEN_PROGRAM,
+                    BEFORE_TRANSFER_USER_SOL,
+                    BEFORE_TRANSFER_PROGRAM_SOL,
+                    BEFORE_TRANSFER_USER_TRANSFER_AUTHORITY,
+                    BEFORE_TRANSFER_PROGRAM_ACCOUNT,
+                    BEFORE_TRANSFER_MINT,
+                ],
+                BEFORE_TRANSFER_ACCOUNTS + 2,
+            ),
+            (
+                "after transfer",
+                &[
+                    AFTER_TRANSFER_TOKEN_PROGRAM,
+                    AFTER_TRANSFER_PROGRAM_ACCOUNT,
+                    AFTER_TRANSFER_PROGRAM_KIN,
+                    AFTER_TRANSFER_PROGRAM_SOL,
+                    AFTER_TRANSFER_DESTINATION,
+                    AFTER_TRANSFER_FEE_RECIPIENT,
+                    AFTER_TRANSFER_PROGRAM_KIN_MINT,
+                    AFTER_TRANSFER_PROGRAM_SOL_MINT,
+                    AFTER_TRANSFER_FEE_TOKEN_PROGRAM,
+                    AFTER_TRANSFER_LP_FEE_RECIPIENT,
+                    AFTER_TRANSFER_REFERRER,
+                ],
+                AFTER_TRANSFER_ACCOUNTS + 3,
+            ),
+            (
+                "swap native in",
+                &[SWAP_NATIVE_IN_USER, SWAP_NATIVE_IN_SYSTEM_PROGRAM, SWAP_NATIVE_IN_SWAP],
+                3,
+            ),
+            (
+                "swap two hop",
+                &[
+                    SWAP_TWO_HOP_PROGRAM_ACCOUNT,
+                    SWAP_TWO_HOP_PROGRAM_TOKEN_IN,
+                    SWAP_TWO_HOP_PROGRAM_TOKEN_MID,
+                    SWAP_TWO_HOP_PROGRAM_TOKEN_OUT,
+                    SWAP_TWO_HOP_POOLS,
+                ],
+                5,
+            ),
+        ];
+        for (name, layout, len) in layouts {
+            assert_eq!(layout.len(), len, "{}", name);
+            for (position, index) in layout.iter().enumerate() {
+                assert_eq!(*index, position, "{} account {}", name, position);
+            }
+        }
+    }
+}
@@ -2,8 +2,12 @@
 
 #![cfg(not(feature = "no-entrypoint"))]
 
-use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
+use {
+    crate::error::AutoswapError,
+    solana_program::{
+        account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+        program_error::PrintProgramError, pubkey::Pubkey,
+    },
 };
 
 entrypoint!(process_instruction);
@@ -12,5 +16,10 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    crate::processor::process_instruction(program_id, accounts, instruction_data)
+    if let Err(error) = crate::processor::process_instruction(program_id, accounts, instruction_data) {
+        // logs the AutoswapError variant name of custom errors
+        error.print::<AutoswapError>();
+        return Err(error);
+    }
+    Ok(())
 }
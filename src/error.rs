@@ -0,0 +1,323 @@
+//! Router errors.
+
+use {
+    num_derive::FromPrimitive,
+    solana_program::{
+        decode_error::DecodeError,
+        msg,
+        program_error::{PrintProgramError, ProgramError},
+    },
+    spl_token::error::TokenError,
+    thiserror::Error,
+};
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error, FromPrimitive)]
+pub enum AutoswapError {
+    /// Token balance went down where the router expected it to grow
+    #[error("Unexpected balance decrease")]
+    UnexpectedBalanceDecrease = 1001,
+    /// Token balance went up where the router expected it to shrink
+    #[error("Unexpected balance increase")]
+    UnexpectedBalanceIncrease = 1002,
+    /// Invoked program spent more than the swap input
+    #[error("Invoked program overspent")]
+    Overspent = 1003,
+    /// Swap returned less than the minimum output
+    #[error("Slippage exceeded")]
+    SlippageExceeded = 1004,
+    /// Account the invoked program writes to is passed read-only
+    #[error("Account must be writable")]
+    AccountNotWritable = 1005,
+    /// Swap output is below the configured deliverable minimum
+    #[error("Output is dust")]
+    OutputDust = 1006,
+    /// Token account isn't owned by the token program used to move it
+    #[error("Token program mismatch")]
+    TokenProgramMismatch = 1007,
+    /// No candidate pool can deliver the minimum output
+    #[error("Insufficient output")]
+    InsufficientOutput = 1008,
+    /// Swaps are disabled during the configured maintenance window
+    #[error("Maintenance window")]
+    MaintenanceWindow = 1009,
+    /// Realized output per unit of input is below the minimum rate
+    #[error("Rate too low")]
+    RateTooLow = 1010,
+    /// Swap input is above the configured share of the pool reserve
+    #[error("Consumes too much liquidity")]
+    ConsumesTooMuchLiquidity = 1011,
+    /// Exact output swap needs more input than the maximum
+    #[error("Excessive input")]
+    ExcessiveInput = 1012,
+    /// Token transfer source doesn't hold the amount
+    #[error("Token insufficient funds")]
+    TokenInsufficientFunds = 1101,
+    /// Token transfer source or destination is frozen
+    #[error("Token account frozen")]
+    TokenAccountFrozen = 1102,
+    /// Token transfer authority or mint doesn't match the account
+    #[error("Token owner mismatch")]
+    TokenOwnerMismatch = 1103,
+    /// Admin instruction sequence isn't the next one expected by the config
+    #[error("Bad sequence")]
+    BadSequence = 1104,
+    /// Pool coin and pc vaults are passed in each other's place
+    #[error("Vaults swapped")]
+    VaultsSwapped = 1105,
+    /// Serum event queue has no room left, the market must be cranked
+    #[error("Event queue full")]
+    EventQueueFull = 1106,
+    /// Serum event queue is filled above the configured threshold
+    #[error("Stale market")]
+    StaleMarket = 1107,
+    /// Not enough compute units left to run the next phase of a composed instruction
+    #[error("Out of compute budget")]
+    OutOfComputeBudget = 1108,
+    /// Pool program isn't a supported Raydium program
+    #[error("Invalid pool program")]
+    InvalidPoolProgram = 1109,
+    /// Fee recipient is the account the fee is drawn from, not the configured
+    /// recipient or not a token account of the fee mint
+    #[error("Invalid fee recipient")]
+    InvalidFeeRecipient = 1110,
+    /// Swap executed after its deadline
+    #[error("Deadline exceeded")]
+    DeadlineExceeded = 1111,
+    /// Admin instruction signed by a key other than the config admin
+    #[error("Unauthorized")]
+    Unauthorized = 1112,
+    /// Router is paused by the admin
+    #[error("Paused")]
+    Paused = 1113,
+    /// Program token account mint doesn't match the pool vault on its side
+    #[error("Invalid token mint")]
+    InvalidTokenMint = 1114,
+    /// Token account to close still holds tokens
+    #[error("Non-zero balance")]
+    NonZeroBalance = 1115,
+    /// Transfer and swap phase out of the before, swap, after order or repeated
+    #[error("Invalid swap sequence")]
+    InvalidSwapSequence = 1116,
+    /// Instruction amount is zero where a positive amount is required
+    #[error("Invalid amount")]
+    InvalidAmount = 1117,
+    /// Deposit credited to the program account differs from the amount
+    /// by more than the tolerance
+    #[error("Transfer amount mismatch")]
+    TransferAmountMismatch = 1118,
+    /// Swap input is below the configured minimum swap size
+    #[error("Below minimum swap")]
+    BelowMinimumSwap = 1119,
+    /// Swap would move the pool price by more than the price impact cap
+    #[error("Price impact too high")]
+    PriceImpactTooHigh = 1120,
+    /// Token account is the configured fee recipient
+    #[error("Fee account protected")]
+    FeeAccountProtected = 1121,
+    /// Pool vaults aren't the ones recorded in the amm state
+    #[error("Invalid pool accounts")]
+    InvalidPoolAccounts = 1122,
+    /// Batch holds more legs or candidate pools than MAX_BATCH
+    #[error("Too many swaps")]
+    TooManySwaps = 1123,
+    /// Pool quotes zero output and the client set no min_token_amount_out
+    #[error("Zero output quote")]
+    ZeroOutputQuote = 1124,
+    /// Every SwapConfig fee override slot holds another mint
+    #[error("Fee overrides full")]
+    FeeOverridesFull = 1125,
+    /// Account creation left the new account below the rent-exempt minimum,
+    /// or the payer can't fund it
+    #[error("Insufficient funds for rent")]
+    InsufficientFundsForRent = 1126,
+    /// Swap input and output token accounts hold the same mint
+    #[error("Same input and output mint")]
+    SameInputOutputMint = 1127,
+    /// Swap amounts name both or neither of token a and b as the input
+    #[error("Ambiguous swap direction")]
+    AmbiguousSwapDirection = 1128,
+    /// Protocol and lp fees together above SwapConfig::MAX_FEE_BPS
+    #[error("Fee too high")]
+    FeeTooHigh = 1129,
+    /// Open orders account isn't the one recorded in the amm state
+    #[error("Invalid open orders")]
+    InvalidOpenOrders = 1130,
+    /// Pool protocol is switched off in the config
+    #[error("Protocol disabled")]
+    ProtocolDisabled = 1131,
+}
+
+impl From<AutoswapError> for ProgramError {
+    fn from(e: AutoswapError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for AutoswapError {
+    fn type_of() -> &'static str {
+        "AutoswapError"
+    }
+}
+
+impl PrintProgramError for AutoswapError {
+    fn print<E>(&self) {
+        msg!("Error: {}", self);
+    }
+}
+
+/// Maps the common spl_token transfer failures to AutoswapError.
+/// Other errors are returned unchanged.
+pub fn map_token_error(error: ProgramError) -> ProgramError {
+    let mapped = match error {
+        ProgramError::Custom(code) if code == TokenError::InsufficientFunds as u32 => {
+            AutoswapError::TokenInsufficientFunds
+        }
+        ProgramError::Custom(code) if code == TokenError::AccountFrozen as u32 => {
+            AutoswapError::TokenAccountFrozen
+        }
+        ProgramError::Custom(code)
+            if code == TokenError::OwnerMismatch as u32
+                || code == TokenError::MintMismatch as u32 =>
+        {
+            AutoswapError::TokenOwnerMismatch
+        }
+        _ => return error,
+    };
+    msg!("Error: Token transfer failed with {}, mapped to {:?}", error, mapped);
+    mapped.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::{account_info::AccountInfo, program_pack::Pack, pubkey::Pubkey};
+    use spl_token::state::{Account, AccountState};
+
+    struct TokenAccount {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    fn token_account(mint: Pubkey, owner: Pubkey, amount: u64, state: AccountState) -> TokenAccount {
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint,
+                owner,
+                amount,
+                state,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        TokenAccount {
+            key: Pubkey::new_unique(),
+            lamports: 0,
+            data,
+        }
+    }
+
+    /// Runs a transfer of amount through the spl_token processor and maps its error
+    fn transfer(
+        source: &mut TokenAccount,
+        destination: &mut TokenAccount,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> ProgramError {
+        let token_program = spl_token::id();
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![];
+        let system = solana_program::system_program::id();
+        let accounts = [
+            AccountInfo::new(&source.key, false, true, &mut source.lamports, &mut source.data, &token_program, false, 0),
+            AccountInfo::new(&destination.key, false, true, &mut destination.lamports, &mut destination.data, &token_program, false, 0),
+            AccountInfo::new(authority, true, false, &mut authority_lamports, &mut authority_data, &system, false, 0),
+        ];
+        let instruction = spl_token::instruction::transfer(
+            &token_program,
+            &source.key,
+            &destination.key,
+            authority,
+            &[],
+            amount,
+        )
+        .unwrap();
+
+        map_token_error(
+            spl_token::processor::Processor::process(&token_program, &accounts, &instruction.data)
+                .unwrap_err(),
+        )
+    }
+
+    #[test]
+    fn test_insufficient_funds() {
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut source = token_account(mint, owner, 100, AccountState::Initialized);
+        let mut destination = token_account(mint, owner, 0, AccountState::Initialized);
+
+        assert_eq!(
+            transfer(&mut source, &mut destination, &owner, 101),
+            ProgramError::Custom(1101)
+        );
+    }
+
+    #[test]
+    fn test_account_frozen() {
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut source = token_account(mint, owner, 100, AccountState::Frozen);
+        let mut destination = token_account(mint, owner, 0, AccountState::Initialized);
+
+        assert_eq!(
+            transfer(&mut source, &mut destination, &owner, 10),
+            ProgramError::Custom(1102)
+        );
+    }
+
+    #[test]
+    fn test_owner_mismatch() {
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut source = token_account(mint, owner, 100, AccountState::Initialized);
+        let mut destination = token_account(mint, owner, 0, AccountState::Initialized);
+
+        assert_eq!(
+            transfer(&mut source, &mut destination, &Pubkey::new_unique(), 10),
+            ProgramError::Custom(1103)
+        );
+
+        let mut other_mint = token_account(Pubkey::new_unique(), owner, 0, AccountState::Initialized);
+        assert_eq!(
+            transfer(&mut source, &mut other_mint, &owner, 10),
+            ProgramError::Custom(1103)
+        );
+    }
+
+    #[test]
+    fn test_error_codes_decode() {
+        use num_traits::FromPrimitive;
+
+        for &(code, error) in &[
+            (1004, AutoswapError::SlippageExceeded),
+            (1012, AutoswapError::ExcessiveInput),
+            (1109, AutoswapError::InvalidPoolProgram),
+        ] {
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+            assert_eq!(AutoswapError::from_u32(code), Some(error));
+        }
+        assert_eq!(AutoswapError::from_u32(1013), None);
+        assert_eq!(AutoswapError::SlippageExceeded.to_string(), "Slippage exceeded");
+    }
+
+    #[test]
+    fn test_other_errors_unchanged() {
+        for error in [
+            ProgramError::InvalidArgument,
+            ProgramError::Custom(1004),
+            TokenError::NotRentExempt.into(),
+        ] {
+            assert_eq!(map_token_error(error.clone()), error);
+        }
+    }
+}
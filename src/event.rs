@@ -0,0 +1,92 @@
+//! Binary events for indexers.
+//!
+//! Handlers keep their msg! lines for people reading logs, events are logged
+//! next to them with sol_log_data and show up as base64 "Program data:" lines.
+
+use {
+    crate::utils::pack::check_exact_data_len,
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{log::sol_log_data, msg, program_error::ProgramError},
+};
+
+/// Layout version, the first byte of every event
+pub const EVENT_VERSION: u8 = 1;
+
+/// Protocol byte of events not tied to a pool, e.g. the after_transfer payout
+pub const NO_PROTOCOL: u8 = u8::MAX;
+
+/// Logged at the end of swap and after_transfer
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SwapEvent {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    /// Route protocol byte, see ProgramIDType::from_route_protocol, or NO_PROTOCOL
+    pub protocol: u8,
+    pub fee_paid: u64,
+}
+
+impl SwapEvent {
+    /// version, amount in, amount out, protocol, fee paid
+    pub const LEN: usize = 26;
+
+    pub fn pack(&self) -> [u8; SwapEvent::LEN] {
+        let mut output = [0; SwapEvent::LEN];
+        let data = array_mut_ref![output, 0, SwapEvent::LEN];
+        let (version, amount_in, amount_out, protocol, fee_paid) = mut_array_refs![data, 1, 8, 8, 1, 8];
+
+        version[0] = EVENT_VERSION;
+        *amount_in = self.amount_in.to_le_bytes();
+        *amount_out = self.amount_out.to_le_bytes();
+        protocol[0] = self.protocol;
+        *fee_paid = self.fee_paid.to_le_bytes();
+
+        output
+    }
+
+    /// Decodes an event of the current version
+    pub fn unpack(input: &[u8]) -> Result<SwapEvent, ProgramError> {
+        check_exact_data_len(input, SwapEvent::LEN)?;
+        let input = array_ref![input, 0, SwapEvent::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, amount_in, amount_out, protocol, fee_paid) = array_refs![input, 1, 8, 8, 1, 8];
+        if version[0] != EVENT_VERSION {
+            msg!("Error: Unknown event version {}", version[0]);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(SwapEvent {
+            amount_in: u64::from_le_bytes(*amount_in),
+            amount_out: u64::from_le_bytes(*amount_out),
+            protocol: protocol[0],
+            fee_paid: u64::from_le_bytes(*fee_paid),
+        })
+    }
+
+    pub fn log(&self) {
+        sol_log_data(&[&self.pack()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_event_round_trip() {
+        let event = SwapEvent {
+            amount_in: 1_000,
+            amount_out: 1_994,
+            protocol: 0,
+            fee_paid: 3,
+        };
+        let data = event.pack();
+
+        assert_eq!(data[0], EVENT_VERSION);
+        assert_eq!(SwapEvent::unpack(&data), Ok(event));
+        assert_eq!(SwapEvent::unpack(&data[..SwapEvent::LEN - 1]), Err(ProgramError::AccountDataTooSmall));
+
+        let mut data = data;
+        data[0] = EVENT_VERSION + 1;
+        assert_eq!(SwapEvent::unpack(&data), Err(ProgramError::InvalidAccountData));
+    }
+}
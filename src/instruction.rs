@@ -1,34 +1,264 @@
 //! Raydium router instructions.
 
 use {
-    crate::utils::pack::check_data_len,
+    crate::{
+        protocol::raydium::SwapDirection,
+        state::FeeToken,
+        utils::pack::{self, check_data_len, check_exact_data_len},
+    },
     arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
     num_enum::TryFromPrimitive,
-    solana_program::program_error::ProgramError,
+    solana_program::{program_error::ProgramError, pubkey::Pubkey},
 };
 
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(borsh::BorshSerialize, borsh::BorshDeserialize))]
 pub enum AmmInstruction {
     /// Swap tokens in the AMM Pool
     /// # Account references are protocol specific,
     ///   see particular Router instructions handlers for more info
     BeforeTransfer {
         amount: u64,
+        /// Most the deposit may fall short of amount, for mints with a transfer fee
+        tolerance: u64,
     },
-    Swap {
-        token_a_amount_in: u64,
-        token_b_amount_in: u64,
-        min_token_amount_out: u64,
-    },
+    /// Leaves the amount out in return data, see set_swap_result for the layout
+    Swap(SwapParams),
+    /// Pays out the swap, referral_bps of the protocol fee goes to an optional
+    /// referrer, see after_transfer for the accounts. A non-zero fee_amount is
+    /// the fee taken instead of the bps of amount, it can't be below that fee
     AfterTransfer {
         amount: u64,
+        referral_bps: u16,
+        fee_amount: u64,
     },
+    /// Creates the router PDA of instance, see tokens::ProgramAuthority
     CreateAccount {
         size: u64,
+        instance: u8,
     },
+    /// Admin only, moves amount out of the program sol account
     Harvest {
         amount: u64,
-    }
+    },
+    /// Admin only, sets the smallest swap output worth delivering
+    SetMinDeliverableOut {
+        amount: u64,
+        sequence: u64,
+    },
+    /// Admin only, sets the smallest fee taken in after_transfer
+    SetMinFee {
+        amount: u64,
+        sequence: u64,
+    },
+    /// Admin only, sets the protocol and lp fee shares in basis points
+    SetFeeSplit {
+        fee_bps: u16,
+        lp_fee_bps: u16,
+        sequence: u64,
+    },
+    /// Admin only, reports config inconsistencies as a bitmask in return data
+    /// and optionally repairs the clampable ones
+    VerifyConfig {
+        repair: bool,
+    },
+    /// Admin only, moves the PDA's lamports above rent exemption to a treasury
+    SweepLamports,
+    /// Swap through the candidate pool with the lowest fee tier that still meets
+    /// min_token_amount_out, see swap_best_fee for the accounts
+    SwapBestFee(SwapParams),
+    /// Admin only, rejects swaps from start_slot up to end_slot, an empty range disables it
+    SetMaintenanceWindow {
+        start_slot: u64,
+        end_slot: u64,
+        sequence: u64,
+    },
+    /// Admin only, lets before_transfer skip the deposit when the program account is already funded
+    SetSkipFundedDeposit {
+        enabled: bool,
+        sequence: u64,
+    },
+    /// Admin only, caps the share of the input reserve one swap may add, zero disables it
+    SetMaxPoolConsume {
+        bps: u16,
+        sequence: u64,
+    },
+    /// Swap, then stake the output in a Raydium farm, see swap_and_stake for the accounts
+    SwapAndStake(SwapParams),
+    /// Admin only, selects the token after_transfer draws the fee from
+    SetFeeToken {
+        fee_token: FeeToken,
+        sequence: u64,
+    },
+    /// Admin only, reorders swapped pool coin and pc vaults instead of rejecting the swap
+    SetAutoCorrectVaults {
+        enabled: bool,
+        sequence: u64,
+    },
+    /// Swap for at least token_amount_out, spending at most max_token_amount_in,
+    /// same accounts as Swap
+    SwapExactOut {
+        token_amount_out: u64,
+        max_token_amount_in: u64,
+        direction: SwapDirection,
+    },
+    /// Admin only, caps the Serum event queue fill a swap accepts, zero disables it
+    SetMaxEventQueueFill {
+        bps: u16,
+        sequence: u64,
+    },
+    /// Swap through an Orca pool, see swap_orca for the accounts
+    SwapOrca {
+        token_a_amount_in: u64,
+        token_b_amount_in: u64,
+        min_token_amount_out: u64,
+    },
+    /// Swap for at least the pool quote less slippage_bps, same accounts as Swap
+    SwapWithSlippageBps {
+        token_a_amount_in: u64,
+        token_b_amount_in: u64,
+        slippage_bps: u16,
+    },
+    /// Creates the config in the router PDA of instance with the signer as admin
+    Initialize {
+        fee_bps: u16,
+        instance: u8,
+    },
+    /// Admin only, sets the protocol fee keeping the lp fee
+    SetFee {
+        fee_bps: u16,
+        sequence: u64,
+    },
+    /// Admin only, sets the fee recipient to the token account passed in
+    SetFeeRecipient {
+        sequence: u64,
+    },
+    /// Admin only, stops swaps and transfers while set
+    SetPaused {
+        paused: bool,
+        sequence: u64,
+    },
+    /// Swap through two Raydium pools, min_final_out applies to the second leg only,
+    /// see swap_two_hop for the accounts
+    SwapTwoHop {
+        amount_in: u64,
+        min_final_out: u64,
+    },
+    /// Admin only, moves amount of the collected fees to a destination,
+    /// 0 withdraws the whole balance
+    WithdrawFees {
+        amount: u64,
+    },
+    /// Wrap native SOL, swap it and return the unspent SOL,
+    /// see swap_native_in for the accounts
+    SwapNativeIn {
+        amount_in: u64,
+        min_token_amount_out: u64,
+    },
+    /// Admin only, closes an empty token account held by the router PDA and sends
+    /// its rent to a destination
+    CloseTempAccount,
+    /// Leaves the Raydium pool quote in return data without swapping, see
+    /// quote_swap for the accounts and layout
+    Quote {
+        token_a_amount_in: u64,
+        token_b_amount_in: u64,
+    },
+    /// Swap against the Serum order book with an immediate or cancel order,
+    /// token a is the market coin, see swap_serum_direct for the accounts
+    SwapSerumDirect {
+        token_a_amount_in: u64,
+        token_b_amount_in: u64,
+        min_token_amount_out: u64,
+    },
+    /// Swap through a Saber stable swap pool, see swap_saber for the accounts
+    SwapSaber {
+        token_a_amount_in: u64,
+        token_b_amount_in: u64,
+        min_token_amount_out: u64,
+    },
+    /// Sell amount_in of token a through the protocol picked by the protocol byte,
+    /// see route for the bytes, accounts are those of the protocol instruction
+    Route {
+        protocol: u8,
+        amount_in: u64,
+        min_out: u64,
+    },
+    /// Followed by count packed Route instructions, swapped in order and reverted
+    /// together, see batch_swap for the accounts
+    BatchSwap {
+        count: u8,
+    },
+    /// Admin only, rejects swaps spending less than amount, zero disables it
+    SetMinSwap {
+        amount: u64,
+        sequence: u64,
+    },
+    /// Admin only, moves the whole balance of a token account held by the router
+    /// PDA to a destination, the fee recipient only with allow_fee_account
+    RescueTokens {
+        allow_fee_account: bool,
+    },
+    /// Leaves the crate version and build features in return data, takes no
+    /// accounts, see version::pack_version for the layout
+    GetVersion,
+    /// Admin only, proposes the account passed in as the next admin, the config
+    /// admin changes once it signs AcceptAdmin. The default Pubkey withdraws the proposal
+    TransferAdmin {
+        sequence: u64,
+    },
+    /// Signed by the admin a TransferAdmin proposed, makes it the config admin
+    AcceptAdmin,
+    /// Admin only, sets the protocol fee of fees drawn in mint,
+    /// SwapConfig::NO_FEE_OVERRIDE returns the mint to the config fee
+    SetMintFee {
+        mint: Pubkey,
+        fee_bps: u16,
+        sequence: u64,
+    },
+    /// Admin only, moves the whole balance of count fee token accounts held by
+    /// the router PDA to their destinations, see sweep_fees for the accounts
+    SweepFees {
+        count: u8,
+    },
+    /// Leaves the packed SwapConfig of the program account passed in in return
+    /// data, see SwapConfig::unpack. Read only, clients simulate the transaction
+    GetConfig,
+    /// Admin only, sets the slippage off the pool quote a Swap without
+    /// min_token_amount_out accepts, see SwapConfig::default_slippage_bps
+    SetDefaultSlippage {
+        slippage_bps: u16,
+        sequence: u64,
+    },
+    /// Admin only, switches swaps through the pools of a protocol, numbered as
+    /// in Route, on or off
+    SetProtocolEnabled {
+        protocol: u8,
+        enabled: bool,
+        sequence: u64,
+    },
+}
+
+/// Swap amounts, exactly one of the two inputs must be non-zero
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "borsh", derive(borsh::BorshSerialize, borsh::BorshDeserialize))]
+pub struct SwapParams {
+    pub token_a_amount_in: u64,
+    pub token_b_amount_in: u64,
+    /// Lower bound on the output in base units of the output mint, the pool quote
+    /// is used when it is higher. Zero takes the quote less the config default slippage
+    pub min_token_amount_out: u64,
+    /// Lower bound on the price as min_rate_num / min_rate_den whole output tokens
+    /// per whole input token, scaled by the pool decimals. A zero denominator disables it
+    pub min_rate_num: u64,
+    pub min_rate_den: u64,
+    /// Opaque id echoed in logs and after the amount out in return data, all zeros means none
+    pub client_order_id: [u8; 16],
+    /// Unix timestamp after which the swap is rejected, zero means none
+    pub deadline: i64,
+    /// Most the swap may move the pool price, in basis points of the price
+    /// before it, zero means no cap
+    pub max_price_impact_bps: u16,
 }
 
 #[repr(u8)]
@@ -39,19 +269,116 @@ pub enum AmmInstructionType {
     AfterTransfer,
     CreateAccount,
     Harvest,
+    SetMinDeliverableOut,
+    SetMinFee,
+    SetFeeSplit,
+    VerifyConfig,
+    SweepLamports,
+    SwapBestFee,
+    SetMaintenanceWindow,
+    SetSkipFundedDeposit,
+    SetMaxPoolConsume,
+    SwapAndStake,
+    SetFeeToken,
+    SetAutoCorrectVaults,
+    SwapExactOut,
+    SetMaxEventQueueFill,
+    SwapOrca,
+    SwapWithSlippageBps,
+    Initialize,
+    SetFee,
+    SetFeeRecipient,
+    SetPaused,
+    SwapTwoHop,
+    WithdrawFees,
+    SwapNativeIn,
+    CloseTempAccount,
+    Quote,
+    SwapSerumDirect,
+    SwapSaber,
+    Route,
+    BatchSwap,
+    SetMinSwap,
+    RescueTokens,
+    GetVersion,
+    TransferAdmin,
+    AcceptAdmin,
+    SetMintFee,
+    SweepFees,
+    GetConfig,
+    SetDefaultSlippage,
+    SetProtocolEnabled,
 }
 
 impl AmmInstruction {
     pub const LEN: usize = 9;
-    pub const SWAP_LEN: usize = 25;
+    pub const SWAP_LEN: usize = 67;
+    /// Admin config setters append the expected config sequence to a LEN payload,
+    /// see SwapConfig::check_sequence
+    pub const SEQUENCED_LEN: usize = 17;
+    pub const SET_MAINTENANCE_WINDOW_LEN: usize = 25;
+    pub const SWAP_EXACT_OUT_LEN: usize = 25;
+    pub const SWAP_ORCA_LEN: usize = 25;
+    pub const SWAP_WITH_SLIPPAGE_BPS_LEN: usize = 25;
+    pub const SWAP_TWO_HOP_LEN: usize = 17;
+    pub const SWAP_NATIVE_IN_LEN: usize = 17;
+    pub const BEFORE_TRANSFER_LEN: usize = 17;
+    pub const CREATE_ACCOUNT_LEN: usize = 10;
+    pub const AFTER_TRANSFER_LEN: usize = 25;
+    pub const QUOTE_LEN: usize = 17;
+    pub const SWAP_SERUM_DIRECT_LEN: usize = 25;
+    pub const SWAP_SABER_LEN: usize = 25;
+    pub const ROUTE_LEN: usize = 18;
+    /// BatchSwap header, count Route instructions of ROUTE_LEN follow
+    pub const BATCH_SWAP_LEN: usize = 2;
+    pub const SET_MINT_FEE_LEN: usize = 43;
 
     pub fn pack(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
         match self {
             Self::BeforeTransfer { .. } => self.pack_before_transfer(output),
-            Self::Swap { .. } => self.pack_swap(output),
+            Self::Swap(..) => self.pack_swap(output),
             Self::AfterTransfer { .. } => self.pack_after_transfer(output),
             Self::CreateAccount { .. } => self.pack_create_account(output),
             Self::Harvest { .. } => self.pack_harvest(output),
+            Self::SetMinDeliverableOut { .. } => self.pack_set_min_deliverable_out(output),
+            Self::SetMinFee { .. } => self.pack_set_min_fee(output),
+            Self::SetFeeSplit { .. } => self.pack_set_fee_split(output),
+            Self::VerifyConfig { .. } => self.pack_verify_config(output),
+            Self::SweepLamports => self.pack_sweep_lamports(output),
+            Self::SwapBestFee(..) => self.pack_swap(output),
+            Self::SetMaintenanceWindow { .. } => self.pack_set_maintenance_window(output),
+            Self::SetSkipFundedDeposit { .. } => self.pack_set_skip_funded_deposit(output),
+            Self::SetMaxPoolConsume { .. } => self.pack_set_max_pool_consume(output),
+            Self::SwapAndStake(..) => self.pack_swap(output),
+            Self::SetFeeToken { .. } => self.pack_set_fee_token(output),
+            Self::SetAutoCorrectVaults { .. } => self.pack_set_auto_correct_vaults(output),
+            Self::SwapExactOut { .. } => self.pack_swap_exact_out(output),
+            Self::SetMaxEventQueueFill { .. } => self.pack_set_max_event_queue_fill(output),
+            Self::SwapOrca { .. } => self.pack_swap_orca(output),
+            Self::SwapWithSlippageBps { .. } => self.pack_swap_with_slippage_bps(output),
+            Self::Initialize { .. } => self.pack_initialize(output),
+            Self::SetFee { .. } => self.pack_set_fee(output),
+            Self::SetFeeRecipient { .. } => self.pack_set_fee_recipient(output),
+            Self::SetPaused { .. } => self.pack_set_paused(output),
+            Self::SwapTwoHop { .. } => self.pack_swap_two_hop(output),
+            Self::WithdrawFees { .. } => self.pack_withdraw_fees(output),
+            Self::SwapNativeIn { .. } => self.pack_swap_native_in(output),
+            Self::CloseTempAccount => self.pack_close_temp_account(output),
+            Self::Quote { .. } => self.pack_quote(output),
+            Self::SwapSerumDirect { .. } => self.pack_swap_serum_direct(output),
+            Self::SwapSaber { .. } => self.pack_swap_saber(output),
+            Self::Route { .. } => self.pack_route(output),
+            Self::BatchSwap { .. } => self.pack_batch_swap(output),
+            Self::SetMinSwap { .. } => self.pack_set_min_swap(output),
+            Self::RescueTokens { .. } => self.pack_rescue_tokens(output),
+            Self::GetVersion => self.pack_get_version(output),
+            Self::TransferAdmin { .. } => self.pack_transfer_admin(output),
+            Self::AcceptAdmin => self.pack_accept_admin(output),
+            Self::SetMintFee { .. } => self.pack_set_mint_fee(output),
+            Self::SweepFees { .. } => self.pack_sweep_fees(output),
+            Self::GetConfig => self.pack_get_config(output),
+            Self::SetDefaultSlippage { .. } => self.pack_set_default_slippage(output),
+            Self::SetProtocolEnabled { .. } => self.pack_set_protocol_enabled(output),
         }
     }
 
@@ -61,30 +388,92 @@ impl AmmInstruction {
             .or(Err(ProgramError::InvalidInstructionData))?;
         match instruction_type {
             AmmInstructionType::BeforeTransfer => AmmInstruction::unpack_before_transfer(input),
-            AmmInstructionType::Swap => AmmInstruction::unpack_swap(input),
+            AmmInstructionType::Swap => {
+                Ok(AmmInstruction::Swap(AmmInstruction::unpack_swap_params(input)?))
+            }
             AmmInstructionType::AfterTransfer => AmmInstruction::unpack_after_transfer(input),
             AmmInstructionType::CreateAccount => AmmInstruction::unpack_create_account(input),
             AmmInstructionType::Harvest => AmmInstruction::unpack_harvest(input),
+            AmmInstructionType::SetMinDeliverableOut => {
+                AmmInstruction::unpack_set_min_deliverable_out(input)
+            }
+            AmmInstructionType::SetMinFee => AmmInstruction::unpack_set_min_fee(input),
+            AmmInstructionType::SetFeeSplit => AmmInstruction::unpack_set_fee_split(input),
+            AmmInstructionType::VerifyConfig => AmmInstruction::unpack_verify_config(input),
+            AmmInstructionType::SweepLamports => AmmInstruction::unpack_sweep_lamports(input),
+            AmmInstructionType::SwapBestFee => {
+                Ok(AmmInstruction::SwapBestFee(AmmInstruction::unpack_swap_params(input)?))
+            }
+            AmmInstructionType::SetMaintenanceWindow => {
+                AmmInstruction::unpack_set_maintenance_window(input)
+            }
+            AmmInstructionType::SetSkipFundedDeposit => {
+                AmmInstruction::unpack_set_skip_funded_deposit(input)
+            }
+            AmmInstructionType::SetMaxPoolConsume => {
+                AmmInstruction::unpack_set_max_pool_consume(input)
+            }
+            AmmInstructionType::SwapAndStake => {
+                Ok(AmmInstruction::SwapAndStake(AmmInstruction::unpack_swap_params(input)?))
+            }
+            AmmInstructionType::SetFeeToken => AmmInstruction::unpack_set_fee_token(input),
+            AmmInstructionType::SetAutoCorrectVaults => {
+                AmmInstruction::unpack_set_auto_correct_vaults(input)
+            }
+            AmmInstructionType::SwapExactOut => AmmInstruction::unpack_swap_exact_out(input),
+            AmmInstructionType::SetMaxEventQueueFill => AmmInstruction::unpack_set_max_event_queue_fill(input),
+            AmmInstructionType::SwapOrca => AmmInstruction::unpack_swap_orca(input),
+            AmmInstructionType::SwapWithSlippageBps => {
+                AmmInstruction::unpack_swap_with_slippage_bps(input)
+            }
+            AmmInstructionType::Initialize => AmmInstruction::unpack_initialize(input),
+            AmmInstructionType::SetFee => AmmInstruction::unpack_set_fee(input),
+            AmmInstructionType::SetFeeRecipient => AmmInstruction::unpack_set_fee_recipient(input),
+            AmmInstructionType::SetPaused => AmmInstruction::unpack_set_paused(input),
+            AmmInstructionType::SwapTwoHop => AmmInstruction::unpack_swap_two_hop(input),
+            AmmInstructionType::WithdrawFees => AmmInstruction::unpack_withdraw_fees(input),
+            AmmInstructionType::SwapNativeIn => AmmInstruction::unpack_swap_native_in(input),
+            AmmInstructionType::CloseTempAccount => {
+                AmmInstruction::unpack_close_temp_account(input)
+            }
+            AmmInstructionType::Quote => AmmInstruction::unpack_quote(input),
+            AmmInstructionType::SwapSerumDirect => AmmInstruction::unpack_swap_serum_direct(input),
+            AmmInstructionType::SwapSaber => AmmInstruction::unpack_swap_saber(input),
+            AmmInstructionType::Route => AmmInstruction::unpack_route(input),
+            AmmInstructionType::BatchSwap => AmmInstruction::unpack_batch_swap(input),
+            AmmInstructionType::SetMinSwap => AmmInstruction::unpack_set_min_swap(input),
+            AmmInstructionType::RescueTokens => AmmInstruction::unpack_rescue_tokens(input),
+            AmmInstructionType::GetVersion => AmmInstruction::unpack_get_version(input),
+            AmmInstructionType::TransferAdmin => AmmInstruction::unpack_transfer_admin(input),
+            AmmInstructionType::AcceptAdmin => AmmInstruction::unpack_accept_admin(input),
+            AmmInstructionType::SetMintFee => AmmInstruction::unpack_set_mint_fee(input),
+            AmmInstructionType::SweepFees => AmmInstruction::unpack_sweep_fees(input),
+            AmmInstructionType::GetConfig => AmmInstruction::unpack_get_config(input),
+            AmmInstructionType::SetDefaultSlippage => AmmInstruction::unpack_set_default_slippage(input),
+            AmmInstructionType::SetProtocolEnabled => AmmInstruction::unpack_set_protocol_enabled(input),
         }
     }
 
     fn pack_before_transfer(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
-        check_data_len(output, AmmInstruction::LEN)?;
+        check_data_len(output, AmmInstruction::BEFORE_TRANSFER_LEN)?;
         if let AmmInstruction::BeforeTransfer {
             amount,
+            tolerance,
         } = self
         {
-            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let output = array_mut_ref![output, 0, AmmInstruction::BEFORE_TRANSFER_LEN];
             let (
                 instruction_type_pack,
                 amount_pack,
-            ) = mut_array_refs![output, 1, 8];
+                tolerance_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
 
             instruction_type_pack[0] = AmmInstructionType::BeforeTransfer as u8;
 
             *amount_pack = amount.to_le_bytes();
+            *tolerance_pack = tolerance.to_le_bytes();
 
-            Ok(AmmInstruction::LEN)
+            Ok(AmmInstruction::BEFORE_TRANSFER_LEN)
         } else {
             Err(ProgramError::InvalidInstructionData)
         }
@@ -93,71 +482,89 @@ impl AmmInstruction {
     fn pack_swap(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
         check_data_len(output, AmmInstruction::SWAP_LEN)?;
 
-        if let AmmInstruction::Swap {
-            token_a_amount_in,
-            token_b_amount_in,
-            min_token_amount_out,
-        } = self
-        {
-            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_LEN];
-            let (
-                instruction_type_pack,
-                token_a_amount_in_pack,
-                token_b_amount_in_pack,
-                min_token_amount_out_pack,
-            ) = mut_array_refs![output, 1, 8, 8, 8];
+        let (instruction_type, params) = match self {
+            AmmInstruction::Swap(params) => (AmmInstructionType::Swap, params),
+            AmmInstruction::SwapBestFee(params) => (AmmInstructionType::SwapBestFee, params),
+            AmmInstruction::SwapAndStake(params) => (AmmInstructionType::SwapAndStake, params),
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
 
-            instruction_type_pack[0] = AmmInstructionType::Swap as u8;
+        let output = array_mut_ref![output, 0, AmmInstruction::SWAP_LEN];
+        let (
+            instruction_type_pack,
+            token_a_amount_in_pack,
+            token_b_amount_in_pack,
+            min_token_amount_out_pack,
+            min_rate_num_pack,
+            min_rate_den_pack,
+            client_order_id_pack,
+            deadline_pack,
+            max_price_impact_bps_pack,
+        ) = mut_array_refs![output, 1, 8, 8, 8, 8, 8, 16, 8, 2];
 
-            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
-            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
-            *min_token_amount_out_pack = min_token_amount_out.to_le_bytes();
+        instruction_type_pack[0] = instruction_type as u8;
 
-            Ok(AmmInstruction::SWAP_LEN)
-        } else {
-            Err(ProgramError::InvalidInstructionData)
-        }
+        *token_a_amount_in_pack = params.token_a_amount_in.to_le_bytes();
+        *token_b_amount_in_pack = params.token_b_amount_in.to_le_bytes();
+        *min_token_amount_out_pack = params.min_token_amount_out.to_le_bytes();
+        *min_rate_num_pack = params.min_rate_num.to_le_bytes();
+        *min_rate_den_pack = params.min_rate_den.to_le_bytes();
+        *client_order_id_pack = params.client_order_id;
+        *deadline_pack = params.deadline.to_le_bytes();
+        *max_price_impact_bps_pack = params.max_price_impact_bps.to_le_bytes();
+
+        Ok(AmmInstruction::SWAP_LEN)
     }
 
     fn pack_after_transfer(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
-        check_data_len(output, AmmInstruction::LEN)?;
+        check_data_len(output, AmmInstruction::AFTER_TRANSFER_LEN)?;
         if let AmmInstruction::AfterTransfer {
             amount,
+            referral_bps,
+            fee_amount,
         } = self
         {
-            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let output = array_mut_ref![output, 0, AmmInstruction::AFTER_TRANSFER_LEN];
             let (
                 instruction_type_pack,
                 amount_pack,
-            ) = mut_array_refs![output, 1, 8];
+                referral_bps_pack,
+                _,
+                fee_amount_pack,
+            ) = mut_array_refs![output, 1, 8, 2, 6, 8];
 
             instruction_type_pack[0] = AmmInstructionType::AfterTransfer as u8;
 
             *amount_pack = amount.to_le_bytes();
+            *referral_bps_pack = referral_bps.to_le_bytes();
+            *fee_amount_pack = fee_amount.to_le_bytes();
 
-            Ok(AmmInstruction::LEN)
+            Ok(AmmInstruction::AFTER_TRANSFER_LEN)
         } else {
             Err(ProgramError::InvalidInstructionData)
         }
     }
 
     fn pack_create_account(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
-        check_data_len(output, AmmInstruction::LEN)?;
+        check_data_len(output, AmmInstruction::CREATE_ACCOUNT_LEN)?;
         if let AmmInstruction::CreateAccount {
             size,
+            instance,
         } = self
         {
-            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let output = array_mut_ref![output, 0, AmmInstruction::CREATE_ACCOUNT_LEN];
             let (
                 instruction_type_pack,
                 size_pack,
-            ) = mut_array_refs![output, 1, 8];
+                instance_pack,
+            ) = mut_array_refs![output, 1, 8, 1];
 
             instruction_type_pack[0] = AmmInstructionType::CreateAccount as u8;
 
             *size_pack = size.to_le_bytes();
+            instance_pack[0] = *instance;
 
-            Ok(AmmInstruction::LEN)
+            Ok(AmmInstruction::CREATE_ACCOUNT_LEN)
         } else {
             Err(ProgramError::InvalidInstructionData)
         }
@@ -185,78 +592,1907 @@ impl AmmInstruction {
         }
     }
 
-    fn unpack_before_transfer(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
-        check_data_len(input, AmmInstruction::LEN)?;
+    fn pack_set_min_deliverable_out(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetMinDeliverableOut {
+            amount,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                amount_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
 
-        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (amount, _) = array_refs![input, 8, 0];
+            instruction_type_pack[0] = AmmInstructionType::SetMinDeliverableOut as u8;
 
-        Ok(Self::BeforeTransfer {
-            amount: u64::from_le_bytes(*amount),
-        })
+            *amount_pack = amount.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
     }
 
-    fn unpack_swap(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
-        check_data_len(input, AmmInstruction::SWAP_LEN)?;
+    fn pack_set_min_fee(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetMinFee {
+            amount,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                amount_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMinFee as u8;
 
-        let input = array_ref![input, 1, AmmInstruction::SWAP_LEN - 1];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (token_a_amount_in, token_b_amount_in, min_token_amount_out) =
-            array_refs![input, 8, 8, 8];
+            *amount_pack = amount.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
 
-        Ok(Self::Swap {
-            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
-            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
-            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
-        })
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
     }
 
-    fn unpack_after_transfer(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
-        check_data_len(input, AmmInstruction::LEN)?;
+    fn pack_set_fee_split(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetFeeSplit {
+            fee_bps,
+            lp_fee_bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                fee_bps_pack,
+                lp_fee_bps_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 2, 2, 4, 8];
 
-        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (amount, _) = array_refs![input, 8, 0];
+            instruction_type_pack[0] = AmmInstructionType::SetFeeSplit as u8;
 
-        Ok(Self::AfterTransfer {
-            amount: u64::from_le_bytes(*amount),
-        })
+            *fee_bps_pack = fee_bps.to_le_bytes();
+            *lp_fee_bps_pack = lp_fee_bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
     }
 
-    fn unpack_create_account(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
-        check_data_len(input, AmmInstruction::LEN)?;
+    fn pack_verify_config(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::VerifyConfig {
+            repair,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                repair_pack,
+                _,
+            ) = mut_array_refs![output, 1, 1, 7];
 
-        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (size, _) = array_refs![input, 8, 0];
+            instruction_type_pack[0] = AmmInstructionType::VerifyConfig as u8;
 
-        Ok(Self::CreateAccount {
-            size: u64::from_le_bytes(*size),
-        })
+            repair_pack[0] = *repair as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
     }
 
-    fn unpack_harvest(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
-        check_data_len(input, AmmInstruction::LEN)?;
+    fn pack_sweep_lamports(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::SweepLamports = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8];
 
-        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
-        #[allow(clippy::ptr_offset_with_cast)]
-        let (amount, _) = array_refs![input, 8, 0];
+            instruction_type_pack[0] = AmmInstructionType::SweepLamports as u8;
 
-        Ok(Self::Harvest {
-            amount: u64::from_le_bytes(*amount),
-        })
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
     }
-}
 
-impl std::fmt::Display for AmmInstructionType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match *self {
-            AmmInstructionType::BeforeTransfer => write!(f, "before transfer"),
-            AmmInstructionType::Swap => write!(f, "Swap"),
-            AmmInstructionType::AfterTransfer => write!(f, "before transfer"),
-            AmmInstructionType::CreateAccount => write!(f, "create account"),
-            AmmInstructionType::Harvest => write!(f, "harvest"),
+    fn pack_set_maintenance_window(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SET_MAINTENANCE_WINDOW_LEN)?;
+        if let AmmInstruction::SetMaintenanceWindow {
+            start_slot,
+            end_slot,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SET_MAINTENANCE_WINDOW_LEN];
+            let (
+                instruction_type_pack,
+                start_slot_pack,
+                end_slot_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMaintenanceWindow as u8;
+
+            *start_slot_pack = start_slot.to_le_bytes();
+            *end_slot_pack = end_slot.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SET_MAINTENANCE_WINDOW_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_skip_funded_deposit(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetSkipFundedDeposit {
+            enabled,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                enabled_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 1, 7, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetSkipFundedDeposit as u8;
+
+            enabled_pack[0] = *enabled as u8;
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_max_pool_consume(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetMaxPoolConsume {
+            bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                bps_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 2, 6, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMaxPoolConsume as u8;
+
+            *bps_pack = bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_fee_token(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetFeeToken {
+            fee_token,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                fee_token_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 1, 7, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetFeeToken as u8;
+
+            fee_token_pack[0] = *fee_token as u8;
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_auto_correct_vaults(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetAutoCorrectVaults {
+            enabled,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                enabled_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 1, 7, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetAutoCorrectVaults as u8;
+
+            enabled_pack[0] = *enabled as u8;
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_exact_out(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_EXACT_OUT_LEN)?;
+        if let AmmInstruction::SwapExactOut {
+            token_amount_out,
+            max_token_amount_in,
+            direction,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_EXACT_OUT_LEN];
+            let (
+                instruction_type_pack,
+                token_amount_out_pack,
+                max_token_amount_in_pack,
+                direction_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8, 8, 1, 7];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapExactOut as u8;
+
+            *token_amount_out_pack = token_amount_out.to_le_bytes();
+            *max_token_amount_in_pack = max_token_amount_in.to_le_bytes();
+            direction_pack[0] = *direction as u8;
+
+            Ok(AmmInstruction::SWAP_EXACT_OUT_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
         }
     }
-}
\ No newline at end of file
+
+    fn pack_set_max_event_queue_fill(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetMaxEventQueueFill {
+            bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                bps_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 2, 6, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMaxEventQueueFill as u8;
+
+            *bps_pack = bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_orca(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_ORCA_LEN)?;
+        if let AmmInstruction::SwapOrca {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_ORCA_LEN];
+            let (
+                instruction_type_pack,
+                token_a_amount_in_pack,
+                token_b_amount_in_pack,
+                min_token_amount_out_pack,
+            ) = mut_array_refs![output, 1, 8, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapOrca as u8;
+
+            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
+            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
+            *min_token_amount_out_pack = min_token_amount_out.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_ORCA_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_with_slippage_bps(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN)?;
+        if let AmmInstruction::SwapWithSlippageBps {
+            token_a_amount_in,
+            token_b_amount_in,
+            slippage_bps,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN];
+            let (
+                instruction_type_pack,
+                token_a_amount_in_pack,
+                token_b_amount_in_pack,
+                slippage_bps_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8, 8, 2, 6];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapWithSlippageBps as u8;
+
+            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
+            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
+            *slippage_bps_pack = slippage_bps.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_initialize(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::Initialize {
+            fee_bps,
+            instance,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                fee_bps_pack,
+                instance_pack,
+                _,
+            ) = mut_array_refs![output, 1, 2, 1, 5];
+
+            instruction_type_pack[0] = AmmInstructionType::Initialize as u8;
+
+            *fee_bps_pack = fee_bps.to_le_bytes();
+            instance_pack[0] = *instance;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_fee(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetFee {
+            fee_bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                fee_bps_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 2, 6, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetFee as u8;
+
+            *fee_bps_pack = fee_bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_fee_recipient(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::SetFeeRecipient {
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetFeeRecipient as u8;
+
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_paused(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetPaused {
+            paused,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                paused_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 1, 7, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetPaused as u8;
+
+            paused_pack[0] = *paused as u8;
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_two_hop(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_TWO_HOP_LEN)?;
+        if let AmmInstruction::SwapTwoHop {
+            amount_in,
+            min_final_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_TWO_HOP_LEN];
+            let (
+                instruction_type_pack,
+                amount_in_pack,
+                min_final_out_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapTwoHop as u8;
+
+            *amount_in_pack = amount_in.to_le_bytes();
+            *min_final_out_pack = min_final_out.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_TWO_HOP_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_withdraw_fees(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::WithdrawFees {
+            amount,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                amount_pack,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::WithdrawFees as u8;
+
+            *amount_pack = amount.to_le_bytes();
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_native_in(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_NATIVE_IN_LEN)?;
+        if let AmmInstruction::SwapNativeIn {
+            amount_in,
+            min_token_amount_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_NATIVE_IN_LEN];
+            let (
+                instruction_type_pack,
+                amount_in_pack,
+                min_token_amount_out_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapNativeIn as u8;
+
+            *amount_in_pack = amount_in.to_le_bytes();
+            *min_token_amount_out_pack = min_token_amount_out.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_NATIVE_IN_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_close_temp_account(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::CloseTempAccount = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::CloseTempAccount as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_quote(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::QUOTE_LEN)?;
+        if let AmmInstruction::Quote {
+            token_a_amount_in,
+            token_b_amount_in,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::QUOTE_LEN];
+            let (
+                instruction_type_pack,
+                token_a_amount_in_pack,
+                token_b_amount_in_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::Quote as u8;
+
+            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
+            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
+
+            Ok(AmmInstruction::QUOTE_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_serum_direct(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_SERUM_DIRECT_LEN)?;
+        if let AmmInstruction::SwapSerumDirect {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_SERUM_DIRECT_LEN];
+            let (
+                instruction_type_pack,
+                token_a_amount_in_pack,
+                token_b_amount_in_pack,
+                min_token_amount_out_pack,
+            ) = mut_array_refs![output, 1, 8, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapSerumDirect as u8;
+
+            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
+            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
+            *min_token_amount_out_pack = min_token_amount_out.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_SERUM_DIRECT_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_swap_saber(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SWAP_SABER_LEN)?;
+        if let AmmInstruction::SwapSaber {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SWAP_SABER_LEN];
+            let (
+                instruction_type_pack,
+                token_a_amount_in_pack,
+                token_b_amount_in_pack,
+                min_token_amount_out_pack,
+            ) = mut_array_refs![output, 1, 8, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SwapSaber as u8;
+
+            *token_a_amount_in_pack = token_a_amount_in.to_le_bytes();
+            *token_b_amount_in_pack = token_b_amount_in.to_le_bytes();
+            *min_token_amount_out_pack = min_token_amount_out.to_le_bytes();
+
+            Ok(AmmInstruction::SWAP_SABER_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_route(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::ROUTE_LEN)?;
+        if let AmmInstruction::Route {
+            protocol,
+            amount_in,
+            min_out,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::ROUTE_LEN];
+            let (
+                instruction_type_pack,
+                protocol_pack,
+                amount_in_pack,
+                min_out_pack,
+            ) = mut_array_refs![output, 1, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::Route as u8;
+
+            protocol_pack[0] = *protocol;
+            *amount_in_pack = amount_in.to_le_bytes();
+            *min_out_pack = min_out.to_le_bytes();
+
+            Ok(AmmInstruction::ROUTE_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    /// Packs the header only, the Route instructions are appended by the caller
+    fn pack_batch_swap(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::BATCH_SWAP_LEN)?;
+        if let AmmInstruction::BatchSwap { count } = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::BATCH_SWAP_LEN];
+            let (
+                instruction_type_pack,
+                count_pack,
+            ) = mut_array_refs![output, 1, 1];
+
+            instruction_type_pack[0] = AmmInstructionType::BatchSwap as u8;
+
+            count_pack[0] = *count;
+
+            Ok(AmmInstruction::BATCH_SWAP_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_min_swap(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetMinSwap {
+            amount,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                amount_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMinSwap as u8;
+
+            *amount_pack = amount.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_rescue_tokens(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::RescueTokens {
+            allow_fee_account,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                allow_fee_account_pack,
+                _,
+            ) = mut_array_refs![output, 1, 1, 7];
+
+            instruction_type_pack[0] = AmmInstructionType::RescueTokens as u8;
+
+            allow_fee_account_pack[0] = *allow_fee_account as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_get_version(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::GetVersion = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::GetVersion as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_transfer_admin(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::TransferAdmin {
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::TransferAdmin as u8;
+
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_accept_admin(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::AcceptAdmin = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::AcceptAdmin as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_mint_fee(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SET_MINT_FEE_LEN)?;
+        if let AmmInstruction::SetMintFee {
+            mint,
+            fee_bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SET_MINT_FEE_LEN];
+            let (
+                instruction_type_pack,
+                mint_pack,
+                fee_bps_pack,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 32, 2, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetMintFee as u8;
+
+            mint_pack.copy_from_slice(mint.as_ref());
+            *fee_bps_pack = fee_bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SET_MINT_FEE_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_sweep_fees(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::SweepFees {
+            count,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                count_pack,
+                _,
+            ) = mut_array_refs![output, 1, 1, 7];
+
+            instruction_type_pack[0] = AmmInstructionType::SweepFees as u8;
+
+            count_pack[0] = *count;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_get_config(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::LEN)?;
+        if let AmmInstruction::GetConfig = self {
+            let output = array_mut_ref![output, 0, AmmInstruction::LEN];
+            let (
+                instruction_type_pack,
+                _,
+            ) = mut_array_refs![output, 1, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::GetConfig as u8;
+
+            Ok(AmmInstruction::LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_default_slippage(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetDefaultSlippage {
+            slippage_bps,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                slippage_bps_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 2, 6, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetDefaultSlippage as u8;
+
+            *slippage_bps_pack = slippage_bps.to_le_bytes();
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_set_protocol_enabled(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, AmmInstruction::SEQUENCED_LEN)?;
+        if let AmmInstruction::SetProtocolEnabled {
+            protocol,
+            enabled,
+            sequence,
+        } = self
+        {
+            let output = array_mut_ref![output, 0, AmmInstruction::SEQUENCED_LEN];
+            let (
+                instruction_type_pack,
+                protocol_pack,
+                enabled_pack,
+                _,
+                sequence_pack,
+            ) = mut_array_refs![output, 1, 1, 1, 6, 8];
+
+            instruction_type_pack[0] = AmmInstructionType::SetProtocolEnabled as u8;
+
+            protocol_pack[0] = *protocol;
+            enabled_pack[0] = *enabled as u8;
+            *sequence_pack = sequence.to_le_bytes();
+
+            Ok(AmmInstruction::SEQUENCED_LEN)
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn unpack_before_transfer(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::BEFORE_TRANSFER_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::BEFORE_TRANSFER_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, tolerance) = array_refs![input, 8, 8];
+
+        Ok(Self::BeforeTransfer {
+            amount: u64::from_le_bytes(*amount),
+            tolerance: u64::from_le_bytes(*tolerance),
+        })
+    }
+
+    fn unpack_swap_params(input: &[u8]) -> Result<SwapParams, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+            min_rate_num,
+            min_rate_den,
+            client_order_id,
+            deadline,
+            max_price_impact_bps,
+        ) = array_refs![input, 8, 8, 8, 8, 8, 16, 8, 2];
+
+        Ok(SwapParams {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
+            min_rate_num: u64::from_le_bytes(*min_rate_num),
+            min_rate_den: u64::from_le_bytes(*min_rate_den),
+            client_order_id: *client_order_id,
+            deadline: i64::from_le_bytes(*deadline),
+            max_price_impact_bps: u16::from_le_bytes(*max_price_impact_bps),
+        })
+    }
+
+    fn unpack_after_transfer(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::AFTER_TRANSFER_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::AFTER_TRANSFER_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, referral_bps, _, fee_amount) = array_refs![input, 8, 2, 6, 8];
+
+        Ok(Self::AfterTransfer {
+            amount: u64::from_le_bytes(*amount),
+            referral_bps: u16::from_le_bytes(*referral_bps),
+            fee_amount: u64::from_le_bytes(*fee_amount),
+        })
+    }
+
+    fn unpack_create_account(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::CREATE_ACCOUNT_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::CREATE_ACCOUNT_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (size, instance) = array_refs![input, 8, 1];
+
+        Ok(Self::CreateAccount {
+            size: u64::from_le_bytes(*size),
+            instance: instance[0],
+        })
+    }
+
+    fn unpack_harvest(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, _) = array_refs![input, 8, 0];
+
+        Ok(Self::Harvest {
+            amount: u64::from_le_bytes(*amount),
+        })
+    }
+
+    fn unpack_set_min_deliverable_out(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, sequence) = array_refs![input, 8, 8];
+
+        Ok(Self::SetMinDeliverableOut {
+            amount: u64::from_le_bytes(*amount),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_min_fee(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, sequence) = array_refs![input, 8, 8];
+
+        Ok(Self::SetMinFee {
+            amount: u64::from_le_bytes(*amount),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_fee_split(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (fee_bps, lp_fee_bps, _, sequence) = array_refs![input, 2, 2, 4, 8];
+
+        Ok(Self::SetFeeSplit {
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            lp_fee_bps: u16::from_le_bytes(*lp_fee_bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_verify_config(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (repair, _) = array_refs![input, 1, 7];
+
+        Ok(Self::VerifyConfig {
+            repair: repair[0] != 0,
+        })
+    }
+
+    fn unpack_sweep_lamports(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        Ok(Self::SweepLamports)
+    }
+
+    fn unpack_set_maintenance_window(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SET_MAINTENANCE_WINDOW_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SET_MAINTENANCE_WINDOW_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (start_slot, end_slot, sequence) = array_refs![input, 8, 8, 8];
+
+        Ok(Self::SetMaintenanceWindow {
+            start_slot: u64::from_le_bytes(*start_slot),
+            end_slot: u64::from_le_bytes(*end_slot),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_skip_funded_deposit(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (enabled, _, sequence) = array_refs![input, 1, 7, 8];
+
+        Ok(Self::SetSkipFundedDeposit {
+            enabled: enabled[0] != 0,
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_max_pool_consume(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (bps, _, sequence) = array_refs![input, 2, 6, 8];
+
+        Ok(Self::SetMaxPoolConsume {
+            bps: u16::from_le_bytes(*bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_fee_token(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (fee_token, _, sequence) = array_refs![input, 1, 7, 8];
+
+        Ok(Self::SetFeeToken {
+            fee_token: FeeToken::try_from_primitive(fee_token[0])
+                .or(Err(ProgramError::InvalidInstructionData))?,
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_auto_correct_vaults(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (enabled, _, sequence) = array_refs![input, 1, 7, 8];
+
+        Ok(Self::SetAutoCorrectVaults {
+            enabled: enabled[0] != 0,
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_swap_exact_out(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_EXACT_OUT_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_EXACT_OUT_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_amount_out, max_token_amount_in, direction, _) = array_refs![input, 8, 8, 1, 7];
+
+        Ok(Self::SwapExactOut {
+            token_amount_out: u64::from_le_bytes(*token_amount_out),
+            max_token_amount_in: u64::from_le_bytes(*max_token_amount_in),
+            direction: SwapDirection::try_from_primitive(direction[0])
+                .or(Err(ProgramError::InvalidInstructionData))?,
+        })
+    }
+
+    fn unpack_set_max_event_queue_fill(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (bps, _, sequence) = array_refs![input, 2, 6, 8];
+
+        Ok(Self::SetMaxEventQueueFill {
+            bps: u16::from_le_bytes(*bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_swap_orca(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_ORCA_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_ORCA_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_amount_in, token_b_amount_in, min_token_amount_out) = array_refs![input, 8, 8, 8];
+
+        Ok(Self::SwapOrca {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
+        })
+    }
+
+    fn unpack_swap_with_slippage_bps(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_amount_in, token_b_amount_in, slippage_bps, _) = array_refs![input, 8, 8, 2, 6];
+
+        Ok(Self::SwapWithSlippageBps {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+            slippage_bps: u16::from_le_bytes(*slippage_bps),
+        })
+    }
+
+    fn unpack_initialize(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (fee_bps, instance, _) = array_refs![input, 2, 1, 5];
+
+        Ok(Self::Initialize {
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            instance: instance[0],
+        })
+    }
+
+    fn unpack_set_fee(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (fee_bps, _, sequence) = array_refs![input, 2, 6, 8];
+
+        Ok(Self::SetFee {
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_fee_recipient(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (sequence, _) = array_refs![input, 8, 0];
+
+        Ok(Self::SetFeeRecipient {
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_paused(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (paused, _, sequence) = array_refs![input, 1, 7, 8];
+
+        Ok(Self::SetPaused {
+            paused: paused[0] != 0,
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_swap_two_hop(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_TWO_HOP_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_TWO_HOP_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount_in, min_final_out) = array_refs![input, 8, 8];
+
+        Ok(Self::SwapTwoHop {
+            amount_in: u64::from_le_bytes(*amount_in),
+            min_final_out: u64::from_le_bytes(*min_final_out),
+        })
+    }
+
+    fn unpack_withdraw_fees(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, _) = array_refs![input, 8, 0];
+
+        Ok(Self::WithdrawFees {
+            amount: u64::from_le_bytes(*amount),
+        })
+    }
+
+    fn unpack_swap_native_in(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_NATIVE_IN_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_NATIVE_IN_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount_in, min_token_amount_out) = array_refs![input, 8, 8];
+
+        Ok(Self::SwapNativeIn {
+            amount_in: u64::from_le_bytes(*amount_in),
+            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
+        })
+    }
+
+    fn unpack_close_temp_account(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+        Ok(Self::CloseTempAccount)
+    }
+
+    fn unpack_quote(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::QUOTE_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::QUOTE_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_amount_in, token_b_amount_in) = array_refs![input, 8, 8];
+
+        Ok(Self::Quote {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+        })
+    }
+
+    fn unpack_swap_serum_direct(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_SERUM_DIRECT_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_SERUM_DIRECT_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_amount_in, token_b_amount_in, min_token_amount_out) = array_refs![input, 8, 8, 8];
+
+        Ok(Self::SwapSerumDirect {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
+        })
+    }
+
+    fn unpack_swap_saber(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SWAP_SABER_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SWAP_SABER_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_amount_in, token_b_amount_in, min_token_amount_out) = array_refs![input, 8, 8, 8];
+
+        Ok(Self::SwapSaber {
+            token_a_amount_in: u64::from_le_bytes(*token_a_amount_in),
+            token_b_amount_in: u64::from_le_bytes(*token_b_amount_in),
+            min_token_amount_out: u64::from_le_bytes(*min_token_amount_out),
+        })
+    }
+
+    fn unpack_route(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::ROUTE_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::ROUTE_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (protocol, amount_in, min_out) = array_refs![input, 1, 8, 8];
+
+        Ok(Self::Route {
+            protocol: protocol[0],
+            amount_in: u64::from_le_bytes(*amount_in),
+            min_out: u64::from_le_bytes(*min_out),
+        })
+    }
+
+    /// Checks the data holds exactly count Route instructions after the header,
+    /// batch_swap unpacks them
+    fn unpack_batch_swap(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_data_len(input, AmmInstruction::BATCH_SWAP_LEN)?;
+        let count = input[1];
+        check_exact_data_len(
+            input,
+            AmmInstruction::BATCH_SWAP_LEN + count as usize * AmmInstruction::ROUTE_LEN,
+        )?;
+        Ok(Self::BatchSwap { count })
+    }
+
+    fn unpack_set_min_swap(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (amount, sequence) = array_refs![input, 8, 8];
+
+        Ok(Self::SetMinSwap {
+            amount: u64::from_le_bytes(*amount),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_rescue_tokens(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        Ok(Self::RescueTokens {
+            allow_fee_account: input[1] != 0,
+        })
+    }
+
+    fn unpack_get_version(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+        Ok(Self::GetVersion)
+    }
+
+    fn unpack_transfer_admin(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (sequence, _) = array_refs![input, 8, 0];
+
+        Ok(Self::TransferAdmin {
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_accept_admin(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+        Ok(Self::AcceptAdmin)
+    }
+
+    fn unpack_set_mint_fee(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SET_MINT_FEE_LEN)?;
+        let mint = pack::unpack_pubkey(input, 1)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SET_MINT_FEE_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (_, fee_bps, sequence) = array_refs![input, 32, 2, 8];
+
+        Ok(Self::SetMintFee {
+            mint,
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_sweep_fees(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+
+        Ok(Self::SweepFees {
+            count: input[1],
+        })
+    }
+
+    fn unpack_get_config(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::LEN)?;
+        Ok(Self::GetConfig)
+    }
+
+    fn unpack_set_default_slippage(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (slippage_bps, _, sequence) = array_refs![input, 2, 6, 8];
+
+        Ok(Self::SetDefaultSlippage {
+            slippage_bps: u16::from_le_bytes(*slippage_bps),
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+
+    fn unpack_set_protocol_enabled(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        check_exact_data_len(input, AmmInstruction::SEQUENCED_LEN)?;
+
+        let input = array_ref![input, 1, AmmInstruction::SEQUENCED_LEN - 1];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (protocol, enabled, _, sequence) = array_refs![input, 1, 1, 6, 8];
+
+        Ok(Self::SetProtocolEnabled {
+            protocol: protocol[0],
+            enabled: enabled[0] != 0,
+            sequence: u64::from_le_bytes(*sequence),
+        })
+    }
+}
+
+/// Borsh encoding for clients that would rather not hand roll the layout.
+/// Borsh writes the variant index as the first byte and the fields in order,
+/// little endian, so it matches pack except where the layout pads the fields,
+/// there the borsh bytes are shorter. A BatchSwap encodes the header only, the
+/// routes are appended as with pack.
+#[cfg(feature = "borsh")]
+impl AmmInstruction {
+    pub fn pack_borsh(&self) -> Result<Vec<u8>, ProgramError> {
+        borsh::to_vec(self).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    pub fn try_unpack_borsh(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        borsh::from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    /// Fixed layout first, borsh when the data doesn't fit it, the error of the
+    /// layout when neither does. The program built with the borsh feature
+    /// accepts both formats through it.
+    pub fn unpack_with_borsh(input: &[u8]) -> Result<AmmInstruction, ProgramError> {
+        AmmInstruction::unpack(input)
+            .or_else(|err| AmmInstruction::try_unpack_borsh(input).map_err(|_| err))
+    }
+}
+
+impl AmmInstructionType {
+    /// Packed length of the instruction, the header alone for BatchSwap
+    pub fn packed_len(self) -> usize {
+        match self {
+            AmmInstructionType::BeforeTransfer => AmmInstruction::BEFORE_TRANSFER_LEN,
+            AmmInstructionType::Swap => AmmInstruction::SWAP_LEN,
+            AmmInstructionType::AfterTransfer => AmmInstruction::AFTER_TRANSFER_LEN,
+            AmmInstructionType::CreateAccount => AmmInstruction::CREATE_ACCOUNT_LEN,
+            AmmInstructionType::Harvest => AmmInstruction::LEN,
+            AmmInstructionType::SetMinDeliverableOut => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetMinFee => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetFeeSplit => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::VerifyConfig => AmmInstruction::LEN,
+            AmmInstructionType::SweepLamports => AmmInstruction::LEN,
+            AmmInstructionType::SwapBestFee => AmmInstruction::SWAP_LEN,
+            AmmInstructionType::SetMaintenanceWindow => AmmInstruction::SET_MAINTENANCE_WINDOW_LEN,
+            AmmInstructionType::SetSkipFundedDeposit => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetMaxPoolConsume => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SwapAndStake => AmmInstruction::SWAP_LEN,
+            AmmInstructionType::SetFeeToken => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetAutoCorrectVaults => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SwapExactOut => AmmInstruction::SWAP_EXACT_OUT_LEN,
+            AmmInstructionType::SetMaxEventQueueFill => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SwapOrca => AmmInstruction::SWAP_ORCA_LEN,
+            AmmInstructionType::SwapWithSlippageBps => AmmInstruction::SWAP_WITH_SLIPPAGE_BPS_LEN,
+            AmmInstructionType::Initialize => AmmInstruction::LEN,
+            AmmInstructionType::SetFee => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetFeeRecipient => AmmInstruction::LEN,
+            AmmInstructionType::SetPaused => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SwapTwoHop => AmmInstruction::SWAP_TWO_HOP_LEN,
+            AmmInstructionType::WithdrawFees => AmmInstruction::LEN,
+            AmmInstructionType::SwapNativeIn => AmmInstruction::SWAP_NATIVE_IN_LEN,
+            AmmInstructionType::CloseTempAccount => AmmInstruction::LEN,
+            AmmInstructionType::Quote => AmmInstruction::QUOTE_LEN,
+            AmmInstructionType::SwapSerumDirect => AmmInstruction::SWAP_SERUM_DIRECT_LEN,
+            AmmInstructionType::SwapSaber => AmmInstruction::SWAP_SABER_LEN,
+            AmmInstructionType::Route => AmmInstruction::ROUTE_LEN,
+            AmmInstructionType::BatchSwap => AmmInstruction::BATCH_SWAP_LEN,
+            AmmInstructionType::SetMinSwap => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::RescueTokens => AmmInstruction::LEN,
+            AmmInstructionType::GetVersion => AmmInstruction::LEN,
+            AmmInstructionType::TransferAdmin => AmmInstruction::LEN,
+            AmmInstructionType::AcceptAdmin => AmmInstruction::LEN,
+            AmmInstructionType::SetMintFee => AmmInstruction::SET_MINT_FEE_LEN,
+            AmmInstructionType::SweepFees => AmmInstruction::LEN,
+            AmmInstructionType::GetConfig => AmmInstruction::LEN,
+            AmmInstructionType::SetDefaultSlippage => AmmInstruction::SEQUENCED_LEN,
+            AmmInstructionType::SetProtocolEnabled => AmmInstruction::SEQUENCED_LEN,
+        }
+    }
+}
+
+impl std::fmt::Display for AmmInstructionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            AmmInstructionType::BeforeTransfer => write!(f, "before transfer"),
+            AmmInstructionType::Swap => write!(f, "Swap"),
+            AmmInstructionType::AfterTransfer => write!(f, "after transfer"),
+            AmmInstructionType::CreateAccount => write!(f, "create account"),
+            AmmInstructionType::Harvest => write!(f, "harvest"),
+            AmmInstructionType::SetMinDeliverableOut => write!(f, "set min deliverable out"),
+            AmmInstructionType::SetMinFee => write!(f, "set min fee"),
+            AmmInstructionType::SetFeeSplit => write!(f, "set fee split"),
+            AmmInstructionType::VerifyConfig => write!(f, "verify config"),
+            AmmInstructionType::SweepLamports => write!(f, "sweep lamports"),
+            AmmInstructionType::SwapBestFee => write!(f, "swap best fee"),
+            AmmInstructionType::SetMaintenanceWindow => write!(f, "set maintenance window"),
+            AmmInstructionType::SetSkipFundedDeposit => write!(f, "set skip funded deposit"),
+            AmmInstructionType::SetMaxPoolConsume => write!(f, "set max pool consume"),
+            AmmInstructionType::SwapAndStake => write!(f, "swap and stake"),
+            AmmInstructionType::SetFeeToken => write!(f, "set fee token"),
+            AmmInstructionType::SetAutoCorrectVaults => write!(f, "set auto correct vaults"),
+            AmmInstructionType::SwapExactOut => write!(f, "swap exact out"),
+            AmmInstructionType::SetMaxEventQueueFill => write!(f, "set max event queue fill"),
+            AmmInstructionType::SwapOrca => write!(f, "swap orca"),
+            AmmInstructionType::SwapWithSlippageBps => write!(f, "swap with slippage bps"),
+            AmmInstructionType::Initialize => write!(f, "initialize"),
+            AmmInstructionType::SetFee => write!(f, "set fee"),
+            AmmInstructionType::SetFeeRecipient => write!(f, "set fee recipient"),
+            AmmInstructionType::SetPaused => write!(f, "set paused"),
+            AmmInstructionType::SwapTwoHop => write!(f, "swap two hop"),
+            AmmInstructionType::WithdrawFees => write!(f, "withdraw fees"),
+            AmmInstructionType::SwapNativeIn => write!(f, "swap native in"),
+            AmmInstructionType::CloseTempAccount => write!(f, "close temp account"),
+            AmmInstructionType::Quote => write!(f, "quote"),
+            AmmInstructionType::SwapSerumDirect => write!(f, "swap serum direct"),
+            AmmInstructionType::SwapSaber => write!(f, "swap saber"),
+            AmmInstructionType::Route => write!(f, "route"),
+            AmmInstructionType::BatchSwap => write!(f, "batch swap"),
+            AmmInstructionType::SetMinSwap => write!(f, "set min swap"),
+            AmmInstructionType::RescueTokens => write!(f, "rescue tokens"),
+            AmmInstructionType::GetVersion => write!(f, "get version"),
+            AmmInstructionType::TransferAdmin => write!(f, "transfer admin"),
+            AmmInstructionType::AcceptAdmin => write!(f, "accept admin"),
+            AmmInstructionType::SetMintFee => write!(f, "set mint fee"),
+            AmmInstructionType::SweepFees => write!(f, "sweep fees"),
+            AmmInstructionType::GetConfig => write!(f, "get config"),
+            AmmInstructionType::SetDefaultSlippage => write!(f, "set default slippage"),
+            AmmInstructionType::SetProtocolEnabled => write!(f, "set protocol enabled"),
+        }
+    }
+}
+
+/// Variant name and fields, swap params without the client order id.
+impl std::fmt::Display for AmmInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Swap(params) => write!(f, "Swap {}", params),
+            Self::SwapBestFee(params) => write!(f, "SwapBestFee {}", params),
+            Self::SwapAndStake(params) => write!(f, "SwapAndStake {}", params),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::fmt::Display for SwapParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{ token_a_amount_in: {}, token_b_amount_in: {}, min_token_amount_out: {}, \
+             min_rate_num: {}, min_rate_den: {}, deadline: {}, max_price_impact_bps: {} }}",
+            self.token_a_amount_in,
+            self.token_b_amount_in,
+            self.min_token_amount_out,
+            self.min_rate_num,
+            self.min_rate_den,
+            self.deadline,
+            self.max_price_impact_bps
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One instruction of each variant with its Display rendering
+    fn instruction_samples() -> Vec<(AmmInstruction, String)> {
+        let params = SwapParams {
+            token_a_amount_in: 1_000,
+            min_token_amount_out: 900,
+            client_order_id: [7; 16],
+            ..SwapParams::default()
+        };
+        let swap_params = "{ token_a_amount_in: 1000, token_b_amount_in: 0, \
+            min_token_amount_out: 900, min_rate_num: 0, min_rate_den: 0, deadline: 0, \
+            max_price_impact_bps: 0 }";
+        vec![
+            (
+                AmmInstruction::BeforeTransfer { amount: 5, tolerance: 1 },
+                "BeforeTransfer { amount: 5, tolerance: 1 }".to_string(),
+            ),
+            (AmmInstruction::Swap(params), format!("Swap {}", swap_params)),
+            (
+                AmmInstruction::AfterTransfer { amount: 5, referral_bps: 2_500, fee_amount: 3 },
+                "AfterTransfer { amount: 5, referral_bps: 2500, fee_amount: 3 }".to_string(),
+            ),
+            (
+                AmmInstruction::CreateAccount { size: 165, instance: 1 },
+                "CreateAccount { size: 165, instance: 1 }".to_string(),
+            ),
+            (AmmInstruction::Harvest { amount: 5 }, "Harvest { amount: 5 }".to_string()),
+            (
+                AmmInstruction::SetMinDeliverableOut { amount: 5, sequence: 1 },
+                "SetMinDeliverableOut { amount: 5, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetMinFee { amount: 5, sequence: 1 },
+                "SetMinFee { amount: 5, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetFeeSplit { fee_bps: 30, lp_fee_bps: 20, sequence: 1 },
+                "SetFeeSplit { fee_bps: 30, lp_fee_bps: 20, sequence: 1 }".to_string(),
+            ),
+            (AmmInstruction::VerifyConfig { repair: true }, "VerifyConfig { repair: true }".to_string()),
+            (AmmInstruction::SweepLamports, "SweepLamports".to_string()),
+            (AmmInstruction::SwapBestFee(params), format!("SwapBestFee {}", swap_params)),
+            (
+                AmmInstruction::SetMaintenanceWindow { start_slot: 10, end_slot: 20, sequence: 1 },
+                "SetMaintenanceWindow { start_slot: 10, end_slot: 20, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetSkipFundedDeposit { enabled: true, sequence: 1 },
+                "SetSkipFundedDeposit { enabled: true, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetMaxPoolConsume { bps: 500, sequence: 1 },
+                "SetMaxPoolConsume { bps: 500, sequence: 1 }".to_string(),
+            ),
+            (AmmInstruction::SwapAndStake(params), format!("SwapAndStake {}", swap_params)),
+            (
+                AmmInstruction::SetFeeToken { fee_token: FeeToken::Output, sequence: 1 },
+                "SetFeeToken { fee_token: Output, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetAutoCorrectVaults { enabled: false, sequence: 1 },
+                "SetAutoCorrectVaults { enabled: false, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SwapExactOut {
+                    token_amount_out: 5,
+                    max_token_amount_in: 6,
+                    direction: SwapDirection::PcToCoin,
+                },
+                "SwapExactOut { token_amount_out: 5, max_token_amount_in: 6, direction: PcToCoin }"
+                    .to_string(),
+            ),
+            (
+                AmmInstruction::SetMaxEventQueueFill { bps: 8_000, sequence: 1 },
+                "SetMaxEventQueueFill { bps: 8000, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SwapOrca {
+                    token_a_amount_in: 0,
+                    token_b_amount_in: 5,
+                    min_token_amount_out: 4,
+                },
+                "SwapOrca { token_a_amount_in: 0, token_b_amount_in: 5, min_token_amount_out: 4 }"
+                    .to_string(),
+            ),
+            (
+                AmmInstruction::SwapWithSlippageBps {
+                    token_a_amount_in: 5,
+                    token_b_amount_in: 0,
+                    slippage_bps: 50,
+                },
+                "SwapWithSlippageBps { token_a_amount_in: 5, token_b_amount_in: 0, slippage_bps: 50 }"
+                    .to_string(),
+            ),
+            (
+                AmmInstruction::Initialize { fee_bps: 30, instance: 1 },
+                "Initialize { fee_bps: 30, instance: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetFee { fee_bps: 30, sequence: 1 },
+                "SetFee { fee_bps: 30, sequence: 1 }".to_string(),
+            ),
+            (AmmInstruction::SetFeeRecipient { sequence: 1 }, "SetFeeRecipient { sequence: 1 }".to_string()),
+            (
+                AmmInstruction::SetPaused { paused: true, sequence: 1 },
+                "SetPaused { paused: true, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SwapTwoHop { amount_in: 5, min_final_out: 4 },
+                "SwapTwoHop { amount_in: 5, min_final_out: 4 }".to_string(),
+            ),
+            (AmmInstruction::WithdrawFees { amount: 0 }, "WithdrawFees { amount: 0 }".to_string()),
+            (
+                AmmInstruction::SwapNativeIn { amount_in: 5, min_token_amount_out: 4 },
+                "SwapNativeIn { amount_in: 5, min_token_amount_out: 4 }".to_string(),
+            ),
+            (AmmInstruction::CloseTempAccount, "CloseTempAccount".to_string()),
+            (
+                AmmInstruction::Quote { token_a_amount_in: 5, token_b_amount_in: 0 },
+                "Quote { token_a_amount_in: 5, token_b_amount_in: 0 }".to_string(),
+            ),
+            (
+                AmmInstruction::SwapSerumDirect {
+                    token_a_amount_in: 5,
+                    token_b_amount_in: 0,
+                    min_token_amount_out: 4,
+                },
+                "SwapSerumDirect { token_a_amount_in: 5, token_b_amount_in: 0, min_token_amount_out: 4 }"
+                    .to_string(),
+            ),
+            (
+                AmmInstruction::SwapSaber {
+                    token_a_amount_in: 0,
+                    token_b_amount_in: 5,
+                    min_token_amount_out: 4,
+                },
+                "SwapSaber { token_a_amount_in: 0, token_b_amount_in: 5, min_token_amount_out: 4 }"
+                    .to_string(),
+            ),
+            (
+                AmmInstruction::Route { protocol: 2, amount_in: 5, min_out: 4 },
+                "Route { protocol: 2, amount_in: 5, min_out: 4 }".to_string(),
+            ),
+            (AmmInstruction::BatchSwap { count: 0 }, "BatchSwap { count: 0 }".to_string()),
+            (
+                AmmInstruction::SetMinSwap { amount: 5, sequence: 1 },
+                "SetMinSwap { amount: 5, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::RescueTokens { allow_fee_account: true },
+                "RescueTokens { allow_fee_account: true }".to_string(),
+            ),
+            (AmmInstruction::GetVersion, "GetVersion".to_string()),
+            (AmmInstruction::TransferAdmin { sequence: 1 }, "TransferAdmin { sequence: 1 }".to_string()),
+            (AmmInstruction::AcceptAdmin, "AcceptAdmin".to_string()),
+            (
+                AmmInstruction::SetMintFee { mint: Pubkey::new_from_array([1; 32]), fee_bps: 30, sequence: 1 },
+                format!(
+                    "SetMintFee {{ mint: {}, fee_bps: 30, sequence: 1 }}",
+                    Pubkey::new_from_array([1; 32])
+                ),
+            ),
+            (AmmInstruction::SweepFees { count: 3 }, "SweepFees { count: 3 }".to_string()),
+            (AmmInstruction::GetConfig, "GetConfig".to_string()),
+            (
+                AmmInstruction::SetDefaultSlippage { slippage_bps: 50, sequence: 1 },
+                "SetDefaultSlippage { slippage_bps: 50, sequence: 1 }".to_string(),
+            ),
+            (
+                AmmInstruction::SetProtocolEnabled { protocol: 1, enabled: true, sequence: 1 },
+                "SetProtocolEnabled { protocol: 1, enabled: true, sequence: 1 }".to_string(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_instruction_display() {
+        for (instruction, expected) in instruction_samples() {
+            assert_eq!(instruction.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_unpack_exact_len() {
+        for (instruction, _) in instruction_samples() {
+            let mut data = [0; 128];
+            let len = instruction.pack(&mut data).unwrap();
+
+            assert_eq!(AmmInstruction::unpack(&data[..len]), Ok(instruction));
+            assert_eq!(
+                AmmInstruction::unpack(&data[..len + 1]),
+                Err(ProgramError::InvalidInstructionData),
+                "{}",
+                instruction
+            );
+            assert_eq!(
+                AmmInstruction::unpack(&data[..len - 1]),
+                Err(ProgramError::AccountDataTooSmall),
+                "{}",
+                instruction
+            );
+        }
+    }
+
+    #[test]
+    fn test_pack_round_trip() {
+        let mut instruction_types = Vec::new();
+        for (instruction, _) in instruction_samples() {
+            let mut data = [0; 128];
+            let len = instruction.pack(&mut data).unwrap();
+            // a buffer of the packed size is enough, one byte less isn't
+            let mut exact = vec![0; len];
+            assert_eq!(instruction.pack(&mut exact), Ok(len), "{}", instruction);
+            assert_eq!(exact, &data[..len], "{}", instruction);
+            assert_eq!(AmmInstruction::unpack(&exact), Ok(instruction));
+            assert_eq!(
+                instruction.pack(&mut exact[..len - 1]),
+                Err(ProgramError::AccountDataTooSmall),
+                "{}",
+                instruction
+            );
+            let instruction_type = AmmInstructionType::try_from_primitive(exact[0]).unwrap();
+            assert_eq!(instruction_type.packed_len(), len, "{}", instruction);
+            instruction_types.push(exact[0]);
+        }
+
+        // the samples cover every instruction type
+        instruction_types.sort_unstable();
+        instruction_types.dedup();
+        let all: Vec<u8> = (0..=AmmInstructionType::SetProtocolEnabled as u8).collect();
+        assert_eq!(instruction_types, all);
+    }
+
+    #[test]
+    fn test_unpack_invalid_instruction_type() {
+        assert_eq!(AmmInstruction::unpack(&[]), Err(ProgramError::AccountDataTooSmall));
+        for instruction_type in [AmmInstructionType::SetProtocolEnabled as u8 + 1, u8::MAX] {
+            let mut data = [0; AmmInstruction::SWAP_LEN];
+            data[0] = instruction_type;
+            assert_eq!(AmmInstruction::unpack(&data), Err(ProgramError::InvalidInstructionData));
+            assert_eq!(AmmInstruction::unpack(&data[..1]), Err(ProgramError::InvalidInstructionData));
+        }
+    }
+
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_matches_layout() {
+        for (instruction, _) in instruction_samples() {
+            let mut data = [0; 128];
+            let len = instruction.pack(&mut data).unwrap();
+            let borsh_data = instruction.pack_borsh().unwrap();
+
+            assert!(borsh_data.len() <= len, "{}", instruction);
+            assert_eq!(AmmInstruction::try_unpack_borsh(&borsh_data), Ok(instruction));
+            // both formats reach the program
+            assert_eq!(AmmInstruction::unpack_with_borsh(&data[..len]), Ok(instruction));
+            assert_eq!(AmmInstruction::unpack_with_borsh(&borsh_data), Ok(instruction));
+        }
+
+        // unpadded layouts give the same bytes
+        for instruction in [
+            AmmInstruction::BeforeTransfer { amount: 5, tolerance: 1 },
+            AmmInstruction::SetMinFee { amount: 5, sequence: 1 },
+            AmmInstruction::Route { protocol: 2, amount_in: 5, min_out: 4 },
+        ] {
+            let mut data = [0; 128];
+            let len = instruction.pack(&mut data).unwrap();
+            assert_eq!(instruction.pack_borsh().unwrap(), &data[..len], "{}", instruction);
+        }
+        // the padded ones are shorter
+        let instruction = AmmInstruction::SetFee { fee_bps: 30, sequence: 1 };
+        assert_eq!(instruction.pack_borsh().unwrap(), [22, 30, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(AmmInstruction::unpack(&instruction.pack_borsh().unwrap()), Err(ProgramError::AccountDataTooSmall));
+
+        let data = AmmInstruction::SetPaused { paused: true, sequence: 1 }.pack_borsh().unwrap();
+        assert_eq!(AmmInstruction::try_unpack_borsh(&data[..data.len() - 1]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(AmmInstruction::try_unpack_borsh(&[u8::MAX]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(AmmInstruction::unpack_with_borsh(&[u8::MAX]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_unpack_batch_swap() {
+        let mut data = vec![0; AmmInstruction::BATCH_SWAP_LEN + 2 * AmmInstruction::ROUTE_LEN];
+        AmmInstruction::BatchSwap { count: 2 }.pack(&mut data).unwrap();
+        for (index, protocol) in [0, 2].iter().enumerate() {
+            let offset = AmmInstruction::BATCH_SWAP_LEN + index * AmmInstruction::ROUTE_LEN;
+            AmmInstruction::Route { protocol: *protocol, amount_in: 5, min_out: 4 }
+                .pack(&mut data[offset..])
+                .unwrap();
+        }
+
+        assert_eq!(AmmInstruction::unpack(&data), Ok(AmmInstruction::BatchSwap { count: 2 }));
+        assert_eq!(
+            AmmInstruction::unpack(&data[..data.len() - 1]),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+        data.push(0);
+        assert_eq!(AmmInstruction::unpack(&data), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_unpack_set_mint_fee_rejects_invalid_mint() {
+        let mut data = vec![0; AmmInstruction::SET_MINT_FEE_LEN];
+        AmmInstruction::SetMintFee { mint: Pubkey::default(), fee_bps: 30, sequence: 1 }
+            .pack(&mut data)
+            .unwrap();
+
+        assert_eq!(AmmInstruction::unpack(&data), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_instruction_type_display() {
+        assert_eq!(AmmInstructionType::BeforeTransfer.to_string(), "before transfer");
+        assert_eq!(AmmInstructionType::AfterTransfer.to_string(), "after transfer");
+    }
+}
@@ -0,0 +1,1262 @@
+//! Client side builders for the router instructions.
+//!
+//! Each builder lays out the accounts in the order its handler in utils::swap
+//! reads them and packs the data with AmmInstruction::pack. Only built with the
+//! `client` feature so the on-chain program doesn't carry it.
+
+use {
+    crate::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::SwapDirection,
+        state::FeeToken,
+        utils::tokens::{ProgramAuthority, DEFAULT_INSTANCE},
+    },
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+    std::convert::TryInto,
+};
+
+/// Raydium pool accounts in the order swap reads them after the program token accounts
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RaydiumPoolAccounts {
+    pub pool_program_id: Pubkey,
+    pub pool_coin_token_account: Pubkey,
+    pub pool_pc_token_account: Pubkey,
+    pub spl_token_id: Pubkey,
+    pub amm_id: Pubkey,
+    pub amm_authority: Pubkey,
+    pub amm_open_orders: Pubkey,
+    pub amm_target: Pubkey,
+    pub serum_market: Pubkey,
+    pub serum_program_id: Pubkey,
+    pub serum_bids: Pubkey,
+    pub serum_asks: Pubkey,
+    pub serum_event_queue: Pubkey,
+    pub serum_coin_vault_account: Pubkey,
+    pub serum_pc_vault_account: Pubkey,
+    pub serum_vault_signer: Pubkey,
+}
+
+impl RaydiumPoolAccounts {
+    /// Writable where the Raydium swap writes, see raydium::build_swap_instruction
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.pool_program_id, false),
+            AccountMeta::new(self.pool_coin_token_account, false),
+            AccountMeta::new(self.pool_pc_token_account, false),
+            AccountMeta::new_readonly(self.spl_token_id, false),
+            AccountMeta::new(self.amm_id, false),
+            AccountMeta::new_readonly(self.amm_authority, false),
+            AccountMeta::new(self.amm_open_orders, false),
+            AccountMeta::new(self.amm_target, false),
+            AccountMeta::new(self.serum_market, false),
+            AccountMeta::new_readonly(self.serum_program_id, false),
+            AccountMeta::new(self.serum_bids, false),
+            AccountMeta::new(self.serum_asks, false),
+            AccountMeta::new(self.serum_event_queue, false),
+            AccountMeta::new(self.serum_coin_vault_account, false),
+            AccountMeta::new(self.serum_pc_vault_account, false),
+            AccountMeta::new_readonly(self.serum_vault_signer, false),
+        ]
+    }
+}
+
+/// Accounts of swap and the instructions sharing its layout,
+/// the program account is derived from the router program id
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SwapAccounts {
+    pub program_token_a_account: Pubkey,
+    pub program_token_b_account: Pubkey,
+    pub pool: RaydiumPoolAccounts,
+}
+
+/// Orca pool accounts in the order swap_orca reads them after the program token accounts
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OrcaPoolAccounts {
+    pub pool_program_id: Pubkey,
+    pub swap_state: Pubkey,
+    pub swap_authority: Pubkey,
+    pub pool_token_a_account: Pubkey,
+    pub pool_token_b_account: Pubkey,
+    pub pool_mint: Pubkey,
+    pub pool_fee_account: Pubkey,
+    pub spl_token_id: Pubkey,
+}
+
+/// Saber pool accounts in the order swap_saber reads them after the program token accounts
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SaberPoolAccounts {
+    pub pool_program_id: Pubkey,
+    pub swap_info: Pubkey,
+    pub swap_authority: Pubkey,
+    pub pool_token_a_account: Pubkey,
+    pub pool_token_b_account: Pubkey,
+    pub admin_fee_a_account: Pubkey,
+    pub admin_fee_b_account: Pubkey,
+    pub spl_token_id: Pubkey,
+}
+
+/// Serum market accounts in the order swap_serum_direct reads them after the
+/// program token accounts, the open orders are owned by the program account
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SerumMarketAccounts {
+    pub serum_program_id: Pubkey,
+    pub serum_market: Pubkey,
+    pub serum_open_orders: Pubkey,
+    pub serum_request_queue: Pubkey,
+    pub serum_event_queue: Pubkey,
+    pub serum_bids: Pubkey,
+    pub serum_asks: Pubkey,
+    pub serum_coin_vault_account: Pubkey,
+    pub serum_pc_vault_account: Pubkey,
+    pub serum_vault_signer: Pubkey,
+    pub spl_token_id: Pubkey,
+}
+
+/// Accounts of before_transfer
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BeforeTransferAccounts {
+    pub user: Pubkey,
+    pub token_program_id: Pubkey,
+    pub user_sol_account: Pubkey,
+    pub program_sol_account: Pubkey,
+    pub user_transfer_authority: Pubkey,
+    /// Deposits with transfer_checked, needed by mints with a transfer fee
+    pub mint: Option<Pubkey>,
+}
+
+/// Accounts of after_transfer, the optional ones are appended when set
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AfterTransferAccounts {
+    pub token_program_id: Pubkey,
+    pub program_kin_account: Pubkey,
+    pub program_sol_account: Pubkey,
+    pub destination_account: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub program_kin_mint: Pubkey,
+    pub program_sol_mint: Pubkey,
+    pub fee_token_program_id: Option<Pubkey>,
+    pub lp_fee_recipient: Option<Pubkey>,
+    /// Takes referral_bps of the protocol fee, the lp fee recipient slot falls
+    /// back to fee_recipient when only the referrer is set
+    pub referrer: Option<Pubkey>,
+}
+
+/// Returns the router PDA holding the config and custody accounts.
+/// The builders address the default instance, except create_account and initialize
+pub fn get_program_account(program_id: &Pubkey) -> Pubkey {
+    get_instance_program_account(program_id, DEFAULT_INSTANCE)
+}
+
+/// Returns the router PDA of instance, see ProgramAuthority
+pub fn get_instance_program_account(program_id: &Pubkey, instance: u8) -> Pubkey {
+    ProgramAuthority::find(program_id, instance).address
+}
+
+fn build_instruction(
+    program_id: &Pubkey,
+    accounts: Vec<AccountMeta>,
+    instruction: AmmInstruction,
+) -> Result<Instruction, ProgramError> {
+    // swap params are the longest payload
+    let mut data = vec![0; AmmInstruction::SWAP_LEN];
+    let len = instruction.pack(&mut data)?;
+    data.truncate(len);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// The program account is writable, a swap inside a before_transfer, swap,
+/// after_transfer sequence records its phase in the config
+fn swap_account_metas(program_id: &Pubkey, accounts: &SwapAccounts) -> Vec<AccountMeta> {
+    let mut metas = vec![
+        AccountMeta::new(get_program_account(program_id), false),
+        AccountMeta::new(accounts.program_token_a_account, false),
+        AccountMeta::new(accounts.program_token_b_account, false),
+    ];
+    metas.extend(accounts.pool.to_account_metas());
+    metas
+}
+
+/// Admin signer and the writable program account, shared by the config setters
+fn admin_account_metas(program_id: &Pubkey, admin: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(get_program_account(program_id), false),
+    ]
+}
+
+pub fn before_transfer_instruction(
+    program_id: &Pubkey,
+    accounts: &BeforeTransferAccounts,
+    amount: u64,
+    tolerance: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.user, true),
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+        AccountMeta::new(accounts.user_sol_account, false),
+        AccountMeta::new(accounts.program_sol_account, false),
+        AccountMeta::new_readonly(accounts.user_transfer_authority, true),
+        AccountMeta::new(get_program_account(program_id), false),
+    ];
+    if let Some(mint) = accounts.mint {
+        metas.push(AccountMeta::new_readonly(mint, false));
+    }
+    build_instruction(program_id, metas, AmmInstruction::BeforeTransfer { amount, tolerance })
+}
+
+pub fn swap_instruction(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    params: SwapParams,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        swap_account_metas(program_id, accounts),
+        AmmInstruction::Swap(params),
+    )
+}
+
+/// Same as swap_instruction, the input a partial fill leaves unconsumed goes to
+/// refund_account
+pub fn swap_with_refund_instruction(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    params: SwapParams,
+    refund_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut metas = swap_account_metas(program_id, accounts);
+    metas.push(AccountMeta::new(*refund_account, false));
+    build_instruction(program_id, metas, AmmInstruction::Swap(params))
+}
+
+pub fn after_transfer_instruction(
+    program_id: &Pubkey,
+    accounts: &AfterTransferAccounts,
+    amount: u64,
+    referral_bps: u16,
+    fee_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.token_program_id, false),
+        AccountMeta::new(get_program_account(program_id), false),
+        AccountMeta::new(accounts.program_kin_account, false),
+        AccountMeta::new(accounts.program_sol_account, false),
+        AccountMeta::new(accounts.destination_account, false),
+        AccountMeta::new(accounts.fee_recipient, false),
+        AccountMeta::new_readonly(accounts.program_kin_mint, false),
+        AccountMeta::new_readonly(accounts.program_sol_mint, false),
+    ];
+    // the lp fee recipient and referrer are positional, each needs the slots before it
+    let lp_fee_recipient = match (accounts.lp_fee_recipient, accounts.referrer) {
+        (None, Some(_)) => Some(accounts.fee_recipient),
+        (lp_fee_recipient, _) => lp_fee_recipient,
+    };
+    if accounts.fee_token_program_id.is_some() || lp_fee_recipient.is_some() {
+        metas.push(AccountMeta::new_readonly(
+            accounts.fee_token_program_id.unwrap_or(accounts.token_program_id),
+            false,
+        ));
+    }
+    if let Some(lp_fee_recipient) = lp_fee_recipient {
+        metas.push(AccountMeta::new(lp_fee_recipient, false));
+    }
+    if let Some(referrer) = accounts.referrer {
+        metas.push(AccountMeta::new(referrer, false));
+    }
+    build_instruction(
+        program_id,
+        metas,
+        AmmInstruction::AfterTransfer { amount, referral_bps, fee_amount },
+    )
+}
+
+pub fn create_account_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    size: u64,
+    instance: u8,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(get_instance_program_account(program_id, instance), false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::CreateAccount { size, instance })
+}
+
+pub fn harvest_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    program_sol_account: &Pubkey,
+    user_account: &Pubkey,
+    admin: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new(*program_sol_account, false),
+        AccountMeta::new(*user_account, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::Harvest { amount })
+}
+
+pub fn set_min_deliverable_out_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    amount: u64,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMinDeliverableOut { amount, sequence },
+    )
+}
+
+pub fn set_min_fee_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    amount: u64,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMinFee { amount, sequence },
+    )
+}
+
+pub fn set_min_swap_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    amount: u64,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMinSwap { amount, sequence },
+    )
+}
+
+pub fn set_fee_split_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_bps: u16,
+    lp_fee_bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetFeeSplit { fee_bps, lp_fee_bps, sequence },
+    )
+}
+
+/// The admin is the main router admin when the stored one can't be trusted
+pub fn verify_config_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    repair: bool,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::VerifyConfig { repair },
+    )
+}
+
+pub fn sweep_lamports_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    treasury: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = admin_account_metas(program_id, admin);
+    accounts.extend([
+        AccountMeta::new(*treasury, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    build_instruction(program_id, accounts, AmmInstruction::SweepLamports)
+}
+
+/// Candidate pools follow the program token accounts in the given order
+pub fn swap_best_fee_instruction(
+    program_id: &Pubkey,
+    program_token_a_account: &Pubkey,
+    program_token_b_account: &Pubkey,
+    pools: &[RaydiumPoolAccounts],
+    params: SwapParams,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new(*program_token_a_account, false),
+        AccountMeta::new(*program_token_b_account, false),
+    ];
+    for pool in pools {
+        accounts.extend(pool.to_account_metas());
+    }
+    build_instruction(program_id, accounts, AmmInstruction::SwapBestFee(params))
+}
+
+pub fn set_maintenance_window_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    start_slot: u64,
+    end_slot: u64,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMaintenanceWindow { start_slot, end_slot, sequence },
+    )
+}
+
+pub fn set_skip_funded_deposit_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    enabled: bool,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetSkipFundedDeposit { enabled, sequence },
+    )
+}
+
+pub fn set_max_pool_consume_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMaxPoolConsume { bps, sequence },
+    )
+}
+
+/// Staking accounts are backend specific and appended as given
+pub fn swap_and_stake_instruction(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    staking_accounts: &[AccountMeta],
+    params: SwapParams,
+) -> Result<Instruction, ProgramError> {
+    let mut metas = swap_account_metas(program_id, accounts);
+    metas.extend_from_slice(staking_accounts);
+    build_instruction(program_id, metas, AmmInstruction::SwapAndStake(params))
+}
+
+pub fn set_fee_token_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_token: FeeToken,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetFeeToken { fee_token, sequence },
+    )
+}
+
+pub fn set_auto_correct_vaults_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    enabled: bool,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetAutoCorrectVaults { enabled, sequence },
+    )
+}
+
+pub fn swap_exact_out_instruction(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    token_amount_out: u64,
+    max_token_amount_in: u64,
+    direction: SwapDirection,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        swap_account_metas(program_id, accounts),
+        AmmInstruction::SwapExactOut { token_amount_out, max_token_amount_in, direction },
+    )
+}
+
+pub fn set_max_event_queue_fill_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMaxEventQueueFill { bps, sequence },
+    )
+}
+
+pub fn swap_orca_instruction(
+    program_id: &Pubkey,
+    program_token_a_account: &Pubkey,
+    program_token_b_account: &Pubkey,
+    pool: &OrcaPoolAccounts,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(get_program_account(program_id), false),
+        AccountMeta::new(*program_token_a_account, false),
+        AccountMeta::new(*program_token_b_account, false),
+        AccountMeta::new_readonly(pool.pool_program_id, false),
+        AccountMeta::new_readonly(pool.swap_state, false),
+        AccountMeta::new_readonly(pool.swap_authority, false),
+        AccountMeta::new(pool.pool_token_a_account, false),
+        AccountMeta::new(pool.pool_token_b_account, false),
+        AccountMeta::new(pool.pool_mint, false),
+        AccountMeta::new(pool.pool_fee_account, false),
+        AccountMeta::new_readonly(pool.spl_token_id, false),
+    ];
+    build_instruction(
+        program_id,
+        accounts,
+        AmmInstruction::SwapOrca { token_a_amount_in, token_b_amount_in, min_token_amount_out },
+    )
+}
+
+pub fn swap_with_slippage_bps_instruction(
+    program_id: &Pubkey,
+    accounts: &SwapAccounts,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    slippage_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        swap_account_metas(program_id, accounts),
+        AmmInstruction::SwapWithSlippageBps { token_a_amount_in, token_b_amount_in, slippage_bps },
+    )
+}
+
+/// The admin pays for the program account when it doesn't exist yet
+pub fn initialize_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_bps: u16,
+    instance: u8,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(get_instance_program_account(program_id, instance), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::Initialize { fee_bps, instance })
+}
+
+pub fn set_fee_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetFee { fee_bps, sequence },
+    )
+}
+
+pub fn set_default_slippage_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    slippage_bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetDefaultSlippage { slippage_bps, sequence },
+    )
+}
+
+pub fn set_protocol_enabled_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    protocol: u8,
+    enabled: bool,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetProtocolEnabled { protocol, enabled, sequence },
+    )
+}
+
+pub fn set_fee_recipient_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_recipient: &Pubkey,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = admin_account_metas(program_id, admin);
+    accounts.push(AccountMeta::new_readonly(*fee_recipient, false));
+    build_instruction(program_id, accounts, AmmInstruction::SetFeeRecipient { sequence })
+}
+
+pub fn set_paused_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    paused: bool,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetPaused { paused, sequence },
+    )
+}
+
+/// Proposes new_admin, which takes over by signing accept_admin_instruction
+pub fn transfer_admin_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = admin_account_metas(program_id, admin);
+    accounts.push(AccountMeta::new_readonly(*new_admin, false));
+    build_instruction(program_id, accounts, AmmInstruction::TransferAdmin { sequence })
+}
+
+pub fn accept_admin_instruction(
+    program_id: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, new_admin),
+        AmmInstruction::AcceptAdmin,
+    )
+}
+
+pub fn set_mint_fee_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    mint: &Pubkey,
+    fee_bps: u16,
+    sequence: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(
+        program_id,
+        admin_account_metas(program_id, admin),
+        AmmInstruction::SetMintFee { mint: *mint, fee_bps, sequence },
+    )
+}
+
+/// Program token b holds the intermediate token between the two pools
+pub fn swap_two_hop_instruction(
+    program_id: &Pubkey,
+    program_token_accounts: [Pubkey; 3],
+    first_pool: &RaydiumPoolAccounts,
+    second_pool: &RaydiumPoolAccounts,
+    amount_in: u64,
+    min_final_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![AccountMeta::new_readonly(get_program_account(program_id), false)];
+    accounts.extend(program_token_accounts.iter().map(|key| AccountMeta::new(*key, false)));
+    accounts.extend(first_pool.to_account_metas());
+    accounts.extend(second_pool.to_account_metas());
+    build_instruction(program_id, accounts, AmmInstruction::SwapTwoHop { amount_in, min_final_out })
+}
+
+pub fn withdraw_fees_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_token_account: &Pubkey,
+    destination: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new(*fee_token_account, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::WithdrawFees { amount })
+}
+
+/// Sweeps each (fee token account, destination) pair, all under token_program_id
+pub fn sweep_fees_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    fee_accounts: &[(Pubkey, Pubkey)],
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let count: u8 = fee_accounts.len().try_into().map_err(|_| ProgramError::InvalidArgument)?;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    for (fee_account, destination) in fee_accounts {
+        accounts.push(AccountMeta::new(*fee_account, false));
+        accounts.push(AccountMeta::new(*destination, false));
+    }
+    build_instruction(program_id, accounts, AmmInstruction::SweepFees { count })
+}
+
+/// The program token account on the input side is the temporary wrapped SOL account
+pub fn swap_native_in_instruction(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    accounts: &SwapAccounts,
+    amount_in: u64,
+    min_token_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut metas = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    metas.extend(swap_account_metas(program_id, accounts));
+    build_instruction(
+        program_id,
+        metas,
+        AmmInstruction::SwapNativeIn { amount_in, min_token_amount_out },
+    )
+}
+
+pub fn close_temp_account_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    token_account: &Pubkey,
+    destination: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::CloseTempAccount)
+}
+
+pub fn rescue_tokens_instruction(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    token_account: &Pubkey,
+    destination: &Pubkey,
+    token_program_id: &Pubkey,
+    allow_fee_account: bool,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(get_program_account(program_id), false),
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    build_instruction(program_id, accounts, AmmInstruction::RescueTokens { allow_fee_account })
+}
+
+pub fn get_version_instruction(program_id: &Pubkey) -> Result<Instruction, ProgramError> {
+    build_instruction(program_id, vec![], AmmInstruction::GetVersion)
+}
+
+pub fn get_config_instruction(program_id: &Pubkey) -> Result<Instruction, ProgramError> {
+    let accounts = vec![AccountMeta::new_readonly(get_program_account(program_id), false)];
+    build_instruction(program_id, accounts, AmmInstruction::GetConfig)
+}
+
+pub fn swap_saber_instruction(
+    program_id: &Pubkey,
+    program_token_a_account: &Pubkey,
+    program_token_b_account: &Pubkey,
+    pool: &SaberPoolAccounts,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(get_program_account(program_id), false),
+        AccountMeta::new(*program_token_a_account, false),
+        AccountMeta::new(*program_token_b_account, false),
+        AccountMeta::new_readonly(pool.pool_program_id, false),
+        AccountMeta::new_readonly(pool.swap_info, false),
+        AccountMeta::new_readonly(pool.swap_authority, false),
+        AccountMeta::new(pool.pool_token_a_account, false),
+        AccountMeta::new(pool.pool_token_b_account, false),
+        AccountMeta::new(pool.admin_fee_a_account, false),
+        AccountMeta::new(pool.admin_fee_b_account, false),
+        AccountMeta::new_readonly(pool.spl_token_id, false),
+    ];
+    build_instruction(
+        program_id,
+        accounts,
+        AmmInstruction::SwapSaber { token_a_amount_in, token_b_amount_in, min_token_amount_out },
+    )
+}
+
+/// Token a is the market coin, exactly one of the amounts in is non-zero
+pub fn swap_serum_direct_instruction(
+    program_id: &Pubkey,
+    program_token_a_account: &Pubkey,
+    program_token_b_account: &Pubkey,
+    market: &SerumMarketAccounts,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new(get_program_account(program_id), false),
+        AccountMeta::new(*program_token_a_account, false),
+        AccountMeta::new(*program_token_b_account, false),
+        AccountMeta::new_readonly(market.serum_program_id, false),
+        AccountMeta::new(market.serum_market, false),
+        AccountMeta::new(market.serum_open_orders, false),
+        AccountMeta::new(market.serum_request_queue, false),
+        AccountMeta::new(market.serum_event_queue, false),
+        AccountMeta::new(market.serum_bids, false),
+        AccountMeta::new(market.serum_asks, false),
+        AccountMeta::new(market.serum_coin_vault_account, false),
+        AccountMeta::new(market.serum_pc_vault_account, false),
+        AccountMeta::new_readonly(market.serum_vault_signer, false),
+        AccountMeta::new_readonly(market.spl_token_id, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    build_instruction(
+        program_id,
+        accounts,
+        AmmInstruction::SwapSerumDirect { token_a_amount_in, token_b_amount_in, min_token_amount_out },
+    )
+}
+
+/// Routes through the protocol instruction whose accounts are passed, e.g. those of
+/// swap_saber_instruction with protocol 2, see ProgramIDType::from_route_protocol
+pub fn route_instruction(
+    program_id: &Pubkey,
+    protocol: u8,
+    accounts: Vec<AccountMeta>,
+    amount_in: u64,
+    min_out: u64,
+) -> Result<Instruction, ProgramError> {
+    build_instruction(program_id, accounts, AmmInstruction::Route { protocol, amount_in, min_out })
+}
+
+/// Chains route_instruction legs into one BatchSwap, their accounts in the same order
+pub fn batch_swap_instruction(
+    program_id: &Pubkey,
+    routes: &[Instruction],
+) -> Result<Instruction, ProgramError> {
+    let count: u8 = routes.len().try_into().map_err(|_| ProgramError::InvalidArgument)?;
+    let mut data = vec![0; AmmInstruction::BATCH_SWAP_LEN];
+    AmmInstruction::BatchSwap { count }.pack(&mut data)?;
+    let mut accounts = vec![];
+    for route in routes {
+        if !matches!(AmmInstruction::unpack(&route.data)?, AmmInstruction::Route { .. }) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        data.extend_from_slice(&route.data);
+        accounts.extend_from_slice(&route.accounts);
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Read only, simulate the transaction and read the quote from its return data
+pub fn quote_instruction(
+    program_id: &Pubkey,
+    pool: &RaydiumPoolAccounts,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        AccountMeta::new_readonly(pool.pool_program_id, false),
+        AccountMeta::new_readonly(pool.amm_id, false),
+        AccountMeta::new_readonly(pool.amm_open_orders, false),
+        AccountMeta::new_readonly(pool.pool_coin_token_account, false),
+        AccountMeta::new_readonly(pool.pool_pc_token_account, false),
+    ];
+    build_instruction(
+        program_id,
+        accounts,
+        AmmInstruction::Quote { token_a_amount_in, token_b_amount_in },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::utils::swap::{RAYDIUM_POOL_ACCOUNTS, SWAP_ACCOUNTS},
+    };
+
+    fn raydium_pool() -> RaydiumPoolAccounts {
+        RaydiumPoolAccounts {
+            pool_program_id: Pubkey::new_unique(),
+            pool_coin_token_account: Pubkey::new_unique(),
+            pool_pc_token_account: Pubkey::new_unique(),
+            spl_token_id: spl_token::id(),
+            amm_id: Pubkey::new_unique(),
+            amm_authority: Pubkey::new_unique(),
+            amm_open_orders: Pubkey::new_unique(),
+            amm_target: Pubkey::new_unique(),
+            serum_market: Pubkey::new_unique(),
+            serum_program_id: Pubkey::new_unique(),
+            serum_bids: Pubkey::new_unique(),
+            serum_asks: Pubkey::new_unique(),
+            serum_event_queue: Pubkey::new_unique(),
+            serum_coin_vault_account: Pubkey::new_unique(),
+            serum_pc_vault_account: Pubkey::new_unique(),
+            serum_vault_signer: Pubkey::new_unique(),
+        }
+    }
+
+    fn swap_accounts() -> SwapAccounts {
+        SwapAccounts {
+            program_token_a_account: Pubkey::new_unique(),
+            program_token_b_account: Pubkey::new_unique(),
+            pool: raydium_pool(),
+        }
+    }
+
+    #[test]
+    fn test_builders_round_trip() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let accounts = swap_accounts();
+        let params = SwapParams {
+            token_a_amount_in: 1_000,
+            min_token_amount_out: 900,
+            client_order_id: [7; 16],
+            deadline: 1_700_000_000,
+            ..SwapParams::default()
+        };
+        let after_transfer_accounts = AfterTransferAccounts {
+            referrer: Some(key),
+            ..AfterTransferAccounts::default()
+        };
+        let staking_accounts = [AccountMeta::new(key, false), AccountMeta::new(key, false)];
+
+        let mut cases = vec![
+            (
+                before_transfer_instruction(&program_id, &BeforeTransferAccounts::default(), 5, 0),
+                AmmInstruction::BeforeTransfer { amount: 5, tolerance: 0 },
+                6,
+            ),
+            (
+                before_transfer_instruction(
+                    &program_id,
+                    &BeforeTransferAccounts { mint: Some(key), ..BeforeTransferAccounts::default() },
+                    5,
+                    1,
+                ),
+                AmmInstruction::BeforeTransfer { amount: 5, tolerance: 1 },
+                7,
+            ),
+            (
+                swap_instruction(&program_id, &accounts, params),
+                AmmInstruction::Swap(params),
+                SWAP_ACCOUNTS,
+            ),
+            (
+                swap_with_refund_instruction(&program_id, &accounts, params, &key),
+                AmmInstruction::Swap(params),
+                SWAP_ACCOUNTS + 1,
+            ),
+            (
+                after_transfer_instruction(&program_id, &after_transfer_accounts, 5, 2_500, 3),
+                AmmInstruction::AfterTransfer { amount: 5, referral_bps: 2_500, fee_amount: 3 },
+                11,
+            ),
+            (
+                create_account_instruction(&program_id, &key, 117, 1),
+                AmmInstruction::CreateAccount { size: 117, instance: 1 },
+                4,
+            ),
+            (
+                harvest_instruction(&program_id, &key, &key, &key, &admin, 5),
+                AmmInstruction::Harvest { amount: 5 },
+                5,
+            ),
+            (
+                set_min_deliverable_out_instruction(&program_id, &admin, 5, 1),
+                AmmInstruction::SetMinDeliverableOut { amount: 5, sequence: 1 },
+                2,
+            ),
+            (
+                set_min_fee_instruction(&program_id, &admin, 5, 1),
+                AmmInstruction::SetMinFee { amount: 5, sequence: 1 },
+                2,
+            ),
+            (
+                set_min_swap_instruction(&program_id, &admin, 5, 1),
+                AmmInstruction::SetMinSwap { amount: 5, sequence: 1 },
+                2,
+            ),
+            (
+                set_fee_split_instruction(&program_id, &admin, 30, 20, 1),
+                AmmInstruction::SetFeeSplit { fee_bps: 30, lp_fee_bps: 20, sequence: 1 },
+                2,
+            ),
+            (
+                verify_config_instruction(&program_id, &admin, true),
+                AmmInstruction::VerifyConfig { repair: true },
+                2,
+            ),
+            (
+                sweep_lamports_instruction(&program_id, &admin, &key),
+                AmmInstruction::SweepLamports,
+                5,
+            ),
+            (
+                swap_best_fee_instruction(&program_id, &key, &key, &[raydium_pool(), raydium_pool()], params),
+                AmmInstruction::SwapBestFee(params),
+                3 + 2 * RAYDIUM_POOL_ACCOUNTS,
+            ),
+            (
+                set_maintenance_window_instruction(&program_id, &admin, 10, 20, 1),
+                AmmInstruction::SetMaintenanceWindow { start_slot: 10, end_slot: 20, sequence: 1 },
+                2,
+            ),
+            (
+                set_skip_funded_deposit_instruction(&program_id, &admin, true, 1),
+                AmmInstruction::SetSkipFundedDeposit { enabled: true, sequence: 1 },
+                2,
+            ),
+            (
+                set_max_pool_consume_instruction(&program_id, &admin, 500, 1),
+                AmmInstruction::SetMaxPoolConsume { bps: 500, sequence: 1 },
+                2,
+            ),
+            (
+                swap_and_stake_instruction(&program_id, &accounts, &staking_accounts, params),
+                AmmInstruction::SwapAndStake(params),
+                SWAP_ACCOUNTS + 2,
+            ),
+            (
+                set_fee_token_instruction(&program_id, &admin, FeeToken::Output, 1),
+                AmmInstruction::SetFeeToken { fee_token: FeeToken::Output, sequence: 1 },
+                2,
+            ),
+            (
+                set_auto_correct_vaults_instruction(&program_id, &admin, true, 1),
+                AmmInstruction::SetAutoCorrectVaults { enabled: true, sequence: 1 },
+                2,
+            ),
+            (
+                swap_exact_out_instruction(&program_id, &accounts, 900, 1_000, SwapDirection::PcToCoin),
+                AmmInstruction::SwapExactOut {
+                    token_amount_out: 900,
+                    max_token_amount_in: 1_000,
+                    direction: SwapDirection::PcToCoin,
+                },
+                SWAP_ACCOUNTS,
+            ),
+            (
+                set_max_event_queue_fill_instruction(&program_id, &admin, 8_000, 1),
+                AmmInstruction::SetMaxEventQueueFill { bps: 8_000, sequence: 1 },
+                2,
+            ),
+            (
+                swap_orca_instruction(&program_id, &key, &key, &OrcaPoolAccounts::default(), 1_000, 0, 900),
+                AmmInstruction::SwapOrca {
+                    token_a_amount_in: 1_000,
+                    token_b_amount_in: 0,
+                    min_token_amount_out: 900,
+                },
+                11,
+            ),
+            (
+                swap_with_slippage_bps_instruction(&program_id, &accounts, 0, 1_000, 50),
+                AmmInstruction::SwapWithSlippageBps {
+                    token_a_amount_in: 0,
+                    token_b_amount_in: 1_000,
+                    slippage_bps: 50,
+                },
+                SWAP_ACCOUNTS,
+            ),
+            (
+                initialize_instruction(&program_id, &admin, 30, 1),
+                AmmInstruction::Initialize { fee_bps: 30, instance: 1 },
+                4,
+            ),
+            (
+                set_fee_instruction(&program_id, &admin, 30, 1),
+                AmmInstruction::SetFee { fee_bps: 30, sequence: 1 },
+                2,
+            ),
+            (
+                set_fee_recipient_instruction(&program_id, &admin, &key, 1),
+                AmmInstruction::SetFeeRecipient { sequence: 1 },
+                3,
+            ),
+            (
+                set_paused_instruction(&program_id, &admin, true, 1),
+                AmmInstruction::SetPaused { paused: true, sequence: 1 },
+                2,
+            ),
+            (
+                transfer_admin_instruction(&program_id, &admin, &key, 1),
+                AmmInstruction::TransferAdmin { sequence: 1 },
+                3,
+            ),
+            (accept_admin_instruction(&program_id, &key), AmmInstruction::AcceptAdmin, 2),
+            (
+                set_mint_fee_instruction(&program_id, &admin, &key, 30, 1),
+                AmmInstruction::SetMintFee { mint: key, fee_bps: 30, sequence: 1 },
+                2,
+            ),
+            (
+                sweep_fees_instruction(&program_id, &admin, &[(key, key); 3], &key),
+                AmmInstruction::SweepFees { count: 3 },
+                9,
+            ),
+            (
+                swap_two_hop_instruction(&program_id, [key; 3], &raydium_pool(), &raydium_pool(), 1_000, 900),
+                AmmInstruction::SwapTwoHop { amount_in: 1_000, min_final_out: 900 },
+                4 + 2 * RAYDIUM_POOL_ACCOUNTS,
+            ),
+            (
+                withdraw_fees_instruction(&program_id, &admin, &key, &key, &key, 5),
+                AmmInstruction::WithdrawFees { amount: 5 },
+                5,
+            ),
+            (
+                swap_native_in_instruction(&program_id, &key, &accounts, 1_000, 900),
+                AmmInstruction::SwapNativeIn { amount_in: 1_000, min_token_amount_out: 900 },
+                2 + SWAP_ACCOUNTS,
+            ),
+            (
+                close_temp_account_instruction(&program_id, &admin, &key, &key, &key),
+                AmmInstruction::CloseTempAccount,
+                5,
+            ),
+            (
+                rescue_tokens_instruction(&program_id, &admin, &key, &key, &key, true),
+                AmmInstruction::RescueTokens { allow_fee_account: true },
+                5,
+            ),
+            (get_version_instruction(&program_id), AmmInstruction::GetVersion, 0),
+            (get_config_instruction(&program_id), AmmInstruction::GetConfig, 1),
+            (
+                set_default_slippage_instruction(&program_id, &admin, 50, 1),
+                AmmInstruction::SetDefaultSlippage { slippage_bps: 50, sequence: 1 },
+                2,
+            ),
+            (
+                set_protocol_enabled_instruction(&program_id, &admin, 1, false, 1),
+                AmmInstruction::SetProtocolEnabled { protocol: 1, enabled: false, sequence: 1 },
+                2,
+            ),
+            (
+                quote_instruction(&program_id, &raydium_pool(), 0, 5),
+                AmmInstruction::Quote { token_a_amount_in: 0, token_b_amount_in: 5 },
+                5,
+            ),
+            (
+                swap_serum_direct_instruction(&program_id, &key, &key, &SerumMarketAccounts::default(), 5, 0, 4),
+                AmmInstruction::SwapSerumDirect {
+                    token_a_amount_in: 5,
+                    token_b_amount_in: 0,
+                    min_token_amount_out: 4,
+                },
+                15,
+            ),
+            (
+                swap_saber_instruction(&program_id, &key, &key, &SaberPoolAccounts::default(), 0, 1_000, 900),
+                AmmInstruction::SwapSaber {
+                    token_a_amount_in: 0,
+                    token_b_amount_in: 1_000,
+                    min_token_amount_out: 900,
+                },
+                11,
+            ),
+            (
+                route_instruction(
+                    &program_id,
+                    2,
+                    swap_saber_instruction(&program_id, &key, &key, &SaberPoolAccounts::default(), 5, 0, 4)
+                        .unwrap()
+                        .accounts,
+                    5,
+                    4,
+                ),
+                AmmInstruction::Route { protocol: 2, amount_in: 5, min_out: 4 },
+                11,
+            ),
+        ];
+
+        let saber_route = route_instruction(
+            &program_id,
+            2,
+            swap_saber_instruction(&program_id, &key, &key, &SaberPoolAccounts::default(), 5, 0, 4)
+                .unwrap()
+                .accounts,
+            5,
+            4,
+        );
+        let batch = batch_swap_instruction(&program_id, &[saber_route.clone().unwrap(), saber_route.unwrap()]);
+        cases.push((batch, AmmInstruction::BatchSwap { count: 2 }, 22));
+
+        for (instruction, expected, account_count) in cases {
+            let instruction = instruction.unwrap();
+            assert_eq!(instruction.program_id, program_id);
+            assert_eq!(AmmInstruction::unpack(&instruction.data), Ok(expected), "{}", expected);
+            assert_eq!(instruction.accounts.len(), account_count, "{}", expected);
+        }
+    }
+
+    #[test]
+    fn test_swap_account_order() {
+        let program_id = Pubkey::new_unique();
+        let accounts = swap_accounts();
+
+        let instruction = swap_instruction(&program_id, &accounts, SwapParams::default()).unwrap();
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+
+        assert_eq!(keys[0], get_program_account(&program_id));
+        assert_eq!(keys[1..3], [accounts.program_token_a_account, accounts.program_token_b_account]);
+        assert_eq!(keys[3], accounts.pool.pool_program_id);
+        assert_eq!(keys[6], spl_token::id());
+        assert_eq!(keys[7], accounts.pool.amm_id);
+        assert_eq!(keys[15], accounts.pool.serum_event_queue);
+        assert_eq!(keys[18], accounts.pool.serum_vault_signer);
+        // swap rejects a read-only amm id, open orders or target before the CPI
+        assert!(instruction.accounts[7].is_writable);
+        assert!(instruction.accounts[9].is_writable);
+        assert!(instruction.accounts[10].is_writable);
+    }
+}
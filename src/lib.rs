@@ -1,7 +1,15 @@
 #![forbid(unsafe_code)]
 
+pub mod accounts_layout;
 pub mod entrypoint;
+pub mod error;
+pub mod event;
 pub mod processor;
 pub mod instruction;
+#[cfg(any(test, feature = "client"))]
+pub mod instruction_builder;
 pub mod utils;
-pub mod protocol;
\ No newline at end of file
+pub mod protocol;
+pub mod quote;
+pub mod state;
+pub mod version;
\ No newline at end of file
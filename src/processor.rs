@@ -2,19 +2,61 @@
 
 use {
     crate::{
-        instruction::AmmInstruction,
+        instruction::{AmmInstruction, AmmInstructionType},
+        protocol::raydium::RaydiumStaking,
+        utils::compute::SyscallComputeMeter,
         utils::swap::{
             before_transfer,
             swap,
             after_transfer,
             create_program_account,
-            harvest
+            harvest,
+            set_min_deliverable_out,
+            set_min_fee,
+            set_fee_split,
+            verify_config,
+            sweep_lamports,
+            swap_best_fee,
+            set_maintenance_window,
+            set_skip_funded_deposit,
+            set_max_pool_consume,
+            swap_and_stake,
+            set_fee_token,
+            set_auto_correct_vaults,
+            swap_exact_out,
+            set_max_event_queue_fill,
+            swap_orca,
+            swap_with_slippage_bps,
+            initialize,
+            set_fee,
+            set_fee_recipient,
+            set_paused,
+            swap_two_hop,
+            withdraw_fees,
+            swap_native_in,
+            close_temp_account,
+            quote_swap,
+            swap_serum_direct,
+            swap_saber,
+            route,
+            batch_swap,
+            set_min_swap,
+            rescue_tokens,
+            transfer_admin,
+            accept_admin,
+            set_mint_fee,
+            sweep_fees,
+            get_version,
+            get_config,
+            set_default_slippage,
+            set_protocol_enabled,
         },
     },
     solana_program::{
         account_info::AccountInfo, entrypoint::ProgramResult, log::sol_log_compute_units, msg,
-        pubkey::Pubkey,
+        program_error::ProgramError, pubkey::Pubkey,
     },
+    std::convert::TryFrom,
 };
 
 /// Program's entrypoint.
@@ -31,39 +73,46 @@ pub fn process_instruction(
     msg!("Swap entrypoint");
 
     // Read and unpack instruction data
-    let instruction = AmmInstruction::unpack(instruction_data)?;
+    #[cfg(not(feature = "borsh"))]
+    let instruction = AmmInstruction::unpack(instruction_data);
+    #[cfg(feature = "borsh")]
+    let instruction = AmmInstruction::unpack_with_borsh(instruction_data);
+    let instruction = instruction.map_err(|err| log_unpack_error(instruction_data, err))?;
 
     match instruction {
         AmmInstruction::BeforeTransfer {
-            amount
+            amount,
+            tolerance,
         } => before_transfer(
+            program_id,
             accounts,
-            amount
+            amount,
+            tolerance,
         )?,
-        AmmInstruction::Swap {
-            token_a_amount_in,
-            token_b_amount_in,
-            min_token_amount_out,
-        } => swap(
+        AmmInstruction::Swap(params) => swap(
             accounts,
             program_id,
-            token_a_amount_in,
-            token_b_amount_in,
-            min_token_amount_out,
+            params,
         )?,
         AmmInstruction::AfterTransfer {
-            amount
+            amount,
+            referral_bps,
+            fee_amount,
         } => after_transfer(
             program_id,
             accounts,
-            amount
+            amount,
+            referral_bps,
+            fee_amount,
         )?,
         AmmInstruction::CreateAccount {
-            size
+            size,
+            instance,
         } => create_program_account(
             program_id,
             accounts,
-            size
+            size,
+            instance,
         )?,
         AmmInstruction::Harvest {
             amount
@@ -72,9 +121,345 @@ pub fn process_instruction(
             accounts,
             amount
         )?,
+        AmmInstruction::SetMinDeliverableOut {
+            amount,
+            sequence,
+        } => set_min_deliverable_out(
+            program_id,
+            accounts,
+            amount,
+            sequence,
+        )?,
+        AmmInstruction::SetMinFee {
+            amount,
+            sequence,
+        } => set_min_fee(
+            program_id,
+            accounts,
+            amount,
+            sequence,
+        )?,
+        AmmInstruction::SetFeeSplit {
+            fee_bps,
+            lp_fee_bps,
+            sequence,
+        } => set_fee_split(
+            program_id,
+            accounts,
+            fee_bps,
+            lp_fee_bps,
+            sequence,
+        )?,
+        AmmInstruction::VerifyConfig {
+            repair
+        } => verify_config(
+            program_id,
+            accounts,
+            repair
+        )?,
+        AmmInstruction::SweepLamports => sweep_lamports(
+            program_id,
+            accounts
+        )?,
+        AmmInstruction::SwapBestFee(params) => swap_best_fee(
+            accounts,
+            program_id,
+            params,
+        )?,
+        AmmInstruction::SetMaintenanceWindow {
+            start_slot,
+            end_slot,
+            sequence,
+        } => set_maintenance_window(
+            program_id,
+            accounts,
+            start_slot,
+            end_slot,
+            sequence,
+        )?,
+        AmmInstruction::SetSkipFundedDeposit {
+            enabled,
+            sequence,
+        } => set_skip_funded_deposit(
+            program_id,
+            accounts,
+            enabled,
+            sequence,
+        )?,
+        AmmInstruction::SetMaxPoolConsume {
+            bps,
+            sequence,
+        } => set_max_pool_consume(
+            program_id,
+            accounts,
+            bps,
+            sequence,
+        )?,
+        AmmInstruction::SwapAndStake(params) => swap_and_stake(
+            accounts,
+            program_id,
+            params,
+            &RaydiumStaking,
+            &SyscallComputeMeter,
+        )?,
+        AmmInstruction::SetFeeToken {
+            fee_token,
+            sequence,
+        } => set_fee_token(
+            program_id,
+            accounts,
+            fee_token,
+            sequence,
+        )?,
+        AmmInstruction::SetAutoCorrectVaults {
+            enabled,
+            sequence,
+        } => set_auto_correct_vaults(
+            program_id,
+            accounts,
+            enabled,
+            sequence,
+        )?,
+        AmmInstruction::SwapExactOut {
+            token_amount_out,
+            max_token_amount_in,
+            direction,
+        } => swap_exact_out(
+            accounts,
+            program_id,
+            token_amount_out,
+            max_token_amount_in,
+            direction,
+        )?,
+        AmmInstruction::SetMaxEventQueueFill {
+            bps,
+            sequence,
+        } => set_max_event_queue_fill(
+            program_id,
+            accounts,
+            bps,
+            sequence,
+        )?,
+        AmmInstruction::SwapOrca {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } => swap_orca(
+            accounts,
+            program_id,
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        )?,
+        AmmInstruction::SwapWithSlippageBps {
+            token_a_amount_in,
+            token_b_amount_in,
+            slippage_bps,
+        } => swap_with_slippage_bps(
+            accounts,
+            program_id,
+            token_a_amount_in,
+            token_b_amount_in,
+            slippage_bps,
+        )?,
+        AmmInstruction::Initialize {
+            fee_bps,
+            instance,
+        } => initialize(
+            program_id,
+            accounts,
+            fee_bps,
+            instance,
+        )?,
+        AmmInstruction::SetFee {
+            fee_bps,
+            sequence,
+        } => set_fee(
+            program_id,
+            accounts,
+            fee_bps,
+            sequence,
+        )?,
+        AmmInstruction::SetFeeRecipient {
+            sequence,
+        } => set_fee_recipient(
+            program_id,
+            accounts,
+            sequence,
+        )?,
+        AmmInstruction::SetPaused {
+            paused,
+            sequence,
+        } => set_paused(
+            program_id,
+            accounts,
+            paused,
+            sequence,
+        )?,
+        AmmInstruction::SwapTwoHop {
+            amount_in,
+            min_final_out,
+        } => swap_two_hop(
+            accounts,
+            program_id,
+            amount_in,
+            min_final_out,
+        )?,
+        AmmInstruction::WithdrawFees {
+            amount,
+        } => withdraw_fees(
+            program_id,
+            accounts,
+            amount,
+        )?,
+        AmmInstruction::SwapNativeIn {
+            amount_in,
+            min_token_amount_out,
+        } => swap_native_in(
+            accounts,
+            program_id,
+            amount_in,
+            min_token_amount_out,
+        )?,
+        AmmInstruction::CloseTempAccount => close_temp_account(
+            program_id,
+            accounts,
+        )?,
+        AmmInstruction::Quote {
+            token_a_amount_in,
+            token_b_amount_in,
+        } => quote_swap(
+            accounts,
+            token_a_amount_in,
+            token_b_amount_in,
+        )?,
+        AmmInstruction::SwapSerumDirect {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } => swap_serum_direct(
+            accounts,
+            program_id,
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        )?,
+        AmmInstruction::SwapSaber {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        } => swap_saber(
+            accounts,
+            program_id,
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        )?,
+        AmmInstruction::Route {
+            protocol,
+            amount_in,
+            min_out,
+        } => route(accounts, program_id, protocol, amount_in, min_out)?,
+        AmmInstruction::BatchSwap {
+            count,
+        } => batch_swap(
+            accounts,
+            program_id,
+            count,
+            &instruction_data[AmmInstruction::BATCH_SWAP_LEN..],
+        )?,
+        AmmInstruction::SetMinSwap {
+            amount,
+            sequence,
+        } => set_min_swap(
+            program_id,
+            accounts,
+            amount,
+            sequence,
+        )?,
+        AmmInstruction::RescueTokens {
+            allow_fee_account,
+        } => rescue_tokens(
+            program_id,
+            accounts,
+            allow_fee_account,
+        )?,
+        AmmInstruction::GetVersion => get_version()?,
+        AmmInstruction::TransferAdmin {
+            sequence,
+        } => transfer_admin(
+            program_id,
+            accounts,
+            sequence,
+        )?,
+        AmmInstruction::AcceptAdmin => accept_admin(
+            program_id,
+            accounts,
+        )?,
+        AmmInstruction::SetMintFee {
+            mint,
+            fee_bps,
+            sequence,
+        } => set_mint_fee(
+            program_id,
+            accounts,
+            &mint,
+            fee_bps,
+            sequence,
+        )?,
+        AmmInstruction::SweepFees {
+            count,
+        } => sweep_fees(
+            program_id,
+            accounts,
+            count,
+        )?,
+        AmmInstruction::GetConfig => get_config(
+            program_id,
+            accounts,
+        )?,
+        AmmInstruction::SetDefaultSlippage {
+            slippage_bps,
+            sequence,
+        } => set_default_slippage(
+            program_id,
+            accounts,
+            slippage_bps,
+            sequence,
+        )?,
+        AmmInstruction::SetProtocolEnabled {
+            protocol,
+            enabled,
+            sequence,
+        } => set_protocol_enabled(
+            program_id,
+            accounts,
+            protocol,
+            enabled,
+            sequence,
+        )?,
     }
 
     sol_log_compute_units();
     msg!("Swap end of instruction");
     Ok(())
 }
+
+/// Logs the instruction type the data names and the length it packs to, returns err.
+fn log_unpack_error(instruction_data: &[u8], err: ProgramError) -> ProgramError {
+    match instruction_data.first() {
+        None => msg!("Error: {}. Empty instruction data", err),
+        Some(&discriminator) => match AmmInstructionType::try_from(discriminator) {
+            Ok(instruction_type) => msg!(
+                "Error: {}. Instruction type {} ({:?}) packs to {} bytes, got {}",
+                err,
+                discriminator,
+                instruction_type,
+                instruction_type.packed_len(),
+                instruction_data.len()
+            ),
+            Err(_) => msg!("Error: {}. Unknown instruction type {}", err, discriminator),
+        },
+    }
+    err
+}
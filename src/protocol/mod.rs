@@ -1 +1,18 @@
-pub mod raydium;
\ No newline at end of file
+pub mod orca;
+pub mod raydium;
+pub mod saber;
+pub mod serum;
+
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult};
+
+/// Staking backend used by composed instructions like SwapAndStake.
+pub trait Staking {
+    /// Stakes amount from the custody account, signing as the router authority.
+    /// Accounts are backend specific.
+    fn stake_with_seeds(
+        &self,
+        accounts: &[AccountInfo],
+        seeds: &[&[&[u8]]],
+        amount: u64,
+    ) -> ProgramResult;
+}
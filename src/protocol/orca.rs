@@ -0,0 +1,155 @@
+//! Orca specific functions
+
+use {
+    crate::{quote, utils::account, utils::orca::OrcaSwap, utils::pack::check_data_len},
+    arrayref::array_ref,
+    solana_program::{
+        account_info::AccountInfo,
+        instruction::{AccountMeta, Instruction},
+        msg,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+pub mod orca_v1 {
+    solana_program::declare_id!("DjVE6JNiYqPL2QXyCUUh8rNjHrbz9hXHNYt99MQ59qw1");
+}
+pub mod orca_v2 {
+    solana_program::declare_id!("9W959DqEETiGZocYWCQPaJ6sBmUzgfxXfqGeTEdp3aQP");
+}
+
+/// Token Swap state: version, is_initialized, bump seed, token program, token a vault,
+/// token b vault, pool mint, token a mint, token b mint, pool fee account, fees, curve
+pub const SWAP_STATE_LEN: usize = 324;
+const TOKEN_A_OFFSET: usize = 35;
+const TOKEN_B_OFFSET: usize = 67;
+const TRADE_FEE_OFFSET: usize = 227;
+const OWNER_TRADE_FEE_OFFSET: usize = 243;
+
+pub fn check_pool_program_id(program_id: &Pubkey) -> bool {
+    program_id == &orca_v1::id() || program_id == &orca_v2::id()
+}
+
+/// Checks the pool vaults are the token a and b vaults named by the swap state.
+pub fn check_pool_vaults(
+    swap_state: &AccountInfo,
+    pool_token_a_account: &AccountInfo,
+    pool_token_b_account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let data = swap_state.try_borrow_data()?;
+    check_data_len(&data, SWAP_STATE_LEN)?;
+    let token_a = Pubkey::new_from_array(*array_ref![data, TOKEN_A_OFFSET, 32]);
+    let token_b = Pubkey::new_from_array(*array_ref![data, TOKEN_B_OFFSET, 32]);
+    if &token_a != pool_token_a_account.key || &token_b != pool_token_b_account.key {
+        msg!(
+            "Error: Pool vaults {} and {} don't belong to swap {}",
+            pool_token_a_account.key,
+            pool_token_b_account.key,
+            swap_state.key
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Returns the trade and owner trade fees of the pool as a single fraction.
+pub fn get_pool_swap_fee(swap_state: &AccountInfo) -> Result<f64, ProgramError> {
+    let data = swap_state.try_borrow_data()?;
+    check_data_len(&data, SWAP_STATE_LEN)?;
+    let mut fee = 0.0;
+    for &offset in &[TRADE_FEE_OFFSET, OWNER_TRADE_FEE_OFFSET] {
+        let numerator = u64::from_le_bytes(*array_ref![data, offset, 8]);
+        let denominator = u64::from_le_bytes(*array_ref![data, offset + 8, 8]);
+        if numerator == 0 {
+            continue;
+        }
+        if denominator == 0 || numerator >= denominator {
+            msg!("Error: Invalid swap fee {}/{}", numerator, denominator);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        fee += numerator as f64 / denominator as f64;
+    }
+
+    Ok(fee)
+}
+
+pub fn get_pool_swap_amounts<'a, 'b>(
+    pool_token_a_account: &'a AccountInfo<'b>,
+    pool_token_b_account: &'a AccountInfo<'b>,
+    swap_state: &'a AccountInfo<'b>,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if (token_a_amount_in == 0 && token_b_amount_in == 0)
+        || (token_a_amount_in > 0 && token_b_amount_in > 0)
+    {
+        msg!("Error: One and only one of token amounts must be non-zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let fee = get_pool_swap_fee(swap_state)?;
+    let token_a_balance = account::get_token_balance(pool_token_a_account)?;
+    let token_b_balance = account::get_token_balance(pool_token_b_account)?;
+    if token_a_amount_in == 0 {
+        // b to a
+        Ok((
+            token_b_amount_in,
+            quote::get_amount_out(token_b_amount_in, token_b_balance, token_a_balance, fee)?,
+        ))
+    } else {
+        // a to b
+        Ok((
+            token_a_amount_in,
+            quote::get_amount_out(token_a_amount_in, token_a_balance, token_b_balance, fee)?,
+        ))
+    }
+}
+
+/// Builds the Orca swap instruction, token a is sold unless a_to_b is false.
+///
+/// Accounts: authority, token a custody, token b custody, pool program, swap state,
+/// swap authority, pool token a vault, pool token b vault, pool mint, pool fee account,
+/// spl token
+pub fn build_swap_instruction(
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    a_to_b: bool,
+) -> Result<Instruction, ProgramError> {
+    if let [authority_account, token_a_custody_account, token_b_custody_account, pool_program_id, swap_state, swap_authority, pool_token_a_account, pool_token_b_account, pool_mint, pool_fee_account, spl_token_id] =
+        accounts
+    {
+        if !check_pool_program_id(pool_program_id.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (source, swap_source, swap_destination, destination) = if a_to_b {
+            (token_a_custody_account, pool_token_a_account, pool_token_b_account, token_b_custody_account)
+        } else {
+            (token_b_custody_account, pool_token_b_account, pool_token_a_account, token_a_custody_account)
+        };
+
+        Ok(Instruction {
+            program_id: *pool_program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*swap_state.key, false),
+                AccountMeta::new_readonly(*swap_authority.key, false),
+                AccountMeta::new_readonly(*authority_account.key, true),
+                AccountMeta::new(*source.key, false),
+                AccountMeta::new(*swap_source.key, false),
+                AccountMeta::new(*swap_destination.key, false),
+                AccountMeta::new(*destination.key, false),
+                AccountMeta::new(*pool_mint.key, false),
+                AccountMeta::new(*pool_fee_account.key, false),
+                AccountMeta::new_readonly(*spl_token_id.key, false),
+            ],
+            data: OrcaSwap {
+                instruction: 1,
+                amount_in,
+                minimum_amount_out,
+            }
+            .to_vec()?,
+        })
+    } else {
+        Err(ProgramError::NotEnoughAccountKeys)
+    }
+}
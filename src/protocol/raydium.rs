@@ -2,6 +2,10 @@
 
 use {
     crate::{
+        accounts_layout as layout,
+        error::AutoswapError,
+        protocol::Staking,
+        quote,
         utils::id::zero,
         utils::raydium::{
             RaydiumAddLiquidity, RaydiumRemoveLiquidity, RaydiumStake, RaydiumSwap, RaydiumUnstake,
@@ -11,6 +15,7 @@ use {
         utils::account,
     },
     arrayref::{array_ref, array_refs},
+    num_enum::TryFromPrimitive,
     solana_program::{
         account_info::AccountInfo,
         entrypoint::ProgramResult,
@@ -20,6 +25,7 @@ use {
         program_error::ProgramError,
         pubkey::Pubkey,
     },
+    std::convert::TryFrom,
 };
 
 pub mod raydium_v2 {
@@ -28,8 +34,12 @@ pub mod raydium_v2 {
 pub mod raydium_v3 {
     solana_program::declare_id!("27haf8L6oxUeXrHrgEgsexjSY5hbVUWEmvv9Nyxg8vQv");
 }
+/// The only AMM deployed on devnet, v2 and v3 are mainnet only
 pub mod raydium_v4 {
+    #[cfg(not(feature = "devnet"))]
     solana_program::declare_id!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+    #[cfg(feature = "devnet")]
+    solana_program::declare_id!("HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8");
 }
 
 pub mod raydium_stake {
@@ -43,6 +53,9 @@ pub mod raydium_stake_v5 {
 }
 
 pub const RAYDIUM_FEE: f64 = 0.0025;
+/// RAYDIUM_FEE as a numerator over a denominator
+pub const RAYDIUM_FEE_NUMERATOR: u64 = 25;
+pub const RAYDIUM_FEE_DENOMINATOR: u64 = 10_000;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct RaydiumUserStakeInfo {
@@ -318,6 +331,8 @@ pub fn get_pool_token_balances<'a, 'b>(
     amm_open_orders: &'a AccountInfo<'b>,
     amm_id: &'a AccountInfo<'b>,
 ) -> Result<(u64, u64), ProgramError> {
+    check_open_orders(amm_id, amm_open_orders)?;
+
     // get token balances
     let mut token_a_balance = account::get_token_balance(pool_coin_token_account)?;
     let mut token_b_balance = account::get_token_balance(pool_pc_token_account)?;
@@ -362,6 +377,115 @@ pub fn get_pool_token_balances<'a, 'b>(
     Ok((token_a_balance, token_b_balance))
 }
 
+/// Returns the (coin vault, pc vault, open orders) recorded in the amm state.
+/// The v2, v3 and v4 layouts, 624, 680 and 752 bytes, all keep the vaults and
+/// then the coin, pc and lp mints and the open orders right after their u64
+/// fields. An amm not owned by a Raydium program or of another size can't be
+/// checked and is rejected.
+pub fn get_amm_accounts(amm_id: &AccountInfo) -> Result<(Pubkey, Pubkey, Pubkey), ProgramError> {
+    if !check_pool_program_id(amm_id.owner) {
+        msg!(
+            "Error: InvalidPoolAccounts. Amm {} is owned by {}, not a Raydium program",
+            amm_id.key,
+            amm_id.owner
+        );
+        return Err(AutoswapError::InvalidPoolAccounts.into());
+    }
+    let token_coin_offset = match amm_id.data_len() {
+        624 => 176,
+        680 => 200,
+        AmmInfoV4::LEN => 336,
+        len => {
+            msg!("Error: InvalidPoolAccounts. Amm {} has an unknown layout of {} bytes", amm_id.key, len);
+            return Err(AutoswapError::InvalidPoolAccounts.into());
+        }
+    };
+    let data = amm_id.try_borrow_data()?;
+    let pubkey_at = |offset| Pubkey::new_from_array(*array_ref![data, offset, 32]);
+    Ok((
+        pubkey_at(token_coin_offset),
+        pubkey_at(token_coin_offset + 32),
+        pubkey_at(token_coin_offset + 160),
+    ))
+}
+
+/// Returns true if the pool coin and pc vaults are passed in each other's place.
+/// Checked against the vaults in the amm state, so an amm can't be paired
+/// with other token accounts, see get_amm_accounts.
+pub fn check_vaults_swapped(
+    amm_id: &AccountInfo,
+    pool_coin_token_account: &AccountInfo,
+    pool_pc_token_account: &AccountInfo,
+) -> Result<bool, ProgramError> {
+    let (token_coin, token_pc, _) = get_amm_accounts(amm_id)?;
+    let vaults = (*pool_coin_token_account.key, *pool_pc_token_account.key);
+    if vaults == (token_coin, token_pc) {
+        Ok(false)
+    } else if vaults == (token_pc, token_coin) {
+        Ok(true)
+    } else {
+        msg!(
+            "Error: InvalidPoolAccounts. Pool vaults {} and {} aren't the vaults of amm {}",
+            pool_coin_token_account.key,
+            pool_pc_token_account.key,
+            amm_id.key
+        );
+        Err(AutoswapError::InvalidPoolAccounts.into())
+    }
+}
+
+/// Checks amm_open_orders is the open orders account recorded in the amm
+/// state, its balances count towards the quote. See get_amm_accounts.
+pub fn check_open_orders(amm_id: &AccountInfo, amm_open_orders: &AccountInfo) -> ProgramResult {
+    let (_, _, open_orders) = get_amm_accounts(amm_id)?;
+    if *amm_open_orders.key != open_orders {
+        msg!(
+            "Error: InvalidOpenOrders. {} isn't the open orders {} of amm {}",
+            amm_open_orders.key,
+            open_orders,
+            amm_id.key
+        );
+        return Err(AutoswapError::InvalidOpenOrders.into());
+    }
+    Ok(())
+}
+
+/// Returns the pool swap fee as the (numerator, denominator) of the input.
+/// Read from the v4 amm state, older layouts use the default RAYDIUM_FEE.
+pub fn get_pool_swap_fee(amm_id: &AccountInfo) -> Result<(u64, u64), ProgramError> {
+    if amm_id.data_len() != AmmInfoV4::LEN {
+        return Ok((RAYDIUM_FEE_NUMERATOR, RAYDIUM_FEE_DENOMINATOR));
+    }
+    let amm_id_data = amm_id.try_borrow_data()?;
+    let swap_fee_numerator = u64::from_le_bytes(*array_ref![amm_id_data, 176, 8]);
+    let swap_fee_denominator = u64::from_le_bytes(*array_ref![amm_id_data, 184, 8]);
+    if swap_fee_denominator == 0 || swap_fee_numerator >= swap_fee_denominator {
+        msg!("Error: Invalid swap fee {}/{}", swap_fee_numerator, swap_fee_denominator);
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    Ok((swap_fee_numerator, swap_fee_denominator))
+}
+
+/// Returns the (coin, pc) mint decimals recorded in the amm state, None when the
+/// amm isn't one of the known layouts. All of them start with status, nonce,
+/// order num, depth, coin decimals, pc decimals.
+pub fn get_pool_decimals(amm_id: &AccountInfo) -> Result<Option<(u8, u8)>, ProgramError> {
+    if ![624, 680, AmmInfoV4::LEN].contains(&amm_id.data_len()) {
+        return Ok(None);
+    }
+    let amm_id_data = amm_id.try_borrow_data()?;
+    let coin_decimals = u64::from_le_bytes(*array_ref![amm_id_data, 32, 8]);
+    let pc_decimals = u64::from_le_bytes(*array_ref![amm_id_data, 40, 8]);
+    match (u8::try_from(coin_decimals), u8::try_from(pc_decimals)) {
+        (Ok(coin_decimals), Ok(pc_decimals)) => Ok(Some((coin_decimals, pc_decimals))),
+        _ => {
+            msg!("Error: Invalid pool decimals {} and {}", coin_decimals, pc_decimals);
+            Err(ProgramError::InvalidAccountData)
+        }
+    }
+}
+
 pub fn get_pool_deposit_amounts<'a, 'b>(
     pool_coin_token_account: &'a AccountInfo<'b>,
     pool_pc_token_account: &'a AccountInfo<'b>,
@@ -393,11 +517,7 @@ pub fn get_pool_deposit_amounts<'a, 'b>(
         let estimated_coin_amount = math::checked_as_u64(
             coin_balance as f64 * max_pc_token_amount as f64 / (pc_balance as f64),
         )?;
-        coin_token_amount = if estimated_coin_amount > 1 {
-            estimated_coin_amount - 1
-        } else {
-            0
-        };
+        coin_token_amount = estimated_coin_amount.saturating_sub(1);
     } else {
         pc_token_amount = math::checked_as_u64(
             pc_balance as f64 * max_coin_token_amount as f64 / (coin_balance as f64),
@@ -459,39 +579,17 @@ pub fn get_pool_swap_amounts<'a, 'b>(
         amm_open_orders,
         amm_id,
     )?;
-    if coin_balance == 0 || pc_balance == 0 {
-        msg!("Error: Can't swap in an empty pool");
-        return Err(ProgramError::Custom(412));
-    }
     if coin_token_amount_in == 0 {
         // pc to coin
-        let amount_in_no_fee = (pc_token_amount_in as f64 * (1.0 - RAYDIUM_FEE)) as u64;
-        let estimated_coin_amount = math::checked_as_u64(
-            coin_balance as f64 * amount_in_no_fee as f64
-                / (pc_balance as f64 + amount_in_no_fee as f64),
-        )?;
         Ok((
             pc_token_amount_in,
-            if estimated_coin_amount > 1 {
-                estimated_coin_amount - 1
-            } else {
-                0
-            },
+            quote::get_amount_out(pc_token_amount_in, pc_balance, coin_balance, RAYDIUM_FEE)?,
         ))
     } else {
         // coin to pc
-        let amount_in_no_fee = (coin_token_amount_in as f64 * (1.0 - RAYDIUM_FEE)) as u64;
-        let estimated_pc_amount = math::checked_as_u64(
-            pc_balance as f64 * amount_in_no_fee as f64
-                / (coin_balance as f64 + amount_in_no_fee as f64),
-        )?;
         Ok((
             coin_token_amount_in,
-            if estimated_pc_amount > 1 {
-                estimated_pc_amount - 1
-            } else {
-                0
-            },
+            quote::get_amount_out(coin_token_amount_in, coin_balance, pc_balance, RAYDIUM_FEE)?,
         ))
     }
 }
@@ -716,18 +814,108 @@ pub fn stake_with_seeds(
     }
 }
 
-pub fn swap_with_seeds(
+/// Raydium farms as a Staking backend, see stake_with_seeds for the accounts
+pub struct RaydiumStaking;
+
+impl Staking for RaydiumStaking {
+    fn stake_with_seeds(
+        &self,
+        accounts: &[AccountInfo],
+        seeds: &[&[&[u8]]],
+        amount: u64,
+    ) -> ProgramResult {
+        stake_with_seeds(accounts, seeds, amount)
+    }
+}
+
+/// Side of the pool a Raydium swap sells
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
+#[cfg_attr(feature = "borsh", derive(borsh::BorshSerialize, borsh::BorshDeserialize))]
+pub enum SwapDirection {
+    /// Token a custody pays coin, token b custody receives pc
+    CoinToPc,
+    /// Token b custody pays pc, token a custody receives coin
+    PcToCoin,
+}
+
+/// Accounts of the router swap, in order.
+#[derive(Clone, Copy, Debug)]
+pub struct RaydiumPoolAccounts<'a, 'b> {
+    pub program_account: &'a AccountInfo<'b>,
+    pub program_token_a_account: &'a AccountInfo<'b>,
+    pub program_token_b_account: &'a AccountInfo<'b>,
+    pub pool_program_id: &'a AccountInfo<'b>,
+    pub pool_coin_token_account: &'a AccountInfo<'b>,
+    pub pool_pc_token_account: &'a AccountInfo<'b>,
+    pub spl_token_id: &'a AccountInfo<'b>,
+    pub amm_id: &'a AccountInfo<'b>,
+    pub amm_authority: &'a AccountInfo<'b>,
+    pub amm_open_orders: &'a AccountInfo<'b>,
+    pub amm_target: &'a AccountInfo<'b>,
+    pub serum_market: &'a AccountInfo<'b>,
+    pub serum_program_id: &'a AccountInfo<'b>,
+    pub serum_bids: &'a AccountInfo<'b>,
+    pub serum_asks: &'a AccountInfo<'b>,
+    pub serum_event_queue: &'a AccountInfo<'b>,
+    pub serum_coin_vault_account: &'a AccountInfo<'b>,
+    pub serum_pc_vault_account: &'a AccountInfo<'b>,
+    pub serum_vault_signer: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> RaydiumPoolAccounts<'a, 'b> {
+    pub const LEN: usize = 19;
+
+    /// Names the accounts of a swap, exactly LEN of them
+    pub fn from_accounts(accounts: &'a [AccountInfo<'b>]) -> Result<Self, ProgramError> {
+        account::check_account_count(accounts, RaydiumPoolAccounts::LEN)?;
+        if accounts.len() > RaydiumPoolAccounts::LEN {
+            msg!("Error: Too many accounts. {} accounts, a swap takes {}", accounts.len(), RaydiumPoolAccounts::LEN);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(RaydiumPoolAccounts {
+            program_account: &accounts[layout::SWAP_PROGRAM_ACCOUNT],
+            program_token_a_account: &accounts[layout::SWAP_PROGRAM_TOKEN_A],
+            program_token_b_account: &accounts[layout::SWAP_PROGRAM_TOKEN_B],
+            pool_program_id: &accounts[layout::SWAP_POOL_PROGRAM],
+            pool_coin_token_account: &accounts[layout::SWAP_POOL_COIN_TOKEN_ACCOUNT],
+            pool_pc_token_account: &accounts[layout::SWAP_POOL_PC_TOKEN_ACCOUNT],
+            spl_token_id: &accounts[layout::SWAP_SPL_TOKEN],
+            amm_id: &accounts[layout::SWAP_AMM_ID],
+            amm_authority: &accounts[layout::SWAP_AMM_AUTHORITY],
+            amm_open_orders: &accounts[layout::SWAP_AMM_OPEN_ORDERS],
+            amm_target: &accounts[layout::SWAP_AMM_TARGET],
+            serum_market: &accounts[layout::SWAP_SERUM_MARKET],
+            serum_program_id: &accounts[layout::SWAP_SERUM_PROGRAM],
+            serum_bids: &accounts[layout::SWAP_SERUM_BIDS],
+            serum_asks: &accounts[layout::SWAP_SERUM_ASKS],
+            serum_event_queue: &accounts[layout::SWAP_SERUM_EVENT_QUEUE],
+            serum_coin_vault_account: &accounts[layout::SWAP_SERUM_COIN_VAULT],
+            serum_pc_vault_account: &accounts[layout::SWAP_SERUM_PC_VAULT],
+            serum_vault_signer: &accounts[layout::SWAP_SERUM_VAULT_SIGNER],
+        })
+    }
+}
+
+/// Builds the Raydium swap instruction the router emits, without executing it.
+/// Accounts follow the swap_with_seeds layout.
+pub fn build_swap_instruction(
     accounts: &[AccountInfo],
-    seeds: &[&[&[u8]]],
     amount_in: u64,
     min_amount_out: u64,
-) -> ProgramResult {
+    direction: SwapDirection,
+) -> Result<Instruction, ProgramError> {
     if let [authority_account, token_a_custody_account, token_b_custody_account, pool_program_id, pool_coin_token_account, pool_pc_token_account, spl_token_id, amm_id, amm_authority, amm_open_orders, amm_target, serum_market, serum_program_id, serum_bids, serum_asks, serum_event_queue, serum_coin_vault_account, serum_pc_vault_account, serum_vault_signer] =
         accounts
     {
         if !check_pool_program_id(pool_program_id.key) {
             return Err(ProgramError::IncorrectProgramId);
         }
+        let (source_account, destination_account) = match direction {
+            SwapDirection::CoinToPc => (token_a_custody_account, token_b_custody_account),
+            SwapDirection::PcToCoin => (token_b_custody_account, token_a_custody_account),
+        };
         let raydium_accounts = vec![
             AccountMeta::new_readonly(*spl_token_id.key, false),
             AccountMeta::new(*amm_id.key, false),
@@ -744,12 +932,12 @@ pub fn swap_with_seeds(
             AccountMeta::new(*serum_coin_vault_account.key, false),
             AccountMeta::new(*serum_pc_vault_account.key, false),
             AccountMeta::new_readonly(*serum_vault_signer.key, false),
-            AccountMeta::new(*token_a_custody_account.key, false),
-            AccountMeta::new(*token_b_custody_account.key, false),
+            AccountMeta::new(*source_account.key, false),
+            AccountMeta::new(*destination_account.key, false),
             AccountMeta::new_readonly(*authority_account.key, true),
         ];
 
-        let instruction = Instruction {
+        Ok(Instruction {
             program_id: *pool_program_id.key,
             accounts: raydium_accounts,
             data: RaydiumSwap {
@@ -758,14 +946,27 @@ pub fn swap_with_seeds(
                 min_amount_out,
             }
             .to_vec()?,
-        };
-
-        invoke_signed(&instruction, accounts, seeds)
+        })
     } else {
         Err(ProgramError::NotEnoughAccountKeys)
     }
 }
 
+/// Accounts: authority, token a custody, token b custody, pool program, pool coin vault,
+/// pool pc vault, spl token, amm id, amm authority, amm open orders, amm target,
+/// serum market, serum program, serum bids, serum asks, serum event queue,
+/// serum coin vault, serum pc vault, serum vault signer
+pub fn swap_with_seeds(
+    accounts: &[AccountInfo],
+    seeds: &[&[&[u8]]],
+    amount_in: u64,
+    min_amount_out: u64,
+) -> ProgramResult {
+    let instruction =
+        build_swap_instruction(accounts, amount_in, min_amount_out, SwapDirection::CoinToPc)?;
+    invoke_signed(&instruction, accounts, seeds)
+}
+
 pub fn unstake_with_seeds(
     accounts: &[AccountInfo],
     seeds: &[&[&[u8]]],
@@ -0,0 +1,160 @@
+//! Saber specific functions
+
+use {
+    crate::{quote, utils::account, utils::id::saber_stable_swap, utils::pack::check_data_len, utils::saber::SaberSwap},
+    arrayref::array_ref,
+    solana_program::{
+        account_info::AccountInfo,
+        instruction::{AccountMeta, Instruction},
+        msg,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+/// Stable swap state: is_initialized, is_paused, nonce, initial and target amp factors,
+/// ramp start and stop, future admin deadline and key, admin key, token a and b infos
+/// (reserves, mint, admin fees, index), pool mint, fees
+pub const SWAP_INFO_LEN: usize = 397;
+const TARGET_AMP_FACTOR_OFFSET: usize = 11;
+const TOKEN_A_RESERVES_OFFSET: usize = 107;
+const TOKEN_A_ADMIN_FEES_OFFSET: usize = 171;
+const TOKEN_B_RESERVES_OFFSET: usize = 204;
+const TOKEN_B_ADMIN_FEES_OFFSET: usize = 268;
+const TRADE_FEE_NUMERATOR_OFFSET: usize = 365;
+/// SwapInstruction::Swap tag of the stable swap program
+const SWAP_INSTRUCTION: u8 = 1;
+
+pub fn check_pool_program_id(program_id: &Pubkey) -> bool {
+    program_id == &saber_stable_swap::id()
+}
+
+/// Checks the reserves and admin fee accounts are the ones named by the swap info.
+pub fn check_pool_accounts(
+    swap_info: &AccountInfo,
+    pool_token_a_account: &AccountInfo,
+    pool_token_b_account: &AccountInfo,
+    admin_fee_a_account: &AccountInfo,
+    admin_fee_b_account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let data = swap_info.try_borrow_data()?;
+    check_data_len(&data, SWAP_INFO_LEN)?;
+    for (offset, key) in [
+        (TOKEN_A_RESERVES_OFFSET, pool_token_a_account.key),
+        (TOKEN_B_RESERVES_OFFSET, pool_token_b_account.key),
+        (TOKEN_A_ADMIN_FEES_OFFSET, admin_fee_a_account.key),
+        (TOKEN_B_ADMIN_FEES_OFFSET, admin_fee_b_account.key),
+    ] {
+        if &Pubkey::new_from_array(*array_ref![data, offset, 32]) != key {
+            msg!("Error: {} doesn't belong to swap {}", key, swap_info.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the target amp factor and the (numerator, denominator) trade fee of the pool.
+/// An amp ramp in progress is not followed, the quote uses where it ends.
+pub fn get_pool_curve(swap_info: &AccountInfo) -> Result<(u64, u64, u64), ProgramError> {
+    let data = swap_info.try_borrow_data()?;
+    check_data_len(&data, SWAP_INFO_LEN)?;
+    Ok((
+        u64::from_le_bytes(*array_ref![data, TARGET_AMP_FACTOR_OFFSET, 8]),
+        u64::from_le_bytes(*array_ref![data, TRADE_FEE_NUMERATOR_OFFSET, 8]),
+        u64::from_le_bytes(*array_ref![data, TRADE_FEE_NUMERATOR_OFFSET + 8, 8]),
+    ))
+}
+
+pub fn get_pool_swap_amounts<'a, 'b>(
+    pool_token_a_account: &'a AccountInfo<'b>,
+    pool_token_b_account: &'a AccountInfo<'b>,
+    swap_info: &'a AccountInfo<'b>,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if (token_a_amount_in == 0 && token_b_amount_in == 0)
+        || (token_a_amount_in > 0 && token_b_amount_in > 0)
+    {
+        msg!("Error: One and only one of token amounts must be non-zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (amp, fee_numerator, fee_denominator) = get_pool_curve(swap_info)?;
+    let token_a_balance = account::get_token_balance(pool_token_a_account)?;
+    let token_b_balance = account::get_token_balance(pool_token_b_account)?;
+    if token_a_amount_in == 0 {
+        // b to a
+        Ok((
+            token_b_amount_in,
+            quote::get_stable_amount_out(
+                token_b_amount_in,
+                token_b_balance,
+                token_a_balance,
+                amp,
+                fee_numerator,
+                fee_denominator,
+            )?,
+        ))
+    } else {
+        // a to b
+        Ok((
+            token_a_amount_in,
+            quote::get_stable_amount_out(
+                token_a_amount_in,
+                token_a_balance,
+                token_b_balance,
+                amp,
+                fee_numerator,
+                fee_denominator,
+            )?,
+        ))
+    }
+}
+
+/// Builds the Saber swap instruction, token a is sold unless a_to_b is false.
+/// The admin fee account of the output token receives the pool admin fee.
+///
+/// Accounts: authority, token a custody, token b custody, pool program, swap info,
+/// swap authority, pool token a reserves, pool token b reserves, token a admin fees,
+/// token b admin fees, spl token
+pub fn build_swap_instruction(
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+    a_to_b: bool,
+) -> Result<Instruction, ProgramError> {
+    if let [authority_account, token_a_custody_account, token_b_custody_account, pool_program_id, swap_info, swap_authority, pool_token_a_account, pool_token_b_account, admin_fee_a_account, admin_fee_b_account, spl_token_id] =
+        accounts
+    {
+        if !check_pool_program_id(pool_program_id.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (source, swap_source, swap_destination, destination, admin_fee_destination) = if a_to_b {
+            (token_a_custody_account, pool_token_a_account, pool_token_b_account, token_b_custody_account, admin_fee_b_account)
+        } else {
+            (token_b_custody_account, pool_token_b_account, pool_token_a_account, token_a_custody_account, admin_fee_a_account)
+        };
+
+        Ok(Instruction {
+            program_id: *pool_program_id.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*swap_info.key, false),
+                AccountMeta::new_readonly(*swap_authority.key, false),
+                AccountMeta::new_readonly(*authority_account.key, true),
+                AccountMeta::new(*source.key, false),
+                AccountMeta::new(*swap_source.key, false),
+                AccountMeta::new(*swap_destination.key, false),
+                AccountMeta::new(*destination.key, false),
+                AccountMeta::new(*admin_fee_destination.key, false),
+                AccountMeta::new_readonly(*spl_token_id.key, false),
+            ],
+            data: SaberSwap {
+                instruction: SWAP_INSTRUCTION,
+                amount_in,
+                minimum_amount_out,
+            }
+            .to_vec()?,
+        })
+    } else {
+        Err(ProgramError::NotEnoughAccountKeys)
+    }
+}
@@ -0,0 +1,217 @@
+//! Serum market accounts read by the router and the direct market swap
+
+use {
+    crate::utils::pack::check_data_len,
+    arrayref::array_ref,
+    solana_program::{
+        account_info::AccountInfo,
+        instruction::{AccountMeta, Instruction},
+        msg,
+        program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+pub mod serum_v2 {
+    solana_program::declare_id!("EUqojwWA2rd19FZrzeBncJsm38Jm1hEhE3zsmX3bRc2o");
+}
+pub mod serum_v3 {
+    #[cfg(not(feature = "devnet"))]
+    solana_program::declare_id!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin");
+    #[cfg(feature = "devnet")]
+    solana_program::declare_id!("DESVgJVGajEgKGXhb6XmqDHGz3VjdgP7rEVESBgxmroY");
+}
+
+/// "serum" head and "padding" tail around every Serum account
+const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
+const ACCOUNT_TAIL_PADDING_LEN: usize = 7;
+/// account flags, head, count, seq num
+const EVENT_QUEUE_HEADER_LEN: usize = 32;
+const EVENT_LEN: usize = 88;
+/// Market state: account flags, own address, vault signer nonce, coin and pc mints,
+/// coin vault, coin totals, pc vault, pc totals, dust threshold, queues, bids, asks,
+/// coin and pc lot sizes, fee rate, referrer rebates
+pub const MARKET_STATE_LEN: usize = 376;
+const COIN_VAULT_OFFSET: usize = 112;
+const PC_VAULT_OFFSET: usize = 160;
+const COIN_LOT_SIZE_OFFSET: usize = 344;
+/// MarketInstruction tags, serialized as a version byte then a u32
+const NEW_ORDER_V3_TAG: u32 = 10;
+const SETTLE_FUNDS_TAG: u32 = 5;
+const ORDER_TYPE_IMMEDIATE_OR_CANCEL: u32 = 1;
+const SELF_TRADE_DECREMENT_TAKE: u32 = 0;
+/// Most book orders one new order may match against
+const MATCH_LIMIT: u16 = u16::MAX;
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Side {
+    /// Buys coin with pc
+    Bid = 0,
+    /// Sells coin for pc
+    Ask = 1,
+}
+
+pub fn check_program_id(program_id: &Pubkey) -> bool {
+    program_id == &serum_v2::id() || program_id == &serum_v3::id()
+}
+
+/// Returns the (count, capacity) of unprocessed events in a Serum event queue.
+pub fn get_event_queue_fill(event_queue: &AccountInfo) -> Result<(u64, u64), ProgramError> {
+    let data = event_queue.try_borrow_data()?;
+    let overhead = ACCOUNT_HEAD_PADDING.len() + EVENT_QUEUE_HEADER_LEN + ACCOUNT_TAIL_PADDING_LEN;
+    check_data_len(&data, overhead)?;
+    if array_ref![data, 0, 5] != ACCOUNT_HEAD_PADDING {
+        msg!("Error: {} is not a Serum account", event_queue.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let count = u64::from_le_bytes(*array_ref![data, ACCOUNT_HEAD_PADDING.len() + 16, 8]);
+    let capacity = ((data.len() - overhead) / EVENT_LEN) as u64;
+
+    Ok((count, capacity))
+}
+
+/// Returns the market state after the "serum" head, checking its length.
+fn get_market_state<'a>(market: &'a AccountInfo) -> Result<std::cell::Ref<'a, [u8]>, ProgramError> {
+    let data = market.try_borrow_data()?;
+    check_data_len(&data, ACCOUNT_HEAD_PADDING.len() + MARKET_STATE_LEN)?;
+    if array_ref![data, 0, 5] != ACCOUNT_HEAD_PADDING {
+        msg!("Error: {} is not a Serum account", market.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(std::cell::Ref::map(data, |data| {
+        &data[ACCOUNT_HEAD_PADDING.len()..ACCOUNT_HEAD_PADDING.len() + MARKET_STATE_LEN]
+    }))
+}
+
+/// Checks the coin and pc vaults are the ones named by the market.
+pub fn check_market_vaults(
+    market: &AccountInfo,
+    coin_vault: &AccountInfo,
+    pc_vault: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let state = get_market_state(market)?;
+    let market_coin_vault = Pubkey::new_from_array(*array_ref![state, COIN_VAULT_OFFSET, 32]);
+    let market_pc_vault = Pubkey::new_from_array(*array_ref![state, PC_VAULT_OFFSET, 32]);
+    if &market_coin_vault != coin_vault.key || &market_pc_vault != pc_vault.key {
+        msg!(
+            "Error: Vaults {} and {} don't belong to market {}",
+            coin_vault.key,
+            pc_vault.key,
+            market.key
+        );
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Returns the (coin, pc) lot sizes of the market.
+pub fn get_lot_sizes(market: &AccountInfo) -> Result<(u64, u64), ProgramError> {
+    let state = get_market_state(market)?;
+    let coin_lot_size = u64::from_le_bytes(*array_ref![state, COIN_LOT_SIZE_OFFSET, 8]);
+    let pc_lot_size = u64::from_le_bytes(*array_ref![state, COIN_LOT_SIZE_OFFSET + 8, 8]);
+    if coin_lot_size == 0 || pc_lot_size == 0 {
+        msg!("Error: Market {} has a zero lot size", market.key);
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok((coin_lot_size, pc_lot_size))
+}
+
+fn market_instruction_data(tag: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5 + payload.len());
+    data.push(0);
+    data.extend_from_slice(&tag.to_le_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+/// Builds an immediate or cancel NewOrderV3 that takes whatever the book offers,
+/// bounded by max_coin_qty lots on an ask and max_native_pc_qty on a bid.
+/// Unfilled input stays in the open orders until settle_funds returns it.
+///
+/// Accounts: market, open orders, request queue, event queue, bids, asks,
+/// order payer, open orders owner, coin vault, pc vault, spl token, rent
+pub fn build_new_order_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    side: Side,
+    max_coin_qty: u64,
+    max_native_pc_qty: u64,
+) -> Result<Instruction, ProgramError> {
+    if let [market, open_orders, request_queue, event_queue, bids, asks, order_payer, owner, coin_vault, pc_vault, spl_token_id, rent] =
+        accounts
+    {
+        if !check_program_id(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // a marketable limit, the lowest price on an ask and the highest on a bid
+        let limit_price = match side {
+            Side::Bid => u64::MAX,
+            Side::Ask => 1,
+        };
+        let mut payload = Vec::with_capacity(46);
+        payload.extend_from_slice(&(side as u32).to_le_bytes());
+        payload.extend_from_slice(&limit_price.to_le_bytes());
+        payload.extend_from_slice(&max_coin_qty.to_le_bytes());
+        payload.extend_from_slice(&max_native_pc_qty.to_le_bytes());
+        payload.extend_from_slice(&SELF_TRADE_DECREMENT_TAKE.to_le_bytes());
+        payload.extend_from_slice(&ORDER_TYPE_IMMEDIATE_OR_CANCEL.to_le_bytes());
+        payload.extend_from_slice(&0u64.to_le_bytes());
+        payload.extend_from_slice(&MATCH_LIMIT.to_le_bytes());
+
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(*market.key, false),
+                AccountMeta::new(*open_orders.key, false),
+                AccountMeta::new(*request_queue.key, false),
+                AccountMeta::new(*event_queue.key, false),
+                AccountMeta::new(*bids.key, false),
+                AccountMeta::new(*asks.key, false),
+                AccountMeta::new(*order_payer.key, false),
+                AccountMeta::new_readonly(*owner.key, true),
+                AccountMeta::new(*coin_vault.key, false),
+                AccountMeta::new(*pc_vault.key, false),
+                AccountMeta::new_readonly(*spl_token_id.key, false),
+                AccountMeta::new_readonly(*rent.key, false),
+            ],
+            data: market_instruction_data(NEW_ORDER_V3_TAG, &payload),
+        })
+    } else {
+        Err(ProgramError::NotEnoughAccountKeys)
+    }
+}
+
+/// Builds SettleFunds, moving the free open orders balances to the wallets.
+///
+/// Accounts: market, open orders, open orders owner, coin vault, pc vault,
+/// coin wallet, pc wallet, vault signer, spl token
+pub fn build_settle_funds_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<Instruction, ProgramError> {
+    if let [market, open_orders, owner, coin_vault, pc_vault, coin_wallet, pc_wallet, vault_signer, spl_token_id] =
+        accounts
+    {
+        if !check_program_id(program_id) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: vec![
+                AccountMeta::new(*market.key, false),
+                AccountMeta::new(*open_orders.key, false),
+                AccountMeta::new_readonly(*owner.key, true),
+                AccountMeta::new(*coin_vault.key, false),
+                AccountMeta::new(*pc_vault.key, false),
+                AccountMeta::new(*coin_wallet.key, false),
+                AccountMeta::new(*pc_wallet.key, false),
+                AccountMeta::new_readonly(*vault_signer.key, false),
+                AccountMeta::new_readonly(*spl_token_id.key, false),
+            ],
+            data: market_instruction_data(SETTLE_FUNDS_TAG, &[]),
+        })
+    } else {
+        Err(ProgramError::NotEnoughAccountKeys)
+    }
+}
@@ -0,0 +1,373 @@
+//! Pure swap quote math.
+//! Works on plain reserves and amounts without reading accounts, so the same
+//! functions back the on-chain handlers and can be called by off-chain clients.
+
+use {
+    crate::utils::math,
+    solana_program::{msg, program_error::ProgramError},
+};
+
+/// Returns the constant product pool output for the given input amount.
+/// The pool fee is taken from the input first and one unit is subtracted
+/// from the result to absorb rounding in the pool program.
+pub fn get_amount_out(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee: f64,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        msg!("Error: Can't swap in an empty pool");
+        return Err(ProgramError::Custom(412));
+    }
+    let amount_in_no_fee = (amount_in as f64 * (1.0 - fee)) as u64;
+    let amount_out = math::checked_as_u64(
+        reserve_out as f64 * amount_in_no_fee as f64
+            / (reserve_in as f64 + amount_in_no_fee as f64),
+    )?;
+
+    Ok(amount_out.saturating_sub(1))
+}
+
+/// Returns the smallest input for which get_amount_out yields at least amount_out.
+/// Fails if the pool can't deliver that much.
+pub fn get_amount_in(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    fee: f64,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        msg!("Error: Can't swap in an empty pool");
+        return Err(ProgramError::Custom(412));
+    }
+    // get_amount_out keeps one unit back
+    let target = amount_out as f64 + 1.0;
+    if target >= reserve_out as f64 {
+        msg!("Error: Output {} is not below the pool reserve {}", amount_out, reserve_out);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let amount_in_no_fee = (reserve_in as f64 * target / (reserve_out as f64 - target)).ceil();
+    let estimate = math::checked_as_u64((amount_in_no_fee / (1.0 - fee)).ceil())?;
+    // float rounding leaves the estimate off by a few units, settle it by bisection
+    let mut high = estimate.saturating_add(estimate / 1_000 + 8);
+    if get_amount_out(high, reserve_in, reserve_out, fee)? < amount_out {
+        msg!("Error: Can't find the input for output {}", amount_out);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let mut low = 0;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if get_amount_out(mid, reserve_in, reserve_out, fee)? >= amount_out {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Ok(high)
+}
+
+/// Quotes a batch of input amounts against one pool's (reserve_in, reserve_out).
+/// Meant for off-chain routers precomputing curves, an empty pool quotes zero
+/// for every amount.
+pub fn quote_many(reserves: (u64, u64), amounts: &[u64], fee: f64) -> Vec<u64> {
+    let (reserve_in, reserve_out) = reserves;
+    amounts
+        .iter()
+        .map(|&amount_in| get_amount_out(amount_in, reserve_in, reserve_out, fee).unwrap_or(0))
+        .collect()
+}
+
+/// Coins in a stable swap pool
+const STABLE_N_COINS: u128 = 2;
+/// Newton iterations before the stable swap invariant is given up on
+const STABLE_MAX_ITERATIONS: usize = 256;
+
+/// Returns the stable swap invariant D of the reserves for the amplification amp.
+fn get_stable_invariant(amp: u128, reserve_a: u128, reserve_b: u128) -> Result<u128, ProgramError> {
+    let sum = math::checked_add(reserve_a, reserve_b)?;
+    let leverage = math::checked_mul(amp, STABLE_N_COINS)?;
+    let mut d = sum;
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        let mut d_p = d;
+        d_p = math::checked_div(math::checked_mul(d_p, d)?, math::checked_mul(reserve_a, STABLE_N_COINS)?)?;
+        d_p = math::checked_div(math::checked_mul(d_p, d)?, math::checked_mul(reserve_b, STABLE_N_COINS)?)?;
+        let d_prev = d;
+        let numerator = math::checked_mul(
+            math::checked_add(math::checked_mul(leverage, sum)?, math::checked_mul(d_p, STABLE_N_COINS)?)?,
+            d,
+        )?;
+        let denominator = math::checked_add(
+            math::checked_mul(leverage - 1, d)?,
+            math::checked_mul(STABLE_N_COINS + 1, d_p)?,
+        )?;
+        d = math::checked_div(numerator, denominator)?;
+        if d.max(d_prev) - d.min(d_prev) <= 1 {
+            return Ok(d);
+        }
+    }
+    msg!("Error: Stable swap invariant doesn't converge");
+    Err(ProgramError::InvalidArgument)
+}
+
+/// Returns the reserve out that keeps the invariant d with reserve_in.
+fn get_stable_reserve_out(amp: u128, reserve_in: u128, d: u128) -> Result<u128, ProgramError> {
+    let leverage = math::checked_mul(amp, STABLE_N_COINS)?;
+    let c = math::checked_div(math::checked_mul(d, d)?, math::checked_mul(reserve_in, STABLE_N_COINS)?)?;
+    let c = math::checked_div(math::checked_mul(c, d)?, math::checked_mul(leverage, STABLE_N_COINS)?)?;
+    let b = math::checked_add(reserve_in, math::checked_div(d, leverage)?)?;
+    let mut y = d;
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        let y_prev = y;
+        y = math::checked_div(
+            math::checked_add(math::checked_mul(y, y)?, c)?,
+            math::checked_sub(math::checked_add(math::checked_mul(y, 2)?, b)?, d)?,
+        )?;
+        if y.max(y_prev) - y.min(y_prev) <= 1 {
+            return Ok(y);
+        }
+    }
+    msg!("Error: Stable swap reserve doesn't converge");
+    Err(ProgramError::InvalidArgument)
+}
+
+/// Returns the stable swap pool output for the given input amount, with the
+/// trade fee fee_numerator / fee_denominator taken from the output.
+/// amp is the pool amplification coefficient, higher keeps the price closer to 1:1.
+pub fn get_stable_amount_out(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    amp: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        msg!("Error: Can't swap in an empty pool");
+        return Err(ProgramError::Custom(412));
+    }
+    if amp == 0 || fee_denominator == 0 || fee_numerator > fee_denominator {
+        msg!("Error: Invalid stable swap amp {} or fee {}/{}", amp, fee_numerator, fee_denominator);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (amp, reserve_in, reserve_out) = (amp as u128, reserve_in as u128, reserve_out as u128);
+    let d = get_stable_invariant(amp, reserve_in, reserve_out)?;
+    let new_reserve_out =
+        get_stable_reserve_out(amp, math::checked_add(reserve_in, amount_in as u128)?, d)?;
+    // integer rounding can take the new reserve to zero on a huge input, one unit stays
+    let amount_out = reserve_out.saturating_sub(new_reserve_out.max(1));
+    let fee = math::checked_div(
+        math::checked_mul(amount_out, fee_numerator as u128)?,
+        fee_denominator as u128,
+    )?;
+
+    math::checked_as_u64(amount_out - fee)
+}
+
+/// Returns how far swapping amount_in for amount_out moves the constant product
+/// pool price reserve_out / reserve_in, in basis points of the price before the
+/// swap, rounded up.
+pub fn get_price_impact_bps(
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        msg!("Error: Can't swap in an empty pool");
+        return Err(ProgramError::Custom(412));
+    }
+    if amount_out >= reserve_out {
+        msg!("Error: Output {} is not below the pool reserve {}", amount_out, reserve_out);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let price_before = reserve_out as f64 / reserve_in as f64;
+    let price_after = (reserve_out - amount_out) as f64 / (reserve_in as f64 + amount_in as f64);
+    math::checked_as_u64(((1.0 - price_after / price_before) * 10_000.0).max(0.0).ceil())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::raydium::{self, raydium_v4, AmmInfoV4, RAYDIUM_FEE};
+    use solana_program::{account_info::AccountInfo, program_pack::Pack, pubkey::Pubkey};
+    use spl_token::state::{Account, AccountState};
+
+    fn pack_token_account(amount: u64) -> Vec<u8> {
+        let account = Account {
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(account, &mut data).unwrap();
+        data
+    }
+
+    fn quote_on_chain(coin_balance: u64, pc_balance: u64, coin_in: u64, pc_in: u64) -> (u64, u64) {
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let (token_program, raydium_program) = (spl_token::id(), raydium_v4::id());
+        let mut lamports = [0u64; 4];
+        let mut coin_data = pack_token_account(coin_balance);
+        let mut pc_data = pack_token_account(pc_balance);
+        let mut open_orders_data = vec![];
+        // v4 amm state naming the open orders
+        let mut amm_data = vec![0; AmmInfoV4::LEN];
+        amm_data[496..528].copy_from_slice(keys[2].as_ref());
+        let [coin_lamports, pc_lamports, open_orders_lamports, amm_lamports] = &mut lamports;
+
+        let coin = AccountInfo::new(&keys[0], false, false, coin_lamports, &mut coin_data, &token_program, false, 0);
+        let pc = AccountInfo::new(&keys[1], false, false, pc_lamports, &mut pc_data, &token_program, false, 0);
+        let open_orders = AccountInfo::new(&keys[2], false, false, open_orders_lamports, &mut open_orders_data, &keys[2], false, 0);
+        let amm = AccountInfo::new(&keys[3], false, false, amm_lamports, &mut amm_data, &raydium_program, false, 0);
+
+        raydium::get_pool_swap_amounts(&coin, &pc, &open_orders, &amm, coin_in, pc_in).unwrap()
+    }
+
+    #[test]
+    fn test_amount_out_matches_pool_quote() {
+        let (coin_balance, pc_balance) = (5_000_000_000, 130_000_000_000);
+        for amount in [1, 1_000, 2_500_000, 1_000_000_000] {
+            assert_eq!(
+                quote_on_chain(coin_balance, pc_balance, amount, 0),
+                (amount, get_amount_out(amount, coin_balance, pc_balance, RAYDIUM_FEE).unwrap())
+            );
+            assert_eq!(
+                quote_on_chain(coin_balance, pc_balance, 0, amount),
+                (amount, get_amount_out(amount, pc_balance, coin_balance, RAYDIUM_FEE).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_amount_out_empty_pool() {
+        assert_eq!(
+            get_amount_out(1_000, 0, 1_000_000, RAYDIUM_FEE),
+            Err(ProgramError::Custom(412))
+        );
+        assert_eq!(
+            get_amount_out(1_000, 1_000_000, 0, RAYDIUM_FEE),
+            Err(ProgramError::Custom(412))
+        );
+    }
+
+    #[test]
+    fn test_amount_in_is_smallest_input() {
+        let (reserve_in, reserve_out) = (5_000_000_000, 130_000_000_000);
+        for amount_out in [0, 1, 1_000, 2_500_000, 1_000_000_000, 100_000_000_000] {
+            let amount_in = get_amount_in(amount_out, reserve_in, reserve_out, RAYDIUM_FEE).unwrap();
+            assert!(get_amount_out(amount_in, reserve_in, reserve_out, RAYDIUM_FEE).unwrap() >= amount_out);
+            if amount_in > 0 {
+                assert!(
+                    get_amount_out(amount_in - 1, reserve_in, reserve_out, RAYDIUM_FEE).unwrap()
+                        < amount_out
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_amount_in_above_reserve() {
+        assert_eq!(
+            get_amount_in(1_000_000, 1_000_000, 1_000_000, RAYDIUM_FEE),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            get_amount_in(1_000, 0, 1_000_000, RAYDIUM_FEE),
+            Err(ProgramError::Custom(412))
+        );
+    }
+
+    #[test]
+    fn test_quote_many_matches_single_quote() {
+        let reserves = (5_000_000_000, 130_000_000_000);
+        let amounts = [0, 1, 1_000, 2_500_000, 1_000_000_000, u64::MAX];
+        let quotes = quote_many(reserves, &amounts, RAYDIUM_FEE);
+
+        assert_eq!(quotes.len(), amounts.len());
+        for (amount, quote) in amounts.iter().zip(quotes) {
+            assert_eq!(
+                quote,
+                get_amount_out(*amount, reserves.0, reserves.1, RAYDIUM_FEE).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_quote_many_monotonic() {
+        let amounts: Vec<u64> = (1..=20).map(|i| i * 250_000_000).collect();
+        let quotes = quote_many((5_000_000_000, 130_000_000_000), &amounts, RAYDIUM_FEE);
+
+        for i in 1..amounts.len() {
+            // larger input buys more output
+            assert!(quotes[i] > quotes[i - 1]);
+            // at a worse rate
+            let rate = quotes[i] as f64 / amounts[i] as f64;
+            let prev_rate = quotes[i - 1] as f64 / amounts[i - 1] as f64;
+            assert!(rate < prev_rate);
+        }
+    }
+
+    #[test]
+    fn test_quote_many_empty_pool() {
+        assert_eq!(quote_many((0, 1_000_000), &[1, 1_000], RAYDIUM_FEE), vec![0, 0]);
+        assert!(quote_many((1_000_000, 1_000_000), &[], RAYDIUM_FEE).is_empty());
+    }
+
+    #[test]
+    fn test_stable_amount_out_near_parity() {
+        let (reserve_a, reserve_b) = (1_000_000_000_000, 1_000_000_000_000);
+        for amount_in in [1_000, 1_000_000, 1_000_000_000] {
+            let amount_out = get_stable_amount_out(amount_in, reserve_a, reserve_b, 100, 0, 1).unwrap();
+            // a balanced stable pool trades close to 1:1, better than constant product
+            assert!(amount_out <= amount_in);
+            assert!(amount_out >= amount_in - amount_in / 10_000 - 2);
+            assert!(amount_out >= get_amount_out(amount_in, reserve_a, reserve_b, 0.0).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_stable_amount_out_fee_and_imbalance() {
+        let amount_out = get_stable_amount_out(1_000_000, 1_000_000_000, 1_000_000_000, 100, 0, 1).unwrap();
+        // 4 bps trade fee on the output
+        assert_eq!(
+            get_stable_amount_out(1_000_000, 1_000_000_000, 1_000_000_000, 100, 4, 10_000),
+            Ok(amount_out - amount_out * 4 / 10_000)
+        );
+        // selling into the heavy side gets less, into the light side more
+        assert!(get_stable_amount_out(1_000_000, 3_000_000_000, 1_000_000_000, 100, 0, 1).unwrap() < amount_out);
+        assert!(get_stable_amount_out(1_000_000, 1_000_000_000, 3_000_000_000, 100, 0, 1).unwrap() > amount_out);
+        // the output never drains the pool
+        assert!(get_stable_amount_out(u64::MAX / 4, 1_000_000_000, 1_000_000_000, 100, 0, 1).unwrap() < 1_000_000_000);
+    }
+
+    #[test]
+    fn test_price_impact() {
+        // 1% of the input reserve moves the price about 2%
+        let amount_out = get_amount_out(10_000, 1_000_000, 1_000_000, 0.0).unwrap();
+        assert_eq!(get_price_impact_bps(10_000, amount_out, 1_000_000, 1_000_000), Ok(198));
+        assert_eq!(get_price_impact_bps(0, 0, 1_000_000, 1_000_000), Ok(0));
+        // swapping the reserve in doubles it and takes half the reserve out, 75%
+        assert_eq!(get_price_impact_bps(1_000, 500, 1_000, 1_000), Ok(7_500));
+
+        assert_eq!(get_price_impact_bps(1, 1, 0, 1_000), Err(ProgramError::Custom(412)));
+        assert_eq!(get_price_impact_bps(1, 1_000, 1_000, 1_000), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_stable_amount_out_invalid_pool() {
+        assert_eq!(
+            get_stable_amount_out(1_000, 0, 1_000_000, 100, 0, 1),
+            Err(ProgramError::Custom(412))
+        );
+        assert_eq!(
+            get_stable_amount_out(1_000, 1_000_000, 1_000_000, 0, 0, 1),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            get_stable_amount_out(1_000, 1_000_000, 1_000_000, 100, 1, 0),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
@@ -0,0 +1,885 @@
+//! Router config stored in the program PDA.
+
+use {
+    crate::{
+        error::AutoswapError,
+        utils::{
+            id::{main_router_admin, ProgramIDType},
+            math,
+            pack::check_data_len,
+            tokens::DEFAULT_INSTANCE,
+        },
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    num_enum::TryFromPrimitive,
+    solana_program::{
+        account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+        pubkey::Pubkey,
+    },
+};
+
+/// Stored version byte differs from SwapConfig::VERSION
+pub const CONFIG_VERSION_MISMATCH: u8 = 1;
+/// fee_bps and lp_fee_bps add up to more than SwapConfig::MAX_FEE_BPS
+pub const CONFIG_FEE_OUT_OF_RANGE: u8 = 1 << 1;
+/// Admin is the default Pubkey
+pub const CONFIG_ZERO_ADMIN: u8 = 1 << 2;
+
+/// Side of the swap after_transfer takes the fee from
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
+#[cfg_attr(feature = "borsh", derive(borsh::BorshSerialize, borsh::BorshDeserialize))]
+pub enum FeeToken {
+    /// Program sol account, the fee is a share of the swap amount
+    Input,
+    /// Program kin account, the fee is a share of the payout
+    Output,
+}
+
+/// Protocol fee after_transfer takes on fees drawn in mint, in place of
+/// SwapConfig::fee_bps. Slots holding the default Pubkey are free
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FeeOverride {
+    pub mint: Pubkey,
+    pub fee_bps: u16,
+}
+
+impl FeeOverride {
+    pub const LEN: usize = 34;
+}
+
+/// Step of the before_transfer, swap, after_transfer sequence the router is in.
+/// Only the stored config tracks it, see SwapConfig::advance_swap_phase
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
+pub enum SwapPhase {
+    /// No sequence in progress, after_transfer is rejected
+    Idle,
+    /// before_transfer ran, a swap is expected next
+    Deposited,
+    /// The swap ran, after_transfer may pay out once
+    Swapped,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SwapConfig {
+    /// Layout version, zero means the account was never written
+    pub version: u8,
+    /// Key allowed to change the config
+    pub admin: Pubkey,
+    /// Protocol fee sent to the fee recipient in after_transfer, in basis points of the swap amount
+    pub fee_bps: u16,
+    /// LP incentive sent to the lp fee recipient in after_transfer, in basis points
+    pub lp_fee_bps: u16,
+    /// Swaps realizing less output than this revert, zero disables the check
+    pub min_deliverable_out: u64,
+    /// Smallest fee taken in after_transfer, zero keeps the plain percentage fee
+    pub min_fee: u64,
+    /// Swaps are rejected from this slot, inclusive
+    pub maintenance_start_slot: u64,
+    /// up to this slot, exclusive. An empty range disables the window
+    pub maintenance_end_slot: u64,
+    /// before_transfer skips the deposit when the program account already holds the amount
+    pub skip_funded_deposit: bool,
+    /// Largest share of the input reserve one swap may add, in basis points, zero disables it
+    pub max_pool_consume_bps: u16,
+    /// Token after_transfer draws the fee from
+    pub fee_token: FeeToken,
+    /// Sequence the next admin config change must carry, counts the applied ones
+    pub sequence: u64,
+    /// Swaps passing the pool coin and pc vaults in each other's place are
+    /// reordered instead of rejected with VaultsSwapped
+    pub auto_correct_vaults: bool,
+    /// Swaps are rejected while the Serum event queue is filled above this share,
+    /// in basis points, zero disables the check
+    pub max_event_queue_fill_bps: u16,
+    /// Token account after_transfer pays the protocol fee to, the default Pubkey leaves it to the caller
+    pub fee_recipient: Pubkey,
+    /// Emergency switch, swaps and transfers are rejected while set
+    pub paused: bool,
+    /// Step of the transfer and swap sequence in progress
+    pub swap_phase: SwapPhase,
+    /// Swaps spending less than this are rejected, zero disables the check
+    pub min_swap_amount: u64,
+    /// Amount the last before_transfer credited, net of any transfer fee,
+    /// zero when the deposit was skipped
+    pub deposited_amount: u64,
+    /// Instance seed of the router PDA holding this config, see tokens::ProgramAuthority
+    pub instance: u8,
+    /// Admin proposed by TransferAdmin, takes over once it signs AcceptAdmin,
+    /// the default Pubkey when no handoff is pending
+    pub pending_admin: Pubkey,
+    /// Mint specific protocol fees, see SwapConfig::fee_bps_for
+    pub fee_overrides: [FeeOverride; SwapConfig::MAX_FEE_OVERRIDES],
+    /// Slippage off the pool quote a Swap without min_token_amount_out accepts,
+    /// in basis points, zero holds it to the full quote
+    pub default_slippage_bps: u16,
+    /// Bit 1 << ProgramIDType of each pool protocol swaps may go through,
+    /// see SwapConfig::check_protocol_enabled
+    pub enabled_protocols: u16,
+}
+
+impl Default for SwapConfig {
+    fn default() -> Self {
+        Self {
+            version: SwapConfig::VERSION,
+            admin: main_router_admin::id(),
+            fee_bps: SwapConfig::DEFAULT_FEE_BPS,
+            lp_fee_bps: 0,
+            min_deliverable_out: 0,
+            min_fee: 0,
+            maintenance_start_slot: 0,
+            maintenance_end_slot: 0,
+            skip_funded_deposit: false,
+            max_pool_consume_bps: 0,
+            fee_token: FeeToken::Input,
+            sequence: 0,
+            auto_correct_vaults: false,
+            max_event_queue_fill_bps: 0,
+            fee_recipient: Pubkey::default(),
+            paused: false,
+            swap_phase: SwapPhase::Idle,
+            min_swap_amount: 0,
+            deposited_amount: 0,
+            instance: DEFAULT_INSTANCE,
+            pending_admin: Pubkey::default(),
+            fee_overrides: [FeeOverride::default(); SwapConfig::MAX_FEE_OVERRIDES],
+            default_slippage_bps: 0,
+            enabled_protocols: SwapConfig::ALL_PROTOCOLS,
+        }
+    }
+}
+
+impl SwapConfig {
+    pub const LEN: usize = 443;
+    pub const VERSION: u8 = 1;
+    pub const DEFAULT_FEE_BPS: u16 = 50;
+    /// Cap on the protocol and lp fees together, no admin instruction sets them higher
+    pub const MAX_FEE_BPS: u16 = 100;
+    pub const MAX_FEE_OVERRIDES: usize = 8;
+    /// SetMintFee fee_bps removing the override of the mint
+    pub const NO_FEE_OVERRIDE: u16 = u16::MAX;
+    pub const ALL_PROTOCOLS: u16 = u16::MAX;
+
+    pub fn get_size(&self) -> usize {
+        SwapConfig::LEN
+    }
+
+    pub fn pack(&self, output: &mut [u8]) -> Result<usize, ProgramError> {
+        check_data_len(output, SwapConfig::LEN)?;
+
+        let output = array_mut_ref![output, 0, SwapConfig::LEN];
+        let (
+            version_out,
+            admin_out,
+            fee_bps_out,
+            lp_fee_bps_out,
+            min_deliverable_out_out,
+            min_fee_out,
+            maintenance_start_slot_out,
+            maintenance_end_slot_out,
+            skip_funded_deposit_out,
+            max_pool_consume_bps_out,
+            fee_token_out,
+            sequence_out,
+            auto_correct_vaults_out,
+            max_event_queue_fill_bps_out,
+            fee_recipient_out,
+            paused_out,
+            swap_phase_out,
+            min_swap_amount_out,
+            deposited_amount_out,
+            instance_out,
+            pending_admin_out,
+            fee_overrides_out,
+            default_slippage_bps_out,
+            enabled_protocols_out,
+        ) = mut_array_refs![output, 1, 32, 2, 2, 8, 8, 8, 8, 1, 2, 1, 8, 1, 2, 32, 1, 1, 8, 8, 1, 32, 272, 2, 2];
+
+        version_out[0] = self.version;
+        admin_out.copy_from_slice(self.admin.as_ref());
+        *fee_bps_out = self.fee_bps.to_le_bytes();
+        *lp_fee_bps_out = self.lp_fee_bps.to_le_bytes();
+        *min_deliverable_out_out = self.min_deliverable_out.to_le_bytes();
+        *min_fee_out = self.min_fee.to_le_bytes();
+        *maintenance_start_slot_out = self.maintenance_start_slot.to_le_bytes();
+        *maintenance_end_slot_out = self.maintenance_end_slot.to_le_bytes();
+        skip_funded_deposit_out[0] = self.skip_funded_deposit as u8;
+        *max_pool_consume_bps_out = self.max_pool_consume_bps.to_le_bytes();
+        fee_token_out[0] = self.fee_token as u8;
+        *sequence_out = self.sequence.to_le_bytes();
+        auto_correct_vaults_out[0] = self.auto_correct_vaults as u8;
+        *max_event_queue_fill_bps_out = self.max_event_queue_fill_bps.to_le_bytes();
+        fee_recipient_out.copy_from_slice(self.fee_recipient.as_ref());
+        paused_out[0] = self.paused as u8;
+        swap_phase_out[0] = self.swap_phase as u8;
+        *min_swap_amount_out = self.min_swap_amount.to_le_bytes();
+        *deposited_amount_out = self.deposited_amount.to_le_bytes();
+        instance_out[0] = self.instance;
+        pending_admin_out.copy_from_slice(self.pending_admin.as_ref());
+        for (fee_override_out, fee_override) in fee_overrides_out
+            .chunks_exact_mut(FeeOverride::LEN)
+            .zip(self.fee_overrides.iter())
+        {
+            fee_override_out[..32].copy_from_slice(fee_override.mint.as_ref());
+            fee_override_out[32..].copy_from_slice(&fee_override.fee_bps.to_le_bytes());
+        }
+        *default_slippage_bps_out = self.default_slippage_bps.to_le_bytes();
+        *enabled_protocols_out = self.enabled_protocols.to_le_bytes();
+
+        Ok(SwapConfig::LEN)
+    }
+
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        check_data_len(input, SwapConfig::LEN)?;
+
+        let input = array_ref![input, 0, SwapConfig::LEN];
+        let (
+            version,
+            admin,
+            fee_bps,
+            lp_fee_bps,
+            min_deliverable_out,
+            min_fee,
+            maintenance_start_slot,
+            maintenance_end_slot,
+            skip_funded_deposit,
+            max_pool_consume_bps,
+            fee_token,
+            sequence,
+            auto_correct_vaults,
+            max_event_queue_fill_bps,
+            fee_recipient,
+            paused,
+            swap_phase,
+            min_swap_amount,
+            deposited_amount,
+            instance,
+            pending_admin,
+            fee_overrides_in,
+            default_slippage_bps,
+            enabled_protocols,
+        ) = array_refs![input, 1, 32, 2, 2, 8, 8, 8, 8, 1, 2, 1, 8, 1, 2, 32, 1, 1, 8, 8, 1, 32, 272, 2, 2];
+
+        let mut fee_overrides = [FeeOverride::default(); SwapConfig::MAX_FEE_OVERRIDES];
+        for (fee_override, fee_override_in) in fee_overrides
+            .iter_mut()
+            .zip(fee_overrides_in.chunks_exact(FeeOverride::LEN))
+        {
+            let fee_override_in = array_ref![fee_override_in, 0, FeeOverride::LEN];
+            let (mint, fee_bps) = array_refs![fee_override_in, 32, 2];
+            *fee_override = FeeOverride {
+                mint: Pubkey::new_from_array(*mint),
+                fee_bps: u16::from_le_bytes(*fee_bps),
+            };
+        }
+
+        Ok(Self {
+            version: version[0],
+            admin: Pubkey::new_from_array(*admin),
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            lp_fee_bps: u16::from_le_bytes(*lp_fee_bps),
+            min_deliverable_out: u64::from_le_bytes(*min_deliverable_out),
+            min_fee: u64::from_le_bytes(*min_fee),
+            maintenance_start_slot: u64::from_le_bytes(*maintenance_start_slot),
+            maintenance_end_slot: u64::from_le_bytes(*maintenance_end_slot),
+            skip_funded_deposit: skip_funded_deposit[0] != 0,
+            max_pool_consume_bps: u16::from_le_bytes(*max_pool_consume_bps),
+            fee_token: FeeToken::try_from_primitive(fee_token[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+            sequence: u64::from_le_bytes(*sequence),
+            auto_correct_vaults: auto_correct_vaults[0] != 0,
+            max_event_queue_fill_bps: u16::from_le_bytes(*max_event_queue_fill_bps),
+            fee_recipient: Pubkey::new_from_array(*fee_recipient),
+            paused: paused[0] != 0,
+            swap_phase: SwapPhase::try_from_primitive(swap_phase[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+            min_swap_amount: u64::from_le_bytes(*min_swap_amount),
+            deposited_amount: u64::from_le_bytes(*deposited_amount),
+            instance: instance[0],
+            pending_admin: Pubkey::new_from_array(*pending_admin),
+            fee_overrides,
+            default_slippage_bps: u16::from_le_bytes(*default_slippage_bps),
+            enabled_protocols: u16::from_le_bytes(*enabled_protocols),
+        })
+    }
+
+    /// Reads the config from the program account.
+    /// Accounts not owned by the router, too small to hold it or never written yield the defaults.
+    pub fn load(program_account: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        if program_account.owner != program_id || program_account.data_len() < SwapConfig::LEN {
+            return Ok(Self::default());
+        }
+        let config = SwapConfig::unpack(&program_account.try_borrow_data()?)?;
+        if config.version == 0 {
+            return Ok(Self::default());
+        }
+        Ok(config)
+    }
+
+    /// Returns a bitmask of the CONFIG_* problems found.
+    /// With repair set, fixes the ones that have an obvious answer: fees are
+    /// clamped, protocol fee first, and a zero admin is reset to the main router admin.
+    /// A version mismatch is only reported, the layout can't be guessed.
+    pub fn verify(&mut self, repair: bool) -> u8 {
+        let mut diagnostics = 0;
+        if self.version != SwapConfig::VERSION {
+            diagnostics |= CONFIG_VERSION_MISMATCH;
+        }
+        if SwapConfig::check_fee_bps(self.fee_bps, self.lp_fee_bps).is_err() {
+            diagnostics |= CONFIG_FEE_OUT_OF_RANGE;
+            if repair {
+                self.fee_bps = self.fee_bps.min(SwapConfig::MAX_FEE_BPS);
+                self.lp_fee_bps = self.lp_fee_bps.min(SwapConfig::MAX_FEE_BPS - self.fee_bps);
+            }
+        }
+        if self.admin == Pubkey::default() {
+            diagnostics |= CONFIG_ZERO_ADMIN;
+            if repair {
+                self.admin = main_router_admin::id();
+            }
+        }
+        diagnostics
+    }
+
+    /// Checks the protocol and lp fees together stay within SwapConfig::MAX_FEE_BPS.
+    pub fn check_fee_bps(fee_bps: u16, lp_fee_bps: u16) -> ProgramResult {
+        if fee_bps as u32 + lp_fee_bps as u32 > SwapConfig::MAX_FEE_BPS as u32 {
+            msg!(
+                "Error: FeeTooHigh. Fees {} + {} bps are above the cap of {} bps",
+                fee_bps,
+                lp_fee_bps,
+                SwapConfig::MAX_FEE_BPS
+            );
+            Err(AutoswapError::FeeTooHigh.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the protocol fee of fees drawn in mint, its override when it has one.
+    pub fn fee_bps_for(&self, mint: &Pubkey) -> u16 {
+        self.fee_overrides
+            .iter()
+            .find(|fee_override| fee_override.mint == *mint)
+            .map_or(self.fee_bps, |fee_override| fee_override.fee_bps)
+    }
+
+    /// Sets the protocol fee of mint in its override slot, or the first free one,
+    /// NO_FEE_OVERRIDE frees the slot so the mint pays fee_bps again.
+    pub fn set_mint_fee(&mut self, mint: &Pubkey, fee_bps: u16) -> ProgramResult {
+        if *mint == Pubkey::default() {
+            msg!("Error: The default Pubkey can't carry a fee override");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let slot = self.fee_overrides.iter().position(|fee_override| fee_override.mint == *mint);
+        if fee_bps == SwapConfig::NO_FEE_OVERRIDE {
+            if let Some(slot) = slot {
+                self.fee_overrides[slot] = FeeOverride::default();
+            }
+            return Ok(());
+        }
+        SwapConfig::check_fee_bps(fee_bps, self.lp_fee_bps)?;
+        let slot = slot
+            .or_else(|| {
+                self.fee_overrides
+                    .iter()
+                    .position(|fee_override| fee_override.mint == Pubkey::default())
+            })
+            .ok_or_else(|| {
+                msg!(
+                    "Error: FeeOverridesFull. All {} fee override slots are taken",
+                    SwapConfig::MAX_FEE_OVERRIDES
+                );
+                ProgramError::from(AutoswapError::FeeOverridesFull)
+            })?;
+        self.fee_overrides[slot] = FeeOverride { mint: *mint, fee_bps };
+        Ok(())
+    }
+
+    /// Checks an admin change carries the expected sequence and advances it,
+    /// so changes apply in order and a replayed one is rejected.
+    pub fn check_sequence(&mut self, sequence: u64) -> ProgramResult {
+        if sequence != self.sequence {
+            msg!(
+                "Error: BadSequence. Expected sequence {}, got {}",
+                self.sequence,
+                sequence
+            );
+            return Err(AutoswapError::BadSequence.into());
+        }
+        self.sequence = math::checked_add(self.sequence, 1)?;
+        Ok(())
+    }
+
+    /// Checks the emergency pause switch is off.
+    pub fn check_not_paused(&self) -> ProgramResult {
+        if self.paused {
+            msg!("Error: Paused. Swaps and transfers are stopped by the admin");
+            return Err(AutoswapError::Paused.into());
+        }
+        Ok(())
+    }
+
+    /// Checks swaps may go through the pools of program_type.
+    pub fn check_protocol_enabled(&self, program_type: ProgramIDType) -> ProgramResult {
+        if self.enabled_protocols & 1 << program_type as u16 == 0 {
+            msg!("Error: ProtocolDisabled. Swaps through {:?} are switched off by the admin", program_type);
+            return Err(AutoswapError::ProtocolDisabled.into());
+        }
+        Ok(())
+    }
+
+    pub fn set_protocol_enabled(&mut self, program_type: ProgramIDType, enabled: bool) {
+        if enabled {
+            self.enabled_protocols |= 1 << program_type as u16;
+        } else {
+            self.enabled_protocols &= !(1 << program_type as u16);
+        }
+    }
+
+    /// Moves the transfer and swap sequence to next, in the order
+    /// before_transfer, swap, after_transfer.
+    /// before_transfer may always start over, discarding a sequence left unfinished.
+    /// A swap out of a sequence or after_transfer without a swap before it,
+    /// including a replayed one, is rejected.
+    pub fn advance_swap_phase(&mut self, next: SwapPhase) -> ProgramResult {
+        let allowed = match next {
+            SwapPhase::Deposited => true,
+            SwapPhase::Swapped => self.swap_phase == SwapPhase::Deposited,
+            SwapPhase::Idle => self.swap_phase == SwapPhase::Swapped,
+        };
+        if !allowed {
+            msg!(
+                "Error: InvalidSwapSequence. Can't move from {:?} to {:?}, expected before transfer, swap, after transfer",
+                self.swap_phase,
+                next
+            );
+            return Err(AutoswapError::InvalidSwapSequence.into());
+        }
+        self.swap_phase = next;
+        Ok(())
+    }
+
+    /// Returns true if the maintenance window is set.
+    pub fn has_maintenance_window(&self) -> bool {
+        self.maintenance_start_slot < self.maintenance_end_slot
+    }
+
+    /// Checks the slot is outside the maintenance window.
+    pub fn check_maintenance_window(&self, slot: u64) -> ProgramResult {
+        if (self.maintenance_start_slot..self.maintenance_end_slot).contains(&slot) {
+            msg!(
+                "Error: MaintenanceWindow. Swaps are disabled from slot {} to {}, current slot {}",
+                self.maintenance_start_slot,
+                self.maintenance_end_slot,
+                slot
+            );
+            Err(AutoswapError::MaintenanceWindow.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks the swap input stays within max_pool_consume_bps of the input reserve.
+    pub fn check_pool_consume(&self, amount_in: u64, reserve_in: u64) -> ProgramResult {
+        if self.max_pool_consume_bps > 0
+            && amount_in as u128 * 10_000 > reserve_in as u128 * self.max_pool_consume_bps as u128
+        {
+            msg!(
+                "Error: ConsumesTooMuchLiquidity. Input {} is above {} bps of the reserve {}",
+                amount_in,
+                self.max_pool_consume_bps,
+                reserve_in
+            );
+            Err(AutoswapError::ConsumesTooMuchLiquidity.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks the Serum event queue holding count of capacity events leaves room
+    /// for the swap's fills and stays within max_event_queue_fill_bps.
+    pub fn check_event_queue_fill(&self, count: u64, capacity: u64) -> ProgramResult {
+        if self.max_event_queue_fill_bps == 0 {
+            return Ok(());
+        }
+        if count >= capacity {
+            msg!(
+                "Error: EventQueueFull. {} of {} events pending, crank the market first",
+                count,
+                capacity
+            );
+            return Err(AutoswapError::EventQueueFull.into());
+        }
+        if count as u128 * 10_000 > capacity as u128 * self.max_event_queue_fill_bps as u128 {
+            msg!(
+                "Error: StaleMarket. {} of {} events pending is above {} bps, crank the market first",
+                count,
+                capacity,
+                self.max_event_queue_fill_bps
+            );
+            return Err(AutoswapError::StaleMarket.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the part of amount_in the deposit actually credited. A mint with
+    /// a transfer fee leaves the program account short of the nominal amount,
+    /// quoting the pool with it would overstate the swap.
+    pub fn net_amount_in(&self, amount_in: u64) -> u64 {
+        if self.swap_phase == SwapPhase::Deposited && self.deposited_amount > 0 {
+            amount_in.min(self.deposited_amount)
+        } else {
+            amount_in
+        }
+    }
+
+    /// Checks the swap input reaches min_swap_amount.
+    pub fn check_min_swap(&self, amount_in: u64) -> ProgramResult {
+        if amount_in < self.min_swap_amount {
+            msg!(
+                "Error: BelowMinimumSwap. Swap input {} is below the minimum {}",
+                amount_in,
+                self.min_swap_amount
+            );
+            Err(AutoswapError::BelowMinimumSwap.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks the realized swap output is worth delivering.
+    pub fn check_deliverable_out(&self, amount_out: u64) -> ProgramResult {
+        if self.min_deliverable_out > 0 && amount_out < self.min_deliverable_out {
+            msg!(
+                "Error: OutputDust. Swap output {} is below the deliverable minimum {}",
+                amount_out,
+                self.min_deliverable_out
+            );
+            Err(AutoswapError::OutputDust.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let config = SwapConfig {
+            version: SwapConfig::VERSION,
+            admin: Pubkey::new_unique(),
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            min_deliverable_out: 1234567891011,
+            min_fee: 5_000,
+            maintenance_start_slot: 1_000,
+            maintenance_end_slot: 2_000,
+            skip_funded_deposit: true,
+            max_pool_consume_bps: 250,
+            fee_token: FeeToken::Output,
+            sequence: 42,
+            auto_correct_vaults: true,
+            max_event_queue_fill_bps: 8_000,
+            fee_recipient: Pubkey::new_unique(),
+            paused: true,
+            swap_phase: SwapPhase::Swapped,
+            min_swap_amount: 1_000,
+            deposited_amount: 990,
+            instance: 3,
+            pending_admin: Pubkey::new_unique(),
+            fee_overrides: [FeeOverride {
+                mint: Pubkey::new_unique(),
+                fee_bps: 10,
+            }; SwapConfig::MAX_FEE_OVERRIDES],
+            default_slippage_bps: 75,
+            enabled_protocols: 0b1010,
+        };
+        let mut packed = [0; SwapConfig::LEN];
+        assert_eq!(config.pack(&mut packed), Ok(SwapConfig::LEN));
+        assert_eq!(SwapConfig::unpack(&packed), Ok(config));
+
+        packed[SwapConfig::LEN - 371] = 2;
+        assert_eq!(SwapConfig::unpack(&packed), Err(ProgramError::InvalidAccountData));
+
+        packed[SwapConfig::LEN - 371] = 1;
+        packed[SwapConfig::LEN - 326] = 3;
+        assert_eq!(SwapConfig::unpack(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_deliverable_out_above_threshold() {
+        let config = SwapConfig {
+            min_deliverable_out: 1_000,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.check_deliverable_out(1_000), Ok(()));
+        assert_eq!(config.check_deliverable_out(250_000), Ok(()));
+    }
+
+    #[test]
+    fn test_deliverable_out_below_threshold() {
+        let config = SwapConfig {
+            min_deliverable_out: 1_000,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.check_deliverable_out(999), Err(ProgramError::Custom(1006)));
+        assert_eq!(config.check_deliverable_out(0), Err(ProgramError::Custom(1006)));
+    }
+
+    #[test]
+    fn test_min_swap() {
+        let config = SwapConfig {
+            min_swap_amount: 1_000,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.check_min_swap(999), Err(ProgramError::Custom(1119)));
+        assert_eq!(config.check_min_swap(0), Err(ProgramError::Custom(1119)));
+        assert_eq!(config.check_min_swap(1_000), Ok(()));
+        assert_eq!(SwapConfig::default().check_min_swap(0), Ok(()));
+    }
+
+    #[test]
+    fn test_net_amount_in() {
+        let mut config = SwapConfig {
+            swap_phase: SwapPhase::Deposited,
+            deposited_amount: 990,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.net_amount_in(1_000), 990);
+        assert_eq!(config.net_amount_in(500), 500);
+
+        config.deposited_amount = 0;
+        assert_eq!(config.net_amount_in(1_000), 1_000);
+
+        config.deposited_amount = 990;
+        config.swap_phase = SwapPhase::Swapped;
+        assert_eq!(config.net_amount_in(1_000), 1_000);
+    }
+
+    #[test]
+    fn test_check_fee_bps() {
+        assert_eq!(SwapConfig::check_fee_bps(SwapConfig::MAX_FEE_BPS, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::check_fee_bps(SwapConfig::MAX_FEE_BPS + 1, 0),
+            Err(ProgramError::Custom(1129))
+        );
+        // the lp share counts against the same cap
+        assert_eq!(SwapConfig::check_fee_bps(60, SwapConfig::MAX_FEE_BPS - 60), Ok(()));
+        assert_eq!(
+            SwapConfig::check_fee_bps(60, SwapConfig::MAX_FEE_BPS - 59),
+            Err(ProgramError::Custom(1129))
+        );
+        assert_eq!(
+            SwapConfig::check_fee_bps(0, u16::MAX),
+            Err(ProgramError::Custom(1129))
+        );
+    }
+
+    #[test]
+    fn test_set_protocol_enabled() {
+        let mut config = SwapConfig::default();
+        assert_eq!(config.check_protocol_enabled(ProgramIDType::Raydium), Ok(()));
+
+        config.set_protocol_enabled(ProgramIDType::Raydium, false);
+        assert_eq!(
+            config.check_protocol_enabled(ProgramIDType::Raydium),
+            Err(ProgramError::Custom(1131))
+        );
+        assert_eq!(config.check_protocol_enabled(ProgramIDType::Orca), Ok(()));
+
+        config.set_protocol_enabled(ProgramIDType::Raydium, true);
+        assert_eq!(config.enabled_protocols, SwapConfig::ALL_PROTOCOLS);
+    }
+
+    #[test]
+    fn test_set_mint_fee() {
+        let mut config = SwapConfig::default();
+        let (mint, other_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(config.set_mint_fee(&mint, 10), Ok(()));
+        assert_eq!(config.fee_bps_for(&mint), 10);
+        assert_eq!(config.fee_bps_for(&other_mint), SwapConfig::DEFAULT_FEE_BPS);
+        // updated in place
+        assert_eq!(config.set_mint_fee(&mint, 0), Ok(()));
+        assert_eq!(config.fee_bps_for(&mint), 0);
+        assert_eq!(config.fee_overrides.iter().filter(|o| o.mint == mint).count(), 1);
+
+        assert_eq!(config.set_mint_fee(&mint, SwapConfig::NO_FEE_OVERRIDE), Ok(()));
+        assert_eq!(config.fee_bps_for(&mint), SwapConfig::DEFAULT_FEE_BPS);
+        assert_eq!(config.fee_overrides, SwapConfig::default().fee_overrides);
+
+        assert_eq!(
+            config.set_mint_fee(&mint, SwapConfig::MAX_FEE_BPS + 1),
+            Err(ProgramError::Custom(1129))
+        );
+        assert_eq!(
+            config.set_mint_fee(&Pubkey::default(), 10),
+            Err(ProgramError::InvalidArgument)
+        );
+        for _ in 0..SwapConfig::MAX_FEE_OVERRIDES {
+            assert_eq!(config.set_mint_fee(&Pubkey::new_unique(), 10), Ok(()));
+        }
+        assert_eq!(config.set_mint_fee(&mint, 10), Err(ProgramError::Custom(1125)));
+    }
+
+    #[test]
+    fn test_deliverable_out_disabled() {
+        assert_eq!(SwapConfig::default().check_deliverable_out(0), Ok(()));
+        assert_eq!(SwapConfig::default().check_deliverable_out(1), Ok(()));
+    }
+
+    fn corrupted_config() -> SwapConfig {
+        SwapConfig {
+            version: 7,
+            admin: Pubkey::default(),
+            fee_bps: 25_000,
+            ..SwapConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_verify_valid_config() {
+        let mut config = SwapConfig::default();
+        assert_eq!(config.verify(true), 0);
+        assert_eq!(config, SwapConfig::default());
+    }
+
+    #[test]
+    fn test_verify_reports_corrupted_config() {
+        let mut config = corrupted_config();
+        assert_eq!(
+            config.verify(false),
+            CONFIG_VERSION_MISMATCH | CONFIG_FEE_OUT_OF_RANGE | CONFIG_ZERO_ADMIN
+        );
+        assert_eq!(config, corrupted_config());
+    }
+
+    #[test]
+    fn test_verify_repairs_corrupted_config() {
+        let mut config = corrupted_config();
+        assert_eq!(
+            config.verify(true),
+            CONFIG_VERSION_MISMATCH | CONFIG_FEE_OUT_OF_RANGE | CONFIG_ZERO_ADMIN
+        );
+        assert_eq!(config.fee_bps, SwapConfig::MAX_FEE_BPS);
+        assert_eq!(config.admin, main_router_admin::id());
+
+        // only the version mismatch is left
+        assert_eq!(config.verify(true), CONFIG_VERSION_MISMATCH);
+    }
+
+    #[test]
+    fn test_verify_repairs_fee_split() {
+        let mut config = SwapConfig {
+            fee_bps: 60,
+            lp_fee_bps: 9_950,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.verify(true), CONFIG_FEE_OUT_OF_RANGE);
+        assert_eq!(config.fee_bps, 60);
+        assert_eq!(config.lp_fee_bps, SwapConfig::MAX_FEE_BPS - 60);
+        assert_eq!(config.verify(true), 0);
+    }
+
+    #[test]
+    fn test_maintenance_window() {
+        let config = SwapConfig {
+            maintenance_start_slot: 1_000,
+            maintenance_end_slot: 2_000,
+            ..SwapConfig::default()
+        };
+        assert!(config.has_maintenance_window());
+        for slot in [1_000, 1_500, 1_999] {
+            assert_eq!(config.check_maintenance_window(slot), Err(ProgramError::Custom(1009)));
+        }
+        for slot in [0, 999, 2_000, u64::MAX] {
+            assert_eq!(config.check_maintenance_window(slot), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_maintenance_window_disabled() {
+        for (start, end) in [(0, 0), (1_000, 1_000), (2_000, 1_000)] {
+            let config = SwapConfig {
+                maintenance_start_slot: start,
+                maintenance_end_slot: end,
+                ..SwapConfig::default()
+            };
+            assert!(!config.has_maintenance_window());
+            for slot in [0, 1_000, 1_500, 2_000] {
+                assert_eq!(config.check_maintenance_window(slot), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pool_consume() {
+        let config = SwapConfig {
+            max_pool_consume_bps: 500,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.check_pool_consume(49_999, 1_000_000), Ok(()));
+        assert_eq!(config.check_pool_consume(50_000, 1_000_000), Ok(()));
+        assert_eq!(config.check_pool_consume(50_001, 1_000_000), Err(ProgramError::Custom(1011)));
+        assert_eq!(config.check_pool_consume(1, 0), Err(ProgramError::Custom(1011)));
+        assert_eq!(SwapConfig::default().check_pool_consume(u64::MAX, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_check_sequence() {
+        let mut config = SwapConfig::default();
+        assert_eq!(config.check_sequence(0), Ok(()));
+        assert_eq!(config.check_sequence(1), Ok(()));
+        assert_eq!(config.sequence, 2);
+
+        // stale and future sequences are rejected without advancing
+        for sequence in [0, 1, 3, u64::MAX] {
+            assert_eq!(config.check_sequence(sequence), Err(ProgramError::Custom(1104)));
+        }
+        assert_eq!(config.sequence, 2);
+    }
+
+    #[test]
+    fn test_swap_phase_order() {
+        let mut config = SwapConfig::default();
+        for phase in [SwapPhase::Deposited, SwapPhase::Swapped, SwapPhase::Idle] {
+            assert_eq!(config.advance_swap_phase(phase), Ok(()));
+            assert_eq!(config.swap_phase, phase);
+        }
+
+        // after transfer without a swap, swap without a deposit
+        let invalid = Err(ProgramError::Custom(1116));
+        assert_eq!(config.advance_swap_phase(SwapPhase::Idle), invalid);
+        assert_eq!(config.advance_swap_phase(SwapPhase::Swapped), invalid);
+        assert_eq!(config.advance_swap_phase(SwapPhase::Deposited), Ok(()));
+        assert_eq!(config.advance_swap_phase(SwapPhase::Idle), invalid);
+        assert_eq!(config.advance_swap_phase(SwapPhase::Swapped), Ok(()));
+        assert_eq!(config.advance_swap_phase(SwapPhase::Swapped), invalid);
+        assert_eq!(config.swap_phase, SwapPhase::Swapped);
+
+        // an unfinished sequence is discarded by the next deposit
+        assert_eq!(config.advance_swap_phase(SwapPhase::Deposited), Ok(()));
+        assert_eq!(config.swap_phase, SwapPhase::Deposited);
+    }
+
+    #[test]
+    fn test_event_queue_fill() {
+        let config = SwapConfig {
+            max_event_queue_fill_bps: 8_000,
+            ..SwapConfig::default()
+        };
+        assert_eq!(config.check_event_queue_fill(0, 10), Ok(()));
+        assert_eq!(config.check_event_queue_fill(8, 10), Ok(()));
+        assert_eq!(config.check_event_queue_fill(9, 10), Err(ProgramError::Custom(1107)));
+        assert_eq!(config.check_event_queue_fill(10, 10), Err(ProgramError::Custom(1106)));
+        assert_eq!(config.check_event_queue_fill(0, 0), Err(ProgramError::Custom(1106)));
+
+        // disabled
+        assert_eq!(SwapConfig::default().check_event_queue_fill(10, 10), Ok(()));
+    }
+}
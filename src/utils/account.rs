@@ -2,6 +2,7 @@
 
 use {
     crate::{
+        error::AutoswapError,
         utils::math, 
         utils::pack::check_data_len
     },
@@ -34,9 +35,25 @@ pub fn get_token_decimals(token_mint: &AccountInfo) -> Result<u8, ProgramError>
     Ok(decimals[0])
 }
 
+/// Checks that the account is owned by the SPL Token or Token-2022 program,
+/// so its data can be read as a token account.
+pub fn assert_token_account_owner(token_account: &AccountInfo) -> ProgramResult {
+    if token_account.owner == &spl_token::id() || token_account.owner == &spl_token_2022::id() {
+        Ok(())
+    } else {
+        msg!(
+            "Error: Illegal owner. Account: {}, owner: {} is not a token program",
+            token_account.key,
+            token_account.owner
+        );
+        Err(ProgramError::IllegalOwner)
+    }
+}
+
 /// Returns Tokens balance.
 /// Extrats balance field without unpacking entire struct.
 pub fn get_token_balance(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    assert_token_account_owner(token_account)?;
     let data = token_account.try_borrow_data()?;
     check_data_len(&data, spl_token::state::Account::get_packed_len())?;
     let amount = array_ref![data, 64, 8];
@@ -64,20 +81,39 @@ pub fn get_token_account_mint(token_account: &AccountInfo) -> Result<Pubkey, Pro
     Ok(Pubkey::new_from_array(*mint))
 }
 
+/// Returns after - before, ArithmeticOverflow if the balance went down instead.
+/// Callers log which account it was.
+pub fn balance_delta(before: u64, after: u64) -> Result<u64, ProgramError> {
+    after.checked_sub(before).ok_or(ProgramError::ArithmeticOverflow)
+}
+
 pub fn get_balance_increase(
     account: &AccountInfo,
     previous_balance: u64,
 ) -> Result<u64, ProgramError> {
     let balance = get_token_balance(account)?;
-    if balance >= previous_balance {
-        Ok(balance - previous_balance)
-    } else {
+    balance_delta(previous_balance, balance).map_err(|_| {
         msg!(
             "Error: Balance decrease was not expected. Account: {}",
             account.key
         );
-        Err(ProgramError::Custom(1001))
+        AutoswapError::UnexpectedBalanceDecrease.into()
+    })
+}
+
+/// Returns what a transfer of sent actually credited destination. Mints with a
+/// transfer fee withhold part of it, callers must carry on with the returned
+/// amount rather than the nominal one.
+pub fn detect_received_amount(
+    destination: &AccountInfo,
+    previous_balance: u64,
+    sent: u64,
+) -> Result<u64, ProgramError> {
+    let received = get_balance_increase(destination, previous_balance)?;
+    if received < sent {
+        msg!("Transfer fee withheld {} of {}", sent - received, sent);
     }
+    Ok(received)
 }
 
 pub fn get_balance_decrease(
@@ -85,15 +121,13 @@ pub fn get_balance_decrease(
     previous_balance: u64,
 ) -> Result<u64, ProgramError> {
     let balance = get_token_balance(account)?;
-    if balance <= previous_balance {
-        Ok(previous_balance - balance)
-    } else {
+    balance_delta(balance, previous_balance).map_err(|_| {
         msg!(
             "Error: Balance increase was not expected. Account: {}",
             account.key
         );
-        Err(ProgramError::Custom(1002))
-    }
+        AutoswapError::UnexpectedBalanceIncrease.into()
+    })
 }
 
 pub fn check_tokens_spent(
@@ -109,7 +143,7 @@ pub fn check_tokens_spent(
             max_amount_spent,
             tokens_spent
         );
-        Err(ProgramError::Custom(1003))
+        Err(AutoswapError::Overspent.into())
     } else {
         Ok(tokens_spent)
     }
@@ -128,12 +162,54 @@ pub fn check_tokens_received(
             min_amount_received,
             tokens_received
         );
-        Err(ProgramError::Custom(1004))
+        Err(AutoswapError::SlippageExceeded.into())
     } else {
         Ok(tokens_received)
     }
 }
 
+/// Checks that at least expected accounts were passed, so a handler fails up
+/// front instead of on the first missing account.
+pub fn check_account_count(accounts: &[AccountInfo], expected: usize) -> ProgramResult {
+    if accounts.len() < expected {
+        msg!(
+            "Error: Not enough accounts. Expected at least {}, got {}",
+            expected,
+            accounts.len()
+        );
+        Err(ProgramError::NotEnoughAccountKeys)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the account was passed as writable.
+/// Catches misconfigured clients before the invoked program fails on write.
+pub fn check_writable(account: &AccountInfo) -> ProgramResult {
+    if account.is_writable {
+        Ok(())
+    } else {
+        msg!("Error: Account must be writable. Account: {}", account.key);
+        Err(AutoswapError::AccountNotWritable.into())
+    }
+}
+
+/// Checks that the token account lives under the token program used to move it.
+/// Mixed SPL Token and Token-2022 pairs need a separate program per transfer.
+pub fn check_token_program(token_account: &AccountInfo, token_program: &AccountInfo) -> ProgramResult {
+    if token_account.owner == token_program.key {
+        Ok(())
+    } else {
+        msg!(
+            "Error: TokenProgramMismatch. Account: {}, owner: {}, token program: {}",
+            token_account.key,
+            token_account.owner,
+            token_program.key
+        );
+        Err(AutoswapError::TokenProgramMismatch.into())
+    }
+}
+
 /// Returns Token Mint data.
 pub fn get_token_mint(token_mint: &AccountInfo) -> Result<Mint, ProgramError> {
     let data = token_mint.try_borrow_data()?;
@@ -376,4 +452,71 @@ mod tests {
         let amount = array_ref![packed, 64, 8];
         assert_eq!(1234567891011, u64::from_le_bytes(*amount));
     }
+
+    #[test]
+    fn test_token_balance_checks_owner() {
+        let key = Pubkey::new_unique();
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                amount: 1_000,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+
+        for owner in [spl_token::id(), spl_token_2022::id()] {
+            let mut lamports = 0;
+            let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            assert_eq!(assert_token_account_owner(&account), Ok(()));
+            assert_eq!(get_token_balance(&account), Ok(1_000));
+        }
+
+        // same data under the system program or any other owner is not trusted
+        for owner in [solana_program::system_program::id(), Pubkey::new_unique()] {
+            let mut lamports = 0;
+            let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            assert_eq!(assert_token_account_owner(&account), Err(ProgramError::IllegalOwner));
+            assert_eq!(get_token_balance(&account), Err(ProgramError::IllegalOwner));
+        }
+    }
+
+    #[test]
+    fn test_balance_delta() {
+        assert_eq!(balance_delta(1_000, 1_500), Ok(500));
+        assert_eq!(balance_delta(1_000, 1_000), Ok(0));
+        assert_eq!(balance_delta(1_000, 999), Err(ProgramError::ArithmeticOverflow));
+        assert_eq!(balance_delta(u64::MAX, 0), Err(ProgramError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn test_balance_checks_reject_wrong_direction() {
+        let key = Pubkey::new_unique();
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                amount: 1_000,
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        let mut lamports = 0;
+        let owner = spl_token::id();
+        let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+
+        // the balance went down, nothing was received
+        assert_eq!(
+            check_tokens_received(&account, 1_001, 0),
+            Err(AutoswapError::UnexpectedBalanceDecrease.into())
+        );
+        // the balance went up, nothing was spent
+        assert_eq!(
+            check_tokens_spent(&account, 999, u64::MAX),
+            Err(AutoswapError::UnexpectedBalanceIncrease.into())
+        );
+        assert_eq!(check_tokens_received(&account, 0, 1_000), Ok(1_000));
+        assert_eq!(check_tokens_spent(&account, u64::MAX, u64::MAX), Ok(u64::MAX - 1_000));
+    }
 }
@@ -0,0 +1,63 @@
+//! Compute budget checkpoints for instructions chaining several CPIs
+
+use {
+    crate::error::AutoswapError,
+    solana_program::{entrypoint::ProgramResult, msg},
+};
+
+/// Units reserved for a Raydium swap CPI and the router checks around it
+pub const SWAP_PHASE_UNITS: u64 = 80_000;
+/// Units reserved for a staking CPI
+pub const STAKE_PHASE_UNITS: u64 = 40_000;
+
+/// Source of the compute units left to the running instruction.
+pub trait ComputeMeter {
+    /// Returns the units left, None if they can't be read.
+    fn remaining_units(&self) -> Option<u64>;
+}
+
+/// Reads the budget with the sol_remaining_compute_units syscall.
+/// Without the compute-guard feature the budget is unknown and checkpoints pass.
+pub struct SyscallComputeMeter;
+
+impl ComputeMeter for SyscallComputeMeter {
+    #[cfg(feature = "compute-guard")]
+    fn remaining_units(&self) -> Option<u64> {
+        Some(solana_program::compute_units::sol_remaining_compute_units())
+    }
+
+    #[cfg(not(feature = "compute-guard"))]
+    fn remaining_units(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Aborts with OutOfComputeBudget before phase if fewer than required units are left.
+pub fn checkpoint(meter: &dyn ComputeMeter, phase: &str, required: u64) -> ProgramResult {
+    match meter.remaining_units() {
+        Some(remaining) if remaining < required => {
+            msg!(
+                "Error: {} compute units left before {}, {} required",
+                remaining,
+                phase,
+                required
+            );
+            Err(AutoswapError::OutOfComputeBudget.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Logs phase and the compute units left at that point, with the profiling
+/// feature only. The difference between two phases is what the code between
+/// them cost.
+#[inline(always)]
+pub fn log_phase(phase: &str) {
+    #[cfg(feature = "profiling")]
+    {
+        msg!("Profiling: {}", phase);
+        solana_program::log::sol_log_compute_units();
+    }
+    #[cfg(not(feature = "profiling"))]
+    let _ = phase;
+}
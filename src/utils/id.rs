@@ -1,13 +1,27 @@
 //! Official accounts and program ids
 
-use serde::{Deserialize, Serialize};
+use {
+    crate::protocol::{orca, raydium, saber, serum},
+    serde::{Deserialize, Serialize},
+    solana_program::pubkey::Pubkey,
+};
+
+// Cluster specific ids are picked by the `devnet` feature, mainnet otherwise
+#[cfg(all(feature = "mainnet", feature = "devnet"))]
+compile_error!("features `mainnet` and `devnet` are mutually exclusive");
 
 pub mod main_router {
+    #[cfg(not(feature = "devnet"))]
     solana_program::declare_id!("RepLaceThisWithVaLidMainRouterProgramPubkey");
+    #[cfg(feature = "devnet")]
+    solana_program::declare_id!("RepLaceThisWithDevnetRouterProgramPubkey111");
 }
 
 pub mod main_router_admin {
+    #[cfg(not(feature = "devnet"))]
     solana_program::declare_id!("RepLaceThisWithCorrectMainRouterAdminPubkey");
+    #[cfg(feature = "devnet")]
+    solana_program::declare_id!("RepLaceThisWithDevnetRouterAdminPubkey11111");
 }
 
 pub mod zero {
@@ -17,6 +31,10 @@ pub mod zero {
     solana_program::declare_id!("zeRosMEYuuABXv5y2LNUbgmPp62yFD5CULW5soHS9HR");
 }
 
+pub mod saber_stable_swap {
+    solana_program::declare_id!("SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ");
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ProgramIDType {
     System,
@@ -35,3 +53,111 @@ pub enum ProgramIDType {
     Saber,
     Orca,
 }
+
+impl ProgramIDType {
+    /// Classifies a known program id, None for anything else
+    pub fn from_program_id(key: &Pubkey) -> Option<ProgramIDType> {
+        if raydium::check_pool_program_id(key) {
+            Some(ProgramIDType::Raydium)
+        } else if serum::check_program_id(key) {
+            Some(ProgramIDType::Serum)
+        } else if orca::check_pool_program_id(key) {
+            Some(ProgramIDType::Orca)
+        } else if saber::check_pool_program_id(key) {
+            Some(ProgramIDType::Saber)
+        } else if key == &spl_token::id() || key == &spl_token_2022::id() {
+            Some(ProgramIDType::Token)
+        } else if key == &solana_program::system_program::id() {
+            Some(ProgramIDType::System)
+        } else if key == &main_router::id() {
+            Some(ProgramIDType::MainRouter)
+        } else {
+            None
+        }
+    }
+
+    /// Protocol picked by the Route instruction byte: 0 Raydium, 1 Orca, 2 Saber, 3 Serum
+    pub fn from_route_protocol(protocol: u8) -> Option<ProgramIDType> {
+        match protocol {
+            0 => Some(ProgramIDType::Raydium),
+            1 => Some(ProgramIDType::Orca),
+            2 => Some(ProgramIDType::Saber),
+            3 => Some(ProgramIDType::Serum),
+            _ => None,
+        }
+    }
+
+    /// Route protocol byte of a pool program type, the inverse of from_route_protocol
+    pub fn route_protocol(self) -> Option<u8> {
+        (0..=u8::MAX).find(|protocol| ProgramIDType::from_route_protocol(*protocol) == Some(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::protocol::{orca::orca_v2, raydium::{raydium_v2, raydium_v4}, serum::serum_v3},
+    };
+
+    #[test]
+    fn test_from_program_id() {
+        for (key, expected) in [
+            (raydium_v2::id(), ProgramIDType::Raydium),
+            (raydium_v4::id(), ProgramIDType::Raydium),
+            (serum_v3::id(), ProgramIDType::Serum),
+            (orca_v2::id(), ProgramIDType::Orca),
+            (saber_stable_swap::id(), ProgramIDType::Saber),
+            (spl_token::id(), ProgramIDType::Token),
+            (spl_token_2022::id(), ProgramIDType::Token),
+            (solana_program::system_program::id(), ProgramIDType::System),
+            (main_router::id(), ProgramIDType::MainRouter),
+        ] {
+            assert_eq!(ProgramIDType::from_program_id(&key), Some(expected), "{}", key);
+        }
+        assert_eq!(ProgramIDType::from_program_id(&Pubkey::new_unique()), None);
+        assert_eq!(ProgramIDType::from_program_id(&zero::id()), None);
+    }
+
+    #[test]
+    fn test_from_route_protocol() {
+        for (protocol, expected) in [
+            (0, Some(ProgramIDType::Raydium)),
+            (1, Some(ProgramIDType::Orca)),
+            (2, Some(ProgramIDType::Saber)),
+            (3, Some(ProgramIDType::Serum)),
+            (4, None),
+            (u8::MAX, None),
+        ] {
+            assert_eq!(ProgramIDType::from_route_protocol(protocol), expected, "{}", protocol);
+            if let Some(program_type) = expected {
+                assert_eq!(program_type.route_protocol(), Some(protocol));
+            }
+        }
+        assert_eq!(ProgramIDType::Token.route_protocol(), None);
+    }
+
+    #[cfg(not(feature = "devnet"))]
+    #[test]
+    fn test_mainnet_ids() {
+        assert_eq!(raydium_v4::id().to_string(), "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+        assert_eq!(serum_v3::id().to_string(), "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin");
+        assert_eq!(main_router::id().to_string(), "RepLaceThisWithVaLidMainRouterProgramPubkey");
+        assert_eq!(
+            main_router_admin::id().to_string(),
+            "RepLaceThisWithCorrectMainRouterAdminPubkey"
+        );
+    }
+
+    #[cfg(feature = "devnet")]
+    #[test]
+    fn test_devnet_ids() {
+        assert_eq!(raydium_v4::id().to_string(), "HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8");
+        assert_eq!(serum_v3::id().to_string(), "DESVgJVGajEgKGXhb6XmqDHGz3VjdgP7rEVESBgxmroY");
+        assert_eq!(main_router::id().to_string(), "RepLaceThisWithDevnetRouterProgramPubkey111");
+        assert_eq!(
+            main_router_admin::id().to_string(),
+            "RepLaceThisWithDevnetRouterAdminPubkey11111"
+        );
+    }
+}
@@ -2,7 +2,7 @@
 
 use {
     solana_program::{msg, program_error::ProgramError},
-    std::fmt::Display,
+    std::{convert::TryFrom, fmt::Display},
 };
 
 pub fn checked_add<T>(arg1: T, arg2: T) -> Result<T, ProgramError>
@@ -65,3 +65,16 @@ where
         Err(ProgramError::Custom(999))
     }
 }
+
+/// Returns bps basis points of amount, rounded down.
+/// The product is taken in u128, only a share above u64::MAX overflows.
+pub fn checked_bps_of(amount: u64, bps: u128) -> Result<u64, ProgramError> {
+    (amount as u128)
+        .checked_mul(bps)
+        .and_then(|v| v.checked_div(10_000))
+        .and_then(|v| u64::try_from(v).ok())
+        .ok_or_else(|| {
+            msg!("Error: Overflow in {} bps of {}", bps, amount);
+            ProgramError::ArithmeticOverflow
+        })
+}
@@ -1,7 +1,10 @@
 pub mod account;
+pub mod compute;
 pub mod math;
 pub mod id;
+pub mod orca;
 pub mod pack;
 pub mod raydium;
+pub mod saber;
 pub mod swap;
 pub mod tokens;
\ No newline at end of file
@@ -1,7 +1,9 @@
 //! Common routines for packing/unpacking
 
 use {
-    solana_program::{program_error::ProgramError},
+    crate::utils::id::zero,
+    arrayref::array_ref,
+    solana_program::{msg, program_error::ProgramError, pubkey::Pubkey, system_program},
 };
 
 /// Checks if the slice has at least min_len size
@@ -11,4 +13,71 @@ pub fn check_data_len(data: &[u8], min_len: usize) -> Result<(), ProgramError> {
     } else {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Checks if the slice has exactly len size, longer data is invalid
+pub fn check_exact_data_len(data: &[u8], len: usize) -> Result<(), ProgramError> {
+    check_data_len(data, len)?;
+    if data.len() > len {
+        Err(ProgramError::InvalidInstructionData)
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads the pubkey packed at offset.
+/// Rejects the zero and system program keys, which are never valid targets.
+pub fn unpack_pubkey(src: &[u8], offset: usize) -> Result<Pubkey, ProgramError> {
+    check_data_len(src, offset.checked_add(32).ok_or(ProgramError::InvalidInstructionData)?)?;
+    let pubkey = Pubkey::new_from_array(*array_ref![src, offset, 32]);
+    if pubkey == system_program::id() || pubkey == zero::id() {
+        msg!("Error: Invalid pubkey {} at offset {}", pubkey, offset);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_pubkeys(prefix: &[u8], pubkeys: &[Pubkey]) -> Vec<u8> {
+        let mut data = prefix.to_vec();
+        for pubkey in pubkeys {
+            data.extend_from_slice(pubkey.as_ref());
+        }
+        data
+    }
+
+    #[test]
+    fn test_unpack_pubkey() {
+        let pubkeys = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let data = pack_pubkeys(&[7], &pubkeys);
+
+        assert_eq!(unpack_pubkey(&data, 1), Ok(pubkeys[0]));
+        assert_eq!(unpack_pubkey(&data, 33), Ok(pubkeys[1]));
+        assert_eq!(unpack_pubkey(&data, 65), Ok(pubkeys[2]));
+    }
+
+    #[test]
+    fn test_unpack_pubkey_rejects_invalid() {
+        for invalid in [Pubkey::default(), system_program::id(), zero::id()] {
+            let valid = Pubkey::new_unique();
+            let data = pack_pubkeys(&[7], &[valid, invalid]);
+
+            assert_eq!(unpack_pubkey(&data, 1), Ok(valid));
+            assert_eq!(unpack_pubkey(&data, 33), Err(ProgramError::InvalidArgument));
+        }
+    }
+
+    #[test]
+    fn test_unpack_pubkey_truncated() {
+        let data = pack_pubkeys(&[7], &[Pubkey::new_unique(), Pubkey::new_unique()]);
+
+        assert_eq!(unpack_pubkey(&data[..32], 1), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(unpack_pubkey(&data, 65), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(unpack_pubkey(&data[..64], 33), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(unpack_pubkey(&data, usize::MAX), Err(ProgramError::InvalidInstructionData));
+    }
+}
@@ -73,7 +73,7 @@ impl RaydiumAddLiquidity {
         let (instruction_out, max_coin_token_amount_out, max_pc_token_amount_out, base_side_out) =
             mut_array_refs![output, 1, 8, 8, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *max_coin_token_amount_out = self.max_coin_token_amount.to_le_bytes();
         *max_pc_token_amount_out = self.max_pc_token_amount.to_le_bytes();
         *base_side_out = self.base_side.to_le_bytes();
@@ -105,7 +105,7 @@ impl RaydiumRemoveLiquidity {
 
         let (instruction_out, amount_out) = mut_array_refs![output, 1, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *amount_out = self.amount.to_le_bytes();
 
         Ok(RaydiumRemoveLiquidity::LEN)
@@ -135,7 +135,7 @@ impl RaydiumSwap {
 
         let (instruction_out, amount_in_out, min_amount_out_out) = mut_array_refs![output, 1, 8, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *amount_in_out = self.amount_in.to_le_bytes();
         *min_amount_out_out = self.min_amount_out.to_le_bytes();
 
@@ -166,7 +166,7 @@ impl SwapRouteIn {
 
         let (instruction_out, amount_in_out) = mut_array_refs![output, 1, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *amount_in_out = self.amount_in.to_le_bytes();
 
         Ok(SwapRouteIn::LEN)
@@ -196,7 +196,7 @@ impl SwapRouteOut {
 
         let (instruction_out, min_amount_out_out) = mut_array_refs![output, 1, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *min_amount_out_out = self.min_amount_out.to_le_bytes();
 
         Ok(SwapRouteOut::LEN)
@@ -226,7 +226,7 @@ impl RaydiumStake {
 
         let (instruction_out, amount_out) = mut_array_refs![output, 1, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *amount_out = self.amount.to_le_bytes();
 
         Ok(RaydiumStake::LEN)
@@ -256,7 +256,7 @@ impl RaydiumUnstake {
 
         let (instruction_out, amount_out) = mut_array_refs![output, 1, 8];
 
-        instruction_out[0] = self.instruction as u8;
+        instruction_out[0] = self.instruction;
         *amount_out = self.amount.to_le_bytes();
 
         Ok(RaydiumUnstake::LEN)
@@ -283,7 +283,7 @@ impl RaydiumHarvest {
         check_data_len(output, RaydiumHarvest::LEN)?;
 
         let output = array_mut_ref![output, 0, RaydiumHarvest::LEN];
-        output[0] = self.instruction as u8;
+        output[0] = self.instruction;
 
         Ok(RaydiumHarvest::LEN)
     }
@@ -2,25 +2,39 @@
 
 use {
     crate::{
-        utils::raydium::{SwapRouteIn, SwapRouteOut, RaydiumSwap},
+        accounts_layout as layout,
+        error::AutoswapError,
+        event::{SwapEvent, NO_PROTOCOL},
+        instruction::{AmmInstruction, SwapParams},
+        quote,
+        state::{FeeToken, SwapConfig, SwapPhase},
+        utils::id::{main_router_admin, zero, ProgramIDType},
         utils::account,
+        utils::compute::{self, ComputeMeter},
+        utils::math,
         utils::tokens::{
             TokenTransferParams,
+            TokenTransferCheckedParams,
             spl_token_transfer,
-            PREFIX,
+            spl_token_transfer_checked,
+            close_token_account,
+            unwrap_sol,
+            wrap_sol,
+            ProgramAuthority,
         },
-        protocol::raydium,
+        protocol::{orca, raydium::{self, RaydiumPoolAccounts}, saber, serum, Staking},
+        version,
     },
     solana_program::{
         account_info::{next_account_info, AccountInfo},
         entrypoint::ProgramResult,
-        instruction::{AccountMeta, Instruction},
         system_instruction,
         msg,
-        program::{invoke, invoke_signed},
+        program::{invoke, invoke_signed, set_return_data},
         program_error::ProgramError,
         pubkey::Pubkey,
         sysvar::{
+            clock::Clock,
             rent::Rent,
             Sysvar,
         },
@@ -28,28 +42,51 @@ use {
     std::convert::TryInto,
 };
 
+/// Least number of accounts each handler reads, optional trailing ones aside,
+/// see account::check_account_count.
+pub const CREATE_ACCOUNT_ACCOUNTS: usize = 4;
+pub const INITIALIZE_ACCOUNTS: usize = 4;
+pub const BEFORE_TRANSFER_ACCOUNTS: usize = 5;
+pub const AFTER_TRANSFER_ACCOUNTS: usize = 8;
+pub const HARVEST_ACCOUNTS: usize = 5;
+pub const SWAP_ORCA_ACCOUNTS: usize = 11;
+pub const SWAP_SABER_ACCOUNTS: usize = 11;
+/// Admin signer and program account of the config setters and verify_config
+pub const ADMIN_ACCOUNTS: usize = 2;
+pub const SET_FEE_RECIPIENT_ACCOUNTS: usize = 3;
+pub const TRANSFER_ADMIN_ACCOUNTS: usize = 3;
+pub const SWEEP_LAMPORTS_ACCOUNTS: usize = 5;
+pub const WITHDRAW_FEES_ACCOUNTS: usize = 5;
+pub const CLOSE_TEMP_ACCOUNT_ACCOUNTS: usize = 5;
+pub const RESCUE_TOKENS_ACCOUNTS: usize = 5;
+/// Admin signer, program account and token program, the pairs follow
+pub const SWEEP_FEES_ACCOUNTS: usize = 3;
+/// Accounts of each (fee token account, destination) pair of a SweepFees
+pub const SWEEP_FEES_STRIDE: usize = 2;
+pub const QUOTE_ACCOUNTS: usize = 5;
+pub const GET_CONFIG_ACCOUNTS: usize = 1;
+pub const SWAP_SERUM_DIRECT_ACCOUNTS: usize = 15;
+/// Enough to read the pool program id, the protocol handler checks the rest
+pub const ROUTE_ACCOUNTS: usize = 4;
+/// Most legs of a BatchSwap and candidate pools of a SwapBestFee
+pub const MAX_BATCH: usize = 6;
+
 pub fn create_program_account(
     program_id : &Pubkey,
     accounts: &[AccountInfo],
     size: u64,
+    instance: u8,
 ) -> ProgramResult {
     
+    account::check_account_count(accounts, CREATE_ACCOUNT_ACCOUNTS)?;
     let account_info_iter = &mut accounts.iter();
     let program_account_info = next_account_info(account_info_iter)?;
     let payer_account_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
     let system_account_info = next_account_info(account_info_iter)?;
 
-    let seed = &[
-        PREFIX.as_bytes(),
-    ];
-
-    let (program_account, bump_seed) = Pubkey::find_program_address(seed, &program_id);
-
-    let program_account_signer_seeds = &[
-        PREFIX.as_bytes(),
-        &[bump_seed],
-    ];
+    let authority = ProgramAuthority::find(program_id, instance);
+    let program_account_signer_seeds = &authority.signer_seeds()[..];
 
     create_or_allocate_account_raw(
         *program_id,
@@ -57,13 +94,76 @@ pub fn create_program_account(
         rent_info,
         system_account_info,
         payer_account_info,
-        size as usize,
+        size.try_into().map_err(|_| ProgramError::InvalidArgument)?,
         program_account_signer_seeds,
     )?;
 
     Ok(())
 }
 
+/// Creates the router PDA if needed and writes the initial config,
+/// with the signer as admin. An initialized config is never overwritten.
+///
+/// Accounts: admin (signer, pays for the account), program account, rent sysvar,
+/// system program
+pub fn initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_bps: u16,
+    instance: u8,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::Initialize");
+    msg!("fee_bps {} ", fee_bps);
+    msg!("instance {} ", instance);
+    account::check_account_count(accounts, INITIALIZE_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_account_info = next_account_info(account_info_iter)?;
+
+    if !admin_account_info.is_signer {
+        msg!("Error: Admin signature required");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let authority = check_program_account_instance(program_id, program_account_info, instance)?;
+    SwapConfig::check_fee_bps(fee_bps, 0)?;
+
+    if program_account_info.owner == program_id {
+        if program_account_info.data_len() >= SwapConfig::LEN
+            && SwapConfig::unpack(&program_account_info.try_borrow_data()?)?.version != 0
+        {
+            msg!("Error: Config is already initialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+    } else {
+        create_or_allocate_account_raw(
+            *program_id,
+            program_account_info,
+            rent_info,
+            system_account_info,
+            admin_account_info,
+            SwapConfig::LEN,
+            &authority.signer_seeds(),
+        )?;
+    }
+
+    SwapConfig {
+        admin: *admin_account_info.key,
+        fee_bps,
+        instance,
+        ..SwapConfig::default()
+    }
+    .pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Funds, allocates and assigns new_account_info to program_id. Sizes above
+/// the runtime's account data limit are rejected before any transfer with
+/// InvalidRealloc, the error the runtime gives data growing past it. A payer
+/// short of the rent, or a transfer leaving the account below the rent-exempt
+/// minimum, fails with InsufficientFundsForRent before the allocation.
 #[inline(always)]
 pub fn create_or_allocate_account_raw<'a>(
     program_id: Pubkey,
@@ -74,16 +174,31 @@ pub fn create_or_allocate_account_raw<'a>(
     size: usize,
     signer_seeds: &[&[u8]],
 ) -> ProgramResult {
+    if size as u64 > system_instruction::MAX_PERMITTED_DATA_LENGTH {
+        msg!(
+            "Error: Account size {} is above the {} byte limit",
+            size,
+            system_instruction::MAX_PERMITTED_DATA_LENGTH
+        );
+        return Err(ProgramError::InvalidRealloc);
+    }
+    let space = size.try_into().map_err(|_| ProgramError::InvalidArgument)?;
     let rent = &Rent::from_account_info(rent_sysvar_info)?;
-    let required_lamports = rent
-        .minimum_balance(size)
-        .max(1)
-        .saturating_sub(new_account_info.lamports());
+    let minimum_balance = rent.minimum_balance(size).max(1);
+    let required_lamports = minimum_balance.saturating_sub(new_account_info.lamports());
 
+    if required_lamports > payer_info.lamports() {
+        msg!(
+            "Error: InsufficientFundsForRent. The payer holds {} lamports, the account needs {} more",
+            payer_info.lamports(),
+            required_lamports
+        );
+        return Err(AutoswapError::InsufficientFundsForRent.into());
+    }
     if required_lamports > 0 {
-        msg!("Transfer {} lamports to the new account", required_lamports*3);
+        msg!("Transfer {} lamports to the new account", required_lamports);
         invoke(
-            &system_instruction::transfer(&payer_info.key, new_account_info.key, required_lamports*3),
+            &system_instruction::transfer(payer_info.key, new_account_info.key, required_lamports),
             &[
                 payer_info.clone(),
                 new_account_info.clone(),
@@ -91,69 +206,264 @@ pub fn create_or_allocate_account_raw<'a>(
             ],
         )?;
     }
+    if new_account_info.lamports() < minimum_balance {
+        msg!(
+            "Error: InsufficientFundsForRent. The account holds {} lamports, rent exemption needs {}",
+            new_account_info.lamports(),
+            minimum_balance
+        );
+        return Err(AutoswapError::InsufficientFundsForRent.into());
+    }
 
     let accounts = &[new_account_info.clone(), system_program_info.clone()];
 
     msg!("Allocate space for the account");
     invoke_signed(
-        &system_instruction::allocate(new_account_info.key, size.try_into().unwrap()),
+        &system_instruction::allocate(new_account_info.key, space),
         accounts,
-        &[&signer_seeds],
+        &[signer_seeds],
     )?;
 
     msg!("Assign the account to the owning program");
     invoke_signed(
         &system_instruction::assign(new_account_info.key, &program_id),
         accounts,
-        &[&signer_seeds],
+        &[signer_seeds],
     )?;
 
     Ok(())
 }
 
+/// First step of the before_transfer, swap, after_transfer sequence, deposits
+/// amount into the program sol account. Passing the program account starts the
+/// sequence in the stored config, after_transfer is rejected without it.
+/// The deposit must credit the program sol account with amount, or at most
+/// tolerance less for mints with a transfer fee. Those need the optional mint
+/// so the deposit is a transfer_checked.
+///
+/// Accounts: user, token program, user sol, program sol, user transfer authority,
+/// optional program account, optional mint
 pub fn before_transfer(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
+    tolerance: u64,
 ) -> ProgramResult {
     msg!("Processing AmmInstruction::BeforeTransfer");
     msg!("amount {} ", amount);
+    msg!("tolerance {} ", tolerance);
+    if amount == 0 {
+        msg!("Error: InvalidAmount. Deposit amount must be positive");
+        return Err(AutoswapError::InvalidAmount.into());
+    }
 
-    let account_info_iter = &mut accounts.iter();
-    let user_account_info = next_account_info(account_info_iter)?;
-    let token_program_id_info = next_account_info(account_info_iter)?;
-    let user_sol_account_info = next_account_info(account_info_iter)?;
-    let program_sol_account_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    account::check_account_count(accounts, BEFORE_TRANSFER_ACCOUNTS)?;
+    let token_program_id_info = &accounts[layout::BEFORE_TRANSFER_TOKEN_PROGRAM];
+    let user_sol_account_info = &accounts[layout::BEFORE_TRANSFER_USER_SOL];
+    let program_sol_account_info = &accounts[layout::BEFORE_TRANSFER_PROGRAM_SOL];
+    let user_transfer_authority_info = &accounts[layout::BEFORE_TRANSFER_USER_TRANSFER_AUTHORITY];
+    // optional, only needed to read the skip_funded_deposit setting
+    let program_account_info = accounts.get(layout::BEFORE_TRANSFER_PROGRAM_ACCOUNT);
+    let mint_info = accounts.get(layout::BEFORE_TRANSFER_MINT);
 
-    spl_token_transfer(
-        TokenTransferParams{
-            source: user_sol_account_info.clone(),
-            destination: program_sol_account_info.clone(),
-            authority: user_transfer_authority_info.clone(),
-            token_program: token_program_id_info.clone(),
-            authority_signer_seeds: &[],
-            amount: amount
+    let config = if let Some(program_account_info) = program_account_info {
+        check_program_account(program_id, program_account_info)?;
+        SwapConfig::load(program_account_info, program_id)?
+    } else {
+        SwapConfig::default()
+    };
+    config.check_not_paused()?;
+    if let Some(program_account_info) = program_account_info {
+        advance_swap_phase(program_id, program_account_info, SwapPhase::Deposited)?;
+    }
+    let initial_balance = account::get_token_balance(program_sol_account_info)?;
+    if !is_deposit_needed(&config, initial_balance, amount) {
+        msg!("Program account already holds the amount, deposit skipped");
+        if let Some(program_account_info) = program_account_info {
+            record_deposit(program_id, program_account_info, 0)?;
         }
-    )?;
+        return Ok(());
+    }
+    let user_balance = account::get_token_balance(user_sol_account_info)?;
+    if amount > user_balance {
+        msg!("Error: TokenInsufficientFunds. Deposit of {} above the balance {}", amount, user_balance);
+        return Err(AutoswapError::TokenInsufficientFunds.into());
+    }
+
+    if let Some(mint_info) = mint_info {
+        spl_token_transfer_checked(
+            TokenTransferCheckedParams{
+                source: user_sol_account_info.clone(),
+                mint: mint_info.clone(),
+                destination: program_sol_account_info.clone(),
+                authority: user_transfer_authority_info.clone(),
+                token_program: token_program_id_info.clone(),
+                authority_signer_seeds: &[],
+                amount,
+                decimals: account::get_token_decimals(mint_info)?,
+            }
+        )?;
+    } else {
+        spl_token_transfer(
+            TokenTransferParams{
+                source: user_sol_account_info.clone(),
+                destination: program_sol_account_info.clone(),
+                authority: user_transfer_authority_info.clone(),
+                token_program: token_program_id_info.clone(),
+                authority_signer_seeds: &[],
+                amount,
+            }
+        )?;
+    }
+
+    let received = account::detect_received_amount(program_sol_account_info, initial_balance, amount)?;
+    check_transfer_amount(amount, received, tolerance)?;
+    if let Some(program_account_info) = program_account_info {
+        record_deposit(program_id, program_account_info, received)?;
+    }
+    Ok(())
+}
 
+/// Checks a deposit of amount credited received, at most tolerance less.
+pub fn check_transfer_amount(amount: u64, received: u64, tolerance: u64) -> ProgramResult {
+    if received > amount || received < amount.saturating_sub(tolerance) {
+        msg!(
+            "Error: TransferAmountMismatch. Sent {}, received {}, tolerance {}",
+            amount,
+            received,
+            tolerance
+        );
+        return Err(AutoswapError::TransferAmountMismatch.into());
+    }
     Ok(())
 }
 
+/// Returns false when skip_funded_deposit is set and the program account
+/// already holds at least the amount.
+pub fn is_deposit_needed(config: &SwapConfig, program_balance: u64, amount: u64) -> bool {
+    !config.skip_funded_deposit || program_balance < amount
+}
+
+/// Swaps through a Raydium pool. When the pool consumes less than the input,
+/// the swap still succeeds if the output keeps the minimum rate, and the
+/// unconsumed input goes to the optional trailing refund account.
 pub fn swap(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    params: SwapParams,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::Swap");
+    swap_within_slippage(accounts, program_id, params, None)
+}
+
+/// Swaps for at least the pool quote less slippage_bps, same accounts as swap.
+pub fn swap_with_slippage_bps(
     accounts: &[AccountInfo],
     program_id: &Pubkey,
     token_a_amount_in: u64,
     token_b_amount_in: u64,
+    slippage_bps: u16,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapWithSlippageBps");
+    msg!("slippage_bps {} ", slippage_bps);
+    if slippage_bps > 10_000 {
+        msg!("Error: Slippage {} bps is above 100%", slippage_bps);
+        return Err(ProgramError::InvalidArgument);
+    }
+    swap_within_slippage(
+        accounts,
+        program_id,
+        SwapParams {
+            token_a_amount_in,
+            token_b_amount_in,
+            ..SwapParams::default()
+        },
+        Some(slippage_bps),
+    )
+}
+
+/// Checks swapping amount_in for amount_out moves the pool price by at most
+/// max_price_impact_bps, zero disables the check.
+pub fn check_price_impact(
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    max_price_impact_bps: u16,
+) -> ProgramResult {
+    if max_price_impact_bps == 0 {
+        return Ok(());
+    }
+    let impact_bps = quote::get_price_impact_bps(amount_in, amount_out, reserve_in, reserve_out)?;
+    if impact_bps > max_price_impact_bps as u64 {
+        msg!(
+            "Error: PriceImpactTooHigh. Swap moves the price {} bps, the cap is {} bps",
+            impact_bps,
+            max_price_impact_bps
+        );
+        return Err(AutoswapError::PriceImpactTooHigh.into());
+    }
+    Ok(())
+}
+
+/// Scales the minimum output of a swap down to the input a partial fill
+/// consumed, rounded up so the minimum rate holds. A swap consuming nothing
+/// isn't a fill and keeps the full minimum.
+pub fn get_partial_fill_min_out(min_amount_out: u64, amount_spent: u64, amount_in: u64) -> Result<u64, ProgramError> {
+    if amount_spent == 0 || amount_spent >= amount_in {
+        return Ok(min_amount_out);
+    }
+    let scaled = (min_amount_out as u128 * amount_spent as u128).div_ceil(amount_in as u128);
+    math::checked_as_u64(scaled)
+}
+
+/// Returns the least output a swap accepts: the pool quote less slippage_bps,
+/// or min_token_amount_out when it is higher.
+pub fn get_min_amount_out(
+    quote: u64,
     min_token_amount_out: u64,
+    slippage_bps: u16,
+) -> Result<u64, ProgramError> {
+    let tolerated_bps = math::checked_sub(10_000, slippage_bps as u128)?;
+    Ok(math::checked_bps_of(quote, tolerated_bps)?.max(min_token_amount_out))
+}
+
+/// Checks the swap has a positive output floor, from the pool quote or the
+/// client. A zero quote without a client minimum would accept any output.
+pub fn check_output_floor(quote: u64, min_token_amount_out: u64) -> ProgramResult {
+    if quote == 0 && min_token_amount_out == 0 {
+        msg!("Error: ZeroOutputQuote. The pool quotes no output and min_token_amount_out is zero");
+        return Err(AutoswapError::ZeroOutputQuote.into());
+    }
+    Ok(())
+}
+
+/// Swaps for at least the pool quote less slippage_bps. Without it the config
+/// default_slippage_bps applies when min_token_amount_out is zero.
+fn swap_within_slippage(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    params: SwapParams,
+    slippage_bps: Option<u16>,
 ) -> ProgramResult {
-    msg!("Processing AmmInstruction::Swap");
+    let SwapParams {
+        token_a_amount_in,
+        token_b_amount_in,
+        min_token_amount_out,
+        ..
+    } = params;
+    compute::log_phase("swap start");
     msg!("token_a_amount_in {} ", token_a_amount_in);
     msg!("token_b_amount_in {} ", token_b_amount_in);
     msg!("min_token_amount_out {} ", min_token_amount_out);
+    check_swap_direction(token_a_amount_in, token_b_amount_in)?;
 
-    #[allow(clippy::deprecated_cfg_attr)]
-    #[cfg_attr(rustfmt, rustfmt_skip)]
-    if let [
+    let (accounts, refund_account) = if accounts.len() == SWAP_ACCOUNTS + 1 {
+        (&accounts[..SWAP_ACCOUNTS], accounts.get(layout::SWAP_REFUND))
+    } else {
+        (accounts, None)
+    };
+    let RaydiumPoolAccounts {
         program_account,
         program_token_a_account,
         program_token_b_account,
@@ -162,199 +472,5300 @@ pub fn swap(
         pool_pc_token_account,
         spl_token_id,
         amm_id,
-        amm_authority,
         amm_open_orders,
         amm_target,
-        serum_market,
-        serum_program_id,
-        serum_bids,
-        serum_asks,
         serum_event_queue,
-        serum_coin_vault_account,
-        serum_pc_vault_account,
-        serum_vault_signer
-        ] = accounts
-    {
-        if !raydium::check_pool_program_id(pool_program_id.key) {
-            return Err(ProgramError::IncorrectProgramId);
+        ..
+    } = RaydiumPoolAccounts::from_accounts(accounts)?;
+
+    match ProgramIDType::from_program_id(pool_program_id.key) {
+        Some(ProgramIDType::Raydium) => {}
+        Some(ProgramIDType::Orca) => {
+            msg!("Error: InvalidPoolProgram. {} is an Orca program, use SwapOrca", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+        Some(ProgramIDType::Serum) => {
+            msg!("Error: InvalidPoolProgram. {} is a Serum program, use SwapSerumDirect", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+        Some(ProgramIDType::Saber) => {
+            msg!("Error: InvalidPoolProgram. {} is a Saber program, use SwapSaber", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
         }
+        _ => {
+            msg!("Error: InvalidPoolProgram. {} is not a Raydium program", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+    }
 
-        let seed = &[
-            PREFIX.as_bytes(),
-        ];
-        
-        let (_program_account_address, bump_seed) = Pubkey::find_program_address(seed, &program_id);
-        let program_authority_seed = &[
-            PREFIX.as_bytes(),
-            &[bump_seed],
-        ];
+    // Raydium writes to these, fail early instead of inside the CPI
+    account::check_writable(amm_id)?;
+    account::check_writable(amm_open_orders)?;
+    account::check_writable(amm_target)?;
+
+    if params.deadline != 0 {
+        check_deadline(params.deadline, Clock::get()?.unix_timestamp)?;
+    }
+
+    let config = SwapConfig::load(program_account, program_id)?;
+    config.check_not_paused()?;
+    config.check_protocol_enabled(ProgramIDType::Raydium)?;
+    if config.has_maintenance_window() {
+        config.check_maintenance_window(Clock::get()?.slot)?;
+    }
+    if config.max_event_queue_fill_bps > 0 {
+        let (count, capacity) = serum::get_event_queue_fill(serum_event_queue)?;
+        config.check_event_queue_fill(count, capacity)?;
+    }
+
+    let vaults_swapped =
+        raydium::check_vaults_swapped(amm_id, pool_coin_token_account, pool_pc_token_account)?;
+    let (pool_coin_token_account, pool_pc_token_account) = if vaults_swapped {
+        if !config.auto_correct_vaults {
+            msg!("Error: VaultsSwapped. Pool coin and pc vaults are passed in reverse order");
+            return Err(AutoswapError::VaultsSwapped.into());
+        }
+        msg!("Pool coin and pc vaults are passed in reverse order, reordered");
+        (pool_pc_token_account, pool_coin_token_account)
+    } else {
+        (pool_coin_token_account, pool_pc_token_account)
+    };
+    check_distinct_mints(program_token_a_account, program_token_b_account)?;
+    check_token_mints(
+        program_token_a_account,
+        program_token_b_account,
+        pool_coin_token_account,
+        pool_pc_token_account,
+    )?;
+
+    let authority = ProgramAuthority::find(program_id, config.instance);
+    let program_authority_seed = &authority.signer_seeds()[..];
 
-        let (amount_in, mut min_amount_out) = raydium::get_pool_swap_amounts(
+    // a fee-on-transfer deposit leaves less than the nominal amount to swap
+    let token_a_amount_in = config.net_amount_in(token_a_amount_in);
+    let token_b_amount_in = config.net_amount_in(token_b_amount_in);
+    let (amount_in, quote) = raydium::get_pool_swap_amounts(
+        pool_coin_token_account,
+        pool_pc_token_account,
+        amm_open_orders,
+        amm_id,
+        token_a_amount_in,
+        token_b_amount_in,
+    )?;
+    config.check_min_swap(amount_in)?;
+    check_output_floor(quote, min_token_amount_out)?;
+    let slippage_bps = match slippage_bps {
+        Some(slippage_bps) => slippage_bps,
+        None if min_token_amount_out == 0 => config.default_slippage_bps,
+        None => 0,
+    };
+    let min_amount_out = get_min_amount_out(quote, min_token_amount_out, slippage_bps)?;
+    if config.max_pool_consume_bps > 0 || params.max_price_impact_bps > 0 {
+        let (coin_balance, pc_balance) = raydium::get_pool_token_balances(
             pool_coin_token_account,
             pool_pc_token_account,
             amm_open_orders,
             amm_id,
-            token_a_amount_in,
-            token_b_amount_in,
         )?;
-        if min_token_amount_out > min_amount_out {
-            min_amount_out = min_token_amount_out;
-        }
-
-        let initial_balance_in = if token_a_amount_in == 0 {
-            account::get_token_balance(program_token_b_account)?
+        let (reserve_in, reserve_out) = if token_a_amount_in == 0 {
+            (pc_balance, coin_balance)
         } else {
-            account::get_token_balance(program_token_a_account)?
+            (coin_balance, pc_balance)
         };
-        let initial_balance_out = if token_a_amount_in == 0 {
-            account::get_token_balance(program_token_a_account)?
+        config.check_pool_consume(amount_in, reserve_in)?;
+        check_price_impact(amount_in, quote, reserve_in, reserve_out, params.max_price_impact_bps)?;
+    }
+
+    compute::log_phase("swap checks done");
+    let initial_balance_in = if token_a_amount_in == 0 {
+        account::get_token_balance(program_token_b_account)?
+    } else {
+        account::get_token_balance(program_token_a_account)?
+    };
+    let initial_balance_out = if token_a_amount_in == 0 {
+        account::get_token_balance(program_token_a_account)?
+    } else {
+        account::get_token_balance(program_token_b_account)?
+    };
+
+    let direction = if token_a_amount_in == 0 {
+        raydium::SwapDirection::PcToCoin
+    } else {
+        raydium::SwapDirection::CoinToPc
+    };
+    let instruction = if vaults_swapped {
+        let mut ordered_accounts = accounts.to_vec();
+        ordered_accounts.swap(layout::SWAP_POOL_COIN_TOKEN_ACCOUNT, layout::SWAP_POOL_PC_TOKEN_ACCOUNT);
+        raydium::build_swap_instruction(&ordered_accounts, amount_in, min_amount_out, direction)?
+    } else {
+        raydium::build_swap_instruction(accounts, amount_in, min_amount_out, direction)?
+    };
+    compute::log_phase("before raydium cpi");
+    invoke_signed(&instruction, accounts, &[program_authority_seed])?;
+    compute::log_phase("after raydium cpi");
+
+    let (program_token_in_account, program_token_out_account) = if token_a_amount_in == 0 {
+        (program_token_b_account, program_token_a_account)
+    } else {
+        (program_token_a_account, program_token_b_account)
+    };
+    let amount_spent = account::check_tokens_spent(program_token_in_account, initial_balance_in, amount_in)?;
+    let amount_out = account::check_tokens_received(
+        program_token_out_account,
+        initial_balance_out,
+        get_partial_fill_min_out(min_amount_out, amount_spent, amount_in)?,
+    )
+    .map_err(|err| {
+        if err == AutoswapError::SlippageExceeded.into() {
+            let received = account::get_balance_increase(program_token_out_account, initial_balance_out)
+                .unwrap_or(0);
+            set_slippage_hint(quote, received);
+        }
+        err
+    })?;
+    config.check_deliverable_out(amount_out)?;
+    if params.min_rate_den != 0 {
+        // without the amm state the rate is taken in base units
+        let (coin_decimals, pc_decimals) = raydium::get_pool_decimals(amm_id)?.unwrap_or_else(|| {
+            msg!("Unknown amm layout, min rate in base units");
+            (0, 0)
+        });
+        let (decimals_in, decimals_out) = if token_a_amount_in == 0 {
+            (pc_decimals, coin_decimals)
         } else {
-            account::get_token_balance(program_token_b_account)?
+            (coin_decimals, pc_decimals)
         };
+        check_min_rate_with_decimals(&params, amount_spent, amount_out, decimals_in, decimals_out)?;
+    }
+    compute::log_phase("balance checks done");
+    echo_client_order_id(&params.client_order_id);
+    if amount_spent < amount_in {
+        let unconsumed = amount_in - amount_spent;
+        msg!("Partial fill, the pool consumed {} of {}", amount_spent, amount_in);
+        if let Some(refund_account) = refund_account {
+            msg!("Refund {} unconsumed tokens", unconsumed);
+            spl_token_transfer(TokenTransferParams {
+                source: program_token_in_account.clone(),
+                destination: refund_account.clone(),
+                authority: program_account.clone(),
+                token_program: spl_token_id.clone(),
+                authority_signer_seeds: program_authority_seed,
+                amount: unconsumed,
+            })?;
+        } else {
+            msg!("No refund account, {} unconsumed tokens stay in custody", unconsumed);
+        }
+        set_partial_fill_result(amount_out, amount_spent, &params.client_order_id);
+    } else {
+        set_swap_result(amount_out, &params.client_order_id);
+    }
+    advance_swap_phase(program_id, program_account, SwapPhase::Swapped)?;
+    SwapEvent {
+        amount_in: amount_spent,
+        amount_out,
+        protocol: ProgramIDType::Raydium.route_protocol().unwrap_or(NO_PROTOCOL),
+        fee_paid: 0,
+    }
+    .log();
+
+    msg!("AmmInstruction::Swap complete");
+    Ok(())
+}
+
+/// Swaps through an Orca pool with the same balance checks as swap.
+///
+/// Accounts: program account, program token a, program token b, pool program,
+/// swap state, swap authority, pool token a vault, pool token b vault, pool mint,
+/// pool fee account, spl token
+pub fn swap_orca(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapOrca");
+    msg!("token_a_amount_in {} ", token_a_amount_in);
+    msg!("token_b_amount_in {} ", token_b_amount_in);
+    msg!("min_token_amount_out {} ", min_token_amount_out);
+    check_swap_direction(token_a_amount_in, token_b_amount_in)?;
+    account::check_account_count(accounts, SWAP_ORCA_ACCOUNTS)?;
+
+    #[allow(clippy::deprecated_cfg_attr)]
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    if let [
+        program_account,
+        program_token_a_account,
+        program_token_b_account,
+        pool_program_id,
+        swap_state,
+        _swap_authority,
+        pool_token_a_account,
+        pool_token_b_account,
+        _pool_mint,
+        _pool_fee_account,
+        _spl_token_id
+        ] = accounts
+    {
+        if !orca::check_pool_program_id(pool_program_id.key) {
+            msg!("Error: InvalidPoolProgram. {} is not an Orca program", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+        orca::check_pool_vaults(swap_state, pool_token_a_account, pool_token_b_account)?;
 
-        let mut raydium_accounts = Vec::with_capacity(18);
-        raydium_accounts.push(AccountMeta::new_readonly(*spl_token_id.key, false));
-        raydium_accounts.push(AccountMeta::new(*amm_id.key, false));
-        raydium_accounts.push(AccountMeta::new_readonly(*amm_authority.key, false));
-        raydium_accounts.push(AccountMeta::new(*amm_open_orders.key, false));
-        raydium_accounts.push(AccountMeta::new(*amm_target.key, false));
-        raydium_accounts.push(AccountMeta::new(*pool_coin_token_account.key, false));
-        raydium_accounts.push(AccountMeta::new(*pool_pc_token_account.key, false));
-        raydium_accounts.push(AccountMeta::new_readonly(*serum_program_id.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_market.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_bids.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_asks.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_event_queue.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_coin_vault_account.key, false));
-        raydium_accounts.push(AccountMeta::new(*serum_pc_vault_account.key, false));
-        raydium_accounts.push(AccountMeta::new_readonly(*serum_vault_signer.key, false));
-        if token_a_amount_in == 0 {
-            raydium_accounts.push(AccountMeta::new(*program_token_b_account.key, false));
-            raydium_accounts.push(AccountMeta::new(*program_token_a_account.key, false));
+        let config = SwapConfig::load(program_account, program_id)?;
+        config.check_not_paused()?;
+        config.check_protocol_enabled(ProgramIDType::Orca)?;
+        if config.has_maintenance_window() {
+            config.check_maintenance_window(Clock::get()?.slot)?;
+        }
+        check_distinct_mints(program_token_a_account, program_token_b_account)?;
+
+        let authority = ProgramAuthority::find(program_id, config.instance);
+        let program_authority_seed = &authority.signer_seeds()[..];
+
+        let (amount_in, quote) = orca::get_pool_swap_amounts(
+            pool_token_a_account,
+            pool_token_b_account,
+            swap_state,
+            token_a_amount_in,
+            token_b_amount_in,
+        )?;
+        config.check_min_swap(amount_in)?;
+        check_output_floor(quote, min_token_amount_out)?;
+        let min_amount_out = quote.max(min_token_amount_out);
+        let a_to_b = token_a_amount_in > 0;
+        let (program_token_in_account, program_token_out_account) = if a_to_b {
+            (program_token_a_account, program_token_b_account)
         } else {
-            raydium_accounts.push(AccountMeta::new(*program_token_a_account.key, false));
-            raydium_accounts.push(AccountMeta::new(*program_token_b_account.key, false));
-        }
-        raydium_accounts.push(AccountMeta::new_readonly(*program_account.key, true));
-
-        let instruction = Instruction {
-            program_id: *pool_program_id.key,
-            accounts: raydium_accounts,
-            data: RaydiumSwap {
-                instruction: 9,
-                amount_in,
-                min_amount_out,
-            }
-            .to_vec()?,
+            (program_token_b_account, program_token_a_account)
         };
+        if config.max_pool_consume_bps > 0 {
+            let reserve_in = account::get_token_balance(if a_to_b {
+                pool_token_a_account
+            } else {
+                pool_token_b_account
+            })?;
+            config.check_pool_consume(amount_in, reserve_in)?;
+        }
+
+        let initial_balance_in = account::get_token_balance(program_token_in_account)?;
+        let initial_balance_out = account::get_token_balance(program_token_out_account)?;
+
+        let instruction = orca::build_swap_instruction(accounts, amount_in, min_amount_out, a_to_b)?;
         invoke_signed(&instruction, accounts, &[program_authority_seed])?;
 
-        account::check_tokens_spent(
-            if token_a_amount_in == 0 {
-                program_token_b_account
-            } else {
-                program_token_a_account
-            },
-            initial_balance_in,
-            amount_in,
-        )?;
-        account::check_tokens_received(
-            if token_a_amount_in == 0 {
-                program_token_a_account
-            } else {
-                program_token_b_account
-            },
+        account::check_tokens_spent(program_token_in_account, initial_balance_in, amount_in)?;
+        let amount_out = account::check_tokens_received(
+            program_token_out_account,
             initial_balance_out,
             min_amount_out,
         )?;
+        config.check_deliverable_out(amount_out)?;
+        set_swap_result(amount_out, &[0; 16]);
+        advance_swap_phase(program_id, program_account, SwapPhase::Swapped)?;
     } else {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
 
-    msg!("AmmInstruction::Swap complete");
+    msg!("AmmInstruction::SwapOrca complete");
     Ok(())
 }
 
-pub fn after_transfer(
-    program_id: &Pubkey,
+/// Swaps through a Saber stable swap pool, the pool quote is the minimum output
+/// unless min_token_amount_out is higher.
+///
+/// Accounts: program account, program token a, program token b, pool program,
+/// swap info, swap authority, pool token a reserves, pool token b reserves,
+/// token a admin fees, token b admin fees, spl token
+pub fn swap_saber(
     accounts: &[AccountInfo],
-    amount: u64,
+    program_id: &Pubkey,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
 ) -> ProgramResult {
-    msg!("Processing AmmInstruction::AfterTransfer");
-    let account_info_iter = &mut accounts.iter();
-    let token_program_id_info = next_account_info(account_info_iter)?;
-    let program_account_info = next_account_info(account_info_iter)?;
-    let program_kin_account_info = next_account_info(account_info_iter)?;
-    let program_sol_account_info = next_account_info(account_info_iter)?;
-    let destination_account_info = next_account_info(account_info_iter)?;
-    let fee_recipient_info = next_account_info(account_info_iter)?;
-    let seed = &[
-        PREFIX.as_bytes(),
-    ];
-    
-    let (program_account, bump_seed) = Pubkey::find_program_address(seed, &program_id);
-    let transfer_authority_seed = &[
-        PREFIX.as_bytes(),
-        &[bump_seed],
-    ];
+    msg!("Processing AmmInstruction::SwapSaber");
+    msg!("token_a_amount_in {} ", token_a_amount_in);
+    msg!("token_b_amount_in {} ", token_b_amount_in);
+    msg!("min_token_amount_out {} ", min_token_amount_out);
+    check_swap_direction(token_a_amount_in, token_b_amount_in)?;
+    account::check_account_count(accounts, SWAP_SABER_ACCOUNTS)?;
 
-    let token_amount = account::get_token_balance(program_kin_account_info)?;
-    spl_token_transfer(
-        TokenTransferParams{
-            source: program_kin_account_info.clone(),
-            destination: destination_account_info.clone(),
-            authority: program_account_info.clone(),
-            token_program: token_program_id_info.clone(),
-            authority_signer_seeds: transfer_authority_seed,
-            amount: token_amount,
+    #[allow(clippy::deprecated_cfg_attr)]
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    if let [
+        program_account,
+        program_token_a_account,
+        program_token_b_account,
+        pool_program_id,
+        swap_info,
+        _swap_authority,
+        pool_token_a_account,
+        pool_token_b_account,
+        admin_fee_a_account,
+        admin_fee_b_account,
+        _spl_token_id
+        ] = accounts
+    {
+        if !saber::check_pool_program_id(pool_program_id.key) {
+            msg!("Error: InvalidPoolProgram. {} is not a Saber program", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
         }
-    )?;
+        saber::check_pool_accounts(
+            swap_info,
+            pool_token_a_account,
+            pool_token_b_account,
+            admin_fee_a_account,
+            admin_fee_b_account,
+        )?;
 
-    spl_token_transfer(
-        TokenTransferParams{
-            source: program_sol_account_info.clone(),
-            destination: fee_recipient_info.clone(),
-            authority: program_account_info.clone(),
-            token_program: token_program_id_info.clone(),
-            authority_signer_seeds: transfer_authority_seed,
-            amount: (amount as f64 * 0.005) as u64,
+        let config = SwapConfig::load(program_account, program_id)?;
+        config.check_not_paused()?;
+        config.check_protocol_enabled(ProgramIDType::Saber)?;
+        if config.has_maintenance_window() {
+            config.check_maintenance_window(Clock::get()?.slot)?;
         }
-    )?;
+        check_distinct_mints(program_token_a_account, program_token_b_account)?;
+
+        let authority = ProgramAuthority::find(program_id, config.instance);
+        let program_authority_seed = &authority.signer_seeds()[..];
+
+        let (amount_in, quote) = saber::get_pool_swap_amounts(
+            pool_token_a_account,
+            pool_token_b_account,
+            swap_info,
+            token_a_amount_in,
+            token_b_amount_in,
+        )?;
+        config.check_min_swap(amount_in)?;
+        check_output_floor(quote, min_token_amount_out)?;
+        let min_amount_out = quote.max(min_token_amount_out);
+        let a_to_b = token_a_amount_in > 0;
+        let (program_token_in_account, program_token_out_account) = if a_to_b {
+            (program_token_a_account, program_token_b_account)
+        } else {
+            (program_token_b_account, program_token_a_account)
+        };
+        if config.max_pool_consume_bps > 0 {
+            let reserve_in = account::get_token_balance(if a_to_b {
+                pool_token_a_account
+            } else {
+                pool_token_b_account
+            })?;
+            config.check_pool_consume(amount_in, reserve_in)?;
+        }
+
+        let initial_balance_in = account::get_token_balance(program_token_in_account)?;
+        let initial_balance_out = account::get_token_balance(program_token_out_account)?;
+
+        let instruction = saber::build_swap_instruction(accounts, amount_in, min_amount_out, a_to_b)?;
+        invoke_signed(&instruction, accounts, &[program_authority_seed])?;
 
+        account::check_tokens_spent(program_token_in_account, initial_balance_in, amount_in)?;
+        let amount_out = account::check_tokens_received(
+            program_token_out_account,
+            initial_balance_out,
+            min_amount_out,
+        )?;
+        config.check_deliverable_out(amount_out)?;
+        set_swap_result(amount_out, &[0; 16]);
+        advance_swap_phase(program_id, program_account, SwapPhase::Swapped)?;
+    } else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    msg!("AmmInstruction::SwapSaber complete");
     Ok(())
 }
 
-pub fn harvest(
+/// Sells amount_in of token a through the protocol named by the protocol byte,
+/// see ProgramIDType::from_route_protocol. The accounts are those of the protocol
+/// instruction: swap for Raydium, swap_orca, swap_saber and swap_serum_direct,
+/// all of which take the pool program id fourth.
+pub fn route(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    protocol: u8,
+    amount_in: u64,
+    min_out: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::Route");
+    msg!("protocol {} ", protocol);
+    account::check_account_count(accounts, ROUTE_ACCOUNTS)?;
+
+    let program_type = ProgramIDType::from_route_protocol(protocol).ok_or_else(|| {
+        msg!("Error: Unknown route protocol {}", protocol);
+        ProgramError::InvalidInstructionData
+    })?;
+    let pool_program_id = accounts[layout::ROUTE_POOL_PROGRAM].key;
+    if ProgramIDType::from_program_id(pool_program_id) != Some(program_type) {
+        msg!("Error: InvalidPoolProgram. {} is not a {:?} program", pool_program_id, program_type);
+        return Err(AutoswapError::InvalidPoolProgram.into());
+    }
+
+    match program_type {
+        ProgramIDType::Raydium => swap_within_slippage(
+            accounts,
+            program_id,
+            SwapParams {
+                token_a_amount_in: amount_in,
+                min_token_amount_out: min_out,
+                ..SwapParams::default()
+            },
+            None,
+        ),
+        ProgramIDType::Orca => swap_orca(accounts, program_id, amount_in, 0, min_out),
+        ProgramIDType::Saber => swap_saber(accounts, program_id, amount_in, 0, min_out),
+        ProgramIDType::Serum => swap_serum_direct(accounts, program_id, amount_in, 0, min_out),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+/// Returns the accounts a Route through protocol takes, the stride of its leg in
+/// a batch swap.
+pub fn get_route_account_count(protocol: u8) -> Result<usize, ProgramError> {
+    match ProgramIDType::from_route_protocol(protocol) {
+        Some(ProgramIDType::Raydium) => Ok(SWAP_ACCOUNTS),
+        Some(ProgramIDType::Orca) => Ok(SWAP_ORCA_ACCOUNTS),
+        Some(ProgramIDType::Saber) => Ok(SWAP_SABER_ACCOUNTS),
+        Some(ProgramIDType::Serum) => Ok(SWAP_SERUM_DIRECT_ACCOUNTS),
+        _ => {
+            msg!("Error: Unknown route protocol {}", protocol);
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+}
+
+/// Routes count legs in order, a failing leg reverts the whole batch.
+/// routes holds count packed Route instructions, at most MAX_BATCH. Each leg
+/// takes the accounts of its Route, get_route_account_count of them, right after
+/// those of the leg before, and the legs take all the accounts.
+pub fn batch_swap(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    count: u8,
+    routes: &[u8],
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::BatchSwap");
+    msg!("count {} ", count);
+    if count == 0 {
+        msg!("Error: Empty batch");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if count as usize > MAX_BATCH {
+        msg!("Error: TooManySwaps. {} legs, at most {}", count, MAX_BATCH);
+        return Err(AutoswapError::TooManySwaps.into());
+    }
+    if routes.len() != count as usize * AmmInstruction::ROUTE_LEN {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut legs = Vec::with_capacity(count as usize);
+    let mut account_count = 0;
+    for (leg, data) in routes.chunks_exact(AmmInstruction::ROUTE_LEN).enumerate() {
+        if let AmmInstruction::Route { protocol, amount_in, min_out } = AmmInstruction::unpack(data)? {
+            account_count += get_route_account_count(protocol)?;
+            legs.push((protocol, amount_in, min_out));
+        } else {
+            msg!("Error: Leg {} is not a Route", leg);
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    }
+    account::check_account_count(accounts, account_count)?;
+    if accounts.len() > account_count {
+        msg!("Error: Too many accounts. {} accounts, the legs take {}", accounts.len(), account_count);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut offset = 0;
+    for (leg, (protocol, amount_in, min_out)) in legs.into_iter().enumerate() {
+        msg!("leg {} ", leg);
+        let end = offset + get_route_account_count(protocol)?;
+        route(&accounts[offset..end], program_id, protocol, amount_in, min_out)?;
+        offset = end;
+    }
+
+    msg!("AmmInstruction::BatchSwap complete");
+    Ok(())
+}
+
+/// Swaps against the Serum order book, for markets whose AMM pool is drained.
+/// Places an immediate or cancel order from the router open orders, then settles
+/// the fill and the unmatched input back to the program token accounts.
+/// The book isn't quoted, min_token_amount_out must be set.
+///
+/// Accounts: program account, program token a (coin), program token b (pc),
+/// serum program, market, open orders owned by the program account, request queue,
+/// event queue, bids, asks, coin vault, pc vault, vault signer, spl token, rent
+pub fn swap_serum_direct(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+    min_token_amount_out: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapSerumDirect");
+    msg!("token_a_amount_in {} ", token_a_amount_in);
+    msg!("token_b_amount_in {} ", token_b_amount_in);
+    msg!("min_token_amount_out {} ", min_token_amount_out);
+    check_swap_direction(token_a_amount_in, token_b_amount_in)?;
+    account::check_account_count(accounts, SWAP_SERUM_DIRECT_ACCOUNTS)?;
+
+    #[allow(clippy::deprecated_cfg_attr)]
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    if let [
+        program_account,
+        program_token_a_account,
+        program_token_b_account,
+        serum_program_id,
+        serum_market,
+        serum_open_orders,
+        serum_request_queue,
+        serum_event_queue,
+        serum_bids,
+        serum_asks,
+        serum_coin_vault_account,
+        serum_pc_vault_account,
+        serum_vault_signer,
+        spl_token_id,
+        rent
+        ] = accounts
+    {
+        if !serum::check_program_id(serum_program_id.key) {
+            msg!("Error: InvalidPoolProgram. {} is not a Serum program", serum_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+        if min_token_amount_out == 0 {
+            msg!("Error: min_token_amount_out is required, the order book isn't quoted");
+            return Err(ProgramError::InvalidArgument);
+        }
+        serum::check_market_vaults(serum_market, serum_coin_vault_account, serum_pc_vault_account)?;
+
+        let config = SwapConfig::load(program_account, program_id)?;
+        config.check_not_paused()?;
+        config.check_protocol_enabled(ProgramIDType::Serum)?;
+        if config.has_maintenance_window() {
+            config.check_maintenance_window(Clock::get()?.slot)?;
+        }
+        if config.max_event_queue_fill_bps > 0 {
+            let (count, capacity) = serum::get_event_queue_fill(serum_event_queue)?;
+            config.check_event_queue_fill(count, capacity)?;
+        }
+        check_distinct_mints(program_token_a_account, program_token_b_account)?;
+
+        let authority = ProgramAuthority::find(program_id, config.instance);
+        let program_authority_seed = &authority.signer_seeds()[..];
+
+        let (coin_lot_size, _pc_lot_size) = serum::get_lot_sizes(serum_market)?;
+        // selling coin is an ask in whole coin lots, buying it a bid capped by the pc spent
+        let (side, amount_in, max_coin_qty, max_native_pc_qty, program_token_in_account, program_token_out_account) =
+            if token_a_amount_in > 0 {
+                let max_coin_qty = token_a_amount_in / coin_lot_size;
+                if max_coin_qty == 0 {
+                    msg!("Error: {} is below one coin lot of {}", token_a_amount_in, coin_lot_size);
+                    return Err(ProgramError::InvalidArgument);
+                }
+                (serum::Side::Ask, token_a_amount_in, max_coin_qty, u64::MAX, program_token_a_account, program_token_b_account)
+            } else {
+                (serum::Side::Bid, token_b_amount_in, u64::MAX, token_b_amount_in, program_token_b_account, program_token_a_account)
+            };
+        config.check_min_swap(amount_in)?;
+
+        let initial_balance_in = account::get_token_balance(program_token_in_account)?;
+        let initial_balance_out = account::get_token_balance(program_token_out_account)?;
+
+        let new_order_accounts = [
+            serum_market.clone(),
+            serum_open_orders.clone(),
+            serum_request_queue.clone(),
+            serum_event_queue.clone(),
+            serum_bids.clone(),
+            serum_asks.clone(),
+            program_token_in_account.clone(),
+            program_account.clone(),
+            serum_coin_vault_account.clone(),
+            serum_pc_vault_account.clone(),
+            spl_token_id.clone(),
+            rent.clone(),
+        ];
+        let instruction = serum::build_new_order_instruction(
+            serum_program_id.key,
+            &new_order_accounts,
+            side,
+            max_coin_qty,
+            max_native_pc_qty,
+        )?;
+        invoke_signed(&instruction, &new_order_accounts, &[program_authority_seed])?;
+
+        let settle_funds_accounts = [
+            serum_market.clone(),
+            serum_open_orders.clone(),
+            program_account.clone(),
+            serum_coin_vault_account.clone(),
+            serum_pc_vault_account.clone(),
+            program_token_a_account.clone(),
+            program_token_b_account.clone(),
+            serum_vault_signer.clone(),
+            spl_token_id.clone(),
+        ];
+        let instruction = serum::build_settle_funds_instruction(serum_program_id.key, &settle_funds_accounts)?;
+        invoke_signed(&instruction, &settle_funds_accounts, &[program_authority_seed])?;
+
+        account::check_tokens_spent(program_token_in_account, initial_balance_in, amount_in)?;
+        let amount_out = account::check_tokens_received(
+            program_token_out_account,
+            initial_balance_out,
+            min_token_amount_out,
+        )?;
+        config.check_deliverable_out(amount_out)?;
+        set_swap_result(amount_out, &[0; 16]);
+        advance_swap_phase(program_id, program_account, SwapPhase::Swapped)?;
+    } else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    msg!("AmmInstruction::SwapSerumDirect complete");
+    Ok(())
+}
+
+/// Swaps for at least token_amount_out, spending at most max_token_amount_in.
+///
+/// The input is the smallest amount the pool reserves quote at token_amount_out,
+/// the swap itself then runs through swap with the same accounts and checks.
+pub fn swap_exact_out(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    token_amount_out: u64,
+    max_token_amount_in: u64,
+    direction: raydium::SwapDirection,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapExactOut");
+    msg!("token_amount_out {} ", token_amount_out);
+    msg!("max_token_amount_in {} ", max_token_amount_in);
+    if token_amount_out == 0 {
+        msg!("Error: Output amount must be non-zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+    account::check_account_count(accounts, SWAP_ACCOUNTS)?;
+    if accounts.len() > SWAP_ACCOUNTS {
+        msg!("Error: Too many accounts. {} accounts, a swap takes {}", accounts.len(), SWAP_ACCOUNTS);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (mut pool_coin_token_account, mut pool_pc_token_account) = (
+        &accounts[layout::SWAP_POOL_COIN_TOKEN_ACCOUNT],
+        &accounts[layout::SWAP_POOL_PC_TOKEN_ACCOUNT],
+    );
+    let amm_open_orders = &accounts[layout::SWAP_AMM_OPEN_ORDERS];
+    let amm_id = &accounts[layout::SWAP_AMM_ID];
+    // swap rejects or reorders them according to the config
+    if raydium::check_vaults_swapped(amm_id, pool_coin_token_account, pool_pc_token_account)? {
+        std::mem::swap(&mut pool_coin_token_account, &mut pool_pc_token_account);
+    }
+    let (coin_balance, pc_balance) = raydium::get_pool_token_balances(
+        pool_coin_token_account,
+        pool_pc_token_account,
+        amm_open_orders,
+        amm_id,
+    )?;
+    let (reserve_in, reserve_out) = match direction {
+        raydium::SwapDirection::CoinToPc => (coin_balance, pc_balance),
+        raydium::SwapDirection::PcToCoin => (pc_balance, coin_balance),
+    };
+    let amount_in = quote::get_amount_in(token_amount_out, reserve_in, reserve_out, raydium::RAYDIUM_FEE)?;
+    msg!("Required input {}", amount_in);
+    if amount_in > max_token_amount_in {
+        msg!(
+            "Error: ExcessiveInput. Output {} needs input {}, above the maximum {}",
+            token_amount_out,
+            amount_in,
+            max_token_amount_in
+        );
+        return Err(AutoswapError::ExcessiveInput.into());
+    }
+
+    let (token_a_amount_in, token_b_amount_in) = match direction {
+        raydium::SwapDirection::CoinToPc => (amount_in, 0),
+        raydium::SwapDirection::PcToCoin => (0, amount_in),
+    };
+    swap(
+        accounts,
+        program_id,
+        SwapParams {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out: token_amount_out,
+            ..SwapParams::default()
+        },
+    )
+}
+
+/// Number of accounts swap takes.
+pub const SWAP_ACCOUNTS: usize = RaydiumPoolAccounts::LEN;
+
+/// Swaps, then stakes the whole output with the staking backend.
+///
+/// Accounts: the SWAP_ACCOUNTS of swap, then the staking backend accounts with
+/// the router authority signing. Any failure reverts both steps. Each step is
+/// preceded by a compute budget checkpoint read from meter.
+pub fn swap_and_stake(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    params: SwapParams,
+    staking: &dyn Staking,
+    meter: &dyn ComputeMeter,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapAndStake");
+    if accounts.len() <= SWAP_ACCOUNTS {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (swap_accounts, stake_accounts) = accounts.split_at(SWAP_ACCOUNTS);
+    let program_token_out_account = if params.token_a_amount_in == 0 {
+        &swap_accounts[layout::SWAP_PROGRAM_TOKEN_A]
+    } else {
+        &swap_accounts[layout::SWAP_PROGRAM_TOKEN_B]
+    };
+
+    let initial_balance_out = account::get_token_balance(program_token_out_account)?;
+    compute::checkpoint(meter, "swap", compute::SWAP_PHASE_UNITS)?;
+    swap(swap_accounts, program_id, params)?;
+    let amount_out = account::get_balance_increase(program_token_out_account, initial_balance_out)?;
+
+    let authority = check_program_account(program_id, &swap_accounts[layout::SWAP_PROGRAM_ACCOUNT])?;
+    compute::checkpoint(meter, "stake", compute::STAKE_PHASE_UNITS)?;
+    msg!("Stake {} tokens", amount_out);
+    staking.stake_with_seeds(stake_accounts, &[&authority.signer_seeds()], amount_out)?;
+
+    msg!("AmmInstruction::SwapAndStake complete");
+    Ok(())
+}
+
+/// Checks the program token a and b accounts hold different mints, a swap of a
+/// mint into itself has nothing to do.
+pub fn check_distinct_mints(
+    program_token_a_account: &AccountInfo,
+    program_token_b_account: &AccountInfo,
+) -> ProgramResult {
+    let mint = account::get_token_account_mint(program_token_a_account)?;
+    if mint == account::get_token_account_mint(program_token_b_account)? {
+        msg!(
+            "Error: SameInputOutputMint. {} and {} both hold {}",
+            program_token_a_account.key,
+            program_token_b_account.key,
+            mint
+        );
+        return Err(AutoswapError::SameInputOutputMint.into());
+    }
+    Ok(())
+}
+
+/// Checks the program token a and b accounts hold the mints of the pool coin
+/// and pc vaults respectively.
+pub fn check_token_mints<'a>(
+    program_token_a_account: &AccountInfo<'a>,
+    program_token_b_account: &AccountInfo<'a>,
+    pool_coin_token_account: &AccountInfo<'a>,
+    pool_pc_token_account: &AccountInfo<'a>,
+) -> ProgramResult {
+    for (program_token_account, pool_token_account) in [
+        (program_token_a_account, pool_coin_token_account),
+        (program_token_b_account, pool_pc_token_account),
+    ] {
+        let program_mint = account::get_token_account_mint(program_token_account)?;
+        let pool_mint = account::get_token_account_mint(pool_token_account)?;
+        if program_mint != pool_mint {
+            msg!(
+                "Error: InvalidTokenMint. {} holds {}, the pool vault holds {}",
+                program_token_account.key,
+                program_mint,
+                pool_mint
+            );
+            return Err(AutoswapError::InvalidTokenMint.into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks exactly one of the token a and b amounts is non-zero, the side with
+/// the amount is the input of the swap.
+pub fn check_swap_direction(token_a_amount_in: u64, token_b_amount_in: u64) -> ProgramResult {
+    if (token_a_amount_in == 0) == (token_b_amount_in == 0) {
+        msg!(
+            "Error: AmbiguousSwapDirection. token_a_amount_in {} and token_b_amount_in {}, one and only one must be non-zero",
+            token_a_amount_in,
+            token_b_amount_in
+        );
+        return Err(AutoswapError::AmbiguousSwapDirection.into());
+    }
+    Ok(())
+}
+
+/// Checks the swap deadline, a unix timestamp, hasn't passed.
+pub fn check_deadline(deadline: i64, unix_timestamp: i64) -> ProgramResult {
+    if unix_timestamp > deadline {
+        msg!("Error: DeadlineExceeded. Deadline {}, now {}", deadline, unix_timestamp);
+        return Err(AutoswapError::DeadlineExceeded.into());
+    }
+    Ok(())
+}
+
+/// Checks the realized output per unit of input meets the swap's min rate,
+/// both amounts in base units.
+pub fn check_min_rate(params: &SwapParams, amount_spent: u64, amount_out: u64) -> ProgramResult {
+    check_min_rate_with_decimals(params, amount_spent, amount_out, 0, 0)
+}
+
+/// Checks the min rate as a price in whole tokens, output per input, against the
+/// base unit amounts of mints with decimals_in and decimals_out decimals.
+/// Same decimals on both sides compare the base units as they are.
+pub fn check_min_rate_with_decimals(
+    params: &SwapParams,
+    amount_spent: u64,
+    amount_out: u64,
+    decimals_in: u8,
+    decimals_out: u8,
+) -> ProgramResult {
+    if params.min_rate_den == 0 {
+        return Ok(());
+    }
+    // amount_out / 10^decimals_out >= min_rate * amount_spent / 10^decimals_in,
+    // the side scaled past u128 is the larger one
+    let received = (amount_out as u128) * (params.min_rate_den as u128);
+    let required = (params.min_rate_num as u128) * (amount_spent as u128);
+    let scale = |value: u128, decimals: u8| 10u128.checked_pow(decimals as u32).and_then(|v| value.checked_mul(v));
+    let rate_too_low = if decimals_out >= decimals_in {
+        scale(required, decimals_out - decimals_in).is_none_or(|required| received < required)
+    } else {
+        scale(received, decimals_in - decimals_out).is_some_and(|received| received < required)
+    };
+    if rate_too_low {
+        msg!(
+            "Error: RateTooLow. Received {} for {}, min rate {}/{}",
+            amount_out,
+            amount_spent,
+            params.min_rate_num,
+            params.min_rate_den
+        );
+        return Err(AutoswapError::RateTooLow.into());
+    }
+    Ok(())
+}
+
+/// Logs the client order id, a zero id is skipped.
+/// Returns the log line.
+pub fn echo_client_order_id(client_order_id: &[u8; 16]) -> Option<String> {
+    if *client_order_id == [0; 16] {
+        return None;
+    }
+    let log = format!(
+        "client_order_id {}",
+        client_order_id.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    );
+    msg!(&log);
+    Some(log)
+}
+
+/// Publishes the output of a completed swap in return data.
+/// Layout: amount out as u64 little endian, followed by the client order id
+/// when it is non-zero.
+pub fn set_swap_result(amount_out: u64, client_order_id: &[u8; 16]) -> Vec<u8> {
+    let mut result = amount_out.to_le_bytes().to_vec();
+    if *client_order_id != [0; 16] {
+        result.extend_from_slice(client_order_id);
+    }
+    set_return_data(&result);
+    result
+}
+
+/// Publishes the output of a partially filled swap in return data.
+/// Layout: amount out then input consumed, both u64 little endian, followed by
+/// the client order id when it is non-zero.
+pub fn set_partial_fill_result(amount_out: u64, amount_spent: u64, client_order_id: &[u8; 16]) -> Vec<u8> {
+    let mut result = amount_out.to_le_bytes().to_vec();
+    result.extend_from_slice(&amount_spent.to_le_bytes());
+    if *client_order_id != [0; 16] {
+        result.extend_from_slice(client_order_id);
+    }
+    set_return_data(&result);
+    result
+}
+
+/// Leaves the pool quote and the amount received in return data when a swap
+/// falls short of its minimum output, so clients can pick a new min_token_amount_out.
+/// Layout: quote then received, both u64 little endian.
+pub fn set_slippage_hint(quote: u64, received: u64) -> [u8; 16] {
+    msg!("Slippage hint: quote {}, received {}", quote, received);
+    let mut hint = [0; 16];
+    hint[..8].copy_from_slice(&quote.to_le_bytes());
+    hint[8..].copy_from_slice(&received.to_le_bytes());
+    set_return_data(&hint);
+    hint
+}
+
+/// Number of accounts describing one Raydium pool in swap, from pool_program_id
+/// to serum_vault_signer.
+pub const RAYDIUM_POOL_ACCOUNTS: usize = 16;
+
+/// Swaps through the cheapest of several pools for the same pair, at most
+/// MAX_BATCH of them.
+///
+/// Accounts: program_account, program_token_a_account, program_token_b_account,
+/// then RAYDIUM_POOL_ACCOUNTS per candidate pool in the same order as swap.
+pub fn swap_best_fee(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    params: SwapParams,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapBestFee");
+    account::check_account_count(accounts, layout::SWAP_POOL_PROGRAM + RAYDIUM_POOL_ACCOUNTS)?;
+    if !(accounts.len() - layout::SWAP_POOL_PROGRAM).is_multiple_of(RAYDIUM_POOL_ACCOUNTS) {
+        msg!("Error: Too many accounts. {} accounts don't split into whole pools", accounts.len());
+        return Err(ProgramError::InvalidArgument);
+    }
+    let pool_count = (accounts.len() - layout::SWAP_POOL_PROGRAM) / RAYDIUM_POOL_ACCOUNTS;
+    if pool_count > MAX_BATCH {
+        msg!("Error: TooManySwaps. {} candidate pools, at most {}", pool_count, MAX_BATCH);
+        return Err(AutoswapError::TooManySwaps.into());
+    }
+    let (program_accounts, pools) = accounts.split_at(layout::SWAP_POOL_PROGRAM);
+    let pool_index = select_lowest_fee_pool(pools, &params)?;
+    msg!("Selected pool {}", pool_index);
+
+    let mut swap_accounts = program_accounts.to_vec();
+    swap_accounts.extend_from_slice(
+        &pools[pool_index * RAYDIUM_POOL_ACCOUNTS..(pool_index + 1) * RAYDIUM_POOL_ACCOUNTS],
+    );
+    swap(&swap_accounts, program_id, params)
+}
+
+/// Returns the index of the pool with the lowest swap fee whose quote still
+/// meets min_token_amount_out. Ties go to the earlier pool.
+pub fn select_lowest_fee_pool(
+    pools: &[AccountInfo],
+    params: &SwapParams,
+) -> Result<usize, ProgramError> {
+    let mut selected: Option<(usize, (u64, u64))> = None;
+    for (index, pool) in pools.chunks_exact(RAYDIUM_POOL_ACCOUNTS).enumerate() {
+        let (pool_program_id, pool_coin_token_account, pool_pc_token_account) =
+            (&pool[0], &pool[1], &pool[2]);
+        let (amm_id, amm_open_orders) = (&pool[4], &pool[6]);
+        if !raydium::check_pool_program_id(pool_program_id.key) {
+            msg!("Error: InvalidPoolProgram. {} is not a Raydium program", pool_program_id.key);
+            return Err(AutoswapError::InvalidPoolProgram.into());
+        }
+        let (fee_numerator, fee_denominator) = raydium::get_pool_swap_fee(amm_id)?;
+        let fee = fee_numerator as f64 / fee_denominator as f64;
+        let (coin_balance, pc_balance) = raydium::get_pool_token_balances(
+            pool_coin_token_account,
+            pool_pc_token_account,
+            amm_open_orders,
+            amm_id,
+        )?;
+        let amount_out = if params.token_a_amount_in == 0 {
+            quote::get_amount_out(params.token_b_amount_in, pc_balance, coin_balance, fee)
+        } else {
+            quote::get_amount_out(params.token_a_amount_in, coin_balance, pc_balance, fee)
+        };
+        match amount_out {
+            Ok(amount_out) if amount_out >= params.min_token_amount_out => {}
+            _ => continue,
+        }
+        // compare the fractions exactly, pools can use different denominators
+        if selected.is_none_or(|(_, (selected_numerator, selected_denominator))| {
+            (fee_numerator as u128 * selected_denominator as u128)
+                < (selected_numerator as u128 * fee_denominator as u128)
+        }) {
+            selected = Some((index, (fee_numerator, fee_denominator)));
+        }
+    }
+
+    selected.map(|(index, _)| index).ok_or_else(|| {
+        msg!("Error: No pool can meet the minimum output {}", params.min_token_amount_out);
+        AutoswapError::InsufficientOutput.into()
+    })
+}
+
+/// Wraps amount_in lamports of the user, swaps them and returns what is left of
+/// the wrapped SOL to the user.
+///
+/// Accounts: user (signer, writable), system program, then the SWAP_ACCOUNTS of
+/// swap. The program token account on the input side must be a temporary wrapped
+/// SOL account owned by the router PDA; it is closed to the user afterwards.
+pub fn swap_native_in(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    amount_in: u64,
+    min_token_amount_out: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapNativeIn");
+    msg!("amount_in {} ", amount_in);
+    let expected = layout::SWAP_NATIVE_IN_SWAP + SWAP_ACCOUNTS;
+    account::check_account_count(accounts, expected)?;
+    if accounts.len() > expected {
+        msg!("Error: Too many accounts. {} accounts, a native swap takes {}", accounts.len(), expected);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (native_accounts, swap_accounts) = accounts.split_at(layout::SWAP_NATIVE_IN_SWAP);
+    let user_account_info = &native_accounts[layout::SWAP_NATIVE_IN_USER];
+    let system_program_info = &native_accounts[layout::SWAP_NATIVE_IN_SYSTEM_PROGRAM];
+    let program_account_info = &swap_accounts[layout::SWAP_PROGRAM_ACCOUNT];
+    let token_program_id_info = &swap_accounts[layout::SWAP_SPL_TOKEN];
+    let program_token_a_account = &swap_accounts[layout::SWAP_PROGRAM_TOKEN_A];
+    let program_token_b_account = &swap_accounts[layout::SWAP_PROGRAM_TOKEN_B];
+    let native_mint = spl_token::native_mint::id();
+    let native_in_a = if account::get_token_account_mint(program_token_a_account)? == native_mint {
+        true
+    } else if account::get_token_account_mint(program_token_b_account)? == native_mint {
+        false
+    } else {
+        msg!("Error: InvalidTokenMint. Neither program token account holds wrapped SOL");
+        return Err(AutoswapError::InvalidTokenMint.into());
+    };
+    let wsol_account_info = if native_in_a { program_token_a_account } else { program_token_b_account };
+    let authority = check_program_account(program_id, program_account_info)?;
+
+    wrap_sol(
+        user_account_info.clone(),
+        wsol_account_info.clone(),
+        system_program_info.clone(),
+        token_program_id_info.clone(),
+        amount_in,
+    )?;
+    swap(
+        swap_accounts,
+        program_id,
+        SwapParams {
+            token_a_amount_in: if native_in_a { amount_in } else { 0 },
+            token_b_amount_in: if native_in_a { 0 } else { amount_in },
+            min_token_amount_out,
+            ..SwapParams::default()
+        },
+    )?;
+    // any failure above reverts the wrap as well, the account is only closed on success
+    unwrap_sol(
+        wsol_account_info.clone(),
+        user_account_info.clone(),
+        program_account_info.clone(),
+        token_program_id_info.clone(),
+        &authority.signer_seeds(),
+    )?;
+
+    msg!("AmmInstruction::SwapNativeIn complete");
+    Ok(())
+}
+
+/// Swaps amount_in through two Raydium pools, A to B then B to C.
+///
+/// Accounts: program_account, program token A, program token B, program token C,
+/// then RAYDIUM_POOL_ACCOUNTS for the A/B pool and RAYDIUM_POOL_ACCOUNTS for the
+/// B/C pool, each in the same order as swap. The second leg spends what the first
+/// one delivered and only it is held to min_final_out. A failing leg reverts both.
+pub fn swap_two_hop(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    amount_in: u64,
+    min_final_out: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SwapTwoHop");
+    msg!("amount_in {} ", amount_in);
+    msg!("min_final_out {} ", min_final_out);
+    let expected = layout::SWAP_TWO_HOP_POOLS + 2 * RAYDIUM_POOL_ACCOUNTS;
+    account::check_account_count(accounts, expected)?;
+    if accounts.len() > expected {
+        msg!("Error: Too many accounts. {} accounts, two hops take {}", accounts.len(), expected);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (program_accounts, pools) = accounts.split_at(layout::SWAP_TWO_HOP_POOLS);
+    let (first_pool, second_pool) = pools.split_at(RAYDIUM_POOL_ACCOUNTS);
+    let program_account = &program_accounts[layout::SWAP_TWO_HOP_PROGRAM_ACCOUNT];
+    let program_token_mid_account = &program_accounts[layout::SWAP_TWO_HOP_PROGRAM_TOKEN_MID];
+
+    let initial_balance_mid = account::get_token_balance(program_token_mid_account)?;
+    swap_leg(
+        program_id,
+        program_account,
+        &program_accounts[layout::SWAP_TWO_HOP_PROGRAM_TOKEN_IN],
+        program_token_mid_account,
+        first_pool,
+        amount_in,
+        0,
+    )?;
+    let amount_mid = account::get_balance_increase(program_token_mid_account, initial_balance_mid)?;
+    msg!("Intermediate amount {}", amount_mid);
+    swap_leg(
+        program_id,
+        program_account,
+        program_token_mid_account,
+        &program_accounts[layout::SWAP_TWO_HOP_PROGRAM_TOKEN_OUT],
+        second_pool,
+        amount_mid,
+        min_final_out,
+    )?;
+
+    msg!("AmmInstruction::SwapTwoHop complete");
+    Ok(())
+}
+
+/// Runs swap for one leg of a multi-hop swap, placing the program token accounts
+/// on the coin or pc side of the pool according to the input mint.
+fn swap_leg<'a>(
+    program_id: &Pubkey,
+    program_account: &AccountInfo<'a>,
+    program_token_in_account: &AccountInfo<'a>,
+    program_token_out_account: &AccountInfo<'a>,
+    pool: &[AccountInfo<'a>],
+    amount_in: u64,
+    min_token_amount_out: u64,
+) -> ProgramResult {
+    let (pool_coin_token_account, pool_pc_token_account, amm_id) = (&pool[1], &pool[2], &pool[4]);
+    let vaults_swapped =
+        raydium::check_vaults_swapped(amm_id, pool_coin_token_account, pool_pc_token_account)?;
+    let coin_vault = if vaults_swapped {
+        pool_pc_token_account
+    } else {
+        pool_coin_token_account
+    };
+    let coin_to_pc = account::get_token_account_mint(program_token_in_account)?
+        == account::get_token_account_mint(coin_vault)?;
+
+    let (program_token_a_account, program_token_b_account) = if coin_to_pc {
+        (program_token_in_account, program_token_out_account)
+    } else {
+        (program_token_out_account, program_token_in_account)
+    };
+    let mut swap_accounts = vec![
+        program_account.clone(),
+        program_token_a_account.clone(),
+        program_token_b_account.clone(),
+    ];
+    swap_accounts.extend_from_slice(pool);
+    swap(
+        &swap_accounts,
+        program_id,
+        SwapParams {
+            token_a_amount_in: if coin_to_pc { amount_in } else { 0 },
+            token_b_amount_in: if coin_to_pc { 0 } else { amount_in },
+            min_token_amount_out,
+            ..SwapParams::default()
+        },
+    )
+}
+
+/// Last step of the before_transfer, swap, after_transfer sequence, pays out
+/// and takes the fees. Runs once per swap, see SwapConfig::advance_swap_phase.
+pub fn after_transfer(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     amount: u64,
+    referral_bps: u16,
+    fee_amount: u64,
 ) -> ProgramResult {
     msg!("Processing AmmInstruction::AfterTransfer");
-    let account_info_iter = &mut accounts.iter();
-    let token_program_id_info = next_account_info(account_info_iter)?;
-    let program_account_info = next_account_info(account_info_iter)?;
-    let program_sol_account_info = next_account_info(account_info_iter)?;
-    let user_account_info = next_account_info(account_info_iter)?;
-    let seed = &[
-        PREFIX.as_bytes(),
-    ];
-    
-    let (program_account, bump_seed) = Pubkey::find_program_address(seed, &program_id);
-    let transfer_authority_seed = &[
-        PREFIX.as_bytes(),
-        &[bump_seed],
-    ];
+    msg!("fee_amount {} ", fee_amount);
+    account::check_account_count(accounts, AFTER_TRANSFER_ACCOUNTS)?;
+    let token_program_id_info = &accounts[layout::AFTER_TRANSFER_TOKEN_PROGRAM];
+    let program_account_info = &accounts[layout::AFTER_TRANSFER_PROGRAM_ACCOUNT];
+    let program_kin_account_info = &accounts[layout::AFTER_TRANSFER_PROGRAM_KIN];
+    let program_sol_account_info = &accounts[layout::AFTER_TRANSFER_PROGRAM_SOL];
+    let destination_account_info = &accounts[layout::AFTER_TRANSFER_DESTINATION];
+    let fee_recipient_info = &accounts[layout::AFTER_TRANSFER_FEE_RECIPIENT];
+    let program_kin_mint_info = &accounts[layout::AFTER_TRANSFER_PROGRAM_KIN_MINT];
+    let program_sol_mint_info = &accounts[layout::AFTER_TRANSFER_PROGRAM_SOL_MINT];
+    // fee side may live under another token program, defaults to the payout one
+    let fee_token_program_id_info = accounts
+        .get(layout::AFTER_TRANSFER_FEE_TOKEN_PROGRAM)
+        .unwrap_or(token_program_id_info);
+    // required only when the config routes an lp fee
+    let lp_fee_recipient_info = accounts.get(layout::AFTER_TRANSFER_LP_FEE_RECIPIENT);
+    // takes referral_bps of the protocol fee, the zero id opts out of the referral
+    let referrer_info = accounts
+        .get(layout::AFTER_TRANSFER_REFERRER)
+        .filter(|info| *info.key != zero::id());
 
-    spl_token_transfer(
-        TokenTransferParams{
-            source: program_sol_account_info.clone(),
-            destination: user_account_info.clone(),
+    if referral_bps > 10_000 {
+        msg!("Error: referral_bps {} is above 10000", referral_bps);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    config.check_not_paused()?;
+    // pays out once per swap, a replay or a call without a swap before it fails here
+    advance_swap_phase(program_id, program_account_info, SwapPhase::Idle)?;
+    // fee recipients live under the token program of the side the fee is drawn from
+    let (fee_source_info, fee_mint_info, fee_recipient_token_program_info) = match config.fee_token {
+        FeeToken::Input => (program_sol_account_info, program_sol_mint_info, fee_token_program_id_info),
+        FeeToken::Output => (program_kin_account_info, program_kin_mint_info, token_program_id_info),
+    };
+
+    account::check_token_program(program_kin_account_info, token_program_id_info)?;
+    account::check_token_program(destination_account_info, token_program_id_info)?;
+    account::check_token_program(program_sol_account_info, fee_token_program_id_info)?;
+    account::check_token_program(fee_recipient_info, fee_recipient_token_program_info)?;
+    if let Some(lp_fee_recipient_info) = lp_fee_recipient_info {
+        account::check_token_program(lp_fee_recipient_info, fee_recipient_token_program_info)?;
+    }
+    if let Some(referrer_info) = referrer_info {
+        account::check_token_program(referrer_info, fee_recipient_token_program_info)?;
+    }
+    for recipient_info in std::iter::once(fee_recipient_info)
+        .chain(lp_fee_recipient_info)
+        .chain(referrer_info)
+    {
+        if recipient_info.key == fee_source_info.key {
+            msg!("Error: InvalidFeeRecipient. {} is the fee source", recipient_info.key);
+            return Err(AutoswapError::InvalidFeeRecipient.into());
+        }
+    }
+    check_fee_recipient(&config, fee_recipient_info, fee_mint_info)?;
+    // the fee mint may carry its own protocol fee
+    let config = SwapConfig {
+        fee_bps: config.fee_bps_for(fee_mint_info.key),
+        ..config
+    };
+
+    let authority = ProgramAuthority::find(program_id, config.instance);
+    let transfer_authority_seed = &authority.signer_seeds()[..];
+
+    let (payout_amount, fee_amount, lp_fee_amount) = get_payout_amounts(
+        amount,
+        &config,
+        account::get_token_balance(program_kin_account_info)?,
+        account::get_token_balance(program_sol_account_info)?,
+        fee_amount,
+    )?;
+    let (fee_amount, referral_amount) = match referrer_info {
+        Some(_) => get_referral_amounts(fee_amount, referral_bps)?,
+        None => (fee_amount, 0),
+    };
+    // checked transfers, the token program rejects accounts of another mint
+    spl_token_transfer_checked(
+        TokenTransferCheckedParams{
+            source: program_kin_account_info.clone(),
+            mint: program_kin_mint_info.clone(),
+            destination: destination_account_info.clone(),
             authority: program_account_info.clone(),
             token_program: token_program_id_info.clone(),
             authority_signer_seeds: transfer_authority_seed,
-            amount: amount,
+            amount: payout_amount,
+            decimals: account::get_token_decimals(program_kin_mint_info)?,
         }
     )?;
 
-    Ok(())
-}
\ No newline at end of file
+    spl_token_transfer_checked(
+        TokenTransferCheckedParams{
+            source: fee_source_info.clone(),
+            mint: fee_mint_info.clone(),
+            destination: fee_recipient_info.clone(),
+            authority: program_account_info.clone(),
+            token_program: fee_recipient_token_program_info.clone(),
+            authority_signer_seeds: transfer_authority_seed,
+            amount: fee_amount,
+            decimals: account::get_token_decimals(fee_mint_info)?,
+        }
+    )?;
+
+    if lp_fee_amount > 0 {
+        let lp_fee_recipient_info = lp_fee_recipient_info.ok_or_else(|| {
+            msg!("Error: LP fee recipient account is required");
+            ProgramError::NotEnoughAccountKeys
+        })?;
+        spl_token_transfer_checked(
+            TokenTransferCheckedParams{
+                source: fee_source_info.clone(),
+                mint: fee_mint_info.clone(),
+                destination: lp_fee_recipient_info.clone(),
+                authority: program_account_info.clone(),
+                token_program: fee_recipient_token_program_info.clone(),
+                authority_signer_seeds: transfer_authority_seed,
+                amount: lp_fee_amount,
+                decimals: account::get_token_decimals(fee_mint_info)?,
+            }
+        )?;
+    }
+
+    if let (Some(referrer_info), true) = (referrer_info, referral_amount > 0) {
+        spl_token_transfer_checked(
+            TokenTransferCheckedParams{
+                source: fee_source_info.clone(),
+                mint: fee_mint_info.clone(),
+                destination: referrer_info.clone(),
+                authority: program_account_info.clone(),
+                token_program: fee_recipient_token_program_info.clone(),
+                authority_signer_seeds: transfer_authority_seed,
+                amount: referral_amount,
+                decimals: account::get_token_decimals(fee_mint_info)?,
+            }
+        )?;
+    }
+
+    SwapEvent {
+        amount_in: amount,
+        amount_out: payout_amount,
+        protocol: NO_PROTOCOL,
+        fee_paid: math::checked_add(math::checked_add(fee_amount, lp_fee_amount)?, referral_amount)?,
+    }
+    .log();
+    Ok(())
+}
+
+/// Moves amount out of the program sol account, only the config admin may harvest.
+///
+/// Accounts: token program, program account, program sol, user, admin (signer)
+pub fn harvest(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::Harvest");
+    account::check_account_count(accounts, HARVEST_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let token_program_id_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let program_sol_account_info = next_account_info(account_info_iter)?;
+    let user_account_info = next_account_info(account_info_iter)?;
+    let admin_account_info = next_account_info(account_info_iter)?;
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+
+    if amount == 0 {
+        msg!("Error: InvalidAmount. Harvest amount must be positive");
+        return Err(AutoswapError::InvalidAmount.into());
+    }
+    let balance = account::get_token_balance(program_sol_account_info)?;
+    if amount > balance {
+        msg!("Error: TokenInsufficientFunds. Harvest of {} above the balance {}", amount, balance);
+        return Err(AutoswapError::TokenInsufficientFunds.into());
+    }
+
+    let transfer_authority_seed = &authority.signer_seeds()[..];
+
+    spl_token_transfer(
+        TokenTransferParams{
+            source: program_sol_account_info.clone(),
+            destination: user_account_info.clone(),
+            authority: program_account_info.clone(),
+            token_program: token_program_id_info.clone(),
+            authority_signer_seeds: transfer_authority_seed,
+            amount,
+        }
+    )?;
+
+    Ok(())
+}
+
+pub fn set_min_deliverable_out(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMinDeliverableOut");
+    msg!("amount {} ", amount);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.min_deliverable_out = amount;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_min_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMinFee");
+    msg!("amount {} ", amount);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.min_fee = amount;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_min_swap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMinSwap");
+    msg!("amount {} ", amount);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.min_swap_amount = amount;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_fee_split(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_bps: u16,
+    lp_fee_bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetFeeSplit");
+    msg!("fee_bps {} ", fee_bps);
+    msg!("lp_fee_bps {} ", lp_fee_bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    SwapConfig::check_fee_bps(fee_bps, lp_fee_bps)?;
+
+    config.check_sequence(sequence)?;
+    config.fee_bps = fee_bps;
+    config.lp_fee_bps = lp_fee_bps;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_maintenance_window(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    start_slot: u64,
+    end_slot: u64,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMaintenanceWindow");
+    msg!("start_slot {} ", start_slot);
+    msg!("end_slot {} ", end_slot);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.maintenance_start_slot = start_slot;
+    config.maintenance_end_slot = end_slot;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_fee_token(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_token: FeeToken,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetFeeToken");
+    msg!("fee_token {:?} ", fee_token);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.fee_token = fee_token;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_max_pool_consume(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMaxPoolConsume");
+    msg!("bps {} ", bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    if bps > 10_000 {
+        msg!("Error: Pool consume limit {} bps is above 100%", bps);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    config.check_sequence(sequence)?;
+    config.max_pool_consume_bps = bps;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_skip_funded_deposit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetSkipFundedDeposit");
+    msg!("enabled {} ", enabled);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.skip_funded_deposit = enabled;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_auto_correct_vaults(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    enabled: bool,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetAutoCorrectVaults");
+    msg!("enabled {} ", enabled);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.auto_correct_vaults = enabled;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_max_event_queue_fill(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMaxEventQueueFill");
+    msg!("bps {} ", bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    if bps > 10_000 {
+        msg!("Error: Event queue fill limit {} bps is above 100%", bps);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    config.check_sequence(sequence)?;
+    config.max_event_queue_fill_bps = bps;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetFee");
+    msg!("fee_bps {} ", fee_bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    SwapConfig::check_fee_bps(fee_bps, config.lp_fee_bps)?;
+
+    config.check_sequence(sequence)?;
+    config.fee_bps = fee_bps;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Accounts: admin (signer), program account
+pub fn set_default_slippage(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    slippage_bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetDefaultSlippage");
+    msg!("slippage_bps {} ", slippage_bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    if slippage_bps > 10_000 {
+        msg!("Error: Slippage {} bps is above 100%", slippage_bps);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    config.check_sequence(sequence)?;
+    config.default_slippage_bps = slippage_bps;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Accounts: admin (signer), program account. protocol is numbered as in Route
+pub fn set_protocol_enabled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    protocol: u8,
+    enabled: bool,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetProtocolEnabled");
+    msg!("protocol {} enabled {} ", protocol, enabled);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+    let program_type = ProgramIDType::from_route_protocol(protocol).ok_or_else(|| {
+        msg!("Error: Unknown protocol {}", protocol);
+        ProgramError::InvalidArgument
+    })?;
+
+    config.check_sequence(sequence)?;
+    config.set_protocol_enabled(program_type, enabled);
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Accounts: admin (signer), program account, new fee recipient token account
+pub fn set_fee_recipient(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetFeeRecipient");
+    account::check_account_count(accounts, SET_FEE_RECIPIENT_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let fee_recipient_info = next_account_info(account_info_iter)?;
+    msg!("fee_recipient {} ", fee_recipient_info.key);
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.fee_recipient = *fee_recipient_info.key;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetPaused");
+    msg!("paused {} ", paused);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.paused = paused;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Accounts: admin (signer), program account, proposed admin
+pub fn transfer_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::TransferAdmin");
+    account::check_account_count(accounts, TRANSFER_ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let pending_admin_info = next_account_info(account_info_iter)?;
+    msg!("pending_admin {} ", pending_admin_info.key);
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.pending_admin = *pending_admin_info.key;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+/// Accounts: pending admin (signer), program account
+pub fn accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Processing AmmInstruction::AcceptAdmin");
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let pending_admin_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    if config.pending_admin == Pubkey::default() {
+        msg!("Error: No admin handoff is pending");
+        return Err(AutoswapError::Unauthorized.into());
+    }
+    check_config_authority(program_id, pending_admin_info, program_account_info, &config.pending_admin)?;
+
+    config.admin = config.pending_admin;
+    config.pending_admin = Pubkey::default();
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn set_mint_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mint: &Pubkey,
+    fee_bps: u16,
+    sequence: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SetMintFee");
+    msg!("mint {} ", mint);
+    msg!("fee_bps {} ", fee_bps);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    let mut config = SwapConfig::load(program_account_info, program_id)?;
+    check_config_authority(program_id, admin_account_info, program_account_info, &config.admin)?;
+
+    config.check_sequence(sequence)?;
+    config.set_mint_fee(mint, fee_bps)?;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+
+    Ok(())
+}
+
+pub fn verify_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    repair: bool,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::VerifyConfig");
+    msg!("repair {} ", repair);
+    account::check_account_count(accounts, ADMIN_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+
+    // stored admin can't be trusted here, fall back to the main router admin
+    check_config_authority(
+        program_id,
+        admin_account_info,
+        program_account_info,
+        &main_router_admin::id(),
+    )?;
+
+    let mut config = SwapConfig::unpack(&program_account_info.try_borrow_data()?)?;
+    let diagnostics = config.verify(repair);
+    msg!("Config diagnostics {:#05b}", diagnostics);
+    if repair && diagnostics != 0 {
+        config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+    }
+    set_return_data(&[diagnostics]);
+
+    Ok(())
+}
+
+pub fn sweep_lamports(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SweepLamports");
+    account::check_account_count(accounts, SWEEP_LAMPORTS_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let treasury_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_account_info = next_account_info(account_info_iter)?;
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+    account::check_writable(program_account_info)?;
+    account::check_writable(treasury_account_info)?;
+    if treasury_account_info.key == program_account_info.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let rent = &Rent::from_account_info(rent_info)?;
+    let excess = program_account_info
+        .lamports()
+        .saturating_sub(rent.minimum_balance(program_account_info.data_len()));
+    msg!("Sweep {} lamports to the treasury", excess);
+    if excess == 0 {
+        return Ok(());
+    }
+
+    if program_account_info.owner == program_id {
+        // the system program can't debit an account it doesn't own
+        **program_account_info.try_borrow_mut_lamports()? -= excess;
+        let treasury_lamports = treasury_account_info.lamports();
+        **treasury_account_info.try_borrow_mut_lamports()? =
+            math::checked_add(treasury_lamports, excess)?;
+    } else {
+        invoke_signed(
+            &system_instruction::transfer(
+                program_account_info.key,
+                treasury_account_info.key,
+                excess,
+            ),
+            &[
+                program_account_info.clone(),
+                treasury_account_info.clone(),
+                system_account_info.clone(),
+            ],
+            &[&authority.signer_seeds()],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks the admin signed and the config lives in the router's writable PDA.
+/// Moves amount from a fee token account held by the router PDA to a destination,
+/// an amount of 0 withdraws the whole balance.
+///
+/// Accounts: admin (signer), program account, fee token account, destination,
+/// token program
+pub fn withdraw_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::WithdrawFees");
+    msg!("amount {} ", amount);
+    account::check_account_count(accounts, WITHDRAW_FEES_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let fee_account_info = next_account_info(account_info_iter)?;
+    let destination_account_info = next_account_info(account_info_iter)?;
+    let token_program_id_info = next_account_info(account_info_iter)?;
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+    account::check_token_program(fee_account_info, token_program_id_info)?;
+    account::check_token_program(destination_account_info, token_program_id_info)?;
+
+    let amount = if amount == 0 {
+        account::get_token_balance(fee_account_info)?
+    } else {
+        amount
+    };
+    msg!("Withdraw {} fee tokens", amount);
+    spl_token_transfer(
+        TokenTransferParams{
+            source: fee_account_info.clone(),
+            destination: destination_account_info.clone(),
+            authority: program_account_info.clone(),
+            token_program: token_program_id_info.clone(),
+            authority_signer_seeds: &authority.signer_seeds(),
+            amount,
+        }
+    )
+}
+
+/// Moves the whole balance of count fee token accounts held by the router PDA
+/// to their destinations, empty fee accounts are skipped.
+///
+/// Accounts: admin (signer), program account, token program, then count
+/// (fee token account, destination) pairs of SWEEP_FEES_STRIDE accounts
+pub fn sweep_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    count: u8,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::SweepFees");
+    msg!("count {} ", count);
+    account::check_account_count(accounts, SWEEP_FEES_ACCOUNTS)?;
+    let expected = SWEEP_FEES_ACCOUNTS + count as usize * SWEEP_FEES_STRIDE;
+    account::check_account_count(accounts, expected)?;
+    if accounts.len() > expected {
+        msg!("Error: Too many accounts. {} accounts, {} fee accounts take {}", accounts.len(), count, expected);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let admin_account_info = &accounts[0];
+    let program_account_info = &accounts[1];
+    let token_program_id_info = &accounts[2];
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+
+    for pair in accounts[SWEEP_FEES_ACCOUNTS..].chunks_exact(SWEEP_FEES_STRIDE) {
+        let (fee_account_info, destination_account_info) = (&pair[0], &pair[1]);
+        account::check_token_program(fee_account_info, token_program_id_info)?;
+        account::check_token_program(destination_account_info, token_program_id_info)?;
+        let amount = account::get_token_balance(fee_account_info)?;
+        if amount == 0 {
+            msg!("Skip empty fee account {}", fee_account_info.key);
+            continue;
+        }
+        msg!("Sweep {} fee tokens from {}", amount, fee_account_info.key);
+        spl_token_transfer(
+            TokenTransferParams{
+                source: fee_account_info.clone(),
+                destination: destination_account_info.clone(),
+                authority: program_account_info.clone(),
+                token_program: token_program_id_info.clone(),
+                authority_signer_seeds: &authority.signer_seeds(),
+                amount,
+            }
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Moves the whole balance of a token account held by the router PDA to a
+/// destination, e.g. tokens stranded by a failed multi-hop. The configured fee
+/// recipient is only drained with allow_fee_account.
+///
+/// Accounts: admin (signer), program account, token account, destination,
+/// token program
+pub fn rescue_tokens(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    allow_fee_account: bool,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::RescueTokens");
+    msg!("allow_fee_account {} ", allow_fee_account);
+    account::check_account_count(accounts, RESCUE_TOKENS_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let destination_account_info = next_account_info(account_info_iter)?;
+    let token_program_id_info = next_account_info(account_info_iter)?;
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+    account::check_token_program(token_account_info, token_program_id_info)?;
+    account::check_token_program(destination_account_info, token_program_id_info)?;
+    if !allow_fee_account && *token_account_info.key == config.fee_recipient {
+        msg!("Error: FeeAccountProtected. {} is the fee recipient", token_account_info.key);
+        return Err(AutoswapError::FeeAccountProtected.into());
+    }
+    let owner = account::get_token_account_owner(token_account_info)?;
+    if owner != *program_account_info.key {
+        msg!("Error: TokenOwnerMismatch. {} is owned by {}", token_account_info.key, owner);
+        return Err(AutoswapError::TokenOwnerMismatch.into());
+    }
+
+    let amount = account::get_token_balance(token_account_info)?;
+    msg!("Rescue {} tokens from {} to {}", amount, token_account_info.key, destination_account_info.key);
+    spl_token_transfer(
+        TokenTransferParams{
+            source: token_account_info.clone(),
+            destination: destination_account_info.clone(),
+            authority: program_account_info.clone(),
+            token_program: token_program_id_info.clone(),
+            authority_signer_seeds: &authority.signer_seeds(),
+            amount,
+        }
+    )
+}
+
+/// Closes an empty token account held by the router PDA, e.g. a leftover
+/// temporary wrapped SOL or intermediate account, and sends its rent to a destination.
+///
+/// Accounts: admin (signer), program account, token account, destination,
+/// token program
+pub fn close_temp_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::CloseTempAccount");
+    account::check_account_count(accounts, CLOSE_TEMP_ACCOUNT_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let admin_account_info = next_account_info(account_info_iter)?;
+    let program_account_info = next_account_info(account_info_iter)?;
+    let token_account_info = next_account_info(account_info_iter)?;
+    let destination_account_info = next_account_info(account_info_iter)?;
+    let token_program_id_info = next_account_info(account_info_iter)?;
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_admin_signer(admin_account_info, &config.admin)?;
+    let authority = check_program_account(program_id, program_account_info)?;
+    account::check_token_program(token_account_info, token_program_id_info)?;
+    // wrapped SOL accounts could be closed with a balance, the tokens would be lost
+    let balance = account::get_token_balance(token_account_info)?;
+    if balance != 0 {
+        msg!("Error: NonZeroBalance. {} still holds {} tokens", token_account_info.key, balance);
+        return Err(AutoswapError::NonZeroBalance.into());
+    }
+
+    msg!(
+        "Close {}, {} lamports to {}",
+        token_account_info.key,
+        token_account_info.lamports(),
+        destination_account_info.key
+    );
+    close_token_account(
+        token_account_info.clone(),
+        destination_account_info.clone(),
+        program_account_info.clone(),
+        token_program_id_info.clone(),
+        &authority.signer_seeds(),
+    )
+}
+
+/// Leaves the Raydium pool quote in return data, amount_in then the amount out
+/// a swap passes to the pool as min_amount_out, both u64 little endian.
+/// Reads the pool only, clients simulate the transaction to get the quote.
+///
+/// Accounts: pool program, amm id, amm open orders, pool coin token account,
+/// pool pc token account
+pub fn quote_swap(
+    accounts: &[AccountInfo],
+    token_a_amount_in: u64,
+    token_b_amount_in: u64,
+) -> ProgramResult {
+    msg!("Processing AmmInstruction::Quote");
+    account::check_account_count(accounts, QUOTE_ACCOUNTS)?;
+    let account_info_iter = &mut accounts.iter();
+    let pool_program_id_info = next_account_info(account_info_iter)?;
+    let amm_id_info = next_account_info(account_info_iter)?;
+    let amm_open_orders_info = next_account_info(account_info_iter)?;
+    let pool_coin_token_account_info = next_account_info(account_info_iter)?;
+    let pool_pc_token_account_info = next_account_info(account_info_iter)?;
+
+    if ProgramIDType::from_program_id(pool_program_id_info.key) != Some(ProgramIDType::Raydium) {
+        msg!("Error: InvalidPoolProgram. {} is not a Raydium program", pool_program_id_info.key);
+        return Err(AutoswapError::InvalidPoolProgram.into());
+    }
+    let (amount_in, min_amount_out) = raydium::get_pool_swap_amounts(
+        pool_coin_token_account_info,
+        pool_pc_token_account_info,
+        amm_open_orders_info,
+        amm_id_info,
+        token_a_amount_in,
+        token_b_amount_in,
+    )?;
+    msg!("Quote: amount_in {}, min_amount_out {}", amount_in, min_amount_out);
+    let mut result = [0; 16];
+    result[..8].copy_from_slice(&amount_in.to_le_bytes());
+    result[8..].copy_from_slice(&min_amount_out.to_le_bytes());
+    set_return_data(&result);
+
+    Ok(())
+}
+
+/// Leaves the crate version and build features in return data, see
+/// version::pack_version. Takes no accounts, clients simulate the transaction.
+pub fn get_version() -> ProgramResult {
+    msg!("Processing AmmInstruction::GetVersion");
+    msg!("version {} features {:#06x}", version::VERSION, version::build_features());
+    set_return_data(&version::pack_version());
+
+    Ok(())
+}
+
+/// Leaves the packed SwapConfig in return data. Accounts: program account
+pub fn get_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("Processing AmmInstruction::GetConfig");
+    account::check_account_count(accounts, GET_CONFIG_ACCOUNTS)?;
+    let program_account_info = &accounts[0];
+
+    let config = SwapConfig::load(program_account_info, program_id)?;
+    check_program_account_instance(program_id, program_account_info, config.instance)?;
+    let mut data = [0; SwapConfig::LEN];
+    config.pack(&mut data)?;
+    set_return_data(&data);
+
+    Ok(())
+}
+
+fn check_config_authority(
+    program_id: &Pubkey,
+    admin_account_info: &AccountInfo,
+    program_account_info: &AccountInfo,
+    admin: &Pubkey,
+) -> ProgramResult {
+    check_admin_signer(admin_account_info, admin)?;
+    check_program_account(program_id, program_account_info)?;
+    if program_account_info.owner != program_id {
+        return Err(ProgramError::IllegalOwner);
+    }
+    account::check_writable(program_account_info)
+}
+
+fn check_admin_signer(admin_account_info: &AccountInfo, admin: &Pubkey) -> ProgramResult {
+    if !admin_account_info.is_signer {
+        msg!("Error: Admin signature required");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if admin_account_info.key != admin {
+        msg!("Error: Unauthorized. {} is not the config admin", admin_account_info.key);
+        return Err(AutoswapError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+/// Checks the account is the router PDA of the instance its stored config
+/// carries, the default one without a config, and returns the authority.
+fn check_program_account(
+    program_id: &Pubkey,
+    program_account_info: &AccountInfo,
+) -> Result<ProgramAuthority, ProgramError> {
+    let instance = SwapConfig::load(program_account_info, program_id)?.instance;
+    check_program_account_instance(program_id, program_account_info, instance)
+}
+
+/// Checks program_account_info is the router PDA of instance.
+fn check_program_account_instance(
+    program_id: &Pubkey,
+    program_account_info: &AccountInfo,
+    instance: u8,
+) -> Result<ProgramAuthority, ProgramError> {
+    let authority = ProgramAuthority::find(program_id, instance);
+    if program_account_info.key != &authority.address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(authority)
+}
+
+/// Advances the swap phase kept in the stored config, see SwapConfig::advance_swap_phase.
+/// Nothing is tracked without a stored config, and a swap outside of a
+/// before_transfer, swap, after_transfer sequence leaves the phase alone.
+fn advance_swap_phase(
+    program_id: &Pubkey,
+    program_account_info: &AccountInfo,
+    next: SwapPhase,
+) -> ProgramResult {
+    if program_account_info.owner != program_id || program_account_info.data_len() < SwapConfig::LEN {
+        return Ok(());
+    }
+    let mut config = SwapConfig::unpack(&program_account_info.try_borrow_data()?)?;
+    if config.version == 0
+        || (next == SwapPhase::Swapped && config.swap_phase != SwapPhase::Deposited)
+    {
+        return Ok(());
+    }
+    config.advance_swap_phase(next)?;
+    account::check_writable(program_account_info)?;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// Stores what the deposit credited so the swap spends the net amount.
+/// Like advance_swap_phase, a program account without a config is left alone.
+fn record_deposit(
+    program_id: &Pubkey,
+    program_account_info: &AccountInfo,
+    received: u64,
+) -> ProgramResult {
+    if program_account_info.owner != program_id || program_account_info.data_len() < SwapConfig::LEN {
+        return Ok(());
+    }
+    let mut config = SwapConfig::unpack(&program_account_info.try_borrow_data()?)?;
+    if config.version == 0 {
+        return Ok(());
+    }
+    config.deposited_amount = received;
+    account::check_writable(program_account_info)?;
+    config.pack(&mut program_account_info.try_borrow_mut_data()?)?;
+    Ok(())
+}
+
+/// Checks fee_recipient_info is the recipient the config pins, if any, and
+/// a token account of the fee mint.
+pub fn check_fee_recipient(
+    config: &SwapConfig,
+    fee_recipient_info: &AccountInfo,
+    fee_mint_info: &AccountInfo,
+) -> ProgramResult {
+    if config.fee_recipient != Pubkey::default() && *fee_recipient_info.key != config.fee_recipient {
+        msg!(
+            "Error: InvalidFeeRecipient. Expected {}, got {}",
+            config.fee_recipient,
+            fee_recipient_info.key
+        );
+        return Err(AutoswapError::InvalidFeeRecipient.into());
+    }
+    let mint = account::get_token_account_mint(fee_recipient_info)?;
+    if mint != *fee_mint_info.key {
+        msg!(
+            "Error: InvalidFeeRecipient. {} holds mint {}, the fee is paid in {}",
+            fee_recipient_info.key,
+            mint,
+            fee_mint_info.key
+        );
+        return Err(AutoswapError::InvalidFeeRecipient.into());
+    }
+    Ok(())
+}
+
+/// Returns the (protocol, lp) fees taken in after_transfer for the given swap amount.
+/// The combined bps fee is raised to the configured minimum and clamped to what is
+/// available, then split by bps share so the portions always add up to the total.
+pub fn get_fee_amounts(
+    amount: u64,
+    config: &SwapConfig,
+    available: u64,
+) -> Result<(u64, u64), ProgramError> {
+    let total_bps = math::checked_add(config.fee_bps as u128, config.lp_fee_bps as u128)?;
+    let fee = math::checked_bps_of(amount, total_bps)?;
+    split_fee(fee.max(config.min_fee).min(available), config)
+}
+
+/// Splits fee into the (protocol, lp) portions by bps share, all protocol when
+/// both shares are zero.
+fn split_fee(fee: u64, config: &SwapConfig) -> Result<(u64, u64), ProgramError> {
+    let total_bps = math::checked_add(config.fee_bps as u128, config.lp_fee_bps as u128)?;
+    if total_bps == 0 {
+        return Ok((fee, 0));
+    }
+    let lp_fee = math::checked_as_u64(math::checked_div(
+        math::checked_mul(fee as u128, config.lp_fee_bps as u128)?,
+        total_bps,
+    )?)?;
+
+    Ok((fee - lp_fee, lp_fee))
+}
+
+/// Returns the (protocol, lp) split of an explicit after_transfer fee, by bps
+/// share like get_fee_amounts. The fee must be available and at least the bps
+/// fee of amount and the configured minimum, anyone can call after_transfer
+/// so it can only raise the fee.
+pub fn get_explicit_fee_amounts(
+    fee_amount: u64,
+    amount: u64,
+    config: &SwapConfig,
+    available: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if fee_amount > available {
+        msg!("Error: TokenInsufficientFunds. Fee {} above the balance {}", fee_amount, available);
+        return Err(AutoswapError::TokenInsufficientFunds.into());
+    }
+    let total_bps = math::checked_add(config.fee_bps as u128, config.lp_fee_bps as u128)?;
+    let min_fee = math::checked_bps_of(amount, total_bps)?.max(config.min_fee);
+    if fee_amount < min_fee {
+        msg!("Error: InvalidAmount. Fee {} below the minimum {}", fee_amount, min_fee);
+        return Err(AutoswapError::InvalidAmount.into());
+    }
+    split_fee(fee_amount, config)
+}
+
+/// Splits the protocol fee into the (fee recipient, referrer) portions,
+/// the referrer gets referral_bps of it rounded down.
+pub fn get_referral_amounts(fee_amount: u64, referral_bps: u16) -> Result<(u64, u64), ProgramError> {
+    let referral_amount = math::checked_bps_of(fee_amount, referral_bps as u128)?;
+    Ok((math::checked_sub(fee_amount, referral_amount)?, referral_amount))
+}
+
+/// Returns the (payout, protocol fee, lp fee) amounts of after_transfer.
+/// With FeeToken::Input the whole output balance is paid out and the fees are a
+/// share of the swap amount drawn from the input balance. With FeeToken::Output
+/// the fees are a share of the output balance and the user receives the remainder.
+/// A non-zero explicit_fee replaces the bps fee, see get_explicit_fee_amounts.
+pub fn get_payout_amounts(
+    amount: u64,
+    config: &SwapConfig,
+    output_balance: u64,
+    input_balance: u64,
+    explicit_fee: u64,
+) -> Result<(u64, u64, u64), ProgramError> {
+    let fee_amounts = |base, available| {
+        if explicit_fee > 0 {
+            get_explicit_fee_amounts(explicit_fee, base, config, available)
+        } else {
+            get_fee_amounts(base, config, available)
+        }
+    };
+    match config.fee_token {
+        FeeToken::Input => {
+            let (fee, lp_fee) = fee_amounts(amount, input_balance)?;
+            Ok((output_balance, fee, lp_fee))
+        }
+        FeeToken::Output => {
+            let (fee, lp_fee) = fee_amounts(output_balance, output_balance)?;
+            Ok((output_balance - fee - lp_fee, fee, lp_fee))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instruction::AmmInstruction,
+        protocol::raydium::{self, raydium_v4},
+        utils::{id::saber_stable_swap, tokens::PREFIX},
+    };
+    use solana_program::{instruction::Instruction, program_pack::Pack, program_stubs};
+    use spl_token::state::{Account, AccountState, Mint};
+
+    thread_local! {
+        /// Token account and amount the stubbed CPI credits, see credit_on_cpi
+        static CPI_CREDIT: std::cell::Cell<Option<(Pubkey, u64)>> = const { std::cell::Cell::new(None) };
+    }
+
+    /// Syscall stubs standing in for a pool paying out: every CPI credits the
+    /// token account set with credit_on_cpi. Threads that set nothing keep the
+    /// default no-op CPI.
+    struct CreditingStubs;
+
+    impl program_stubs::SyscallStubs for CreditingStubs {
+        fn sol_invoke_signed(
+            &self,
+            _instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            if let Some((key, amount)) = CPI_CREDIT.with(|credit| credit.get()) {
+                if let Some(info) = account_infos.iter().find(|info| *info.key == key) {
+                    let mut data = info.try_borrow_mut_data()?;
+                    let mut token_account = Account::unpack(&data)?;
+                    token_account.amount += amount;
+                    Account::pack(token_account, &mut data)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Makes the CPIs of the calling test credit the token account and amount
+    /// given, None restores the no-op CPI
+    fn credit_on_cpi(credit: Option<(Pubkey, u64)>) {
+        static STUBS: std::sync::Once = std::sync::Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(CreditingStubs));
+        });
+        CPI_CREDIT.with(|cell| cell.set(credit));
+    }
+
+    struct TestAccount {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+        is_writable: bool,
+    }
+
+    impl TestAccount {
+        fn new(key: Pubkey) -> Self {
+            Self {
+                key,
+                lamports: 0,
+                data: vec![],
+                owner: Pubkey::default(),
+                is_writable: true,
+            }
+        }
+
+        /// Token account data, owned by the token program so balances can be read
+        fn set_token_data(&mut self, data: Vec<u8>) {
+            self.owner = spl_token::id();
+            self.data = data;
+        }
+    }
+
+    /// Raydium swap accounts, the v4 amm state names the pool vaults and open orders
+    fn swap_test_accounts() -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> =
+            (0..19).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        accounts[3].key = raydium_v4::id();
+        let mut amm_id = vec![0; raydium::AmmInfoV4::LEN];
+        amm_id[336..368].copy_from_slice(accounts[4].key.as_ref());
+        amm_id[368..400].copy_from_slice(accounts[5].key.as_ref());
+        amm_id[496..528].copy_from_slice(accounts[9].key.as_ref());
+        accounts[7].owner = raydium_v4::id();
+        accounts[7].data = amm_id;
+        accounts
+    }
+
+    /// Mint of the pc side of the dust swap fixture, token a and the pool coin
+    /// keep the default mint
+    const PC_MINT: Pubkey = Pubkey::new_from_array([1; 32]);
+
+    fn pack_token_account(amount: u64) -> Vec<u8> {
+        pack_mint_token_account(Pubkey::default(), amount)
+    }
+
+    fn pack_mint_token_account(mint: Pubkey, amount: u64) -> Vec<u8> {
+        let account = Account {
+            mint,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        Account::pack(account, &mut data).unwrap();
+        data
+    }
+
+    fn pack_config(min_deliverable_out: u64) -> Vec<u8> {
+        let mut data = vec![0; SwapConfig::LEN];
+        SwapConfig {
+            min_deliverable_out,
+            ..SwapConfig::default()
+        }
+        .pack(&mut data)
+        .unwrap();
+        data
+    }
+
+    /// Accounts for a swap against a 1:1 pool so small the quote rounds down to zero
+    fn swap_params(token_a_amount_in: u64) -> SwapParams {
+        SwapParams {
+            token_a_amount_in,
+            min_token_amount_out: 1,
+            ..SwapParams::default()
+        }
+    }
+
+    fn dust_swap_test_accounts(program_id: &Pubkey, min_deliverable_out: u64) -> Vec<TestAccount> {
+        let mut accounts = swap_test_accounts();
+        accounts[0].owner = *program_id;
+        accounts[0].data = pack_config(min_deliverable_out);
+        // program token a/b, pool coin/pc
+        for (index, mint) in [(1, Pubkey::default()), (2, PC_MINT), (4, Pubkey::default()), (5, PC_MINT)] {
+            accounts[index].set_token_data(pack_mint_token_account(mint, if index < 4 { 0 } else { 1 }));
+        }
+        // the quote rounds down to zero, the pool pays the client minimum of swap_params
+        credit_on_cpi(Some((accounts[2].key, 1)));
+        accounts
+    }
+
+    fn to_account_infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+        accounts
+            .iter_mut()
+            .map(|account| {
+                AccountInfo::new(
+                    &account.key,
+                    false,
+                    account.is_writable,
+                    &mut account.lamports,
+                    &mut account.data,
+                    &account.owner,
+                    false,
+                    0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_raydium_pool_accounts() {
+        let mut test_accounts = swap_test_accounts();
+        let keys: Vec<Pubkey> = test_accounts.iter().map(|account| account.key).collect();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        let pool = RaydiumPoolAccounts::from_accounts(&accounts).unwrap();
+        for (index, account) in [
+            pool.program_account,
+            pool.program_token_a_account,
+            pool.program_token_b_account,
+            pool.pool_program_id,
+            pool.pool_coin_token_account,
+            pool.pool_pc_token_account,
+            pool.spl_token_id,
+            pool.amm_id,
+            pool.amm_authority,
+            pool.amm_open_orders,
+            pool.amm_target,
+            pool.serum_market,
+            pool.serum_program_id,
+            pool.serum_bids,
+            pool.serum_asks,
+            pool.serum_event_queue,
+            pool.serum_coin_vault_account,
+            pool.serum_pc_vault_account,
+            pool.serum_vault_signer,
+        ]
+        .iter()
+        .enumerate()
+        {
+            assert_eq!(account.key, &keys[index], "account {}", index);
+        }
+        assert_eq!(pool.pool_program_id.key, &raydium_v4::id());
+
+        for (len, error) in [
+            (RaydiumPoolAccounts::LEN - 1, ProgramError::NotEnoughAccountKeys),
+            (RaydiumPoolAccounts::LEN + 1, ProgramError::InvalidArgument),
+        ] {
+            let mut test_accounts: Vec<TestAccount> =
+                (0..len).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(RaydiumPoolAccounts::from_accounts(&accounts).unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn test_swap_rejects_read_only_amm_accounts() {
+        // amm_id, amm_open_orders, amm_target
+        for index in [7, 9, 10] {
+            let mut test_accounts = swap_test_accounts();
+            test_accounts[index].is_writable = false;
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                swap(&accounts, &Pubkey::new_unique(), swap_params(100)),
+                Err(ProgramError::Custom(1005))
+            );
+        }
+    }
+
+    #[test]
+    fn test_handlers_reject_too_few_accounts() {
+        type Handler = fn(&Pubkey, &[AccountInfo]) -> ProgramResult;
+        let handlers: [(Handler, usize); 36] = [
+            (|p, a| create_program_account(p, a, 0, 0), CREATE_ACCOUNT_ACCOUNTS),
+            (|p, a| initialize(p, a, 0, 0), INITIALIZE_ACCOUNTS),
+            (|p, a| before_transfer(p, a, 1, 0), BEFORE_TRANSFER_ACCOUNTS),
+            (|p, a| swap(a, p, swap_params(1)), SWAP_ACCOUNTS),
+            (|p, a| swap_orca(a, p, 1, 0, 0), SWAP_ORCA_ACCOUNTS),
+            (|p, a| after_transfer(p, a, 0, 0, 0), AFTER_TRANSFER_ACCOUNTS),
+            (|p, a| harvest(p, a, 0), HARVEST_ACCOUNTS),
+            (|_, a| quote_swap(a, 1, 0), QUOTE_ACCOUNTS),
+            (|p, a| swap_serum_direct(a, p, 1, 0, 1), SWAP_SERUM_DIRECT_ACCOUNTS),
+            (|p, a| swap_saber(a, p, 1, 0, 0), SWAP_SABER_ACCOUNTS),
+            (|p, a| route(a, p, 0, 1, 0), ROUTE_ACCOUNTS),
+            (|p, a| set_min_deliverable_out(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_min_fee(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_min_swap(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_fee_split(p, a, 0, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_maintenance_window(p, a, 0, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_fee_token(p, a, FeeToken::Input, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_max_pool_consume(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_skip_funded_deposit(p, a, false, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_auto_correct_vaults(p, a, false, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_max_event_queue_fill(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_fee(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_fee_recipient(p, a, 0), SET_FEE_RECIPIENT_ACCOUNTS),
+            (|p, a| set_paused(p, a, false, 0), ADMIN_ACCOUNTS),
+            (|p, a| transfer_admin(p, a, 0), TRANSFER_ADMIN_ACCOUNTS),
+            (|p, a| accept_admin(p, a), ADMIN_ACCOUNTS),
+            (|p, a| set_mint_fee(p, a, &Pubkey::default(), 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| verify_config(p, a, false), ADMIN_ACCOUNTS),
+            (|p, a| sweep_lamports(p, a), SWEEP_LAMPORTS_ACCOUNTS),
+            (|p, a| withdraw_fees(p, a, 0), WITHDRAW_FEES_ACCOUNTS),
+            (|p, a| close_temp_account(p, a), CLOSE_TEMP_ACCOUNT_ACCOUNTS),
+            (|p, a| rescue_tokens(p, a, false), RESCUE_TOKENS_ACCOUNTS),
+            (|p, a| sweep_fees(p, a, 0), SWEEP_FEES_ACCOUNTS),
+            (|p, a| get_config(p, a), GET_CONFIG_ACCOUNTS),
+            (|p, a| set_default_slippage(p, a, 0, 0), ADMIN_ACCOUNTS),
+            (|p, a| set_protocol_enabled(p, a, 0, false, 0), ADMIN_ACCOUNTS),
+        ];
+        let program_id = Pubkey::new_unique();
+        for (index, (handler, count)) in handlers.iter().enumerate() {
+            let mut test_accounts: Vec<TestAccount> =
+                (1..*count).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                handler(&program_id, &accounts),
+                Err(ProgramError::NotEnoughAccountKeys),
+                "handler {}",
+                index
+            );
+        }
+    }
+
+
+    #[test]
+    fn test_handlers_reject_too_many_accounts() {
+        type Handler = fn(&Pubkey, &[AccountInfo]) -> ProgramResult;
+        let handlers: [(Handler, usize); 3] = [
+            (|p, a| swap_exact_out(a, p, 1, 0, raydium::SwapDirection::CoinToPc), SWAP_ACCOUNTS),
+            (|p, a| swap_native_in(a, p, 1, 0), layout::SWAP_NATIVE_IN_SWAP + SWAP_ACCOUNTS),
+            (|p, a| swap_two_hop(a, p, 1, 0), layout::SWAP_TWO_HOP_POOLS + 2 * RAYDIUM_POOL_ACCOUNTS),
+        ];
+        let program_id = Pubkey::new_unique();
+        for (index, (handler, count)) in handlers.iter().enumerate() {
+            for (len, result) in [
+                (count - 1, Err(ProgramError::NotEnoughAccountKeys)),
+                (count + 1, Err(ProgramError::InvalidArgument)),
+            ] {
+                let mut test_accounts: Vec<TestAccount> =
+                    (0..len).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+                let accounts = to_account_infos(&mut test_accounts);
+
+                assert_eq!(handler(&program_id, &accounts), result, "handler {} len {}", index, len);
+            }
+        }
+    }
+    #[test]
+    fn test_swap_rejects_unknown_pool_program() {
+        for pool_program_id in [Pubkey::new_unique(), orca::orca_v2::id(), spl_token::id()] {
+            let mut test_accounts = swap_test_accounts();
+            test_accounts[3].key = pool_program_id;
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                swap(&accounts, &Pubkey::new_unique(), swap_params(100)),
+                Err(ProgramError::Custom(1109))
+            );
+        }
+    }
+
+    #[test]
+    fn test_route_protocols() {
+        let pool_program_ids = [
+            raydium_v4::id(),
+            orca::orca_v2::id(),
+            saber_stable_swap::id(),
+            serum::serum_v3::id(),
+        ];
+        for (protocol, pool_program_id) in pool_program_ids.iter().enumerate() {
+            // the pool program of the next protocol is rejected
+            let mut test_accounts: Vec<TestAccount> =
+                (0..4).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+            test_accounts[3].key = pool_program_ids[(protocol + 1) % pool_program_ids.len()];
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                route(&accounts, &Pubkey::new_unique(), protocol as u8, 100, 0),
+                Err(ProgramError::Custom(1109)),
+                "protocol {}",
+                protocol
+            );
+
+            // its own is handed to the protocol handler, which wants more accounts
+            let mut test_accounts: Vec<TestAccount> =
+                (0..4).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+            test_accounts[3].key = *pool_program_id;
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                route(&accounts, &Pubkey::new_unique(), protocol as u8, 100, 0),
+                Err(ProgramError::NotEnoughAccountKeys),
+                "protocol {}",
+                protocol
+            );
+        }
+
+        let mut test_accounts = swap_test_accounts();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            route(&accounts, &Pubkey::new_unique(), 4, 100, 0),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_swap_accepts_writable_amm_accounts() {
+        let mut test_accounts = swap_test_accounts();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        // passes the writable checks and fails later reading empty pool vaults
+        assert_eq!(
+            swap(&accounts, &Pubkey::new_unique(), swap_params(100)),
+            Err(ProgramError::AccountDataTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_swap_rejects_dust_output() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 2);
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1)),
+            Err(ProgramError::Custom(1006))
+        );
+    }
+
+    #[test]
+    fn test_swap_rejects_zero_quote() {
+        let program_id = Pubkey::new_unique();
+        // a dust pool quotes zero, only a client minimum gives the swap a floor
+        for (min_token_amount_out, result) in [(0, Err(ProgramError::Custom(1124))), (1, Ok(()))] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            let accounts = to_account_infos(&mut test_accounts);
+            let params = SwapParams {
+                min_token_amount_out,
+                ..swap_params(1)
+            };
+            assert_eq!(swap(&accounts, &program_id, params), result);
+        }
+
+        // an empty pool has no quote at all
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+        test_accounts[4].set_token_data(pack_token_account(0));
+        test_accounts[5].set_token_data(pack_mint_token_account(PC_MINT, 0));
+        let accounts = to_account_infos(&mut test_accounts);
+        let params = SwapParams {
+            min_token_amount_out: 0,
+            ..swap_params(1)
+        };
+        assert!(swap(&accounts, &program_id, params).is_err());
+
+        assert_eq!(check_output_floor(0, 0), Err(AutoswapError::ZeroOutputQuote.into()));
+        assert_eq!(check_output_floor(0, 1), Ok(()));
+        assert_eq!(check_output_floor(1, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_swap_pool_consume_limit() {
+        let program_id = Pubkey::new_unique();
+        // swapping 1 into a reserve of 1 consumes 100% of it
+        for (max_pool_consume_bps, result) in [
+            (0, Ok(())),
+            (5_000, Err(ProgramError::Custom(1011))),
+            (10_000, Ok(())),
+        ] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            SwapConfig {
+                max_pool_consume_bps,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[0].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(swap(&accounts, &program_id, swap_params(1)), result);
+        }
+    }
+
+    #[test]
+    fn test_swap_event_queue_fill_limit() {
+        let program_id = Pubkey::new_unique();
+        // event queue with room for 10 events
+        for (count, result) in [
+            (0u64, Ok(())),
+            (8, Ok(())),
+            (9, Err(ProgramError::Custom(1107))),
+            (10, Err(ProgramError::Custom(1106))),
+        ] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            SwapConfig {
+                max_event_queue_fill_bps: 8_000,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[0].data)
+            .unwrap();
+            let mut event_queue = vec![0; 44 + 10 * 88];
+            event_queue[..5].copy_from_slice(b"serum");
+            event_queue[21..29].copy_from_slice(&count.to_le_bytes());
+            test_accounts[15].data = event_queue;
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(swap(&accounts, &program_id, swap_params(1)), result);
+        }
+    }
+
+    /// Pool of 1_000_000 coin and 1_000 pc with a v4 amm state naming the vault mints,
+    /// the vaults are passed in reverse order if swapped is set
+    fn vault_order_test_accounts(
+        program_id: &Pubkey,
+        auto_correct_vaults: bool,
+        swapped: bool,
+    ) -> Vec<TestAccount> {
+        let mut accounts = dust_swap_test_accounts(program_id, 0);
+        SwapConfig {
+            auto_correct_vaults,
+            max_pool_consume_bps: 1_000,
+            ..SwapConfig::default()
+        }
+        .pack(&mut accounts[0].data)
+        .unwrap();
+        let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut amm_id = vec![0; raydium::AmmInfoV4::LEN];
+        // coin and pc vaults, mints, then lp mint and open orders
+        amm_id[336..368].copy_from_slice(accounts[4].key.as_ref());
+        amm_id[368..400].copy_from_slice(accounts[5].key.as_ref());
+        amm_id[400..432].copy_from_slice(coin_mint.as_ref());
+        amm_id[432..464].copy_from_slice(pc_mint.as_ref());
+        amm_id[496..528].copy_from_slice(accounts[9].key.as_ref());
+        accounts[7].data = amm_id;
+        // program token a/b, pool coin/pc
+        for &(index, mint, amount) in &[
+            (1, coin_mint, 0),
+            (2, pc_mint, 0),
+            (4, coin_mint, 1_000_000),
+            (5, pc_mint, 1_000),
+        ] {
+            let mut data = vec![0; Account::LEN];
+            Account::pack(
+                Account {
+                    mint,
+                    amount,
+                    state: AccountState::Initialized,
+                    ..Account::default()
+                },
+                &mut data,
+            )
+            .unwrap();
+            accounts[index].data = data;
+        }
+        if swapped {
+            accounts.swap(4, 5);
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_check_vaults_swapped() {
+        let program_id = Pubkey::new_unique();
+        for swapped in [false, true] {
+            let mut test_accounts = vault_order_test_accounts(&program_id, false, swapped);
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                raydium::check_vaults_swapped(&accounts[7], &accounts[4], &accounts[5]),
+                Ok(swapped)
+            );
+        }
+
+        // a vault the amm doesn't record, even of the right mint
+        let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+        test_accounts[5].key = Pubkey::new_unique();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            raydium::check_vaults_swapped(&accounts[7], &accounts[4], &accounts[5]),
+            Err(ProgramError::Custom(1122))
+        );
+        for auto_correct_vaults in [false, true] {
+            let mut test_accounts = vault_order_test_accounts(&program_id, auto_correct_vaults, false);
+            test_accounts[4].key = Pubkey::new_unique();
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(swap(&accounts, &program_id, swap_params(1_000)), Err(ProgramError::Custom(1122)));
+        }
+
+        // the v2 and v3 layouts keep the vaults after their 22 and 25 u64 fields
+        for (len, token_coin_offset) in [(624, 176), (680, 200)] {
+            let mut test_accounts = swap_test_accounts();
+            let mut amm_id = vec![0; len];
+            amm_id[token_coin_offset..token_coin_offset + 32].copy_from_slice(test_accounts[5].key.as_ref());
+            amm_id[token_coin_offset + 32..token_coin_offset + 64].copy_from_slice(test_accounts[4].key.as_ref());
+            test_accounts[7].data = amm_id;
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(raydium::check_vaults_swapped(&accounts[7], &accounts[4], &accounts[5]), Ok(true));
+        }
+
+        // an amm that can't be read doesn't skip the check
+        let mut test_accounts = swap_test_accounts();
+        test_accounts[7].data = vec![0; raydium::AmmInfoV4::LEN + 1];
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            raydium::check_vaults_swapped(&accounts[7], &accounts[4], &accounts[5]),
+            Err(ProgramError::Custom(1122))
+        );
+        let mut test_accounts = swap_test_accounts();
+        test_accounts[7].owner = Pubkey::new_unique();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            raydium::check_vaults_swapped(&accounts[7], &accounts[4], &accounts[5]),
+            Err(ProgramError::Custom(1122))
+        );
+    }
+
+    #[test]
+    fn test_check_swap_direction() {
+        for (token_a_amount_in, token_b_amount_in, expected) in [
+            (1_000, 0, Ok(())),
+            (0, 1_000, Ok(())),
+            (0, 0, Err(ProgramError::Custom(1128))),
+            (1_000, 1_000, Err(ProgramError::Custom(1128))),
+        ] {
+            assert_eq!(check_swap_direction(token_a_amount_in, token_b_amount_in), expected);
+        }
+
+        let program_id = Pubkey::new_unique();
+        for amount_in in [0, 1_000] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            let accounts = to_account_infos(&mut test_accounts);
+            let params = SwapParams {
+                token_b_amount_in: amount_in,
+                ..swap_params(amount_in)
+            };
+            assert_eq!(swap(&accounts, &program_id, params), Err(ProgramError::Custom(1128)));
+        }
+    }
+
+    #[test]
+    fn test_swap_rejects_same_mint() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+        test_accounts[2].set_token_data(pack_token_account(0));
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1_000)),
+            Err(ProgramError::Custom(1127))
+        );
+    }
+
+    #[test]
+    fn test_swap_rejects_disabled_protocol() {
+        let program_id = Pubkey::new_unique();
+        let mut config = SwapConfig::default();
+        config.set_protocol_enabled(ProgramIDType::Raydium, false);
+        let mut config_data = vec![0; SwapConfig::LEN];
+        config.pack(&mut config_data).unwrap();
+
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+        test_accounts[0].data = config_data.clone();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1_000)),
+            Err(ProgramError::Custom(1131))
+        );
+
+        // orca pools still swap
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        test_accounts[0].data = config_data;
+        credit_on_cpi(Some((test_accounts[2].key, 1)));
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(swap_orca(&accounts, &program_id, 1, 0, 1), Ok(()));
+        credit_on_cpi(None);
+
+        config.set_protocol_enabled(ProgramIDType::Orca, false);
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        config.pack(&mut test_accounts[0].data).unwrap();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_orca(&accounts, &program_id, 1, 0, 1),
+            Err(ProgramError::Custom(1131))
+        );
+    }
+
+    #[test]
+    fn test_swap_rejects_foreign_open_orders() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+        // open orders of another market, its balances would skew the quote
+        test_accounts[9].key = Pubkey::new_unique();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1_000)),
+            Err(ProgramError::Custom(1130))
+        );
+
+        // the v3 layout records the open orders after its 25 u64 fields
+        let mut test_accounts = swap_test_accounts();
+        let mut amm_id = vec![0; 680];
+        amm_id[360..392].copy_from_slice(test_accounts[9].key.as_ref());
+        test_accounts[7].data = amm_id;
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(raydium::check_open_orders(&accounts[7], &accounts[9]), Ok(()));
+        assert_eq!(
+            raydium::check_open_orders(&accounts[7], &accounts[10]),
+            Err(ProgramError::Custom(1130))
+        );
+
+        // an amm of an unknown layout can't vouch for any open orders
+        let mut test_accounts = swap_test_accounts();
+        test_accounts[7].data = vec![0; 3_000];
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            raydium::check_open_orders(&accounts[7], &accounts[9]),
+            Err(ProgramError::Custom(1122))
+        );
+    }
+
+    #[test]
+    fn test_swap_rejects_wrong_token_mint() {
+        let program_id = Pubkey::new_unique();
+        for index in [1, 2] {
+            let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+            test_accounts[index].set_token_data(pack_token_account(0));
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                swap(&accounts, &program_id, swap_params(1_000)),
+                Err(ProgramError::Custom(1114))
+            );
+        }
+
+        // program token accounts passed in each other's place
+        let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+        test_accounts.swap(1, 2);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1_000)),
+            Err(ProgramError::Custom(1114))
+        );
+    }
+
+    #[test]
+    fn test_swap_vaults_swapped() {
+        let program_id = Pubkey::new_unique();
+        // 50_000 coin is 5% of the coin reserve, but far above 10% of the pc one
+        let params = swap_params(50_000);
+
+        let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+        let accounts = to_account_infos(&mut test_accounts);
+        // gets past the pre-swap checks, then nothing arrives from the stubbed CPI
+        let ordered = swap(&accounts, &program_id, params);
+        assert_eq!(ordered, Err(ProgramError::Custom(1004)));
+
+        // strict mode rejects
+        let mut test_accounts = vault_order_test_accounts(&program_id, false, true);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(swap(&accounts, &program_id, params), Err(ProgramError::Custom(1105)));
+
+        // auto-correct swaps in the right direction
+        let mut test_accounts = vault_order_test_accounts(&program_id, true, true);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(swap(&accounts, &program_id, params), ordered);
+    }
+
+    #[test]
+    fn test_build_swap_instruction() {
+        let mut test_accounts = swap_test_accounts();
+        let accounts = to_account_infos(&mut test_accounts);
+        let keys: Vec<Pubkey> = accounts.iter().map(|account| *account.key).collect();
+
+        let instruction =
+            raydium::build_swap_instruction(&accounts, 1_000, 900, raydium::SwapDirection::CoinToPc)
+                .unwrap();
+        assert_eq!(instruction.program_id, raydium_v4::id());
+        let mut data = vec![9];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        data.extend_from_slice(&900u64.to_le_bytes());
+        assert_eq!(instruction.data, data);
+
+        // Raydium order: spl token, amm, serum accounts, then source, destination, authority
+        let expected = [6, 7, 8, 9, 10, 4, 5, 12, 11, 13, 14, 15, 16, 17, 18, 1, 2, 0];
+        let metas: Vec<(Pubkey, bool, bool)> = instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_writable, meta.is_signer))
+            .collect();
+        let expected_metas: Vec<(Pubkey, bool, bool)> = expected
+            .iter()
+            .map(|&index| (keys[index], ![6, 8, 12, 18, 0].contains(&index), index == 0))
+            .collect();
+        assert_eq!(metas, expected_metas);
+
+        // the other way round only swaps source and destination
+        let instruction =
+            raydium::build_swap_instruction(&accounts, 1_000, 900, raydium::SwapDirection::PcToCoin)
+                .unwrap();
+        assert_eq!(instruction.accounts[15].pubkey, keys[2]);
+        assert_eq!(instruction.accounts[16].pubkey, keys[1]);
+    }
+
+    #[test]
+    fn test_build_swap_instruction_invalid_accounts() {
+        let mut test_accounts = swap_test_accounts();
+        test_accounts[3].key = Pubkey::new_unique();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            raydium::build_swap_instruction(&accounts, 1, 0, raydium::SwapDirection::CoinToPc),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            raydium::build_swap_instruction(&accounts[..18], 1, 0, raydium::SwapDirection::CoinToPc),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    /// Swap accounts against a pool of 1_000_000 coin and 2_000_000 pc
+    fn exact_out_test_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mut accounts = dust_swap_test_accounts(program_id, 0);
+        accounts[4].set_token_data(pack_token_account(1_000_000));
+        accounts[5].set_token_data(pack_mint_token_account(PC_MINT, 2_000_000));
+        credit_on_cpi(None);
+        accounts
+    }
+
+    #[test]
+    fn test_swap_exact_out_max_input() {
+        let program_id = Pubkey::new_unique();
+        for (direction, reserve_in, reserve_out) in [
+            (raydium::SwapDirection::CoinToPc, 1_000_000, 2_000_000),
+            (raydium::SwapDirection::PcToCoin, 2_000_000, 1_000_000),
+        ] {
+            let amount_in =
+                quote::get_amount_in(10_000, reserve_in, reserve_out, raydium::RAYDIUM_FEE).unwrap();
+            let mut test_accounts = exact_out_test_accounts(&program_id);
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                swap_exact_out(&accounts, &program_id, 10_000, amount_in - 1, direction),
+                Err(ProgramError::Custom(1012))
+            );
+            // within the limit the swap runs, then nothing arrives from the stubbed CPI
+            assert_eq!(
+                swap_exact_out(&accounts, &program_id, 10_000, amount_in, direction),
+                Err(ProgramError::Custom(1004))
+            );
+        }
+    }
+
+    #[test]
+    fn test_swap_exact_out_invalid() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = exact_out_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let direction = raydium::SwapDirection::CoinToPc;
+
+        assert_eq!(
+            swap_exact_out(&accounts, &program_id, 0, 1_000, direction),
+            Err(ProgramError::InvalidArgument)
+        );
+        // more than the pool holds
+        assert_eq!(
+            swap_exact_out(&accounts, &program_id, 2_000_000, u64::MAX, direction),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            swap_exact_out(&accounts[..18], &program_id, 10_000, u64::MAX, direction),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_swap_exact_out_instruction() {
+        let instruction = AmmInstruction::SwapExactOut {
+            token_amount_out: 10_000,
+            max_token_amount_in: 5_500,
+            direction: raydium::SwapDirection::PcToCoin,
+        };
+        let mut data = [0; AmmInstruction::SWAP_EXACT_OUT_LEN];
+        assert_eq!(instruction.pack(&mut data), Ok(AmmInstruction::SWAP_EXACT_OUT_LEN));
+        assert_eq!(AmmInstruction::unpack(&data), Ok(instruction));
+
+        data[17] = 2;
+        assert_eq!(AmmInstruction::unpack(&data), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_swap_dust_check_disabled() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(swap(&accounts, &program_id, swap_params(1)), Ok(()));
+    }
+
+    fn config_test_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id);
+        let mut accounts = vec![
+            TestAccount::new(main_router_admin::id()),
+            TestAccount::new(program_account),
+        ];
+        accounts[1].owner = *program_id;
+        accounts[1].data = pack_config(0);
+        accounts
+    }
+
+    fn corrupt_config(account: &mut TestAccount) {
+        SwapConfig {
+            admin: Pubkey::default(),
+            fee_bps: 25_000,
+            ..SwapConfig::default()
+        }
+        .pack(&mut account.data)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_verify_config_report_only() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        corrupt_config(&mut test_accounts[1]);
+        let corrupted = test_accounts[1].data.clone();
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(verify_config(&program_id, &accounts, false), Ok(()));
+        assert_eq!(*accounts[1].data.borrow(), &corrupted[..]);
+    }
+
+    #[test]
+    fn test_verify_config_repair() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        corrupt_config(&mut test_accounts[1]);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(verify_config(&program_id, &accounts, true), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                fee_bps: SwapConfig::MAX_FEE_BPS,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_config_requires_stored_admin() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        SwapConfig {
+            admin: Pubkey::new_unique(),
+            ..SwapConfig::default()
+        }
+        .pack(&mut test_accounts[1].data)
+        .unwrap();
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(
+            set_min_fee(&program_id, &accounts, 5_000, 0),
+            Err(ProgramError::Custom(1112))
+        );
+    }
+
+    #[test]
+    fn test_set_min_deliverable_out() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_min_deliverable_out(&program_id, &accounts, 5_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                min_deliverable_out: 5_000,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_min_deliverable_out_requires_admin() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        test_accounts[0].key = Pubkey::new_unique();
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(
+            set_min_deliverable_out(&program_id, &accounts, 5_000, 0),
+            Err(ProgramError::Custom(1112))
+        );
+
+        accounts[0].is_signer = false;
+        assert_eq!(
+            set_min_deliverable_out(&program_id, &accounts, 5_000, 0),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_set_default_slippage() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_default_slippage(&program_id, &accounts, 50, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                default_slippage_bps: 50,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        assert_eq!(
+            set_default_slippage(&program_id, &accounts, 10_001, 1),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_set_protocol_enabled() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        let raydium = ProgramIDType::Raydium.route_protocol().unwrap();
+        assert_eq!(set_protocol_enabled(&program_id, &accounts, raydium, false, 0), Ok(()));
+        let config = SwapConfig::load(&accounts[1], &program_id).unwrap();
+        assert_eq!(config.check_protocol_enabled(ProgramIDType::Raydium), Err(ProgramError::Custom(1131)));
+        assert_eq!(config.check_protocol_enabled(ProgramIDType::Orca), Ok(()));
+        assert_eq!(config.sequence, 1);
+
+        assert_eq!(set_protocol_enabled(&program_id, &accounts, raydium, true, 1), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id).unwrap().enabled_protocols,
+            SwapConfig::ALL_PROTOCOLS
+        );
+        assert_eq!(
+            set_protocol_enabled(&program_id, &accounts, u8::MAX, false, 2),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_set_fee() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        SwapConfig {
+            lp_fee_bps: 20,
+            ..SwapConfig::default()
+        }
+        .pack(&mut test_accounts[1].data)
+        .unwrap();
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_fee(&program_id, &accounts, 30, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                fee_bps: 30,
+                lp_fee_bps: 20,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        // up to the cap with the lp fee is accepted, one bps above isn't
+        assert_eq!(set_fee(&program_id, &accounts, SwapConfig::MAX_FEE_BPS - 20, 1), Ok(()));
+        assert_eq!(
+            set_fee(&program_id, &accounts, SwapConfig::MAX_FEE_BPS - 19, 2),
+            Err(ProgramError::Custom(1129))
+        );
+        assert_eq!(set_fee(&program_id, &accounts, 10_001, 2), Err(ProgramError::Custom(1129)));
+
+        // a protocol fee under the cap alone still counts the lp fee
+        SwapConfig {
+            lp_fee_bps: 50,
+            ..SwapConfig::default()
+        }
+        .pack(&mut accounts[1].try_borrow_mut_data().unwrap())
+        .unwrap();
+        assert_eq!(
+            set_fee(&program_id, &accounts, 60, 0),
+            Err(ProgramError::Custom(1129))
+        );
+    }
+
+    #[test]
+    fn test_set_fee_recipient() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let fee_recipient = Pubkey::new_unique();
+        test_accounts.push(TestAccount::new(fee_recipient));
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_fee_recipient(&program_id, &accounts, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                fee_recipient,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        assert_eq!(
+            set_fee_recipient(&program_id, &accounts[..2], 1),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_set_fee_rejects_wrong_signer() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        test_accounts[0].key = Pubkey::new_unique();
+        test_accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_fee(&program_id, &accounts, 30, 0), Err(ProgramError::Custom(1112)));
+        assert_eq!(
+            set_fee_recipient(&program_id, &accounts, 0),
+            Err(ProgramError::Custom(1112))
+        );
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_transfer_admin() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let new_admin = Pubkey::new_unique();
+        test_accounts.push(TestAccount::new(new_admin));
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+        accounts[2].is_signer = true;
+        let accept_accounts = [accounts[2].clone(), accounts[1].clone()];
+
+        // nothing to accept before a proposal
+        assert_eq!(accept_admin(&program_id, &accept_accounts), Err(ProgramError::Custom(1112)));
+
+        assert_eq!(transfer_admin(&program_id, &accounts, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                pending_admin: new_admin,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        // only the proposed admin accepts
+        assert_eq!(accept_admin(&program_id, &accounts[..2]), Err(ProgramError::Custom(1112)));
+
+        assert_eq!(accept_admin(&program_id, &accept_accounts), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                admin: new_admin,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        // the previous admin is locked out
+        assert_eq!(set_fee(&program_id, &accounts[..2], 30, 1), Err(ProgramError::Custom(1112)));
+        assert_eq!(set_fee(&program_id, &accept_accounts, 30, 1), Ok(()));
+    }
+
+    #[test]
+    fn test_set_mint_fee() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+        let mint = Pubkey::new_unique();
+
+        assert_eq!(set_mint_fee(&program_id, &accounts, &mint, 10, 0), Ok(()));
+        let config = SwapConfig::load(&accounts[1], &program_id).unwrap();
+        assert_eq!((config.fee_bps_for(&mint), config.sequence), (10, 1));
+        assert_eq!(config.fee_bps_for(&Pubkey::new_unique()), SwapConfig::DEFAULT_FEE_BPS);
+
+        accounts[0].is_signer = false;
+        assert_eq!(
+            set_mint_fee(&program_id, &accounts, &mint, 20, 1),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_transfer_admin_rejects_wrong_signer() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        test_accounts[0].key = Pubkey::new_unique();
+        test_accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(transfer_admin(&program_id, &accounts, 0), Err(ProgramError::Custom(1112)));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig::default())
+        );
+
+        // the admin key without its signature
+        let mut test_accounts = config_test_accounts(&program_id);
+        test_accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            transfer_admin(&program_id, &accounts, 0),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_swap_paused() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+        SwapConfig {
+            paused: true,
+            ..SwapConfig::default()
+        }
+        .pack(&mut test_accounts[0].data)
+        .unwrap();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            swap(&accounts, &program_id, swap_params(1)),
+            Err(ProgramError::Custom(1113))
+        );
+        SwapConfig::default()
+            .pack(&mut accounts[0].try_borrow_mut_data().unwrap())
+            .unwrap();
+        assert_eq!(swap(&accounts, &program_id, swap_params(1)), Ok(()));
+    }
+
+    #[test]
+    fn test_set_paused() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_paused(&program_id, &accounts, true, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id).unwrap().check_not_paused(),
+            Err(ProgramError::Custom(1113))
+        );
+        // admin instructions still go through while paused
+        assert_eq!(set_paused(&program_id, &accounts, false, 1), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                sequence: 2,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    /// token program, program, program sol, user, admin
+    fn harvest_test_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mut test_accounts = config_test_accounts(program_id);
+        test_accounts.swap(0, 1);
+        test_accounts.insert(0, TestAccount::new(spl_token::id()));
+        for balance in [1_000, 0] {
+            let mut token_account = TestAccount::new(Pubkey::new_unique());
+            token_account.set_token_data(pack_token_account(balance));
+            test_accounts.insert(test_accounts.len() - 1, token_account);
+        }
+        test_accounts
+    }
+
+    #[test]
+    fn test_harvest_requires_admin() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = harvest_test_accounts(&program_id);
+        test_accounts[4].key = Pubkey::new_unique();
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[4].is_signer = true;
+        assert_eq!(harvest(&program_id, &accounts, 400), Err(ProgramError::Custom(1112)));
+        drop(accounts);
+
+        let mut test_accounts = harvest_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            harvest(&program_id, &accounts, 400),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        accounts[4].is_signer = true;
+        assert_eq!(harvest(&program_id, &accounts, 400), Ok(()));
+    }
+
+    #[test]
+    fn test_harvest_amount() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = harvest_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[4].is_signer = true;
+
+        assert_eq!(harvest(&program_id, &accounts, 0), Err(ProgramError::Custom(1117)));
+        assert_eq!(harvest(&program_id, &accounts, 1_001), Err(ProgramError::Custom(1101)));
+        assert_eq!(harvest(&program_id, &accounts, 400), Ok(()));
+        assert_eq!(harvest(&program_id, &accounts, 1_000), Ok(()));
+    }
+
+    /// token program, program, program kin, program sol, destination, fee recipient,
+    /// kin mint, sol mint
+    fn after_transfer_test_accounts() -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> =
+            (0..8).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        accounts[0].key = spl_token::id();
+        for account in accounts.iter_mut().skip(2) {
+            account.owner = spl_token::id();
+            account.set_token_data(pack_token_account(1_000));
+        }
+        // kin side pays out in the kin mint, the fee is drawn from the sol side
+        let (kin_mint, sol_mint) = (accounts[6].key, accounts[7].key);
+        for (index, mint) in [(2, kin_mint), (3, sol_mint), (4, kin_mint), (5, sol_mint)] {
+            let mut token_account = Account::unpack(&accounts[index].data).unwrap();
+            token_account.mint = mint;
+            Account::pack(token_account, &mut accounts[index].data).unwrap();
+        }
+        for account in accounts.iter_mut().skip(6) {
+            account.data = vec![0; Mint::LEN];
+            Mint::pack(
+                Mint {
+                    decimals: 6,
+                    is_initialized: true,
+                    ..Mint::default()
+                },
+                &mut account.data,
+            )
+            .unwrap();
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_after_transfer_shared_token_program() {
+        let mut test_accounts = after_transfer_test_accounts();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_after_transfer_replay_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = after_transfer_test_accounts();
+        test_accounts[1].owner = program_id;
+        test_accounts[1].data = vec![0; SwapConfig::LEN];
+        SwapConfig {
+            swap_phase: SwapPhase::Swapped,
+            ..SwapConfig::default()
+        }
+        .pack(&mut test_accounts[1].data)
+        .unwrap();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&program_id, &accounts, 1_000, 0, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id).unwrap().swap_phase,
+            SwapPhase::Idle
+        );
+        assert_eq!(
+            after_transfer(&program_id, &accounts, 1_000, 0, 0),
+            Err(ProgramError::Custom(1116))
+        );
+    }
+
+    #[test]
+    fn test_before_transfer_starts_swap_sequence() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 1_000, true);
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(before_transfer(&program_id, &accounts, 1_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[5], &program_id).unwrap().swap_phase,
+            SwapPhase::Deposited
+        );
+    }
+
+    #[test]
+    fn test_after_transfer_separate_fee_token_program() {
+        let mut test_accounts = after_transfer_test_accounts();
+        test_accounts.push(TestAccount::new(spl_token::id()));
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_after_transfer_rejects_fee_source_as_recipient() {
+        let mut test_accounts = after_transfer_test_accounts();
+        // the fee is drawn from program sol by default
+        test_accounts[5].key = test_accounts[3].key;
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0),
+            Err(ProgramError::Custom(1110))
+        );
+    }
+
+    #[test]
+    fn test_after_transfer_rejects_wrong_fee_recipient() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = after_transfer_test_accounts();
+        let fee_recipient = test_accounts[5].key;
+        for (pinned, result) in [
+            (Pubkey::new_unique(), Err(ProgramError::Custom(1110))),
+            (fee_recipient, Ok(())),
+        ] {
+            test_accounts[1].owner = program_id;
+            test_accounts[1].data = vec![0; SwapConfig::LEN];
+            SwapConfig {
+                fee_recipient: pinned,
+                swap_phase: SwapPhase::Swapped,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[1].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(after_transfer(&program_id, &accounts, 1_000, 0, 0), result);
+        }
+    }
+
+    #[test]
+    fn test_after_transfer_rejects_wrong_mint_fee_recipient() {
+        let mut test_accounts = after_transfer_test_accounts();
+        // fee recipient of the payout mint
+        let mut token_account = Account::unpack(&test_accounts[5].data).unwrap();
+        token_account.mint = test_accounts[6].key;
+        Account::pack(token_account, &mut test_accounts[5].data).unwrap();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0),
+            Err(ProgramError::Custom(1110))
+        );
+    }
+
+    #[test]
+    fn test_after_transfer_rejects_mismatched_token_programs() {
+        // payout destination, fee recipient under a different token program
+        for index in [4, 5] {
+            let mut test_accounts = after_transfer_test_accounts();
+            test_accounts[index].owner = Pubkey::new_unique();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0),
+                Err(ProgramError::Custom(1007))
+            );
+        }
+
+        // fee token program does not own the fee side accounts
+        let mut test_accounts = after_transfer_test_accounts();
+        test_accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0),
+            Err(ProgramError::Custom(1007))
+        );
+    }
+
+    #[test]
+    fn test_fee_amount_minimum_applied() {
+        let config = SwapConfig {
+            min_fee: 5_000,
+            ..SwapConfig::default()
+        };
+        // 0.5% of 100 rounds to zero
+        assert_eq!(get_fee_amounts(100, &config, 1_000_000), Ok((5_000, 0)));
+        // but never more than the program holds
+        assert_eq!(get_fee_amounts(100, &config, 3_000), Ok((3_000, 0)));
+        assert_eq!(get_fee_amounts(100, &SwapConfig::default(), 1_000_000), Ok((0, 0)));
+    }
+
+    #[test]
+    fn test_fee_amount_bounds() {
+        let config = SwapConfig::default();
+        assert_eq!(SwapConfig::DEFAULT_FEE_BPS, 50);
+        assert_eq!(get_fee_amounts(0, &config, u64::MAX), Ok((0, 0)));
+        // 0.5% rounds down, the first whole unit of fee is at 200
+        assert_eq!(get_fee_amounts(199, &config, u64::MAX), Ok((0, 0)));
+        assert_eq!(get_fee_amounts(200, &config, u64::MAX), Ok((1, 0)));
+        assert_eq!(get_fee_amounts(399, &config, u64::MAX), Ok((1, 0)));
+        assert_eq!(
+            get_fee_amounts(u64::MAX, &config, u64::MAX),
+            Ok((u64::MAX / 200, 0))
+        );
+        // a fee above 100% of u64::MAX doesn't fit the payout
+        let config = SwapConfig {
+            fee_bps: 10_000,
+            lp_fee_bps: 1,
+            ..SwapConfig::default()
+        };
+        assert!(get_fee_amounts(u64::MAX / 2, &config, u64::MAX).is_ok());
+        assert_eq!(
+            get_fee_amounts(u64::MAX, &config, u64::MAX),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn test_fee_amount_percentage_dominates() {
+        let config = SwapConfig {
+            min_fee: 5_000,
+            ..SwapConfig::default()
+        };
+        assert_eq!(
+            get_fee_amounts(100_000_000, &config, 1_000_000_000),
+            Ok((500_000, 0))
+        );
+    }
+
+    #[test]
+    fn test_fee_amounts_split() {
+        let config = SwapConfig {
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            ..SwapConfig::default()
+        };
+        assert_eq!(
+            get_fee_amounts(100_000_000, &config, 1_000_000_000),
+            Ok((300_000, 200_000))
+        );
+    }
+
+    #[test]
+    fn test_fee_amounts_split_conserves_total() {
+        let config = SwapConfig {
+            fee_bps: 33,
+            lp_fee_bps: 17,
+            min_fee: 3,
+            ..SwapConfig::default()
+        };
+        for amount in [0, 1, 333, 9_999, 123_456_789, u64::MAX] {
+            let total = ((amount as u128 * 50 / 10_000) as u64).max(3);
+            let (fee, lp_fee) = get_fee_amounts(amount, &config, u64::MAX).unwrap();
+            assert_eq!(fee + lp_fee, total);
+            assert!(lp_fee <= fee);
+        }
+    }
+
+    #[test]
+    fn test_after_transfer_routes_lp_fee() {
+        let mut test_accounts = after_transfer_test_accounts();
+        test_accounts[1].owner = Pubkey::new_unique();
+        let program_id = test_accounts[1].owner;
+        test_accounts[1].data = vec![0; SwapConfig::LEN];
+        let config = SwapConfig {
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            swap_phase: SwapPhase::Swapped,
+            ..SwapConfig::default()
+        };
+        config.pack(&mut test_accounts[1].data).unwrap();
+
+        // lp fee recipient missing
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            after_transfer(&program_id, &accounts, 1_000_000, 0, 0),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        drop(accounts);
+
+        // the runtime reverts the phase change of the failed call
+        config.pack(&mut test_accounts[1].data).unwrap();
+        test_accounts.push(TestAccount::new(spl_token::id()));
+        let mut lp_fee_recipient = TestAccount::new(Pubkey::new_unique());
+        lp_fee_recipient.owner = spl_token::id();
+        lp_fee_recipient.set_token_data(pack_token_account(0));
+        test_accounts.push(lp_fee_recipient);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(after_transfer(&program_id, &accounts, 1_000_000, 0, 0), Ok(()));
+    }
+
+    /// after_transfer accounts with the fee token program, an lp fee recipient
+    /// slot and a referrer appended
+    fn referral_test_accounts(referrer: Pubkey) -> Vec<TestAccount> {
+        let mut test_accounts = after_transfer_test_accounts();
+        test_accounts.push(TestAccount::new(spl_token::id()));
+        let fee_recipient = test_accounts[5].key;
+        let mut lp_fee_recipient = TestAccount::new(fee_recipient);
+        lp_fee_recipient.set_token_data(pack_token_account(0));
+        test_accounts.push(lp_fee_recipient);
+        let mut referrer_account = TestAccount::new(referrer);
+        referrer_account.set_token_data(pack_token_account(0));
+        test_accounts.push(referrer_account);
+        test_accounts
+    }
+
+    #[test]
+    fn test_referral_amounts() {
+        // zero referral leaves the whole fee to the fee recipient
+        assert_eq!(get_referral_amounts(3_000, 0), Ok((3_000, 0)));
+        assert_eq!(get_referral_amounts(3_000, 5_000), Ok((1_500, 1_500)));
+        // rounding favours the fee recipient
+        assert_eq!(get_referral_amounts(3_001, 5_000), Ok((1_501, 1_500)));
+        assert_eq!(get_referral_amounts(3_000, 10_000), Ok((0, 3_000)));
+        assert_eq!(get_referral_amounts(u64::MAX, 10_000), Ok((0, u64::MAX)));
+    }
+
+    #[test]
+    fn test_after_transfer_zero_referral() {
+        let mut test_accounts = referral_test_accounts(Pubkey::new_unique());
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 0, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_after_transfer_referral_split() {
+        let mut test_accounts = referral_test_accounts(Pubkey::new_unique());
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 5_000, 0), Ok(()));
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 10_001, 0),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_after_transfer_referrer_checks() {
+        // the referrer is a fee recipient like the others
+        let mut test_accounts = referral_test_accounts(Pubkey::new_unique());
+        test_accounts[10].owner = Pubkey::new_unique();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 5_000, 0),
+            Err(ProgramError::Custom(1007))
+        );
+        drop(accounts);
+
+        let mut test_accounts = referral_test_accounts(Pubkey::new_unique());
+        test_accounts[10].key = test_accounts[3].key;
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 5_000, 0),
+            Err(ProgramError::Custom(1110))
+        );
+    }
+
+    #[test]
+    fn test_after_transfer_no_referrer_sentinel() {
+        // the zero id is skipped before any check, it isn't a token account
+        let mut test_accounts = referral_test_accounts(zero::id());
+        test_accounts[10].owner = Pubkey::default();
+        test_accounts[10].data = vec![];
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(after_transfer(&Pubkey::new_unique(), &accounts, 1_000, 5_000, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_payout_amounts_fee_from_input() {
+        let config = SwapConfig {
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            ..SwapConfig::default()
+        };
+        // fees are a share of the swap amount, the output is paid out whole
+        assert_eq!(
+            get_payout_amounts(1_000_000, &config, 250_000, 2_000_000, 0),
+            Ok((250_000, 3_000, 2_000))
+        );
+    }
+
+    #[test]
+    fn test_payout_amounts_explicit_fee() {
+        let config = SwapConfig {
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            ..SwapConfig::default()
+        };
+        // above the bps fee of 50, split by bps share
+        assert_eq!(
+            get_payout_amounts(10_000, &config, 250_000, 2_000_000, 100),
+            Ok((250_000, 60, 40))
+        );
+        let output_config = SwapConfig {
+            fee_token: FeeToken::Output,
+            ..config
+        };
+        // the bps fee of the output is 1_250
+        assert_eq!(
+            get_payout_amounts(10_000, &output_config, 250_000, 2_000_000, 1_300),
+            Ok((248_700, 780, 520))
+        );
+        assert_eq!(
+            get_payout_amounts(10_000, &output_config, 250_000, 2_000_000, 100),
+            Err(ProgramError::Custom(1117))
+        );
+
+        assert_eq!(
+            get_payout_amounts(10_000, &config, 250_000, 99, 100),
+            Err(ProgramError::Custom(1101))
+        );
+        // an explicit fee can't undercut the bps fee of the swap amount
+        assert_eq!(
+            get_payout_amounts(1_000_000, &config, 250_000, 2_000_000, 100),
+            Err(ProgramError::Custom(1117))
+        );
+        assert_eq!(
+            get_payout_amounts(1_000_000, &config, 250_000, 2_000_000, 5_000),
+            Ok((250_000, 3_000, 2_000))
+        );
+        let config = SwapConfig {
+            min_fee: 101,
+            ..config
+        };
+        assert_eq!(
+            get_payout_amounts(10_000, &config, 250_000, 2_000_000, 100),
+            Err(ProgramError::Custom(1117))
+        );
+    }
+
+    #[test]
+    fn test_payout_amounts_fee_from_output() {
+        let config = SwapConfig {
+            fee_bps: 30,
+            lp_fee_bps: 20,
+            fee_token: FeeToken::Output,
+            ..SwapConfig::default()
+        };
+        // fees are a share of the output, the user receives the remainder
+        assert_eq!(
+            get_payout_amounts(1_000_000, &config, 250_000, 2_000_000, 0),
+            Ok((248_750, 750, 500))
+        );
+
+        // the minimum fee never takes more than the output
+        let config = SwapConfig {
+            min_fee: 5_000,
+            ..config
+        };
+        assert_eq!(get_payout_amounts(1_000_000, &config, 3_000, 2_000_000, 0), Ok((0, 1_800, 1_200)));
+    }
+
+    #[test]
+    fn test_after_transfer_fee_token() {
+        for (fee_token, result) in [
+            (FeeToken::Input, Err(ProgramError::Custom(1007))),
+            (FeeToken::Output, Ok(())),
+        ] {
+            // sol side under its own token program, fee recipient under the kin one
+            let mut test_accounts = after_transfer_test_accounts();
+            let fee_token_program = spl_token_2022::id();
+            test_accounts[3].owner = fee_token_program;
+            test_accounts.push(TestAccount::new(fee_token_program));
+            let mut token_account = Account::unpack(&test_accounts[5].data).unwrap();
+            token_account.mint = test_accounts[6].key;
+            Account::pack(token_account, &mut test_accounts[5].data).unwrap();
+            test_accounts[1].owner = Pubkey::new_unique();
+            let program_id = test_accounts[1].owner;
+            test_accounts[1].data = vec![0; SwapConfig::LEN];
+            SwapConfig {
+                fee_token,
+                swap_phase: SwapPhase::Swapped,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[1].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(after_transfer(&program_id, &accounts, 1_000_000, 0, 0), result);
+        }
+    }
+
+    #[test]
+    fn test_set_fee_token() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_fee_token(&program_id, &accounts, FeeToken::Output, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                fee_token: FeeToken::Output,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+
+        accounts[0].is_signer = false;
+        assert_eq!(
+            set_fee_token(&program_id, &accounts, FeeToken::Input, 1),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    fn sweep_test_accounts(program_id: &Pubkey, lamports: u64) -> Vec<TestAccount> {
+        let mut accounts = config_test_accounts(program_id);
+        accounts[1].lamports = lamports;
+        accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let mut rent = TestAccount::new(solana_program::sysvar::rent::id());
+        rent.data = vec![0; Rent::size_of()];
+        accounts.push(rent);
+        accounts.push(TestAccount::new(solana_program::system_program::id()));
+        accounts
+    }
+
+    fn sweep_account_infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+        let mut accounts = to_account_infos(accounts);
+        accounts[0].is_signer = true;
+        Rent::default().to_account_info(&mut accounts[3]).unwrap();
+        accounts
+    }
+
+    #[test]
+    fn test_sweep_lamports() {
+        let program_id = Pubkey::new_unique();
+        let rent_exempt = Rent::default().minimum_balance(SwapConfig::LEN);
+        let mut test_accounts = sweep_test_accounts(&program_id, rent_exempt * 3);
+        let accounts = sweep_account_infos(&mut test_accounts);
+
+        assert_eq!(sweep_lamports(&program_id, &accounts), Ok(()));
+        assert_eq!(accounts[1].lamports(), rent_exempt);
+        assert_eq!(accounts[2].lamports(), rent_exempt * 2);
+
+        // nothing left to sweep
+        assert_eq!(sweep_lamports(&program_id, &accounts), Ok(()));
+        assert_eq!(accounts[1].lamports(), rent_exempt);
+        assert_eq!(accounts[2].lamports(), rent_exempt * 2);
+    }
+
+    #[test]
+    fn test_sweep_lamports_requires_admin() {
+        let program_id = Pubkey::new_unique();
+        let rent_exempt = Rent::default().minimum_balance(SwapConfig::LEN);
+        let mut test_accounts = sweep_test_accounts(&program_id, rent_exempt * 3);
+        let mut accounts = sweep_account_infos(&mut test_accounts);
+        accounts[0].is_signer = false;
+
+        assert_eq!(
+            sweep_lamports(&program_id, &accounts),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(accounts[1].lamports(), rent_exempt * 3);
+    }
+
+    #[test]
+    fn test_set_fee_split() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_fee_split(&program_id, &accounts, 30, 20, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                fee_bps: 30,
+                lp_fee_bps: 20,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        // the admin can't move the fee past the cap through the lp share
+        assert_eq!(
+            set_fee_split(&program_id, &accounts, SwapConfig::MAX_FEE_BPS, 9_900, 1),
+            Err(ProgramError::Custom(1129))
+        );
+        assert_eq!(
+            set_fee_split(&program_id, &accounts, 0, SwapConfig::MAX_FEE_BPS + 1, 1),
+            Err(ProgramError::Custom(1129))
+        );
+        assert_eq!(
+            set_fee_split(&program_id, &accounts, SwapConfig::MAX_FEE_BPS + 1, 0, 1),
+            Err(ProgramError::Custom(1129))
+        );
+    }
+
+    #[test]
+    fn test_set_maintenance_window() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_maintenance_window(&program_id, &accounts, 1_000, 2_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                maintenance_start_slot: 1_000,
+                maintenance_end_slot: 2_000,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_max_pool_consume() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_max_pool_consume(&program_id, &accounts, 500, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                max_pool_consume_bps: 500,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        assert_eq!(
+            set_max_pool_consume(&program_id, &accounts, 10_001, 1),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_set_max_event_queue_fill() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_max_event_queue_fill(&program_id, &accounts, 8_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                max_event_queue_fill_bps: 8_000,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+        assert_eq!(
+            set_max_event_queue_fill(&program_id, &accounts, 10_001, 1),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_set_min_fee() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_min_fee(&program_id, &accounts, 5_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                min_fee: 5_000,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_min_swap() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_min_swap(&program_id, &accounts, 1_000, 0), Ok(()));
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                min_swap_amount: 1_000,
+                sequence: 1,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_swap_min_swap_amount() {
+        let program_id = Pubkey::new_unique();
+        for (min_swap_amount, result) in [
+            (2, Err(ProgramError::Custom(1119))),
+            (1, Ok(())),
+            (0, Ok(())),
+        ] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            SwapConfig {
+                min_swap_amount,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[0].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(swap(&accounts, &program_id, swap_params(1)), result);
+        }
+    }
+
+    #[test]
+    fn test_swap_spends_net_deposit() {
+        let program_id = Pubkey::new_unique();
+        // the deposit credited 1 of the nominal 2, the minimum only passes on the nominal amount
+        for (swap_phase, result) in [
+            (SwapPhase::Deposited, Err(ProgramError::Custom(1119))),
+            (SwapPhase::Idle, Ok(())),
+        ] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            SwapConfig {
+                min_swap_amount: 2,
+                swap_phase,
+                deposited_amount: 1,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[0].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(swap(&accounts, &program_id, swap_params(2)), result);
+        }
+    }
+
+    #[test]
+    fn test_create_account_rejects_oversized() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts: Vec<TestAccount> =
+            (0..CREATE_ACCOUNT_ACCOUNTS).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        let accounts = to_account_infos(&mut test_accounts);
+
+        for size in [system_instruction::MAX_PERMITTED_DATA_LENGTH + 1, u64::MAX] {
+            assert_eq!(
+                create_program_account(&program_id, &accounts, size, 0),
+                Err(ProgramError::InvalidRealloc)
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_account_checks_rent() {
+        let program_id = Pubkey::new_unique();
+        // new account, payer, rent with a 1 lamport minimum, system program
+        let mut test_accounts: Vec<TestAccount> =
+            (0..CREATE_ACCOUNT_ACCOUNTS).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        test_accounts[2].key = solana_program::sysvar::rent::id();
+        test_accounts[2].data = vec![0; Rent::size_of()];
+        let accounts = to_account_infos(&mut test_accounts);
+
+        // the payer holds nothing
+        assert_eq!(
+            create_program_account(&program_id, &accounts, 0, 0),
+            Err(ProgramError::Custom(1126))
+        );
+        // the stubbed transfer leaves the account unfunded
+        **accounts[1].lamports.borrow_mut() = 1;
+        assert_eq!(
+            create_program_account(&program_id, &accounts, 0, 0),
+            Err(ProgramError::Custom(1126))
+        );
+        assert_eq!(accounts[0].lamports(), 0);
+    }
+
+    #[test]
+    fn test_swap_max_price_impact() {
+        let program_id = Pubkey::new_unique();
+        // half the coin reserve of a 1_000/1_000 pool moves the price about 56%,
+        // past the guard the mock pool pays nothing and the slippage check fails
+        for (max_price_impact_bps, result) in [
+            (5_000, Err(ProgramError::Custom(1120))),
+            (6_000, Err(ProgramError::Custom(1004))),
+            (0, Err(ProgramError::Custom(1004))),
+        ] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, 0);
+            test_accounts[4].set_token_data(pack_token_account(1_000));
+            test_accounts[5].set_token_data(pack_mint_token_account(PC_MINT, 1_000));
+            let accounts = to_account_infos(&mut test_accounts);
+            let params = SwapParams {
+                max_price_impact_bps,
+                ..swap_params(500)
+            };
+
+            assert_eq!(swap(&accounts, &program_id, params), result);
+        }
+    }
+
+    #[test]
+    fn test_admin_changes_in_sequence() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = config_test_accounts(&program_id);
+        let mut accounts = to_account_infos(&mut test_accounts);
+        accounts[0].is_signer = true;
+
+        assert_eq!(set_min_fee(&program_id, &accounts, 5_000, 0), Ok(()));
+        // replayed and skipped sequences are rejected
+        assert_eq!(
+            set_min_fee(&program_id, &accounts, 5_000, 0),
+            Err(ProgramError::Custom(1104))
+        );
+        assert_eq!(
+            set_fee_split(&program_id, &accounts, 30, 20, 2),
+            Err(ProgramError::Custom(1104))
+        );
+        assert_eq!(set_fee_split(&program_id, &accounts, 30, 20, 1), Ok(()));
+        // a rejected change doesn't consume the sequence
+        assert_eq!(
+            set_max_pool_consume(&program_id, &accounts, 10_001, 2),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(set_max_pool_consume(&program_id, &accounts, 500, 2), Ok(()));
+
+        assert_eq!(
+            SwapConfig::load(&accounts[1], &program_id),
+            Ok(SwapConfig {
+                min_fee: 5_000,
+                fee_bps: 30,
+                lp_fee_bps: 20,
+                max_pool_consume_bps: 500,
+                sequence: 3,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_admin_instruction_sequence_roundtrip() {
+        for instruction in [
+            AmmInstruction::SetMinFee {
+                amount: 5_000,
+                sequence: 7,
+            },
+            AmmInstruction::SetFeeSplit {
+                fee_bps: 30,
+                lp_fee_bps: 20,
+                sequence: u64::MAX,
+            },
+            AmmInstruction::SetMaintenanceWindow {
+                start_slot: 1_000,
+                end_slot: 2_000,
+                sequence: 3,
+            },
+            AmmInstruction::SetFeeToken {
+                fee_token: FeeToken::Output,
+                sequence: 1,
+            },
+        ] {
+            let mut data = [0; AmmInstruction::SET_MAINTENANCE_WINDOW_LEN];
+            let len = instruction.pack(&mut data).unwrap();
+            assert_eq!(AmmInstruction::unpack(&data[..len]), Ok(instruction));
+            assert!(AmmInstruction::unpack(&data[..len - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_swap_params_from_instruction() {
+        let params = SwapParams {
+            token_a_amount_in: 1,
+            token_b_amount_in: 0,
+            min_token_amount_out: 1,
+            min_rate_num: 3,
+            min_rate_den: 2,
+            client_order_id: *b"order-0000000042",
+            deadline: 0,
+            max_price_impact_bps: 0,
+        };
+        let mut data = [0; AmmInstruction::SWAP_LEN];
+        AmmInstruction::Swap(params).pack(&mut data).unwrap();
+        let unpacked = match AmmInstruction::unpack(&data) {
+            Ok(AmmInstruction::Swap(unpacked)) => unpacked,
+            other => panic!("unexpected instruction {:?}", other),
+        };
+        assert_eq!(unpacked, params);
+
+        let program_id = Pubkey::new_unique();
+        for min_deliverable_out in [0, 2] {
+            let mut test_accounts = dust_swap_test_accounts(&program_id, min_deliverable_out);
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                swap(&accounts, &program_id, unpacked),
+                swap(&accounts, &program_id, swap_params(1))
+            );
+        }
+    }
+
+    fn rate_params(min_rate_num: u64, min_rate_den: u64) -> SwapParams {
+        SwapParams {
+            min_rate_num,
+            min_rate_den,
+            ..swap_params(1_000)
+        }
+    }
+
+    #[test]
+    fn test_min_rate() {
+        // 3/2 bound, 1_000 spent
+        let params = rate_params(3, 2);
+        assert_eq!(check_min_rate(&params, 1_000, 1_500), Ok(()));
+        assert_eq!(check_min_rate(&params, 1_000, 1_501), Ok(()));
+        assert_eq!(check_min_rate(&params, 1_000, 1_499), Err(ProgramError::Custom(1010)));
+        assert_eq!(check_min_rate(&params, 1_000, 0), Err(ProgramError::Custom(1010)));
+
+        // no overflow at the extremes
+        let params = rate_params(u64::MAX, 1);
+        assert_eq!(check_min_rate(&params, u64::MAX, u64::MAX), Err(ProgramError::Custom(1010)));
+        assert_eq!(check_min_rate(&params, 1, u64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_client_order_id_echo() {
+        let client_order_id = [0xab; 16];
+        let params = SwapParams {
+            client_order_id,
+            ..swap_params(1)
+        };
+        let mut data = [0; AmmInstruction::SWAP_LEN];
+        AmmInstruction::Swap(params).pack(&mut data).unwrap();
+        assert_eq!(data[41..57], client_order_id);
+        assert_eq!(AmmInstruction::unpack(&data), Ok(AmmInstruction::Swap(params)));
+
+        assert_eq!(
+            echo_client_order_id(&client_order_id),
+            Some(format!("client_order_id {}", "ab".repeat(16)))
+        );
+        assert_eq!(echo_client_order_id(&[0; 16]), None);
+        assert_eq!(set_swap_result(7, &[0; 16]), 7u64.to_le_bytes().to_vec());
+        assert_eq!(set_swap_result(7, &client_order_id)[8..], client_order_id);
+    }
+
+    #[test]
+    fn test_partial_fill() {
+        assert_eq!(get_partial_fill_min_out(1_000, 1_000, 1_000), Ok(1_000));
+        // the scaled minimum rounds up to keep the rate
+        assert_eq!(get_partial_fill_min_out(1_000, 1, 3), Ok(334));
+        assert_eq!(get_partial_fill_min_out(u64::MAX, 1, 2), Ok(u64::MAX / 2 + 1));
+        assert_eq!(get_partial_fill_min_out(1_000, 0, 3), Ok(1_000));
+
+        let result = set_partial_fill_result(7, 3, &[0; 16]);
+        assert_eq!(result[..8], 7u64.to_le_bytes());
+        assert_eq!(result[8..], 3u64.to_le_bytes());
+        assert_eq!(set_partial_fill_result(7, 3, &[0xab; 16])[16..], [0xab; 16]);
+    }
+
+    #[test]
+    fn test_min_rate_with_decimals() {
+        // 6 decimals in, 9 out, at least 2 tokens out per token in
+        let params = rate_params(2, 1);
+        assert_eq!(check_min_rate_with_decimals(&params, 1_000_000, 2_000_000_000, 6, 9), Ok(()));
+        assert_eq!(
+            check_min_rate_with_decimals(&params, 1_000_000, 1_999_999_999, 6, 9),
+            Err(ProgramError::Custom(1010))
+        );
+        // the base unit rate would have passed on 2_000_000
+        assert_eq!(check_min_rate(&params, 1_000_000, 2_000_000), Ok(()));
+        assert_eq!(
+            check_min_rate_with_decimals(&params, 1_000_000, 2_000_000, 6, 9),
+            Err(ProgramError::Custom(1010))
+        );
+
+        // 9 decimals in, 6 out
+        assert_eq!(check_min_rate_with_decimals(&params, 1_000_000_000, 2_000_000, 9, 6), Ok(()));
+        assert_eq!(
+            check_min_rate_with_decimals(&params, 1_000_000_000, 1_999_999, 9, 6),
+            Err(ProgramError::Custom(1010))
+        );
+
+        // scaling past u128 decides for the scaled side
+        let params = rate_params(u64::MAX, 1);
+        assert_eq!(
+            check_min_rate_with_decimals(&params, u64::MAX, u64::MAX, 0, 255),
+            Err(ProgramError::Custom(1010))
+        );
+        assert_eq!(check_min_rate_with_decimals(&params, 1, u64::MAX, 255, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_pool_decimals() {
+        let key = Pubkey::new_unique();
+        let owner = raydium_v4::id();
+        let mut data = vec![0; raydium::AmmInfoV4::LEN];
+        data[32..40].copy_from_slice(&6u64.to_le_bytes());
+        data[40..48].copy_from_slice(&9u64.to_le_bytes());
+        let mut lamports = 0;
+        let amm_id = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(raydium::get_pool_decimals(&amm_id), Ok(Some((6, 9))));
+
+        let mut data = vec![0; 100];
+        let mut lamports = 0;
+        let amm_id = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(raydium::get_pool_decimals(&amm_id), Ok(None));
+    }
+
+    #[test]
+    fn test_min_rate_disabled() {
+        for min_rate_num in [0, 1, u64::MAX] {
+            let params = rate_params(min_rate_num, 0);
+            assert_eq!(check_min_rate(&params, 1_000, 0), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_min_amount_out_slippage() {
+        // 0 bps takes the quote as is
+        assert_eq!(get_min_amount_out(100_000, 0, 0), Ok(100_000));
+        assert_eq!(get_min_amount_out(100_000, 0, 50), Ok(99_500));
+        assert_eq!(get_min_amount_out(199, 0, 50), Ok(198));
+        assert_eq!(get_min_amount_out(100_000, 0, 10_000), Ok(0));
+        // an explicit minimum above the tolerated output wins
+        assert_eq!(get_min_amount_out(100_000, 99_900, 50), Ok(99_900));
+        assert_eq!(get_min_amount_out(u64::MAX, 0, 0), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_swap_with_slippage_bps() {
+        let program_id = Pubkey::new_unique();
+        for (slippage_bps, result) in [
+            // the stubbed pool delivers nothing against a quote of 9
+            (0, Err(ProgramError::Custom(1004))),
+            (50, Err(ProgramError::Custom(1004))),
+            (10_000, Ok(())),
+            (10_001, Err(ProgramError::InvalidArgument)),
+        ] {
+            let mut test_accounts = vault_order_test_accounts(&program_id, false, false);
+            let accounts = to_account_infos(&mut test_accounts);
+
+            assert_eq!(
+                swap_with_slippage_bps(&accounts, &program_id, 10_000, 0, slippage_bps),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_deadline() {
+        assert_eq!(check_deadline(1_000, 999), Ok(()));
+        assert_eq!(check_deadline(1_000, 1_000), Ok(()));
+        assert_eq!(check_deadline(1_000, 1_001), Err(ProgramError::Custom(1111)));
+    }
+
+    /// One pool's accounts with the given swap fee in the v4 amm state
+    fn fee_tier_pool(coin_balance: u64, pc_balance: u64, swap_fee_numerator: u64) -> Vec<TestAccount> {
+        let mut pool: Vec<TestAccount> = (0..RAYDIUM_POOL_ACCOUNTS)
+            .map(|_| TestAccount::new(Pubkey::new_unique()))
+            .collect();
+        pool[0].key = raydium_v4::id();
+        pool[1].set_token_data(pack_token_account(coin_balance));
+        pool[2].set_token_data(pack_token_account(pc_balance));
+        pool[4].owner = raydium_v4::id();
+        pool[4].data = vec![0; raydium::AmmInfoV4::LEN];
+        let open_orders = pool[6].key;
+        pool[4].data[496..528].copy_from_slice(open_orders.as_ref());
+        pool[4].data[176..184].copy_from_slice(&swap_fee_numerator.to_le_bytes());
+        pool[4].data[184..192].copy_from_slice(&10_000u64.to_le_bytes());
+        pool
+    }
+
+    fn select_pool(pools: Vec<Vec<TestAccount>>, params: SwapParams) -> Result<usize, ProgramError> {
+        let mut pools: Vec<TestAccount> = pools.into_iter().flatten().collect();
+        let accounts = to_account_infos(&mut pools);
+        select_lowest_fee_pool(&accounts, &params)
+    }
+
+    #[test]
+    fn test_select_lowest_fee_pool() {
+        let params = SwapParams {
+            token_a_amount_in: 1_000_000,
+            ..SwapParams::default()
+        };
+        let (coin, pc) = (5_000_000_000, 130_000_000_000);
+        assert_eq!(
+            select_pool(vec![fee_tier_pool(coin, pc, 25), fee_tier_pool(coin, pc, 5)], params),
+            Ok(1)
+        );
+        assert_eq!(
+            select_pool(vec![fee_tier_pool(coin, pc, 5), fee_tier_pool(coin, pc, 25)], params),
+            Ok(0)
+        );
+        assert_eq!(
+            select_pool(vec![fee_tier_pool(coin, pc, 25), fee_tier_pool(coin, pc, 25)], params),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_select_lowest_fee_pool_compares_exactly() {
+        let params = SwapParams {
+            token_a_amount_in: 1_000_000,
+            ..SwapParams::default()
+        };
+        let (coin, pc) = (5_000_000_000, 130_000_000_000);
+        let with_fee = |numerator: u64, denominator: u64| {
+            let mut pool = fee_tier_pool(coin, pc, 0);
+            pool[4].data[176..184].copy_from_slice(&numerator.to_le_bytes());
+            pool[4].data[184..192].copy_from_slice(&denominator.to_le_bytes());
+            pool
+        };
+        // the same fee over different denominators is a tie
+        assert_eq!(select_pool(vec![with_fee(25, 10_000), with_fee(250, 100_000)], params), Ok(0));
+        // both fees round to the same f64
+        assert_eq!(
+            select_pool(vec![with_fee((1 << 53) + 1, 1 << 54), with_fee(1 << 53, 1 << 54)], params),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn test_select_lowest_fee_pool_meets_min_out() {
+        let params = SwapParams {
+            token_a_amount_in: 1_000_000,
+            token_b_amount_in: 0,
+            min_token_amount_out: 25_000_000,
+            ..SwapParams::default()
+        };
+        // the cheaper pool is too shallow to deliver min_token_amount_out
+        assert_eq!(
+            select_pool(
+                vec![
+                    fee_tier_pool(5_000_000_000, 130_000_000_000, 25),
+                    fee_tier_pool(5_000_000, 130_000_000, 5),
+                ],
+                params
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            select_pool(vec![fee_tier_pool(5_000_000, 130_000_000, 5)], params),
+            Err(ProgramError::Custom(1008))
+        );
+    }
+
+    #[test]
+    fn test_swap_best_fee_account_count() {
+        let mut test_accounts = swap_test_accounts();
+        test_accounts.pop();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_best_fee(&accounts, &Pubkey::new_unique(), swap_params(100)),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // a trailing account that is not a whole pool
+        let mut test_accounts = swap_test_accounts();
+        test_accounts.push(TestAccount::new(Pubkey::new_unique()));
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_best_fee(&accounts, &Pubkey::new_unique(), swap_params(100)),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_swap_best_fee_max_pools() {
+        let program_id = Pubkey::new_unique();
+        for (pool_count, result) in [
+            // the random pool program fails the first candidate
+            (MAX_BATCH, Err(ProgramError::Custom(1109))),
+            (MAX_BATCH + 1, Err(ProgramError::Custom(1123))),
+        ] {
+            let mut test_accounts: Vec<TestAccount> = (0..layout::SWAP_POOL_PROGRAM
+                + pool_count * RAYDIUM_POOL_ACCOUNTS)
+                .map(|_| TestAccount::new(Pubkey::new_unique()))
+                .collect();
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(swap_best_fee(&accounts, &program_id, swap_params(100)), result);
+        }
+    }
+
+    #[test]
+    fn test_batch_swap_count() {
+        let program_id = Pubkey::new_unique();
+        let routes = |count: usize| {
+            let mut data = vec![0; count * AmmInstruction::ROUTE_LEN];
+            for leg in data.chunks_exact_mut(AmmInstruction::ROUTE_LEN) {
+                AmmInstruction::Route { protocol: 0, amount_in: 1, min_out: 0 }.pack(leg).unwrap();
+            }
+            data
+        };
+        for (count, account_count, result) in [
+            (0, 0, Err(ProgramError::InvalidInstructionData)),
+            // the accounts are checked before the first leg runs
+            (MAX_BATCH, MAX_BATCH * SWAP_ACCOUNTS - 1, Err(ProgramError::NotEnoughAccountKeys)),
+            // an extra account would belong to no leg
+            (MAX_BATCH, MAX_BATCH * SWAP_ACCOUNTS + 1, Err(ProgramError::InvalidArgument)),
+            // the random pool program fails the first leg
+            (MAX_BATCH, MAX_BATCH * SWAP_ACCOUNTS, Err(ProgramError::Custom(1109))),
+            (MAX_BATCH + 1, (MAX_BATCH + 1) * SWAP_ACCOUNTS, Err(ProgramError::Custom(1123))),
+            (u8::MAX as usize, 0, Err(ProgramError::Custom(1123))),
+        ] {
+            let mut test_accounts: Vec<TestAccount> =
+                (0..account_count).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                batch_swap(&accounts, &program_id, count as u8, &routes(count)),
+                result,
+                "count {} with {} accounts",
+                count,
+                account_count
+            );
+        }
+    }
+
+    /// user, token program, user sol, program sol, user transfer authority, program account
+    fn before_transfer_test_accounts(
+        program_id: &Pubkey,
+        program_balance: u64,
+        skip_funded_deposit: bool,
+    ) -> Vec<TestAccount> {
+        let mut accounts = config_test_accounts(program_id);
+        SwapConfig {
+            skip_funded_deposit,
+            ..SwapConfig::default()
+        }
+        .pack(&mut accounts[1].data)
+        .unwrap();
+        let program_account = accounts.remove(1);
+
+        let mut accounts: Vec<TestAccount> =
+            (0..5).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        // accounts[1] is not the token program, reaching the transfer fails with IncorrectProgramId
+        accounts[2].set_token_data(pack_token_account(1_000_000));
+        accounts[3].set_token_data(pack_token_account(program_balance));
+        accounts.push(program_account);
+        accounts
+    }
+
+    #[test]
+    fn test_before_transfer_skips_funded_deposit() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 1_000, true);
+        let accounts = to_account_infos(&mut test_accounts);
+
+        assert_eq!(before_transfer(&program_id, &accounts, 1_000, 0), Ok(()));
+        assert_eq!(account::get_token_balance(&accounts[3]), Ok(1_000));
+    }
+
+    #[test]
+    fn test_before_transfer_deposits() {
+        let program_id = Pubkey::new_unique();
+        // not enough in the program account
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 999, true);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 1_000, 0),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        // mode disabled
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 1_000, false);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 1_000, 0),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        // no program account, config defaults
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 1_000, true);
+        test_accounts.pop();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 1_000, 0),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_before_transfer_checks_amount() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = before_transfer_test_accounts(&program_id, 0, false);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 0, 0),
+            Err(ProgramError::Custom(1117))
+        );
+
+        // the user holds 1_000_000
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 1_000_001, 0),
+            Err(ProgramError::Custom(1101))
+        );
+        assert_eq!(
+            before_transfer(&program_id, &accounts, 1_000_000, 0),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn test_check_transfer_amount() {
+        assert_eq!(check_transfer_amount(1_000, 1_000, 0), Ok(()));
+        assert_eq!(check_transfer_amount(1_000, 990, 10), Ok(()));
+        assert_eq!(check_transfer_amount(1_000, 1_000, 10), Ok(()));
+        assert_eq!(check_transfer_amount(1_000, 989, 10), Err(ProgramError::Custom(1118)));
+        assert_eq!(check_transfer_amount(1_000, 999, 0), Err(ProgramError::Custom(1118)));
+        // more than sent isn't a deposit of amount either
+        assert_eq!(check_transfer_amount(1_000, 1_001, 10), Err(ProgramError::Custom(1118)));
+        assert_eq!(check_transfer_amount(1_000, 0, u64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_deposit_needed() {
+        let config = SwapConfig {
+            skip_funded_deposit: true,
+            ..SwapConfig::default()
+        };
+        assert!(!is_deposit_needed(&config, 1_000, 1_000));
+        assert!(!is_deposit_needed(&config, 1_001, 1_000));
+        assert!(is_deposit_needed(&config, 999, 1_000));
+        assert!(is_deposit_needed(&SwapConfig::default(), 1_000, 1_000));
+    }
+
+    struct MockStaking {
+        result: ProgramResult,
+        staked: std::cell::RefCell<Vec<(u64, usize)>>,
+    }
+
+    impl MockStaking {
+        fn new(result: ProgramResult) -> Self {
+            Self {
+                result,
+                staked: std::cell::RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Staking for MockStaking {
+        fn stake_with_seeds(
+            &self,
+            accounts: &[AccountInfo],
+            seeds: &[&[&[u8]]],
+            amount: u64,
+        ) -> ProgramResult {
+            assert_eq!(seeds[0][0], PREFIX.as_bytes());
+            self.staked.borrow_mut().push((amount, accounts.len()));
+            self.result.clone()
+        }
+    }
+
+    /// Reports the given readings in order, then an unknown budget
+    struct MockComputeMeter {
+        readings: std::cell::RefCell<Vec<u64>>,
+    }
+
+    impl MockComputeMeter {
+        fn new(mut readings: Vec<u64>) -> Self {
+            readings.reverse();
+            Self {
+                readings: std::cell::RefCell::new(readings),
+            }
+        }
+
+        fn unmetered() -> Self {
+            Self::new(vec![])
+        }
+    }
+
+    impl ComputeMeter for MockComputeMeter {
+        fn remaining_units(&self) -> Option<u64> {
+            self.readings.borrow_mut().pop()
+        }
+    }
+
+    /// Dust swap accounts with the router PDA as program account, then 3 stake accounts
+    fn swap_and_stake_test_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mut accounts = dust_swap_test_accounts(program_id, 0);
+        accounts[0].key = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0;
+        accounts[2].set_token_data(pack_mint_token_account(PC_MINT, 7));
+        accounts.extend((0..3).map(|_| TestAccount::new(Pubkey::new_unique())));
+        accounts
+    }
+
+    #[test]
+    fn test_swap_and_stake() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let staking = MockStaking::new(Ok(()));
+
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &MockComputeMeter::unmetered()),
+            Ok(())
+        );
+        // the swap output, measured on program token b, is staked once
+        assert_eq!(*staking.staked.borrow(), vec![(1, 3)]);
+    }
+
+    #[test]
+    fn test_swap_and_stake_reverts_on_failure() {
+        let program_id = Pubkey::new_unique();
+
+        // staking fails after the swap
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let staking = MockStaking::new(Err(ProgramError::Custom(77)));
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &MockComputeMeter::unmetered()),
+            Err(ProgramError::Custom(77))
+        );
+
+        // swap fails, nothing is staked
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        SwapConfig {
+            min_deliverable_out: 2,
+            ..SwapConfig::default()
+        }
+        .pack(&mut test_accounts[0].data)
+        .unwrap();
+        let accounts = to_account_infos(&mut test_accounts);
+        let staking = MockStaking::new(Ok(()));
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &MockComputeMeter::unmetered()),
+            Err(ProgramError::Custom(1006))
+        );
+        assert!(staking.staked.borrow().is_empty());
+
+        // no stake accounts
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        test_accounts.truncate(SWAP_ACCOUNTS);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &MockComputeMeter::unmetered()),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_swap_and_stake_compute_checkpoints() {
+        let program_id = Pubkey::new_unique();
+
+        // not enough left to start the swap
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let staking = MockStaking::new(Ok(()));
+        let meter = MockComputeMeter::new(vec![compute::SWAP_PHASE_UNITS - 1]);
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &meter),
+            Err(ProgramError::Custom(1108))
+        );
+        assert!(staking.staked.borrow().is_empty());
+
+        // the swap ran but the stake phase doesn't fit
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let meter = MockComputeMeter::new(vec![
+            compute::SWAP_PHASE_UNITS + compute::STAKE_PHASE_UNITS,
+            compute::STAKE_PHASE_UNITS - 1,
+        ]);
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &meter),
+            Err(ProgramError::Custom(1108))
+        );
+        assert!(staking.staked.borrow().is_empty());
+
+        let mut test_accounts = swap_and_stake_test_accounts(&program_id);
+        let accounts = to_account_infos(&mut test_accounts);
+        let meter = MockComputeMeter::new(vec![
+            compute::SWAP_PHASE_UNITS + compute::STAKE_PHASE_UNITS,
+            compute::STAKE_PHASE_UNITS,
+        ]);
+        assert_eq!(
+            swap_and_stake(&accounts, &program_id, swap_params(1), &staking, &meter),
+            Ok(())
+        );
+        assert_eq!(*staking.staked.borrow(), vec![(1, 3)]);
+    }
+
+    /// Orca pool of reserve tokens a and b with a 0.25% trade and 0.05% owner fee,
+    /// program custody accounts are empty
+    fn orca_swap_test_accounts(program_id: &Pubkey, reserve: u64) -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> =
+            (0..11).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        accounts[0].owner = *program_id;
+        accounts[0].data = pack_config(0);
+        accounts[3].key = orca::orca_v2::id();
+        let mut swap_state = vec![0; orca::SWAP_STATE_LEN];
+        swap_state[35..67].copy_from_slice(accounts[6].key.as_ref());
+        swap_state[67..99].copy_from_slice(accounts[7].key.as_ref());
+        for &(offset, value) in &[(227, 25u64), (235, 10_000), (243, 5), (251, 10_000)] {
+            swap_state[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        accounts[4].data = swap_state;
+        for &(index, amount) in &[(1, 0), (6, reserve)] {
+            accounts[index].set_token_data(pack_token_account(amount));
+        }
+        for &(index, amount) in &[(2, 0), (7, reserve)] {
+            accounts[index].set_token_data(pack_mint_token_account(PC_MINT, amount));
+        }
+        accounts
+    }
+
+    /// Saber pool of reserve tokens a and b with an amp of 100 and a 0.04% trade
+    /// fee, program custody accounts are empty
+    fn saber_swap_test_accounts(program_id: &Pubkey, reserve: u64) -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> =
+            (0..11).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        accounts[0].owner = *program_id;
+        accounts[0].data = pack_config(0);
+        accounts[3].key = saber_stable_swap::id();
+        let mut swap_info = vec![0; saber::SWAP_INFO_LEN];
+        swap_info[11..19].copy_from_slice(&100u64.to_le_bytes());
+        for &(offset, index) in &[(107, 6), (171, 8), (204, 7), (268, 9)] {
+            swap_info[offset..offset + 32].copy_from_slice(accounts[index].key.as_ref());
+        }
+        swap_info[365..373].copy_from_slice(&4u64.to_le_bytes());
+        swap_info[373..381].copy_from_slice(&10_000u64.to_le_bytes());
+        accounts[4].data = swap_info;
+        for &(index, amount) in &[(1, 0), (6, reserve)] {
+            accounts[index].set_token_data(pack_token_account(amount));
+        }
+        for &(index, amount) in &[(2, 0), (7, reserve)] {
+            accounts[index].set_token_data(pack_mint_token_account(PC_MINT, amount));
+        }
+        accounts
+    }
+
+    /// Serum market with a coin lot of 10 and a pc lot of 1, program custody
+    /// accounts are empty
+    fn serum_direct_test_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mut accounts: Vec<TestAccount> =
+            (0..15).map(|_| TestAccount::new(Pubkey::new_unique())).collect();
+        accounts[0].owner = *program_id;
+        accounts[0].data = pack_config(0);
+        accounts[3].key = serum::serum_v3::id();
+        let mut market = vec![0; 5 + serum::MARKET_STATE_LEN + 7];
+        market[..5].copy_from_slice(b"serum");
+        market[5 + 112..5 + 144].copy_from_slice(accounts[10].key.as_ref());
+        market[5 + 160..5 + 192].copy_from_slice(accounts[11].key.as_ref());
+        market[5 + 344..5 + 352].copy_from_slice(&10u64.to_le_bytes());
+        market[5 + 352..5 + 360].copy_from_slice(&1u64.to_le_bytes());
+        accounts[4].data = market;
+        accounts[1].set_token_data(pack_token_account(0));
+        accounts[2].set_token_data(pack_mint_token_account(PC_MINT, 0));
+        accounts
+    }
+
+    #[test]
+    fn test_protocol_swaps_check_params() {
+        type Handler = fn(&[AccountInfo], &Pubkey, u64, u64, u64) -> ProgramResult;
+        type Fixture = fn(&Pubkey) -> Vec<TestAccount>;
+        let program_id = Pubkey::new_unique();
+        let fixtures: [(&str, Handler, Fixture); 3] = [
+            ("orca", swap_orca, |program_id| orca_swap_test_accounts(program_id, 1_000_000)),
+            ("saber", swap_saber, |program_id| saber_swap_test_accounts(program_id, 1_000_000)),
+            ("serum", swap_serum_direct, serum_direct_test_accounts),
+        ];
+        for (name, handler, fixture) in fixtures {
+            // both sides set
+            let mut test_accounts = fixture(&program_id);
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                handler(&accounts, &program_id, 1_000, 1_000, 1),
+                Err(ProgramError::Custom(1128)),
+                "{}",
+                name
+            );
+
+            // token a and b of the same mint
+            let mut test_accounts = fixture(&program_id);
+            test_accounts[2].set_token_data(pack_token_account(0));
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                handler(&accounts, &program_id, 1_000, 0, 1),
+                Err(ProgramError::Custom(1127)),
+                "{}",
+                name
+            );
+
+            // below the configured minimum swap
+            let mut test_accounts = fixture(&program_id);
+            SwapConfig {
+                min_swap_amount: 1_001,
+                ..SwapConfig::default()
+            }
+            .pack(&mut test_accounts[0].data)
+            .unwrap();
+            let accounts = to_account_infos(&mut test_accounts);
+            assert_eq!(
+                handler(&accounts, &program_id, 1_000, 0, 1),
+                Err(ProgramError::Custom(1119)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_orca_pool_swap_amounts() {
+        let program_id = Pubkey::new_unique();
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1_000_000);
+        let accounts = to_account_infos(&mut test_accounts);
+
+        let expected = quote::get_amount_out(1_000, 1_000_000, 1_000_000, 0.003).unwrap();
+        assert_eq!(
+            orca::get_pool_swap_amounts(&accounts[6], &accounts[7], &accounts[4], 1_000, 0),
+            Ok((1_000, expected))
+        );
+        assert_eq!(
+            orca::get_pool_swap_amounts(&accounts[6], &accounts[7], &accounts[4], 0, 1_000),
+            Ok((1_000, expected))
+        );
+        assert_eq!(
+            orca::get_pool_swap_amounts(&accounts[6], &accounts[7], &accounts[4], 1, 1),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_swap_orca() {
+        let program_id = Pubkey::new_unique();
+        // the quote rounds down to zero, the pool pays the client minimum
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        credit_on_cpi(Some((test_accounts[2].key, 1)));
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(swap_orca(&accounts, &program_id, 1, 0, 1), Ok(()));
+        // without one the zero quote leaves no floor
+        assert_eq!(swap_orca(&accounts, &program_id, 1, 0, 0), Err(ProgramError::Custom(1124)));
+        credit_on_cpi(None);
+
+        // nothing received from a pool quoting a real output
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1_000_000);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_orca(&accounts, &program_id, 0, 1_000, 0),
+            Err(ProgramError::Custom(1004))
+        );
+    }
+
+    #[test]
+    fn test_swap_orca_rejects_invalid_pool() {
+        let program_id = Pubkey::new_unique();
+
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        test_accounts[3].key = raydium_v4::id();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_orca(&accounts, &program_id, 1, 0, 0),
+            Err(ProgramError::Custom(1109))
+        );
+
+        // vaults in reverse order
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        test_accounts.swap(6, 7);
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_orca(&accounts, &program_id, 1, 0, 0),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut test_accounts = orca_swap_test_accounts(&program_id, 1);
+        test_accounts.pop();
+        let accounts = to_account_infos(&mut test_accounts);
+        assert_eq!(
+            swap_orca(&accounts, &program_id, 1, 0, 0),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+}
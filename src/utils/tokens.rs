@@ -1,9 +1,14 @@
 use {
+    crate::error::map_token_error,
     solana_program::{
         account_info::{ AccountInfo},
         entrypoint::ProgramResult,
         instruction::Instruction,
+        msg,
         program::{invoke, invoke_signed},
+        program_error::ProgramError,
+        pubkey::Pubkey,
+        system_instruction,
     },
 };
 
@@ -16,8 +21,76 @@ pub struct TokenTransferParams<'a: 'b, 'b> {
     pub token_program: AccountInfo<'a>,
 }
 
+pub struct TokenTransferCheckedParams<'a: 'b, 'b> {
+    pub source: AccountInfo<'a>,
+    pub mint: AccountInfo<'a>,
+    pub destination: AccountInfo<'a>,
+    pub amount: u64,
+    pub decimals: u8,
+    pub authority: AccountInfo<'a>,
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    pub token_program: AccountInfo<'a>,
+}
+
+pub struct CreateAtaParams<'a: 'b, 'b> {
+    /// System account paying the rent, usually a PDA of the calling program
+    pub payer: AccountInfo<'a>,
+    pub ata: AccountInfo<'a>,
+    pub wallet: AccountInfo<'a>,
+    pub mint: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    pub associated_token_program: AccountInfo<'a>,
+    pub payer_signer_seeds: &'b [&'b [u8]],
+}
+
 pub const PREFIX:&str = "kinswap";
 
+/// Instance of the router PDA every deployment used before instances existed
+pub const DEFAULT_INSTANCE: u8 = 0;
+
+/// Router PDA, the program account holding the config and signing for the
+/// router token accounts.
+///
+/// Seeds are PREFIX for the default instance and PREFIX then the instance byte
+/// for any other, the bump seed follows when signing. The program id is part of
+/// every derivation, the instance tells apart deployments sharing one program,
+/// or forks keeping the PREFIX. Initialize stores the instance in the config,
+/// handlers read it back to know which seeds to sign with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProgramAuthority {
+    pub address: Pubkey,
+    pub instance: u8,
+    pub bump_seed: u8,
+}
+
+impl ProgramAuthority {
+    pub fn find(program_id: &Pubkey, instance: u8) -> Self {
+        let instance_seed = [instance];
+        let seeds: &[&[u8]] = if instance == DEFAULT_INSTANCE {
+            &[PREFIX.as_bytes()]
+        } else {
+            &[PREFIX.as_bytes(), &instance_seed]
+        };
+        let (address, bump_seed) = Pubkey::find_program_address(seeds, program_id);
+        Self {
+            address,
+            instance,
+            bump_seed,
+        }
+    }
+
+    /// Seeds to sign for the PDA with
+    pub fn signer_seeds(&self) -> Vec<&[u8]> {
+        let mut seeds = vec![PREFIX.as_bytes()];
+        if self.instance != DEFAULT_INSTANCE {
+            seeds.push(std::slice::from_ref(&self.instance));
+        }
+        seeds.push(std::slice::from_ref(&self.bump_seed));
+        seeds
+    }
+}
+
 #[inline(always)]
 pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
     let TokenTransferParams {
@@ -28,19 +101,191 @@ pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult
         amount,
         authority_signer_seeds,
     } = params;
-    let result = invoke_optionally_signed(
-        &spl_token::instruction::transfer(
+    invoke_optionally_signed(
+        &build_transfer_instruction(
             token_program.key,
             source.key,
             destination.key,
             authority.key,
-            &[],
             amount,
         )?,
         &[source, destination, authority, token_program],
         authority_signer_seeds,
+    )
+    .map_err(map_token_error)
+}
+
+/// Same as spl_token_transfer, but the token program also checks the accounts
+/// hold mint and that it has decimals.
+#[inline(always)]
+pub fn spl_token_transfer_checked(params: TokenTransferCheckedParams<'_, '_>) -> ProgramResult {
+    let TokenTransferCheckedParams {
+        source,
+        mint,
+        destination,
+        authority,
+        token_program,
+        amount,
+        decimals,
+        authority_signer_seeds,
+    } = params;
+    invoke_optionally_signed(
+        &build_transfer_checked_instruction(
+            token_program.key,
+            source.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            amount,
+            decimals,
+        )?,
+        &[source, mint, destination, authority, token_program],
+        authority_signer_seeds,
+    )
+    .map_err(map_token_error)
+}
+
+/// Moves lamports from a system account into a wrapped SOL token account and
+/// syncs its token balance with them.
+pub fn wrap_sol<'a>(
+    source: AccountInfo<'a>,
+    wsol_account: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    token_program: AccountInfo<'a>,
+    lamports: u64,
+) -> ProgramResult {
+    invoke(
+        &system_instruction::transfer(source.key, wsol_account.key, lamports),
+        &[source, wsol_account.clone(), system_program],
+    )?;
+    invoke(
+        &spl_token::instruction::sync_native(token_program.key, wsol_account.key)?,
+        &[wsol_account, token_program],
+    )
+}
+
+/// Creates the associated token account of wallet for mint unless it is already
+/// initialized, an existing one is left as it is.
+/// Returns true if the account was created.
+pub fn get_or_create_ata(params: CreateAtaParams<'_, '_>) -> Result<bool, ProgramError> {
+    let CreateAtaParams {
+        payer,
+        ata,
+        wallet,
+        mint,
+        system_program,
+        token_program,
+        associated_token_program,
+        payer_signer_seeds,
+    } = params;
+    if *associated_token_program.key != spl_associated_token_account::id() {
+        msg!("Error: {} is not the associated token program", associated_token_program.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let expected_ata = spl_associated_token_account::get_associated_token_address_with_program_id(
+        wallet.key,
+        mint.key,
+        token_program.key,
     );
-    Ok(())
+    if *ata.key != expected_ata {
+        msg!("Error: {} is not the associated token account of {}, expected {}", ata.key, wallet.key, expected_ata);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if ata.owner == token_program.key && ata.data_len() > 0 {
+        return Ok(false);
+    }
+
+    invoke_optionally_signed(
+        &spl_associated_token_account::instruction::create_associated_token_account(
+            payer.key,
+            wallet.key,
+            mint.key,
+            token_program.key,
+        ),
+        &[payer, ata, wallet, mint, system_program, token_program, associated_token_program],
+        payer_signer_seeds,
+    )?;
+    Ok(true)
+}
+
+/// Closes a wrapped SOL token account, its whole lamport balance, rent and
+/// any unspent SOL, goes to destination.
+pub fn unwrap_sol<'a>(
+    wsol_account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    token_program: AccountInfo<'a>,
+    authority_signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    close_token_account(wsol_account, destination, authority, token_program, authority_signer_seeds)
+}
+
+/// Closes a token account, its lamports go to destination.
+pub fn close_token_account<'a>(
+    account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    token_program: AccountInfo<'a>,
+    authority_signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    invoke_optionally_signed(
+        &spl_token::instruction::close_account(
+            token_program.key,
+            account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[account, destination, authority, token_program],
+        authority_signer_seeds,
+    )
+    .map_err(map_token_error)
+}
+
+/// Builds a transfer for the classic SPL Token or the Token-2022 program,
+/// any other token program is rejected.
+/// Token-2022 mints whose extensions require transfer_checked aren't supported.
+pub fn build_transfer_instruction(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token::id() {
+        spl_token::instruction::transfer(token_program_id, source, destination, authority, &[], amount)
+    } else if *token_program_id == spl_token_2022::id() {
+        #[allow(deprecated)]
+        spl_token_2022::instruction::transfer(token_program_id, source, destination, authority, &[], amount)
+    } else {
+        msg!("Error: {} is not a token program", token_program_id);
+        Err(ProgramError::IncorrectProgramId)
+    }
+}
+
+/// Builds a transfer_checked for the classic SPL Token or the Token-2022 program,
+/// any other token program is rejected.
+pub fn build_transfer_checked_instruction(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token::id() {
+        spl_token::instruction::transfer_checked(
+            token_program_id, source, mint, destination, authority, &[], amount, decimals,
+        )
+    } else if *token_program_id == spl_token_2022::id() {
+        spl_token_2022::instruction::transfer_checked(
+            token_program_id, source, mint, destination, authority, &[], amount, decimals,
+        )
+    } else {
+        msg!("Error: {} is not a token program", token_program_id);
+        Err(ProgramError::IncorrectProgramId)
+    }
 }
 
 #[inline(always)]
@@ -50,9 +295,38 @@ fn invoke_optionally_signed(
     authority_signer_seeds: &[&[u8]],
 ) -> ProgramResult {
     if authority_signer_seeds.is_empty() {
-        invoke(instruction, account_infos);
+        invoke(instruction, account_infos)?;
     } else {
-        invoke_signed(instruction, account_infos, &[authority_signer_seeds]);
+        invoke_signed(instruction, account_infos, &[authority_signer_seeds])?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_authority_instances() {
+        let program_id = Pubkey::new_unique();
+        let default = ProgramAuthority::find(&program_id, DEFAULT_INSTANCE);
+        // the default instance keeps the address of the PREFIX only derivation
+        assert_eq!(
+            (default.address, default.bump_seed),
+            Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id)
+        );
+
+        let first = ProgramAuthority::find(&program_id, 1);
+        let second = ProgramAuthority::find(&program_id, 2);
+        assert_ne!(first.address, default.address);
+        assert_ne!(first.address, second.address);
+        assert_ne!(first.address, ProgramAuthority::find(&Pubkey::new_unique(), 1).address);
+
+        for authority in [default, first, second] {
+            assert_eq!(
+                Pubkey::create_program_address(&authority.signer_seeds(), &program_id),
+                Ok(authority.address)
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Build information GetVersion returns, so operators can tell which build is
+//! deployed by simulating the instruction.
+
+use {
+    crate::utils::pack::check_data_len,
+    solana_program::program_error::ProgramError,
+    std::convert::TryInto,
+};
+
+/// Crate version of the build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Bits of the feature mask, one per cargo feature
+pub const FEATURE_NO_ENTRYPOINT: u16 = 1;
+pub const FEATURE_DEBUG: u16 = 1 << 1;
+pub const FEATURE_COMPUTE_GUARD: u16 = 1 << 2;
+pub const FEATURE_MAINNET: u16 = 1 << 3;
+pub const FEATURE_DEVNET: u16 = 1 << 4;
+pub const FEATURE_CLIENT: u16 = 1 << 5;
+pub const FEATURE_PROFILING: u16 = 1 << 6;
+pub const FEATURE_BORSH: u16 = 1 << 7;
+
+/// Mask of the features the program was built with
+pub fn build_features() -> u16 {
+    [
+        (cfg!(feature = "no-entrypoint"), FEATURE_NO_ENTRYPOINT),
+        (cfg!(feature = "debug"), FEATURE_DEBUG),
+        (cfg!(feature = "compute-guard"), FEATURE_COMPUTE_GUARD),
+        (cfg!(feature = "mainnet"), FEATURE_MAINNET),
+        (cfg!(feature = "devnet"), FEATURE_DEVNET),
+        (cfg!(feature = "client"), FEATURE_CLIENT),
+        (cfg!(feature = "profiling"), FEATURE_PROFILING),
+        (cfg!(feature = "borsh"), FEATURE_BORSH),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .fold(0, |mask, (_, bit)| mask | bit)
+}
+
+/// Layout: feature mask as u16 little endian, then the UTF-8 version
+pub fn pack_version() -> Vec<u8> {
+    let mut data = build_features().to_le_bytes().to_vec();
+    data.extend_from_slice(VERSION.as_bytes());
+    data
+}
+
+/// Decodes GetVersion return data into the feature mask and the version
+pub fn unpack_version(input: &[u8]) -> Result<(u16, String), ProgramError> {
+    check_data_len(input, 2)?;
+    let features = u16::from_le_bytes(input[..2].try_into().unwrap());
+    let version = std::str::from_utf8(&input[2..]).map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok((features, version.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_round_trip() {
+        let data = pack_version();
+        assert_eq!(unpack_version(&data), Ok((build_features(), VERSION.to_string())));
+        assert_eq!(build_features() & FEATURE_CLIENT, FEATURE_CLIENT * cfg!(feature = "client") as u16);
+
+        assert_eq!(unpack_version(&data[..1]), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(unpack_version(&[0, 0, 0xff]), Err(ProgramError::InvalidAccountData));
+    }
+}
@@ -0,0 +1,230 @@
+//! BatchSwap routes its legs in order and reverts all of them when one fails.
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program::invoke_signed,
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::AmmInstruction,
+        protocol::saber::SWAP_INFO_LEN,
+        quote,
+        utils::{id::saber_stable_swap, tokens::PREFIX},
+    },
+    std::convert::TryInto,
+};
+
+const AMP: u64 = 100;
+const FEE_NUMERATOR: u64 = 4;
+const FEE_DENOMINATOR: u64 = 10_000;
+
+fn token_balance(account: &AccountInfo) -> u64 {
+    TokenAccount::unpack(&account.data.borrow()).unwrap().amount
+}
+
+/// Stable swap stand-in that takes amount_in into the source reserve and pays
+/// the stable swap quote from the destination reserve
+fn mock_saber(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (swap_authority, user_authority) = (&accounts[1], &accounts[2]);
+    let (source, swap_source) = (&accounts[3], &accounts[4]);
+    let (swap_destination, destination) = (&accounts[5], &accounts[6]);
+    let token_program = &accounts[8];
+
+    let amount_out = quote::get_stable_amount_out(
+        amount_in,
+        token_balance(swap_source),
+        token_balance(swap_destination),
+        AMP,
+        FEE_NUMERATOR,
+        FEE_DENOMINATOR,
+    )?;
+    if amount_out < minimum_amount_out {
+        return Err(ProgramError::Custom(16));
+    }
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            swap_source.key,
+            user_authority.key,
+            &[],
+            amount_in,
+        )?,
+        &[source.clone(), swap_source.clone(), user_authority.clone(), token_program.clone()],
+        &[],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[accounts[0].key.as_ref()], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            swap_destination.key,
+            destination.key,
+            swap_authority.key,
+            &[],
+            amount_out,
+        )?,
+        &[swap_destination.clone(), destination.clone(), swap_authority.clone(), token_program.clone()],
+        &[&[accounts[0].key.as_ref(), &[bump_seed]]],
+    )
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+/// Saber protocol byte of Route
+const SABER: u8 = 2;
+
+#[tokio::test]
+async fn test_batch_swap_reverts_on_failed_leg() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("saber", saber_stable_swap::id(), processor!(mock_saber));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let swap_info = Pubkey::new_unique();
+    let (swap_authority, _) = Pubkey::find_program_address(&[swap_info.as_ref()], &saber_stable_swap::id());
+    let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let program_token_a = add_token_account(&mut program_test, &mint_a, &program_account, 3_000_000);
+    let program_token_b = add_token_account(&mut program_test, &mint_b, &program_account, 0);
+    let reserve_a = add_token_account(&mut program_test, &mint_a, &swap_authority, 1_000_000_000);
+    let reserve_b = add_token_account(&mut program_test, &mint_b, &swap_authority, 1_000_000_000);
+    let admin_fee_a = add_token_account(&mut program_test, &mint_a, &swap_authority, 0);
+    let admin_fee_b = add_token_account(&mut program_test, &mint_b, &swap_authority, 0);
+
+    let mut swap_info_data = vec![0; SWAP_INFO_LEN];
+    swap_info_data[11..19].copy_from_slice(&AMP.to_le_bytes());
+    swap_info_data[107..139].copy_from_slice(reserve_a.as_ref());
+    swap_info_data[171..203].copy_from_slice(admin_fee_a.as_ref());
+    swap_info_data[204..236].copy_from_slice(reserve_b.as_ref());
+    swap_info_data[268..300].copy_from_slice(admin_fee_b.as_ref());
+    swap_info_data[365..373].copy_from_slice(&FEE_NUMERATOR.to_le_bytes());
+    swap_info_data[373..381].copy_from_slice(&FEE_DENOMINATOR.to_le_bytes());
+    program_test.add_account(
+        swap_info,
+        Account {
+            lamports: 1_000_000_000,
+            data: swap_info_data,
+            owner: saber_stable_swap::id(),
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // every leg sells token a into the same pool
+    let leg_accounts = vec![
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_token_a, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(saber_stable_swap::id(), false),
+        AccountMeta::new_readonly(swap_info, false),
+        AccountMeta::new_readonly(swap_authority, false),
+        AccountMeta::new(reserve_a, false),
+        AccountMeta::new(reserve_b, false),
+        AccountMeta::new(admin_fee_a, false),
+        AccountMeta::new(admin_fee_b, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    let batch_swap = |min_outs: [u64; 3]| {
+        let mut data = vec![0; AmmInstruction::BATCH_SWAP_LEN + 3 * AmmInstruction::ROUTE_LEN];
+        AmmInstruction::BatchSwap { count: 3 }.pack(&mut data).unwrap();
+        for (leg, min_out) in min_outs.iter().enumerate() {
+            let offset = AmmInstruction::BATCH_SWAP_LEN + leg * AmmInstruction::ROUTE_LEN;
+            AmmInstruction::Route { protocol: SABER, amount_in: 1_000_000, min_out: *min_out }
+                .pack(&mut data[offset..])
+                .unwrap();
+        }
+        Instruction {
+            program_id,
+            accounts: [leg_accounts.clone(), leg_accounts.clone(), leg_accounts.clone()].concat(),
+            data,
+        }
+    };
+    let tracked = [program_token_a, program_token_b, reserve_a, reserve_b];
+
+    let mut balances = vec![];
+    for address in tracked {
+        let account = banks_client.get_account(address).await.unwrap().unwrap();
+        balances.push(TokenAccount::unpack(&account.data).unwrap().amount);
+    }
+    for (min_outs, result) in [
+        // the middle leg asks for more than the pool pays
+        ([0, 2_000_000, 0], Err(TransactionError::InstructionError(0, InstructionError::Custom(16)))),
+        ([0, 0, 0], Ok(())),
+    ] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[batch_swap(min_outs)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let metadata = banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert_eq!(metadata.result, result);
+
+        if result.is_err() {
+            for (address, balance) in tracked.iter().zip(&balances) {
+                let account = banks_client.get_account(*address).await.unwrap().unwrap();
+                assert_eq!(TokenAccount::unpack(&account.data).unwrap().amount, *balance, "{}", address);
+            }
+        }
+    }
+
+    let program_token_a = banks_client.get_account(program_token_a).await.unwrap().unwrap();
+    let program_token_b = banks_client.get_account(program_token_b).await.unwrap().unwrap();
+    assert_eq!(TokenAccount::unpack(&program_token_a.data).unwrap().amount, 0);
+    let mut expected_out = 0;
+    for leg in 0..3 {
+        expected_out += quote::get_stable_amount_out(
+            1_000_000,
+            1_000_000_000 + leg * 1_000_000,
+            1_000_000_000 - expected_out,
+            AMP,
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+        )
+        .unwrap();
+    }
+    assert_eq!(TokenAccount::unpack(&program_token_b.data).unwrap().amount, expected_out);
+}
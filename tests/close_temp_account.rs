@@ -0,0 +1,141 @@
+//! CloseTempAccount returns the rent of an empty router token account.
+
+use {
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{instruction::AmmInstruction, state::SwapConfig, utils::tokens::PREFIX},
+};
+
+/// Starts a router whose PDA holds an empty and a funded token account,
+/// returns the client, the config admin and the two token accounts
+async fn setup(program_id: &Pubkey) -> (BanksClient, Keypair, Pubkey, Pubkey) {
+    let mut program_test = ProgramTest::new(
+        "swap",
+        *program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0;
+    let admin = Keypair::new();
+    let mut data = vec![0; SwapConfig::LEN];
+    SwapConfig {
+        admin: admin.pubkey(),
+        ..SwapConfig::default()
+    }
+    .pack(&mut data)
+    .unwrap();
+    program_test.add_account(
+        program_account,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        admin.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let mint = Pubkey::new_unique();
+    let [empty, funded] = [0, 1_000].map(|amount| {
+        let address = Pubkey::new_unique();
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(
+            TokenAccount {
+                mint,
+                owner: program_account,
+                amount,
+                state: AccountState::Initialized,
+                ..TokenAccount::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        program_test.add_account(
+            address,
+            Account {
+                lamports: 2_039_280,
+                data,
+                owner: spl_token::id(),
+                ..Account::default()
+            },
+        );
+        address
+    });
+    let (banks_client, _, _) = program_test.start().await;
+    (banks_client, admin, empty, funded)
+}
+
+async fn close_temp_account(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    admin: &Keypair,
+    token_account: &Pubkey,
+    destination: &Pubkey,
+) -> Result<(), TransactionError> {
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0;
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::CloseTempAccount.pack(&mut data).unwrap();
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(admin.pubkey(), true),
+            AccountMeta::new_readonly(program_account, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&admin.pubkey()),
+        &[admin],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+#[tokio::test]
+async fn test_close_temp_account() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, admin, empty, _) = setup(&program_id).await;
+    let destination = Pubkey::new_unique();
+
+    assert_eq!(
+        close_temp_account(&mut banks_client, &program_id, &admin, &empty, &destination).await,
+        Ok(())
+    );
+    assert_eq!(banks_client.get_account(empty).await.unwrap(), None);
+    assert_eq!(banks_client.get_balance(destination).await.unwrap(), 2_039_280);
+}
+
+#[tokio::test]
+async fn test_close_temp_account_rejects_non_zero_balance() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, admin, _, funded) = setup(&program_id).await;
+    let destination = Pubkey::new_unique();
+
+    assert_eq!(
+        close_temp_account(&mut banks_client, &program_id, &admin, &funded, &destination).await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1115)))
+    );
+    assert_eq!(banks_client.get_balance(funded).await.unwrap(), 2_039_280);
+}
@@ -0,0 +1,245 @@
+//! Fixtures shared by the integration tests: token accounts, mints, the stored
+//! config and mock pool programs. Each test binary uses a subset of them.
+#![allow(dead_code)]
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, invoke_signed},
+        program_error::ProgramError,
+        program_option::COption,
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, BanksClient, BanksTransactionResultWithMetadata, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    spl_token::state::{Account as TokenAccount, AccountState, Mint},
+    swap::{
+        protocol::raydium::{raydium_v4, AmmInfoV4},
+        state::SwapConfig,
+        utils::tokens::ProgramAuthority,
+    },
+    std::convert::TryInto,
+};
+
+pub const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Raydium swap instruction index the mock pools answer to
+pub const RAYDIUM_SWAP: u8 = 9;
+
+/// Raydium stand-in taking amount_in into the coin vault and paying
+/// min_amount_out from the pc vault, coin to pc only
+pub fn mock_raydium(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if data.len() != 17 || data[0] != RAYDIUM_SWAP {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (token_program, amm_authority) = (&accounts[0], &accounts[2]);
+    let (pool_coin, pool_pc) = (&accounts[5], &accounts[6]);
+    let (source, destination, owner) = (&accounts[15], &accounts[16], &accounts[17]);
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            pool_coin.key,
+            owner.key,
+            &[],
+            amount_in,
+        )?,
+        &[source.clone(), pool_coin.clone(), owner.clone(), token_program.clone()],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            pool_pc.key,
+            destination.key,
+            amm_authority.key,
+            &[],
+            min_amount_out,
+        )?,
+        &[pool_pc.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+pub fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+pub fn add_mint(program_test: &mut ProgramTest, decimals: u8) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: u64::MAX / 2,
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+/// Stores config in the router PDA of its instance and returns the address
+pub fn add_config(program_test: &mut ProgramTest, program_id: &Pubkey, config: &SwapConfig) -> Pubkey {
+    let program_account = ProgramAuthority::find(program_id, config.instance).address;
+    let mut data = vec![0; SwapConfig::LEN];
+    config.pack(&mut data).unwrap();
+    program_test.add_account(
+        program_account,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    program_account
+}
+
+pub async fn token_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+pub async fn load_config(banks_client: &mut BanksClient, program_account: Pubkey) -> SwapConfig {
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    SwapConfig::unpack(&account.data).unwrap()
+}
+
+/// Sends instruction in its own transaction paid by payer
+pub async fn process_instruction(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    signers: &[&Keypair],
+    instruction: Instruction,
+) -> BanksTransactionResultWithMetadata {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap()
+}
+
+/// Adds a v4 amm state naming the pool vaults and a new open orders account,
+/// returns the (amm id, open orders)
+pub fn add_raydium_amm(program_test: &mut ProgramTest, pool_coin: &Pubkey, pool_pc: &Pubkey) -> (Pubkey, Pubkey) {
+    let (amm_id, amm_open_orders) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = vec![0; AmmInfoV4::LEN];
+    data[336..368].copy_from_slice(pool_coin.as_ref());
+    data[368..400].copy_from_slice(pool_pc.as_ref());
+    data[496..528].copy_from_slice(amm_open_orders.as_ref());
+    program_test.add_account(
+        amm_id,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: raydium_v4::id(),
+            ..Account::default()
+        },
+    );
+    (amm_id, amm_open_orders)
+}
+
+/// Raydium pool of the mock program, vaults held by its amm authority
+pub struct MockRaydiumPool {
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub pool_coin: Pubkey,
+    pub pool_pc: Pubkey,
+    pub amm_authority: Pubkey,
+    pub amm_id: Pubkey,
+    pub amm_open_orders: Pubkey,
+}
+
+impl MockRaydiumPool {
+    /// Registers mock_raydium under the Raydium v4 id and funds the vaults
+    pub fn add(program_test: &mut ProgramTest, coin_reserve: u64, pc_reserve: u64) -> Self {
+        program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+        let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_v4::id());
+        let (coin_mint, pc_mint) = (add_mint(program_test, 6), add_mint(program_test, 6));
+        let pool_coin = add_token_account(program_test, &coin_mint, &amm_authority, coin_reserve);
+        let pool_pc = add_token_account(program_test, &pc_mint, &amm_authority, pc_reserve);
+        let (amm_id, amm_open_orders) = add_raydium_amm(program_test, &pool_coin, &pool_pc);
+        Self {
+            coin_mint,
+            pc_mint,
+            pool_coin,
+            pool_pc,
+            amm_authority,
+            amm_id,
+            amm_open_orders,
+        }
+    }
+
+    /// Swap accounts against this pool, see accounts_layout
+    pub fn swap_accounts(&self, program_account: Pubkey, program_token_a: Pubkey, program_token_b: Pubkey) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(program_account, false),
+            AccountMeta::new(program_token_a, false),
+            AccountMeta::new(program_token_b, false),
+            AccountMeta::new_readonly(raydium_v4::id(), false),
+            AccountMeta::new(self.pool_coin, false),
+            AccountMeta::new(self.pool_pc, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.amm_id, false),
+            AccountMeta::new_readonly(self.amm_authority, false),
+            AccountMeta::new(self.amm_open_orders, false),
+        ];
+        // amm target, serum market, program, bids, asks, event queue, coin and
+        // pc vaults, vault signer
+        accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+        accounts
+    }
+}
@@ -0,0 +1,106 @@
+//! CreateAccount funds the program account with the rent-exempt minimum, and
+//! fails cleanly when the payer can't.
+
+use {
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        rent::Rent,
+        system_program, sysvar,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    swap::{instruction::AmmInstruction, state::SwapConfig, utils::tokens::PREFIX},
+};
+
+#[tokio::test]
+async fn test_create_program_account_is_rent_exempt() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, recent_blockhash) = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    )
+    .start()
+    .await;
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id).0;
+    let size = SwapConfig::LEN as u64;
+
+    let mut data = [0; AmmInstruction::CREATE_ACCOUNT_LEN];
+    AmmInstruction::CreateAccount { size, instance: 0 }.pack(&mut data).unwrap();
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(program_account, false),
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let rent: Rent = banks_client.get_sysvar().await.unwrap();
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), SwapConfig::LEN);
+    assert_eq!(account.lamports, rent.minimum_balance(SwapConfig::LEN));
+}
+
+#[tokio::test]
+async fn test_create_program_account_payer_short_of_rent() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let funder = Keypair::new();
+    program_test.add_account(
+        funder.pubkey(),
+        Account {
+            lamports: 1_000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id).0;
+
+    let mut data = [0; AmmInstruction::CREATE_ACCOUNT_LEN];
+    AmmInstruction::CreateAccount { size: SwapConfig::LEN as u64, instance: 0 }
+        .pack(&mut data)
+        .unwrap();
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(program_account, false),
+            AccountMeta::new(funder.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &funder],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(1126))
+    );
+    assert_eq!(banks_client.get_account(program_account).await.unwrap(), None);
+}
@@ -0,0 +1,115 @@
+//! get_or_create_ata creates a missing associated token account and leaves an
+//! existing one alone.
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::set_return_data,
+        program_option::COption,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        system_program,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+    spl_associated_token_account::get_associated_token_address,
+    spl_token::state::{Account as TokenAccount, Mint},
+    swap::utils::tokens::{get_or_create_ata, CreateAtaParams},
+};
+
+const PAYER_SEED: &[u8] = b"payer";
+
+/// Calls get_or_create_ata with its PDA as payer and returns whether it created
+/// the account.
+///
+/// Accounts: payer PDA, ata, wallet, mint, system program, spl token, associated token program
+fn ata_caller(program_id: &Pubkey, accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    let (_, bump_seed) = Pubkey::find_program_address(&[PAYER_SEED], program_id);
+    let created = get_or_create_ata(CreateAtaParams {
+        payer: accounts[0].clone(),
+        ata: accounts[1].clone(),
+        wallet: accounts[2].clone(),
+        mint: accounts[3].clone(),
+        system_program: accounts[4].clone(),
+        token_program: accounts[5].clone(),
+        associated_token_program: accounts[6].clone(),
+        payer_signer_seeds: &[PAYER_SEED, &[bump_seed]],
+    })?;
+    set_return_data(&[created as u8]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_or_create_ata() {
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("ata_caller", caller_id, processor!(ata_caller));
+    let (payer_pda, _) = Pubkey::find_program_address(&[PAYER_SEED], &caller_id);
+    program_test.add_account(
+        payer_pda,
+        Account {
+            lamports: 1_000_000_000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+    let mint = Pubkey::new_unique();
+    let mut mint_data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut mint_data,
+    )
+    .unwrap();
+    program_test.add_account(
+        mint,
+        Account {
+            lamports: 1_000_000_000,
+            data: mint_data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let wallet = Pubkey::new_unique();
+    let ata = get_associated_token_address(&wallet, &mint);
+    let accounts = vec![
+        AccountMeta::new(payer_pda, false),
+        AccountMeta::new(ata, false),
+        AccountMeta::new_readonly(wallet, false),
+        AccountMeta::new_readonly(mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+    ];
+    // created once, then found, the data byte only keeps the transactions distinct
+    for (data, created) in [(0u8, 1u8), (1, 0)] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id: caller_id,
+                accounts: accounts.clone(),
+                data: vec![data],
+            }],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert_eq!(result.result, Ok(()));
+        assert_eq!(result.metadata.unwrap().return_data.unwrap().data, [created]);
+
+        let ata = banks_client.get_account(ata).await.unwrap().unwrap();
+        assert_eq!(ata.owner, spl_token::id());
+        let ata = TokenAccount::unpack(&ata.data).unwrap();
+        assert_eq!((ata.mint, ata.owner, ata.amount), (mint, wallet, 0));
+    }
+}
@@ -0,0 +1,77 @@
+//! A Swap without min_token_amount_out accepts the pool quote less the config
+//! default_slippage_bps.
+
+mod common;
+
+use {
+    common::{add_config, add_token_account, process_instruction, MockRaydiumPool},
+    solana_program::{instruction::Instruction, pubkey::Pubkey},
+    solana_program_test::{processor, tokio, ProgramTest},
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::RAYDIUM_FEE,
+        quote,
+        state::SwapConfig,
+        utils::swap::get_min_amount_out,
+    },
+    std::convert::TryInto,
+};
+
+const COIN_RESERVE: u64 = 1_000_000;
+const PC_RESERVE: u64 = 2_000_000;
+const AMOUNT_IN: u64 = 1_000;
+
+/// Swaps AMOUNT_IN with the default slippage set, returns the amount out.
+/// The mock pool pays exactly the minimum the router asks for
+async fn swap_amount_out(default_slippage_bps: u16, min_token_amount_out: u64) -> u64 {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let pool = MockRaydiumPool::add(&mut program_test, COIN_RESERVE, PC_RESERVE);
+    let program_account = add_config(
+        &mut program_test,
+        &program_id,
+        &SwapConfig {
+            default_slippage_bps,
+            ..SwapConfig::default()
+        },
+    );
+    let program_coin = add_token_account(&mut program_test, &pool.coin_mint, &program_account, AMOUNT_IN);
+    let program_pc = add_token_account(&mut program_test, &pool.pc_mint, &program_account, 0);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: AMOUNT_IN,
+        min_token_amount_out,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let swap = Instruction {
+        program_id,
+        accounts: pool.swap_accounts(program_account, program_coin, program_pc),
+        data: data.to_vec(),
+    };
+    let result = process_instruction(&mut banks_client, &payer, &[], swap).await;
+    assert_eq!(result.result, Ok(()));
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    u64::from_le_bytes(return_data.data[..8].try_into().unwrap())
+}
+
+#[tokio::test]
+async fn test_swap_applies_default_slippage() {
+    let quote = quote::get_amount_out(AMOUNT_IN, COIN_RESERVE, PC_RESERVE, RAYDIUM_FEE).unwrap();
+
+    // zero asks for the quote less the default
+    let amount_out = swap_amount_out(100, 0).await;
+    assert_eq!(amount_out, get_min_amount_out(quote, 0, 100).unwrap());
+    assert!(amount_out < quote);
+    // without a default the full quote
+    assert_eq!(swap_amount_out(0, 0).await, quote);
+    // an explicit minimum leaves the default out
+    assert_eq!(swap_amount_out(100, 1).await, quote);
+}
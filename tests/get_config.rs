@@ -0,0 +1,72 @@
+//! GetConfig leaves the stored config in return data for a simulated
+//! transaction.
+
+mod common;
+
+use {
+    common::add_config,
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    swap::{instruction::AmmInstruction, state::SwapConfig},
+};
+
+fn get_config_instruction(program_id: Pubkey, program_account: Pubkey) -> Instruction {
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::GetConfig.pack(&mut data).unwrap();
+    Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(program_account, false)],
+        data: data.to_vec(),
+    }
+}
+
+#[tokio::test]
+async fn test_get_config() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let config = SwapConfig {
+        fee_bps: 35,
+        paused: true,
+        admin: Pubkey::new_unique(),
+        ..SwapConfig::default()
+    };
+    let program_account = add_config(&mut program_test, &program_id, &config);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[get_config_instruction(program_id, program_account)],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    assert_eq!(SwapConfig::unpack(&return_data.data), Ok(config));
+
+    // only the router PDA holds the config
+    let transaction = Transaction::new_signed_with_payer(
+        &[get_config_instruction(program_id, Pubkey::new_unique())],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+    assert_eq!(
+        simulation.result,
+        Some(Err(TransactionError::InstructionError(0, InstructionError::InvalidSeeds)))
+    );
+}
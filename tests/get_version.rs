@@ -0,0 +1,49 @@
+//! GetVersion leaves the crate version and build features in return data for a
+//! simulated transaction.
+
+use {
+    solana_program::{instruction::Instruction, pubkey::Pubkey},
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{signature::Signer, transaction::Transaction},
+    swap::{instruction::AmmInstruction, version},
+};
+
+#[tokio::test]
+async fn test_get_version() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::GetVersion.pack(&mut data).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![],
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+
+    let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    let (features, deployed_version) = version::unpack_version(&return_data.data).unwrap();
+    // the version in Cargo.toml
+    let manifest = include_str!("../Cargo.toml");
+    let manifest_version = manifest
+        .lines()
+        .find_map(|line| line.strip_prefix("version = \""))
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap();
+    assert_eq!(deployed_version, manifest_version);
+    assert_eq!(features, version::build_features());
+}
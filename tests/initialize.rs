@@ -0,0 +1,173 @@
+//! Initialize writes the config once, with the signer as admin, and instances
+//! of one program keep their configs apart.
+
+use {
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    swap::{
+        instruction::AmmInstruction,
+        state::SwapConfig,
+        utils::tokens::{ProgramAuthority, PREFIX},
+    },
+};
+
+async fn initialize(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    admin: &Keypair,
+    fee_bps: u16,
+    instance: u8,
+) -> Result<(), TransactionError> {
+    let program_account = ProgramAuthority::find(program_id, instance).address;
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::Initialize { fee_bps, instance }.pack(&mut data).unwrap();
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(admin.pubkey(), true),
+            AccountMeta::new(program_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&admin.pubkey()),
+        &[admin],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+#[tokio::test]
+async fn test_initialize_once() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    )
+    .start()
+    .await;
+
+    assert_eq!(initialize(&mut banks_client, &program_id, &payer, 30, 0).await, Ok(()));
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id).0;
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(
+        SwapConfig::unpack(&account.data),
+        Ok(SwapConfig {
+            admin: payer.pubkey(),
+            fee_bps: 30,
+            ..SwapConfig::default()
+        })
+    );
+
+    assert_eq!(
+        initialize(&mut banks_client, &program_id, &payer, 40, 0).await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::AccountAlreadyInitialized
+        ))
+    );
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    assert_eq!(SwapConfig::unpack(&account.data).unwrap().fee_bps, 30);
+}
+
+#[tokio::test]
+async fn test_initialize_instances() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    )
+    .start()
+    .await;
+
+    for (instance, fee_bps) in [(0, 30), (1, 40), (2, 50)] {
+        assert_eq!(
+            initialize(&mut banks_client, &program_id, &payer, fee_bps, instance).await,
+            Ok(())
+        );
+    }
+    for (instance, fee_bps) in [(0, 30), (1, 40), (2, 50)] {
+        let program_account = ProgramAuthority::find(&program_id, instance).address;
+        let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+        assert_eq!(
+            SwapConfig::unpack(&account.data),
+            Ok(SwapConfig {
+                admin: payer.pubkey(),
+                fee_bps,
+                instance,
+                ..SwapConfig::default()
+            })
+        );
+    }
+
+    // the instance seed must match the account
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id).0;
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::Initialize { fee_bps: 30, instance: 3 }.pack(&mut data).unwrap();
+    let instruction = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(payer.pubkey(), true),
+            AccountMeta::new(program_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|err| err.unwrap()),
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidSeeds))
+    );
+}
+
+#[tokio::test]
+async fn test_initialize_fee_cap() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    )
+    .start()
+    .await;
+
+    assert_eq!(
+        initialize(&mut banks_client, &program_id, &payer, SwapConfig::MAX_FEE_BPS + 1, 0).await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1129)))
+    );
+    assert_eq!(
+        initialize(&mut banks_client, &program_id, &payer, SwapConfig::MAX_FEE_BPS, 0).await,
+        Ok(())
+    );
+    let program_account = ProgramAuthority::find(&program_id, 0).address;
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    assert_eq!(SwapConfig::unpack(&account.data).unwrap().fee_bps, SwapConfig::MAX_FEE_BPS);
+}
@@ -0,0 +1,171 @@
+//! A swap the pool only partly fills succeeds, refunds the unconsumed input and
+//! reports both amounts in return data.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, invoke_signed},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+        utils::tokens::PREFIX,
+    },
+    std::convert::TryInto,
+};
+
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Most input the shallow pool takes in one swap
+const POOL_DEPTH: u64 = 400;
+
+/// Shallow Raydium stand-in that takes at most POOL_DEPTH of amount_in into the
+/// coin vault and pays min_amount_out pro rata, rounded up, from the pc vault
+fn mock_raydium(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (token_program, amm_authority) = (&accounts[0], &accounts[2]);
+    let (pool_coin, pool_pc) = (&accounts[5], &accounts[6]);
+    let (source, destination, owner) = (&accounts[15], &accounts[16], &accounts[17]);
+
+    let filled = amount_in.min(POOL_DEPTH);
+    let amount_out = (min_amount_out * filled).div_ceil(amount_in);
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            pool_coin.key,
+            owner.key,
+            &[],
+            filled,
+        )?,
+        &[source.clone(), pool_coin.clone(), owner.clone(), token_program.clone()],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            pool_pc.key,
+            destination.key,
+            amm_authority.key,
+            &[],
+            amount_out,
+        )?,
+        &[pool_pc.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_partial_fill_refunds_input() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_v4::id());
+    let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let program_token_a = add_token_account(&mut program_test, &coin_mint, &program_account, 1_000);
+    let program_token_b = add_token_account(&mut program_test, &pc_mint, &program_account, 0);
+    let pool_coin = add_token_account(&mut program_test, &coin_mint, &amm_authority, 1_000_000);
+    let pool_pc = add_token_account(&mut program_test, &pc_mint, &amm_authority, 2_000_000);
+    let user_token_a = add_token_account(&mut program_test, &coin_mint, &Pubkey::new_unique(), 0);
+    let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut accounts = vec![
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_token_a, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(raydium_v4::id(), false),
+        AccountMeta::new(pool_coin, false),
+        AccountMeta::new(pool_pc, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(amm_open_orders, false),
+    ];
+    // amm target, serum market, program, bids, asks, event queue, coin and pc
+    // vaults, vault signer
+    accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    accounts.push(AccountMeta::new(user_token_a, false));
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: 1_000,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+
+    let result = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert_eq!(result.result, Ok(()));
+
+    let quote = quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap();
+    let expected_out = (quote * POOL_DEPTH).div_ceil(1_000);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    assert_eq!(return_data.data[..8], expected_out.to_le_bytes());
+    assert_eq!(return_data.data[8..], POOL_DEPTH.to_le_bytes());
+
+    assert_eq!(token_balance(&mut banks_client, program_token_a).await, 0);
+    assert_eq!(token_balance(&mut banks_client, user_token_a).await, 1_000 - POOL_DEPTH);
+    assert_eq!(token_balance(&mut banks_client, program_token_b).await, expected_out);
+}
@@ -0,0 +1,100 @@
+//! Quote leaves the Raydium pool quote in return data for a simulated transaction.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::AmmInstruction,
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+    },
+    std::convert::TryInto,
+};
+
+fn add_token_account(program_test: &mut ProgramTest, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+#[tokio::test]
+async fn test_quote_return_data() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let pool_coin = add_token_account(&mut program_test, 1_000_000);
+    let pool_pc = add_token_account(&mut program_test, 2_000_000);
+    let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // no pnl to take and open orders without data leave the vault balances unadjusted
+    let accounts = vec![
+        AccountMeta::new_readonly(raydium_v4::id(), false),
+        AccountMeta::new_readonly(amm_id, false),
+        AccountMeta::new_readonly(amm_open_orders, false),
+        AccountMeta::new_readonly(pool_coin, false),
+        AccountMeta::new_readonly(pool_pc, false),
+    ];
+    for (token_a_amount_in, token_b_amount_in, expected_out) in [
+        (1_000, 0, quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap()),
+        (0, 4_000, quote::get_amount_out(4_000, 2_000_000, 1_000_000, RAYDIUM_FEE).unwrap()),
+    ] {
+        let mut data = [0; AmmInstruction::QUOTE_LEN];
+        AmmInstruction::Quote { token_a_amount_in, token_b_amount_in }
+            .pack(&mut data)
+            .unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: accounts.clone(),
+                data: data.to_vec(),
+            }],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+        assert_eq!(simulation.result, Some(Ok(())));
+
+        let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+        assert_eq!(return_data.program_id, program_id);
+        assert_eq!(return_data.data.len(), 16);
+        let amount_in = u64::from_le_bytes(return_data.data[..8].try_into().unwrap());
+        let min_amount_out = u64::from_le_bytes(return_data.data[8..].try_into().unwrap());
+        assert_eq!(amount_in, token_a_amount_in + token_b_amount_in);
+        assert_eq!(min_amount_out, expected_out);
+    }
+}
@@ -0,0 +1,126 @@
+//! A swap failing on slippage leaves the pool quote in return data.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+        utils::tokens::PREFIX,
+    },
+};
+
+/// Raydium stand-in that accepts the swap and delivers nothing
+fn mock_raydium(_program_id: &Pubkey, _accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    Ok(())
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+#[tokio::test]
+async fn test_slippage_failure_returns_quote() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let program_token_a = add_token_account(&mut program_test, &coin_mint, 0);
+    let program_token_b = add_token_account(&mut program_test, &pc_mint, 0);
+    let pool_coin = add_token_account(&mut program_test, &coin_mint, 1_000_000);
+    let pool_pc = add_token_account(&mut program_test, &pc_mint, 2_000_000);
+    let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut accounts = vec![
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_token_a, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(raydium_v4::id(), false),
+        AccountMeta::new(pool_coin, false),
+        AccountMeta::new(pool_pc, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(amm_id, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(amm_open_orders, false),
+    ];
+    // amm target, serum market, program, bids, asks, event queue, coin and pc
+    // vaults, vault signer
+    accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: 1_000,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+
+    let result = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert_eq!(
+        result.result,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1004)))
+    );
+
+    let expected_quote = quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap();
+    assert!(expected_quote > 0);
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    assert_eq!(&return_data.data[..8], &expected_quote.to_le_bytes());
+    assert_eq!(&return_data.data[8..], &0u64.to_le_bytes());
+}
@@ -0,0 +1,99 @@
+//! Swaps executed after their deadline are rejected.
+
+mod common;
+
+use {
+    common::{add_token_account, MockRaydiumPool},
+    solana_program::{
+        clock::Clock,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest, ProgramTestContext},
+    solana_sdk::{
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        utils::tokens::PREFIX,
+    },
+};
+
+const NOW: i64 = 1_700_000_000;
+
+/// Starts a router next to a pool so small the quote of 1 token rounds down to zero,
+/// with the clock at NOW. The pool pays the client minimum of 1, twice.
+/// Returns the context and the swap accounts.
+async fn setup(program_id: &Pubkey) -> (ProgramTestContext, Vec<AccountMeta>) {
+    let mut program_test = ProgramTest::new(
+        "swap",
+        *program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let pool = MockRaydiumPool::add(&mut program_test, 1, 2);
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id);
+    let program_coin = add_token_account(&mut program_test, &pool.coin_mint, &program_account, 2);
+    let program_pc = add_token_account(&mut program_test, &pool.pc_mint, &program_account, 0);
+    let accounts = pool.swap_accounts(program_account, program_coin, program_pc);
+
+    let context = program_test.start_with_context().await;
+    let mut clock: Clock = context.banks_client.clone().get_sysvar().await.unwrap();
+    clock.unix_timestamp = NOW;
+    context.set_sysvar(&clock);
+    (context, accounts)
+}
+
+async fn swap_with_deadline(
+    context: &mut ProgramTestContext,
+    program_id: &Pubkey,
+    accounts: &[AccountMeta],
+    deadline: i64,
+) -> Result<(), TransactionError> {
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: 1,
+        min_token_amount_out: 1,
+        deadline,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let recent_blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: *program_id,
+            accounts: accounts.to_vec(),
+            data: data.to_vec(),
+        }],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        recent_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+#[tokio::test]
+async fn test_swap_before_deadline() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, accounts) = setup(&program_id).await;
+
+    assert_eq!(swap_with_deadline(&mut context, &program_id, &accounts, NOW + 60).await, Ok(()));
+    // zero means no deadline
+    assert_eq!(swap_with_deadline(&mut context, &program_id, &accounts, 0).await, Ok(()));
+}
+
+#[tokio::test]
+async fn test_swap_after_deadline_fails() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, accounts) = setup(&program_id).await;
+
+    assert_eq!(
+        swap_with_deadline(&mut context, &program_id, &accounts, NOW - 1).await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1111)))
+    );
+}
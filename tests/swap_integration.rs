@@ -0,0 +1,183 @@
+//! The before_transfer, swap, after_transfer sequence against a mock Raydium
+//! pool: the user deposits, the router swaps, pays out and takes its fee.
+
+mod common;
+
+use {
+    common::{
+        add_config, add_token_account, load_config, process_instruction, token_balance,
+        MockRaydiumPool,
+    },
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::signature::{Keypair, Signer},
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::RAYDIUM_FEE,
+        quote,
+        state::{FeeToken, SwapConfig, SwapPhase},
+    },
+    std::convert::TryInto,
+};
+
+const COIN_RESERVE: u64 = 1_000_000;
+const PC_RESERVE: u64 = 2_000_000;
+const DEPOSIT: u64 = 1_000;
+
+/// Runs the sequence swapping swap_amount of a DEPOSIT, returns the amount out
+/// the swap reported and the (payout, fee) the user and fee recipient received.
+/// fee_override sets the fee of the fee mint, or of the other mint of the pool when
+/// its flag is false. fee_amount is passed on to AfterTransfer
+async fn run_sequence(
+    fee_token: FeeToken,
+    swap_amount: u64,
+    fee_override: Option<(bool, u16)>,
+    fee_amount: u64,
+) -> (u64, u64, u64) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let pool = MockRaydiumPool::add(&mut program_test, COIN_RESERVE, PC_RESERVE);
+    let fee_mint = match fee_token {
+        FeeToken::Input => pool.coin_mint,
+        FeeToken::Output => pool.pc_mint,
+    };
+    let fee_recipient = add_token_account(&mut program_test, &fee_mint, &Pubkey::new_unique(), 0);
+    let mut config = SwapConfig {
+        fee_token,
+        fee_recipient,
+        ..SwapConfig::default()
+    };
+    if let Some((on_fee_mint, fee_bps)) = fee_override {
+        let other_mint = if fee_mint == pool.coin_mint { pool.pc_mint } else { pool.coin_mint };
+        let mint = if on_fee_mint { fee_mint } else { other_mint };
+        config.set_mint_fee(&mint, fee_bps).unwrap();
+    }
+    let program_account = add_config(&mut program_test, &program_id, &config);
+    let user = Keypair::new();
+    let user_coin = add_token_account(&mut program_test, &pool.coin_mint, &user.pubkey(), DEPOSIT);
+    let user_pc = add_token_account(&mut program_test, &pool.pc_mint, &user.pubkey(), 0);
+    let program_coin = add_token_account(&mut program_test, &pool.coin_mint, &program_account, 0);
+    let program_pc = add_token_account(&mut program_test, &pool.pc_mint, &program_account, 0);
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let mut data = [0; AmmInstruction::BEFORE_TRANSFER_LEN];
+    AmmInstruction::BeforeTransfer { amount: DEPOSIT, tolerance: 0 }.pack(&mut data).unwrap();
+    let before_transfer = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(user.pubkey(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(user_coin, false),
+            AccountMeta::new(program_coin, false),
+            AccountMeta::new_readonly(user.pubkey(), true),
+            AccountMeta::new(program_account, false),
+        ],
+        data: data.to_vec(),
+    };
+    let result = process_instruction(&mut banks_client, &payer, &[&user], before_transfer).await;
+    assert_eq!(result.result, Ok(()));
+    assert_eq!(token_balance(&mut banks_client, program_coin).await, DEPOSIT);
+
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: swap_amount,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let swap = Instruction {
+        program_id,
+        accounts: pool.swap_accounts(program_account, program_coin, program_pc),
+        data: data.to_vec(),
+    };
+    let result = process_instruction(&mut banks_client, &payer, &[], swap).await;
+    assert_eq!(result.result, Ok(()));
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    let amount_out = u64::from_le_bytes(return_data.data[..].try_into().unwrap());
+    assert_eq!(token_balance(&mut banks_client, program_pc).await, amount_out);
+    assert_eq!(load_config(&mut banks_client, program_account).await.swap_phase, SwapPhase::Swapped);
+
+    let mut data = [0; AmmInstruction::AFTER_TRANSFER_LEN];
+    AmmInstruction::AfterTransfer { amount: DEPOSIT, referral_bps: 0, fee_amount }.pack(&mut data).unwrap();
+    let after_transfer = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(program_account, false),
+            AccountMeta::new(program_pc, false),
+            AccountMeta::new(program_coin, false),
+            AccountMeta::new(user_pc, false),
+            AccountMeta::new(fee_recipient, false),
+            AccountMeta::new_readonly(pool.pc_mint, false),
+            AccountMeta::new_readonly(pool.coin_mint, false),
+        ],
+        data: data.to_vec(),
+    };
+    let result = process_instruction(&mut banks_client, &payer, &[], after_transfer).await;
+    assert_eq!(result.result, Ok(()));
+    assert_eq!(load_config(&mut banks_client, program_account).await.swap_phase, SwapPhase::Idle);
+
+    // the pool took the input, the router keeps nothing
+    assert_eq!(token_balance(&mut banks_client, pool.pool_coin).await, COIN_RESERVE + swap_amount);
+    assert_eq!(token_balance(&mut banks_client, pool.pool_pc).await, PC_RESERVE - amount_out);
+    assert_eq!(token_balance(&mut banks_client, user_coin).await, 0);
+    assert_eq!(token_balance(&mut banks_client, program_coin).await, 0);
+    assert_eq!(token_balance(&mut banks_client, program_pc).await, 0);
+    (
+        amount_out,
+        token_balance(&mut banks_client, user_pc).await,
+        token_balance(&mut banks_client, fee_recipient).await,
+    )
+}
+
+#[tokio::test]
+async fn test_swap_sequence_fee_from_input() {
+    // the default 50 bps of the deposit stay in the program account for the fee
+    let fee = DEPOSIT * SwapConfig::DEFAULT_FEE_BPS as u64 / 10_000;
+    let (amount_out, payout, fee_paid) = run_sequence(FeeToken::Input, DEPOSIT - fee, None, 0).await;
+
+    assert_eq!(amount_out, quote::get_amount_out(DEPOSIT - fee, COIN_RESERVE, PC_RESERVE, RAYDIUM_FEE).unwrap());
+    assert_eq!(payout, amount_out);
+    assert_eq!(fee_paid, fee);
+}
+
+#[tokio::test]
+async fn test_swap_sequence_fee_from_output() {
+    let (amount_out, payout, fee_paid) = run_sequence(FeeToken::Output, DEPOSIT, None, 0).await;
+
+    assert_eq!(amount_out, quote::get_amount_out(DEPOSIT, COIN_RESERVE, PC_RESERVE, RAYDIUM_FEE).unwrap());
+    let fee = amount_out * SwapConfig::DEFAULT_FEE_BPS as u64 / 10_000;
+    assert_eq!(fee_paid, fee);
+    assert_eq!(payout, amount_out - fee);
+}
+
+#[tokio::test]
+async fn test_swap_sequence_mint_fee_override() {
+    // the fee mint pays its own 10 bps
+    let fee = DEPOSIT * 10 / 10_000;
+    let (amount_out, payout, fee_paid) = run_sequence(FeeToken::Input, DEPOSIT - fee, Some((true, 10)), 0).await;
+    assert_eq!(payout, amount_out);
+    assert_eq!(fee_paid, fee);
+
+    // an override on the other mint leaves the default fee
+    let fee = DEPOSIT * SwapConfig::DEFAULT_FEE_BPS as u64 / 10_000;
+    let (amount_out, payout, fee_paid) = run_sequence(FeeToken::Input, DEPOSIT - fee, Some((false, 10)), 0).await;
+    assert_eq!(payout, amount_out);
+    assert_eq!(fee_paid, fee);
+}
+
+#[tokio::test]
+async fn test_swap_sequence_explicit_fee() {
+    // the client keeps 7 of the deposit back and charges exactly that
+    let (amount_out, payout, fee_paid) = run_sequence(FeeToken::Input, DEPOSIT - 7, None, 7).await;
+    assert_eq!(payout, amount_out);
+    assert_eq!(fee_paid, 7);
+}
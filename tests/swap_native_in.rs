@@ -0,0 +1,185 @@
+//! SwapNativeIn wraps the user's SOL, swaps it and returns the temporary wrapped
+//! SOL account's lamports to the user.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, invoke_signed},
+        program_option::COption,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        system_program,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    spl_token::{
+        native_mint,
+        state::{Account as TokenAccount, AccountState},
+    },
+    std::convert::TryInto,
+    swap::{
+        instruction::AmmInstruction,
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+        utils::tokens::PREFIX,
+    },
+};
+
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Raydium stand-in that takes amount_in into the coin vault and pays exactly
+/// min_amount_out from the pc vault
+fn mock_raydium(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (token_program, amm_authority) = (&accounts[0], &accounts[2]);
+    let (pool_coin, pool_pc) = (&accounts[5], &accounts[6]);
+    let (source, destination, owner) = (&accounts[15], &accounts[16], &accounts[17]);
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            pool_coin.key,
+            owner.key,
+            &[],
+            amount_in,
+        )?,
+        &[source.clone(), pool_coin.clone(), owner.clone(), token_program.clone()],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            pool_pc.key,
+            destination.key,
+            amm_authority.key,
+            &[],
+            min_amount_out,
+        )?,
+        &[pool_pc.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+/// Adds a token account, a wrapped SOL one holds its amount in lamports on top of rent
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let rent = Rent::default().minimum_balance(TokenAccount::LEN);
+    let is_native = *mint == native_mint::id();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            is_native: if is_native { COption::Some(rent) } else { COption::None },
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: if is_native { rent + amount } else { rent },
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+async fn lamports(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    banks_client.get_balance(address).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_swap_native_in() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+    let user = Keypair::new();
+    program_test.add_account(
+        user.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            owner: system_program::id(),
+            ..Account::default()
+        },
+    );
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_v4::id());
+    let pc_mint = Pubkey::new_unique();
+    let program_wsol = add_token_account(&mut program_test, &native_mint::id(), &program_account, 0);
+    let program_token_b = add_token_account(&mut program_test, &pc_mint, &program_account, 0);
+    let pool_coin = add_token_account(&mut program_test, &native_mint::id(), &amm_authority, 1_000_000);
+    let pool_pc = add_token_account(&mut program_test, &pc_mint, &amm_authority, 2_000_000);
+    let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let wsol_rent = lamports(&mut banks_client, program_wsol).await;
+    let user_lamports = lamports(&mut banks_client, user.pubkey()).await;
+
+    let mut accounts = vec![
+        AccountMeta::new(user.pubkey(), true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_wsol, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(raydium_v4::id(), false),
+        AccountMeta::new(pool_coin, false),
+        AccountMeta::new(pool_pc, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(amm_open_orders, false),
+    ];
+    // amm target, serum market, program, bids, asks, event queue, coin and pc
+    // vaults, vault signer
+    accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    let mut data = [0; AmmInstruction::SWAP_NATIVE_IN_LEN];
+    AmmInstruction::SwapNativeIn {
+        amount_in: 1_000,
+        min_token_amount_out: 1,
+    }
+    .pack(&mut data)
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the user spent amount_in and got the temporary account's rent back
+    assert_eq!(
+        lamports(&mut banks_client, user.pubkey()).await,
+        user_lamports - 1_000 + wsol_rent
+    );
+    assert_eq!(banks_client.get_account(program_wsol).await.unwrap(), None);
+    assert_eq!(lamports(&mut banks_client, pool_coin).await, wsol_rent + 1_001_000);
+    let expected_out = quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap();
+    let program_token_b = banks_client.get_account(program_token_b).await.unwrap().unwrap();
+    assert_eq!(TokenAccount::unpack(&program_token_b.data).unwrap().amount, expected_out);
+}
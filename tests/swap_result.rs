@@ -0,0 +1,195 @@
+//! A completed swap leaves the amount out in return data for the calling program.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{get_return_data, invoke, invoke_signed, set_return_data},
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::{AmmInstruction, SwapParams},
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+        utils::tokens::PREFIX,
+    },
+    std::convert::TryInto,
+};
+
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Raydium stand-in that takes amount_in into the coin vault and pays exactly
+/// min_amount_out from the pc vault
+fn mock_raydium(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (token_program, amm_authority) = (&accounts[0], &accounts[2]);
+    let (pool_coin, pool_pc) = (&accounts[5], &accounts[6]);
+    let (source, destination, owner) = (&accounts[15], &accounts[16], &accounts[17]);
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            pool_coin.key,
+            owner.key,
+            &[],
+            amount_in,
+        )?,
+        &[source.clone(), pool_coin.clone(), owner.clone(), token_program.clone()],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            pool_pc.key,
+            destination.key,
+            amm_authority.key,
+            &[],
+            min_amount_out,
+        )?,
+        &[pool_pc.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+/// Calls the router with the remaining accounts and checks the amount out in its
+/// return data against the balance change of the token b account, then republishes it.
+///
+/// Accounts: router program, then the router swap accounts
+fn swap_caller(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (router, swap_accounts) = accounts.split_first().unwrap();
+    let program_token_b = &swap_accounts[2];
+    let balance_before = TokenAccount::unpack(&program_token_b.data.borrow())?.amount;
+    invoke(
+        &Instruction {
+            program_id: *router.key,
+            accounts: swap_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: data.to_vec(),
+        },
+        swap_accounts,
+    )?;
+    let balance_after = TokenAccount::unpack(&program_token_b.data.borrow())?.amount;
+
+    let (program_id, return_data) = get_return_data().ok_or(ProgramError::InvalidAccountData)?;
+    if program_id != *router.key || return_data.len() != 8 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let amount_out = u64::from_le_bytes(return_data[..].try_into().unwrap());
+    if amount_out != balance_after - balance_before {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    set_return_data(&return_data);
+    Ok(())
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+#[tokio::test]
+async fn test_swap_returns_amount_out() {
+    let program_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+    program_test.add_program("swap_caller", caller_id, processor!(swap_caller));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_v4::id());
+    let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let program_token_a = add_token_account(&mut program_test, &coin_mint, &program_account, 1_000);
+    let program_token_b = add_token_account(&mut program_test, &pc_mint, &program_account, 0);
+    let pool_coin = add_token_account(&mut program_test, &coin_mint, &amm_authority, 1_000_000);
+    let pool_pc = add_token_account(&mut program_test, &pc_mint, &amm_authority, 2_000_000);
+    let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program_id, false),
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_token_a, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(raydium_v4::id(), false),
+        AccountMeta::new(pool_coin, false),
+        AccountMeta::new(pool_pc, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(amm_id, false),
+        AccountMeta::new_readonly(amm_authority, false),
+        AccountMeta::new(amm_open_orders, false),
+    ];
+    // amm target, serum market, program, bids, asks, event queue, coin and pc
+    // vaults, vault signer
+    accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    let mut data = [0; AmmInstruction::SWAP_LEN];
+    AmmInstruction::Swap(SwapParams {
+        token_a_amount_in: 1_000,
+        ..SwapParams::default()
+    })
+    .pack(&mut data)
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: caller_id,
+            accounts,
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+
+    let result = banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert_eq!(result.result, Ok(()));
+
+    let expected_out = quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap();
+    let return_data = result.metadata.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, caller_id);
+    assert_eq!(return_data.data, expected_out.to_le_bytes());
+    let program_token_b = banks_client.get_account(program_token_b).await.unwrap().unwrap();
+    assert_eq!(TokenAccount::unpack(&program_token_b.data).unwrap().amount, expected_out);
+}
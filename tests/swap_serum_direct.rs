@@ -0,0 +1,220 @@
+//! SwapSerumDirect places an immediate or cancel order and settles it into the
+//! program token accounts.
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program::invoke_signed,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        sysvar,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{instruction::AmmInstruction, protocol::serum::serum_v3, utils::tokens::PREFIX},
+    std::convert::TryInto,
+};
+
+const COIN_LOT_SIZE: u64 = 10;
+/// pc paid per coin by the mock book
+const PRICE: u64 = 2;
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Serum stand-in with an unlimited book at PRICE. NewOrderV3 takes the input
+/// into the vault and credits the fill to the open orders, SettleFunds pays it out.
+fn mock_serum(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let tag = u32::from_le_bytes(data[1..5].try_into().unwrap());
+    let (market, open_orders) = (&accounts[0], &accounts[1]);
+    let (_, bump_seed) = Pubkey::find_program_address(&[market.key.as_ref()], program_id);
+    let vault_signer_seeds: &[&[u8]] = &[market.key.as_ref(), &[bump_seed]];
+    match tag {
+        10 => {
+            let side = u32::from_le_bytes(data[5..9].try_into().unwrap());
+            let (payer, owner) = (&accounts[6], &accounts[7]);
+            let (coin_vault, pc_vault, token_program) = (&accounts[8], &accounts[9], &accounts[10]);
+            let (vault, amount_in, coin_free, pc_free) = if side == 1 {
+                let coin_in = read_u64(data, 17) * COIN_LOT_SIZE;
+                (coin_vault, coin_in, 0, coin_in * PRICE)
+            } else {
+                let pc_in = read_u64(data, 25);
+                (pc_vault, pc_in, pc_in / PRICE, 0)
+            };
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    token_program.key,
+                    payer.key,
+                    vault.key,
+                    owner.key,
+                    &[],
+                    amount_in,
+                )?,
+                &[payer.clone(), vault.clone(), owner.clone(), token_program.clone()],
+                &[],
+            )?;
+            let mut open_orders_data = open_orders.try_borrow_mut_data()?;
+            open_orders_data[..8].copy_from_slice(&coin_free.to_le_bytes());
+            open_orders_data[8..16].copy_from_slice(&pc_free.to_le_bytes());
+            Ok(())
+        }
+        5 => {
+            let (coin_vault, pc_vault) = (&accounts[3], &accounts[4]);
+            let (coin_wallet, pc_wallet) = (&accounts[5], &accounts[6]);
+            let (vault_signer, token_program) = (&accounts[7], &accounts[8]);
+            let (coin_free, pc_free) = {
+                let open_orders_data = open_orders.try_borrow_data()?;
+                (read_u64(&open_orders_data, 0), read_u64(&open_orders_data, 8))
+            };
+            for (vault, wallet, amount) in [(coin_vault, coin_wallet, coin_free), (pc_vault, pc_wallet, pc_free)] {
+                invoke_signed(
+                    &spl_token::instruction::transfer(
+                        token_program.key,
+                        vault.key,
+                        wallet.key,
+                        vault_signer.key,
+                        &[],
+                        amount,
+                    )?,
+                    &[vault.clone(), wallet.clone(), vault_signer.clone(), token_program.clone()],
+                    &[vault_signer_seeds],
+                )?;
+            }
+            open_orders.try_borrow_mut_data()?[..16].fill(0);
+            Ok(())
+        }
+        _ => panic!("unexpected serum instruction {}", tag),
+    }
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+fn add_serum_account(program_test: &mut ProgramTest, address: Pubkey, data: Vec<u8>) {
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: serum_v3::id(),
+            ..Account::default()
+        },
+    );
+}
+
+#[tokio::test]
+async fn test_swap_serum_direct() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("serum", serum_v3::id(), processor!(mock_serum));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let market = Pubkey::new_unique();
+    let (vault_signer, _) = Pubkey::find_program_address(&[market.as_ref()], &serum_v3::id());
+    let (coin_mint, pc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let program_token_a = add_token_account(&mut program_test, &coin_mint, &program_account, 1_005);
+    let program_token_b = add_token_account(&mut program_test, &pc_mint, &program_account, 1_000);
+    let coin_vault = add_token_account(&mut program_test, &coin_mint, &vault_signer, 1_000_000);
+    let pc_vault = add_token_account(&mut program_test, &pc_mint, &vault_signer, 1_000_000);
+
+    // "serum" head, market state, "padding" tail
+    let mut market_data = vec![0; 5 + 376 + 7];
+    market_data[..5].copy_from_slice(b"serum");
+    market_data[5 + 112..5 + 144].copy_from_slice(coin_vault.as_ref());
+    market_data[5 + 160..5 + 192].copy_from_slice(pc_vault.as_ref());
+    market_data[5 + 344..5 + 352].copy_from_slice(&COIN_LOT_SIZE.to_le_bytes());
+    market_data[5 + 352..5 + 360].copy_from_slice(&1u64.to_le_bytes());
+    add_serum_account(&mut program_test, market, market_data);
+    let open_orders = Pubkey::new_unique();
+    add_serum_account(&mut program_test, open_orders, vec![0; 16]);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let accounts = vec![
+        AccountMeta::new(program_account, false),
+        AccountMeta::new(program_token_a, false),
+        AccountMeta::new(program_token_b, false),
+        AccountMeta::new_readonly(serum_v3::id(), false),
+        AccountMeta::new(market, false),
+        AccountMeta::new(open_orders, false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(Pubkey::new_unique(), false),
+        AccountMeta::new(coin_vault, false),
+        AccountMeta::new(pc_vault, false),
+        AccountMeta::new_readonly(vault_signer, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    for (token_a_amount_in, token_b_amount_in, min_token_amount_out, result) in [
+        // sell coin in whole lots, the remainder stays with the program
+        (1_005, 0, 2_000, Ok(())),
+        // buy coin, the book fills 500 and falls short of the minimum
+        (0, 1_000, 600, Err(TransactionError::InstructionError(0, InstructionError::Custom(1004)))),
+    ] {
+        let mut data = [0; AmmInstruction::SWAP_SERUM_DIRECT_LEN];
+        AmmInstruction::SwapSerumDirect {
+            token_a_amount_in,
+            token_b_amount_in,
+            min_token_amount_out,
+        }
+        .pack(&mut data)
+        .unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: accounts.clone(),
+                data: data.to_vec(),
+            }],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        let metadata = banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert_eq!(metadata.result, result);
+    }
+
+    let balance = |account: Account| TokenAccount::unpack(&account.data).unwrap().amount;
+    let program_token_a = banks_client.get_account(program_token_a).await.unwrap().unwrap();
+    let program_token_b = banks_client.get_account(program_token_b).await.unwrap().unwrap();
+    assert_eq!(balance(program_token_a), 5);
+    assert_eq!(balance(program_token_b), 3_000);
+}
@@ -0,0 +1,205 @@
+//! A two hop swap feeds the first leg's output into the second and reverts both
+//! when the second fails.
+
+mod common;
+
+use {
+    common::add_raydium_amm,
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, invoke_signed},
+        program_error::ProgramError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{account::Account, signature::Signer, transaction::Transaction},
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{
+        instruction::AmmInstruction,
+        protocol::raydium::{raydium_v4, RAYDIUM_FEE},
+        quote,
+        utils::tokens::PREFIX,
+    },
+    std::convert::TryInto,
+};
+
+const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+/// Raydium stand-in that takes amount_in into the coin vault and pays the
+/// constant product quote from the pc vault, failing below min_amount_out
+fn mock_raydium(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+    let (token_program, amm_authority) = (&accounts[0], &accounts[2]);
+    let (pool_coin, pool_pc) = (&accounts[5], &accounts[6]);
+    let (source, destination, owner) = (&accounts[15], &accounts[16], &accounts[17]);
+    let amount_out = quote::get_amount_out(
+        amount_in,
+        TokenAccount::unpack(&pool_coin.data.borrow())?.amount,
+        TokenAccount::unpack(&pool_pc.data.borrow())?.amount,
+        RAYDIUM_FEE,
+    )?;
+    if amount_out < min_amount_out {
+        return Err(ProgramError::Custom(30));
+    }
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            pool_coin.key,
+            owner.key,
+            &[],
+            amount_in,
+        )?,
+        &[source.clone(), pool_coin.clone(), owner.clone(), token_program.clone()],
+    )?;
+    let (_, bump_seed) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], program_id);
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            pool_pc.key,
+            destination.key,
+            amm_authority.key,
+            &[],
+            amount_out,
+        )?,
+        &[pool_pc.clone(), destination.clone(), amm_authority.clone(), token_program.clone()],
+        &[&[AMM_AUTHORITY_SEED, &[bump_seed]]],
+    )
+}
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+async fn get_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+/// Starts a router holding 1_000 of token A with an A/B pool of 1_000_000/2_000_000
+/// and a B/C pool of 4_000_000/1_000_000, swaps all of it to C and returns the
+/// client, whether the swap succeeded and the program A, B and C token accounts
+async fn swap_two_hop(min_final_out: u64) -> (BanksClient, bool, [Pubkey; 3]) {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    program_test.add_program("raydium", raydium_v4::id(), processor!(mock_raydium));
+    let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id);
+    let (amm_authority, _) = Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], &raydium_v4::id());
+    let mints = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let program_tokens = [
+        add_token_account(&mut program_test, &mints[0], &program_account, 1_000),
+        add_token_account(&mut program_test, &mints[1], &program_account, 0),
+        add_token_account(&mut program_test, &mints[2], &program_account, 0),
+    ];
+    let pools = [
+        (
+            add_token_account(&mut program_test, &mints[0], &amm_authority, 1_000_000),
+            add_token_account(&mut program_test, &mints[1], &amm_authority, 2_000_000),
+        ),
+        (
+            add_token_account(&mut program_test, &mints[1], &amm_authority, 4_000_000),
+            add_token_account(&mut program_test, &mints[2], &amm_authority, 1_000_000),
+        ),
+    ];
+    let pools = pools.map(|(pool_coin, pool_pc)| {
+        let (amm_id, amm_open_orders) = add_raydium_amm(&mut program_test, &pool_coin, &pool_pc);
+        (pool_coin, pool_pc, amm_id, amm_open_orders)
+    });
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut accounts = vec![AccountMeta::new(program_account, false)];
+    accounts.extend(program_tokens.iter().map(|address| AccountMeta::new(*address, false)));
+    for (pool_coin, pool_pc, amm_id, amm_open_orders) in pools {
+        accounts.extend([
+            AccountMeta::new_readonly(raydium_v4::id(), false),
+            AccountMeta::new(pool_coin, false),
+            AccountMeta::new(pool_pc, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(amm_id, false),
+            AccountMeta::new_readonly(amm_authority, false),
+            AccountMeta::new(amm_open_orders, false),
+        ]);
+        // amm target, serum market, program, bids, asks, event queue, coin and pc
+        // vaults, vault signer
+        accounts.extend((0..9).map(|_| AccountMeta::new(Pubkey::new_unique(), false)));
+    }
+    let mut data = [0; AmmInstruction::SWAP_TWO_HOP_LEN];
+    AmmInstruction::SwapTwoHop {
+        amount_in: 1_000,
+        min_final_out,
+    }
+    .pack(&mut data)
+    .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: data.to_vec(),
+        }],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+
+    let succeeded = banks_client.process_transaction(transaction).await.is_ok();
+    (banks_client, succeeded, program_tokens)
+}
+
+fn expected_amount_out() -> u64 {
+    let amount_mid = quote::get_amount_out(1_000, 1_000_000, 2_000_000, RAYDIUM_FEE).unwrap();
+    quote::get_amount_out(amount_mid, 4_000_000, 1_000_000, RAYDIUM_FEE).unwrap()
+}
+
+#[tokio::test]
+async fn test_swap_two_hop() {
+    let amount_out = expected_amount_out();
+    assert!(amount_out > 0);
+    let (mut banks_client, succeeded, program_tokens) = swap_two_hop(amount_out).await;
+    assert!(succeeded);
+
+    assert_eq!(get_balance(&mut banks_client, program_tokens[0]).await, 0);
+    assert_eq!(get_balance(&mut banks_client, program_tokens[1]).await, 0);
+    assert_eq!(get_balance(&mut banks_client, program_tokens[2]).await, amount_out);
+}
+
+#[tokio::test]
+async fn test_swap_two_hop_second_leg_failure_reverts() {
+    // the first leg goes through, the second falls short of min_final_out
+    let (mut banks_client, succeeded, program_tokens) = swap_two_hop(expected_amount_out() + 1).await;
+    assert!(!succeeded);
+
+    assert_eq!(get_balance(&mut banks_client, program_tokens[0]).await, 1_000);
+    assert_eq!(get_balance(&mut banks_client, program_tokens[1]).await, 0);
+    assert_eq!(get_balance(&mut banks_client, program_tokens[2]).await, 0);
+}
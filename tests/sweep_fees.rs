@@ -0,0 +1,85 @@
+//! SweepFees empties several fee token accounts of the router PDA in one call.
+
+mod common;
+
+use {
+    common::{add_config, add_mint, add_token_account, process_instruction, token_balance},
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::TransactionError,
+    },
+    swap::{instruction::AmmInstruction, state::SwapConfig},
+};
+
+#[tokio::test]
+async fn test_sweep_fees() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let admin = Keypair::new();
+    let program_account = add_config(
+        &mut program_test,
+        &program_id,
+        &SwapConfig {
+            admin: admin.pubkey(),
+            ..SwapConfig::default()
+        },
+    );
+    // fee accounts of three mints, the second one empty
+    let balances = [1_000, 0, 250];
+    let mut pairs = vec![];
+    for balance in balances {
+        let mint = add_mint(&mut program_test, 6);
+        pairs.push((
+            add_token_account(&mut program_test, &mint, &program_account, balance),
+            add_token_account(&mut program_test, &mint, &Pubkey::new_unique(), 0),
+        ));
+    }
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(admin.pubkey(), true),
+        AccountMeta::new_readonly(program_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for (fee_account, destination) in &pairs {
+        accounts.push(AccountMeta::new(*fee_account, false));
+        accounts.push(AccountMeta::new(*destination, false));
+    }
+    let sweep_fees = |count: u8, accounts: &[AccountMeta]| {
+        let mut data = [0; AmmInstruction::LEN];
+        AmmInstruction::SweepFees { count }.pack(&mut data).unwrap();
+        Instruction {
+            program_id,
+            accounts: accounts.to_vec(),
+            data: data.to_vec(),
+        }
+    };
+
+    // the count must cover the accounts exactly
+    let result = process_instruction(&mut banks_client, &payer, &[&admin], sweep_fees(2, &accounts)).await;
+    assert_eq!(
+        result.result,
+        Err(TransactionError::InstructionError(0, InstructionError::InvalidArgument))
+    );
+    let result = process_instruction(&mut banks_client, &payer, &[&admin], sweep_fees(4, &accounts)).await;
+    assert_eq!(
+        result.result,
+        Err(TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys))
+    );
+
+    let result = process_instruction(&mut banks_client, &payer, &[&admin], sweep_fees(3, &accounts)).await;
+    assert_eq!(result.result, Ok(()));
+    for ((fee_account, destination), balance) in pairs.iter().zip(balances) {
+        assert_eq!(token_balance(&mut banks_client, *fee_account).await, 0);
+        assert_eq!(token_balance(&mut banks_client, *destination).await, balance);
+    }
+}
@@ -0,0 +1,392 @@
+//! Token transfers made by the router surface spl_token failures.
+
+use {
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState, Mint},
+    spl_token_2022::{
+        extension::{
+            transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig},
+            BaseStateWithExtensions, ExtensionType, StateWithExtensionsMut,
+        },
+        state::{Account as Token2022Account, AccountState as Token2022AccountState, Mint as Token2022Mint},
+    },
+    swap::{
+        instruction::AmmInstruction,
+        state::{SwapConfig, SwapPhase},
+        utils::tokens::PREFIX,
+    },
+};
+
+const TRANSFER_FEE_BPS: u16 = 100;
+
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *token_program,
+            ..Account::default()
+        },
+    );
+    address
+}
+
+/// Starts a router with a user holding user_amount tokens of token_program and an empty
+/// program account, returns the client, payer, user, user and program token accounts
+async fn setup(
+    program_id: &Pubkey,
+    token_program: &Pubkey,
+    user_amount: u64,
+) -> (BanksClient, Keypair, Keypair, Pubkey, Pubkey) {
+    let mut program_test = ProgramTest::new(
+        "swap",
+        *program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let user = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            supply: user_amount,
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        mint,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *token_program,
+            ..Account::default()
+        },
+    );
+    let user_token_account =
+        add_token_account(&mut program_test, token_program, &mint, &user.pubkey(), user_amount);
+    let program_token_account =
+        add_token_account(&mut program_test, token_program, &mint, &Pubkey::new_unique(), 0);
+
+    let (banks_client, payer, _) = program_test.start().await;
+    (banks_client, payer, user, user_token_account, program_token_account)
+}
+
+/// Token-2022 account of the transfer fee mint, with room for the withheld fee
+fn add_fee_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let len = ExtensionType::try_calculate_account_len::<Token2022Account>(&[ExtensionType::TransferFeeAmount])
+        .unwrap();
+    let mut data = vec![0; len];
+    let mut state = StateWithExtensionsMut::<Token2022Account>::unpack_uninitialized(&mut data).unwrap();
+    state.base = Token2022Account {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: Token2022AccountState::Initialized,
+        ..Token2022Account::default()
+    };
+    state.pack_base();
+    state.init_extension::<TransferFeeAmount>(true).unwrap();
+    state.init_account_type().unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token_2022::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+/// Same as setup with a Token-2022 mint taking TRANSFER_FEE_BPS of every transfer
+/// and a stored config, also returns the mint
+async fn setup_transfer_fee(
+    program_id: &Pubkey,
+    user_amount: u64,
+) -> (BanksClient, Keypair, Keypair, Pubkey, Pubkey, Pubkey) {
+    let mut program_test = ProgramTest::new(
+        "swap",
+        *program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let user = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let len = ExtensionType::try_calculate_account_len::<Token2022Mint>(&[ExtensionType::TransferFeeConfig])
+        .unwrap();
+    let mut data = vec![0; len];
+    let mut state = StateWithExtensionsMut::<Token2022Mint>::unpack_uninitialized(&mut data).unwrap();
+    state.base = Token2022Mint {
+        supply: user_amount,
+        decimals: 6,
+        is_initialized: true,
+        ..Token2022Mint::default()
+    };
+    state.pack_base();
+    let fee = TransferFee {
+        epoch: 0.into(),
+        maximum_fee: u64::MAX.into(),
+        transfer_fee_basis_points: TRANSFER_FEE_BPS.into(),
+    };
+    let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+    config.older_transfer_fee = fee;
+    config.newer_transfer_fee = fee;
+    state.init_account_type().unwrap();
+    assert!(state.get_extension::<TransferFeeConfig>().is_ok());
+    program_test.add_account(
+        mint,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token_2022::id(),
+            ..Account::default()
+        },
+    );
+    let mut data = vec![0; SwapConfig::LEN];
+    SwapConfig::default().pack(&mut data).unwrap();
+    program_test.add_account(
+        Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    let user_token_account = add_fee_token_account(&mut program_test, &mint, &user.pubkey(), user_amount);
+    let program_token_account = add_fee_token_account(&mut program_test, &mint, &Pubkey::new_unique(), 0);
+
+    let (banks_client, payer, _) = program_test.start().await;
+    (banks_client, payer, user, user_token_account, program_token_account, mint)
+}
+
+/// Runs before_transfer with the token program owning the user token account,
+/// passing the mint makes the deposit a transfer_checked
+#[allow(clippy::too_many_arguments)]
+async fn before_transfer(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    user: &Keypair,
+    user_token_account: &Pubkey,
+    program_token_account: &Pubkey,
+    mint: Option<&Pubkey>,
+    amount: u64,
+    tolerance: u64,
+) -> Result<(), TransactionError> {
+    let token_program = banks_client
+        .get_account(*user_token_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .owner;
+    let mut data = [0; AmmInstruction::BEFORE_TRANSFER_LEN];
+    AmmInstruction::BeforeTransfer { amount, tolerance }.pack(&mut data).unwrap();
+    let mut accounts = vec![
+        AccountMeta::new_readonly(user.pubkey(), false),
+        AccountMeta::new_readonly(token_program, false),
+        AccountMeta::new(*user_token_account, false),
+        AccountMeta::new(*program_token_account, false),
+        AccountMeta::new_readonly(user.pubkey(), true),
+    ];
+    if let Some(mint) = mint {
+        let (program_account, _) = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id);
+        accounts.push(AccountMeta::new(program_account, false));
+        accounts.push(AccountMeta::new_readonly(*mint, false));
+    }
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts,
+        data: data.to_vec(),
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer, user],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*address).await.unwrap().unwrap();
+    // Token-2022 extensions follow the base account
+    TokenAccount::unpack(&account.data[..TokenAccount::LEN]).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_transfer_from_funded_account() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, user, user_token_account, program_token_account) =
+        setup(&program_id, &spl_token::id(), 1_000).await;
+
+    assert_eq!(
+        before_transfer(
+            &mut banks_client,
+            &program_id,
+            &payer,
+            &user,
+            &user_token_account,
+            &program_token_account,
+            None,
+            400,
+            0,
+        )
+        .await,
+        Ok(())
+    );
+    assert_eq!(token_balance(&mut banks_client, &user_token_account).await, 600);
+    assert_eq!(token_balance(&mut banks_client, &program_token_account).await, 400);
+}
+
+#[tokio::test]
+async fn test_transfer_from_underfunded_account_fails() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, user, user_token_account, program_token_account) =
+        setup(&program_id, &spl_token::id(), 100).await;
+
+    assert_eq!(
+        before_transfer(
+            &mut banks_client,
+            &program_id,
+            &payer,
+            &user,
+            &user_token_account,
+            &program_token_account,
+            None,
+            400,
+            0,
+        )
+        .await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1101)))
+    );
+    assert_eq!(token_balance(&mut banks_client, &user_token_account).await, 100);
+    assert_eq!(token_balance(&mut banks_client, &program_token_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_transfer_with_token_2022() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, user, user_token_account, program_token_account) =
+        setup(&program_id, &spl_token_2022::id(), 1_000).await;
+
+    assert_eq!(
+        before_transfer(
+            &mut banks_client,
+            &program_id,
+            &payer,
+            &user,
+            &user_token_account,
+            &program_token_account,
+            None,
+            400,
+            0,
+        )
+        .await,
+        Ok(())
+    );
+    assert_eq!(token_balance(&mut banks_client, &user_token_account).await, 600);
+    assert_eq!(token_balance(&mut banks_client, &program_token_account).await, 400);
+}
+
+#[tokio::test]
+async fn test_transfer_with_unknown_token_program_fails() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, user, user_token_account, program_token_account) =
+        setup(&program_id, &Pubkey::new_unique(), 1_000).await;
+
+    assert_eq!(
+        before_transfer(
+            &mut banks_client,
+            &program_id,
+            &payer,
+            &user,
+            &user_token_account,
+            &program_token_account,
+            None,
+            400,
+            0,
+        )
+        .await,
+        // balances under an unknown owner aren't read, let alone moved
+        Err(TransactionError::InstructionError(0, InstructionError::IllegalOwner))
+    );
+    assert_eq!(token_balance(&mut banks_client, &user_token_account).await, 1_000);
+}
+
+#[tokio::test]
+async fn test_transfer_with_transfer_fee() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, user, user_token_account, program_token_account, mint) =
+        setup_transfer_fee(&program_id, 1_000).await;
+
+    // 1% of 400 is withheld, only a tolerance covering it lets the deposit through
+    for (tolerance, result) in [
+        (3, Err(TransactionError::InstructionError(0, InstructionError::Custom(1118)))),
+        (4, Ok(())),
+    ] {
+        assert_eq!(
+            before_transfer(
+                &mut banks_client,
+                &program_id,
+                &payer,
+                &user,
+                &user_token_account,
+                &program_token_account,
+                Some(&mint),
+                400,
+                tolerance,
+            )
+            .await,
+            result
+        );
+    }
+    assert_eq!(token_balance(&mut banks_client, &user_token_account).await, 600);
+    assert_eq!(token_balance(&mut banks_client, &program_token_account).await, 396);
+
+    // the swap that follows spends what arrived, not the nominal 400
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], &program_id).0;
+    let account = banks_client.get_account(program_account).await.unwrap().unwrap();
+    let config = SwapConfig::unpack(&account.data).unwrap();
+    assert_eq!(config.swap_phase, SwapPhase::Deposited);
+    assert_eq!(config.deposited_amount, 396);
+    assert_eq!(config.net_amount_in(400), 396);
+}
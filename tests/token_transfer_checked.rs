@@ -0,0 +1,218 @@
+//! Checked token transfers are rejected by the token program on a mint or
+//! decimals mismatch.
+
+use {
+    solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState, Mint},
+    std::convert::TryInto,
+    swap::utils::tokens::{spl_token_transfer_checked, TokenTransferCheckedParams},
+};
+
+/// Transfers with spl_token_transfer_checked.
+///
+/// Accounts: source, mint, destination, authority (signer), token program.
+/// Data: amount u64, decimals u8
+fn transfer_checked(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    spl_token_transfer_checked(TokenTransferCheckedParams {
+        source: accounts[0].clone(),
+        mint: accounts[1].clone(),
+        destination: accounts[2].clone(),
+        authority: accounts[3].clone(),
+        token_program: accounts[4].clone(),
+        authority_signer_seeds: &[],
+        amount: u64::from_le_bytes(data[..8].try_into().unwrap()),
+        decimals: data[8],
+    })
+}
+
+fn add_account(program_test: &mut ProgramTest, owner: &Pubkey, data: Vec<u8>) -> Pubkey {
+    let address = Pubkey::new_unique();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *owner,
+            ..Account::default()
+        },
+    );
+    address
+}
+
+fn pack_mint(decimals: u8) -> Vec<u8> {
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            supply: 1_000,
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+fn pack_token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+/// Starts a transfer program with a user holding 1_000 tokens of a 6 decimals mint
+/// under token_program, and a second mint. Returns the client, payer, user,
+/// source, destination, mint and other mint.
+async fn setup(
+    program_id: &Pubkey,
+    token_program: &Pubkey,
+) -> (BanksClient, Keypair, Keypair, [Pubkey; 4]) {
+    let mut program_test = ProgramTest::new(
+        "transfer_checked",
+        *program_id,
+        processor!(transfer_checked),
+    );
+    let user = Keypair::new();
+    let mint = add_account(&mut program_test, token_program, pack_mint(6));
+    let other_mint = add_account(&mut program_test, token_program, pack_mint(6));
+    let source = add_account(
+        &mut program_test,
+        token_program,
+        pack_token_account(&mint, &user.pubkey(), 1_000),
+    );
+    let destination = add_account(
+        &mut program_test,
+        token_program,
+        pack_token_account(&mint, &user.pubkey(), 0),
+    );
+    let (banks_client, payer, _) = program_test.start().await;
+    (
+        banks_client,
+        payer,
+        user,
+        [source, destination, mint, other_mint],
+    )
+}
+
+async fn transfer(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    token_program: &Pubkey,
+    payer: &Keypair,
+    user: &Keypair,
+    [source, destination, mint]: [Pubkey; 3],
+    decimals: u8,
+) -> Result<(), TransactionError> {
+    let mut data = 400u64.to_le_bytes().to_vec();
+    data.push(decimals);
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(user.pubkey(), true),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data,
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[payer, user],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_transfer_checked() {
+    for token_program in [spl_token::id(), spl_token_2022::id()] {
+        let program_id = Pubkey::new_unique();
+        let (mut banks_client, payer, user, [source, destination, mint, other_mint]) =
+            setup(&program_id, &token_program).await;
+
+        // decimals of another mint
+        assert_eq!(
+            transfer(
+                &mut banks_client,
+                &program_id,
+                &token_program,
+                &payer,
+                &user,
+                [source, destination, mint],
+                9
+            )
+            .await,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(spl_token::error::TokenError::MintDecimalsMismatch as u32)
+            ))
+        );
+        // accounts of another mint, mapped to TokenOwnerMismatch
+        assert_eq!(
+            transfer(
+                &mut banks_client,
+                &program_id,
+                &token_program,
+                &payer,
+                &user,
+                [source, destination, other_mint],
+                6
+            )
+            .await,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(1103)
+            ))
+        );
+        assert_eq!(token_balance(&mut banks_client, &source).await, 1_000);
+
+        assert_eq!(
+            transfer(
+                &mut banks_client,
+                &program_id,
+                &token_program,
+                &payer,
+                &user,
+                [source, destination, mint],
+                6
+            )
+            .await,
+            Ok(())
+        );
+        assert_eq!(token_balance(&mut banks_client, &source).await, 600);
+        assert_eq!(token_balance(&mut banks_client, &destination).await, 400);
+    }
+}
@@ -0,0 +1,56 @@
+//! Instruction data that doesn't unpack is logged with the instruction type it
+//! names and the length that type packs to.
+
+mod common;
+
+use {
+    common::process_instruction,
+    solana_program::{
+        instruction::{Instruction, InstructionError},
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, ProgramTest},
+    solana_sdk::transaction::TransactionError,
+    swap::instruction::{AmmInstruction, AmmInstructionType},
+};
+
+#[tokio::test]
+async fn test_unpack_error_logs_instruction_type() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "swap",
+        program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let (mut banks_client, payer, _) = program_test.start().await;
+
+    let mut short_swap = vec![0; 10];
+    short_swap[0] = AmmInstructionType::Swap as u8;
+    for (data, expected_error, expected_log) in [
+        (
+            short_swap,
+            InstructionError::AccountDataTooSmall,
+            format!("Instruction type 1 (Swap) packs to {} bytes, got 10", AmmInstruction::SWAP_LEN),
+        ),
+        (
+            vec![u8::MAX; 10],
+            InstructionError::InvalidInstructionData,
+            "Unknown instruction type 255".to_string(),
+        ),
+    ] {
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![],
+            data,
+        };
+        let result = process_instruction(&mut banks_client, &payer, &[], instruction).await;
+
+        assert_eq!(result.result, Err(TransactionError::InstructionError(0, expected_error)));
+        let log_messages = result.metadata.unwrap().log_messages;
+        assert!(
+            log_messages.iter().any(|log| log.contains(&expected_log)),
+            "{:?}",
+            log_messages
+        );
+    }
+}
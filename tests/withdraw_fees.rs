@@ -0,0 +1,161 @@
+//! WithdrawFees moves collected fees out of the router PDA for the config admin only.
+
+use {
+    solana_program::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_program_test::{processor, tokio, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        system_transaction,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::state::{Account as TokenAccount, AccountState},
+    swap::{instruction::AmmInstruction, state::SwapConfig, utils::tokens::PREFIX},
+};
+
+fn add_token_account(program_test: &mut ProgramTest, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+    let address = Pubkey::new_unique();
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    address
+}
+
+/// Starts a router administered by admin whose PDA holds 1_000 fee tokens,
+/// returns the client, payer, fee and destination token accounts
+async fn setup(program_id: &Pubkey, admin: &Pubkey) -> (BanksClient, Keypair, Pubkey, Pubkey) {
+    let mut program_test = ProgramTest::new(
+        "swap",
+        *program_id,
+        processor!(swap::processor::process_instruction),
+    );
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0;
+    let mut data = vec![0; SwapConfig::LEN];
+    SwapConfig {
+        admin: *admin,
+        ..SwapConfig::default()
+    }
+    .pack(&mut data)
+    .unwrap();
+    program_test.add_account(
+        program_account,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    let mint = Pubkey::new_unique();
+    let fee_account = add_token_account(&mut program_test, &mint, &program_account, 1_000);
+    let destination = add_token_account(&mut program_test, &mint, &Pubkey::new_unique(), 0);
+    let (banks_client, payer, _) = program_test.start().await;
+    (banks_client, payer, fee_account, destination)
+}
+
+async fn withdraw_fees(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    admin: &Keypair,
+    fee_account: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Result<(), TransactionError> {
+    let program_account = Pubkey::find_program_address(&[PREFIX.as_bytes()], program_id).0;
+    let mut data = [0; AmmInstruction::LEN];
+    AmmInstruction::WithdrawFees { amount }.pack(&mut data).unwrap();
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(admin.pubkey(), true),
+            AccountMeta::new_readonly(program_account, false),
+            AccountMeta::new(*fee_account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&admin.pubkey()),
+        &[admin],
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*address).await.unwrap().unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_withdraw_fees() {
+    let program_id = Pubkey::new_unique();
+    let admin = Keypair::new();
+    let (mut banks_client, payer, fee_account, destination) =
+        setup(&program_id, &admin.pubkey()).await;
+    // the admin pays for its own transactions
+    let transfer = system_transaction::transfer(
+        &payer,
+        &admin.pubkey(),
+        1_000_000_000,
+        banks_client.get_latest_blockhash().await.unwrap(),
+    );
+    banks_client.process_transaction(transfer).await.unwrap();
+
+    // partial
+    assert_eq!(
+        withdraw_fees(&mut banks_client, &program_id, &admin, &fee_account, &destination, 300).await,
+        Ok(())
+    );
+    assert_eq!(token_balance(&mut banks_client, &fee_account).await, 700);
+    assert_eq!(token_balance(&mut banks_client, &destination).await, 300);
+
+    // full balance
+    assert_eq!(
+        withdraw_fees(&mut banks_client, &program_id, &admin, &fee_account, &destination, 0).await,
+        Ok(())
+    );
+    assert_eq!(token_balance(&mut banks_client, &fee_account).await, 0);
+    assert_eq!(token_balance(&mut banks_client, &destination).await, 1_000);
+}
+
+#[tokio::test]
+async fn test_withdraw_fees_rejects_other_signer() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, fee_account, destination) =
+        setup(&program_id, &Pubkey::new_unique()).await;
+
+    assert_eq!(
+        withdraw_fees(&mut banks_client, &program_id, &payer, &fee_account, &destination, 0).await,
+        Err(TransactionError::InstructionError(0, InstructionError::Custom(1112)))
+    );
+    assert_eq!(token_balance(&mut banks_client, &fee_account).await, 1_000);
+}